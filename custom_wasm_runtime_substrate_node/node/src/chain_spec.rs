@@ -1,8 +1,21 @@
-use runtime::RuntimeGenesisConfig;
+use runtime::{
+	shared::{AuraId, GrandpaId, DEFAULT_SLOT_DURATION},
+	RuntimeGenesisConfig,
+};
 use sc_service::{ChainType, Properties};
+use sp_keyring::{AccountKeyring, Ed25519Keyring};
 
 pub type ChainSpec = sc_service::GenericChainSpec<RuntimeGenesisConfig>;
 
+/// The free balance of each endowed account in the presets below.
+const ENDOWMENT: runtime::shared::Balance = 1_000_000;
+
+/// The Aura and GRANDPA keys of a well-known keyring account, as inserted in the keystore by
+/// `--alice`, `--bob`, etc.
+fn authority(aura: AccountKeyring, grandpa: Ed25519Keyring) -> (AuraId, GrandpaId) {
+	(aura.public().into(), grandpa.public().into())
+}
+
 fn props() -> Properties {
 	let mut properties = Properties::new();
 	properties.insert("tokenDecimals".to_string(), 0.into());
@@ -10,12 +23,52 @@ fn props() -> Properties {
 	properties
 }
 
+/// Development chain: all of the well-known keyring accounts are endowed, and Alice also has an
+/// initial stake. Alice is the only authority.
 pub fn development_config() -> Result<ChainSpec, String> {
 	Ok(ChainSpec::from_genesis(
 		"Development",
 		"dev",
 		ChainType::Development,
-		move || Default::default(),
+		|| RuntimeGenesisConfig {
+			stakes: vec![(AccountKeyring::Alice.public(), ENDOWMENT / 10)],
+			authorities: vec![authority(AccountKeyring::Alice, Ed25519Keyring::Alice)],
+			slot_duration: Some(DEFAULT_SLOT_DURATION),
+			..RuntimeGenesisConfig::with_endowed(
+				AccountKeyring::iter().map(|account| account.public()).collect(),
+				ENDOWMENT,
+			)
+		},
+		vec![],
+		None,
+		None,
+		None,
+		Some(props()),
+		None,
+	))
+}
+
+/// Local testnet: only Alice and Bob are endowed. Alice, Bob and Charlie are the authorities, so
+/// that it can be run as a network of three nodes, each started with e.g.
+/// `--chain local --consensus aura-6000 --alice --validator --base-path /tmp/alice`, and the
+/// last two with `--bootnodes` pointing to the first.
+pub fn local_testnet_config() -> Result<ChainSpec, String> {
+	Ok(ChainSpec::from_genesis(
+		"Local Testnet",
+		"local_testnet",
+		ChainType::Local,
+		|| RuntimeGenesisConfig {
+			authorities: vec![
+				authority(AccountKeyring::Alice, Ed25519Keyring::Alice),
+				authority(AccountKeyring::Bob, Ed25519Keyring::Bob),
+				authority(AccountKeyring::Charlie, Ed25519Keyring::Charlie),
+			],
+			slot_duration: Some(DEFAULT_SLOT_DURATION),
+			..RuntimeGenesisConfig::with_endowed(
+				vec![AccountKeyring::Alice.public(), AccountKeyring::Bob.public()],
+				ENDOWMENT,
+			)
+		},
 		vec![],
 		None,
 		None,
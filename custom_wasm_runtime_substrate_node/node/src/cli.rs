@@ -9,6 +9,13 @@ pub enum Consensus {
 	ManualSeal(u64),
 	/// Produce a new block instantly, as soon as at least one transaction exists in the pool.
 	InstantSeal,
+	/// Take turns with the other authorities of the chain to author a block in each slot of the
+	/// given duration, with Aura, and finalize them with GRANDPA.
+	///
+	/// Example: `aura-6000` will author in slots of 6 seconds. This must match the slot duration
+	/// in the genesis of the chain, which the node checks on startup. The built-in chain specs
+	/// have slots of 6 seconds, whatever the consensus, so that they always have the same genesis.
+	Aura(u64),
 }
 
 impl std::str::FromStr for Consensus {
@@ -19,6 +26,11 @@ impl std::str::FromStr for Consensus {
 			Consensus::InstantSeal
 		} else if let Some(block_time) = s.strip_prefix("manual-seal-") {
 			Consensus::ManualSeal(block_time.parse().map_err(|_| "invalid block time")?)
+		} else if let Some(slot_duration) = s.strip_prefix("aura-") {
+			match slot_duration.parse() {
+				Ok(0) | Err(_) => return Err("invalid slot duration".into()),
+				Ok(slot_duration) => Consensus::Aura(slot_duration),
+			}
 		} else {
 			return Err("incorrect consensus identifier".into())
 		})
@@ -66,4 +78,8 @@ pub enum Subcommand {
 
 	/// Db meta columns information.
 	ChainInfo(sc_cli::ChainInfoCmd),
+
+	/// Re-execute exported blocks, and show the state changes of each extrinsic, and why a block
+	/// fails to import.
+	ReplayBlocks(crate::replay::ReplayBlocksCmd),
 }
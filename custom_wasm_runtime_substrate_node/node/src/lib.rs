@@ -1,3 +1,5 @@
 pub mod chain_spec;
 pub(crate) mod cli;
+pub mod replay;
+pub mod rpc;
 pub mod service;
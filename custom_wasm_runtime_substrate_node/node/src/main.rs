@@ -6,7 +6,8 @@ mod chain_spec;
 mod service;
 mod cli;
 mod command;
-// mod rpc;
+mod replay;
+mod rpc;
 
 fn main() -> sc_cli::Result<()> {
 	command::run()
@@ -0,0 +1,260 @@
+//! The `replay-blocks` command: re-execute blocks that were exported with `export-blocks`, and show
+//! the state changes of each of their extrinsics, along with why a block fails to import.
+//!
+//! Each block is replayed on top of the state of its parent, as kept in the database of the node,
+//! or as left by the previous block of the input if that is its parent. See [`runtime::replay`].
+
+use parity_scale_codec::{Decode, Encode, IoReader};
+use runtime::{
+	opaque::Block as OpaqueBlock,
+	replay::{self, BlockReplay, Failure, KeyChange, Outcome, Step},
+	shared::{Block, Phase},
+};
+use sc_cli::{CliConfiguration, DatabaseParams, PruningParams, SharedParams};
+use sc_client_api::{Backend, StorageProvider};
+use sp_core::{hexdisplay::HexDisplay, storage::well_known_keys, H256};
+use sp_runtime::{generic::SignedBlock, Storage};
+use std::{
+	collections::BTreeMap,
+	fs::File,
+	io::{self, BufReader, Read, Write},
+	path::PathBuf,
+};
+
+/// Values longer than this many bytes are cut short in the text output.
+const MAX_VALUE_LEN: usize = 32;
+
+/// How to print the replay of each block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+	/// The changes of each step, one per line.
+	Text,
+	/// One JSON object per block, per line.
+	Json,
+}
+
+/// The `replay-blocks` command.
+#[derive(Debug, clap::Parser)]
+pub struct ReplayBlocksCmd {
+	/// Input file, as written by `export-blocks`. Reads from stdin if not given.
+	#[arg(value_name = "INPUT")]
+	pub input: Option<PathBuf>,
+
+	/// The input is in the binary format of `export-blocks --binary`, rather than JSON.
+	#[arg(long)]
+	pub binary: bool,
+
+	/// How to print the replay of each block.
+	#[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+	pub output: OutputFormat,
+
+	/// Only print the blocks that fail to import.
+	#[arg(long)]
+	pub failures_only: bool,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub pruning_params: PruningParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub database_params: DatabaseParams,
+}
+
+impl CliConfiguration for ReplayBlocksCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn pruning_params(&self) -> Option<&PruningParams> {
+		Some(&self.pruning_params)
+	}
+
+	fn database_params(&self) -> Option<&DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
+
+impl ReplayBlocksCmd {
+	/// Replay all blocks of the input, taking the state of the parent of the first one from
+	/// `client`.
+	pub fn run<BE, C>(&self, client: &C) -> sc_cli::Result<()>
+	where
+		BE: Backend<OpaqueBlock>,
+		C: StorageProvider<OpaqueBlock, BE>,
+	{
+		// the panic handler of the node aborts by default, while a block that panics is just
+		// another failure here.
+		let _guard = sp_panic_handler::AbortGuard::force_unwind();
+		let mut out = io::stdout().lock();
+		let mut previous: Option<BlockReplay> = None;
+		for block in self.read_blocks()? {
+			let parent = match &previous {
+				Some(previous) if previous.hash == block.header.parent_hash =>
+					previous.post_state(),
+				_ => parent_state(client, block.header.parent_hash)?,
+			};
+			let replay = replay::replay_block(&parent, &block);
+			if replay.failure.is_some() || !self.failures_only {
+				match self.output {
+					OutputFormat::Text => print_text(&mut out, &replay)?,
+					OutputFormat::Json => writeln!(
+						out,
+						"{}",
+						serde_json::to_string(&replay).map_err(|e| e.to_string())?
+					)?,
+				}
+			}
+			previous = Some(replay);
+		}
+		Ok(())
+	}
+
+	fn read_blocks(&self) -> sc_cli::Result<Vec<Block>> {
+		let input: Box<dyn Read> = match &self.input {
+			Some(path) => Box::new(BufReader::new(File::open(path)?)),
+			None => Box::new(io::stdin()),
+		};
+		let invalid = |e: &dyn std::fmt::Display| format!("Invalid input: {}", e);
+
+		let blocks: Vec<SignedBlock<OpaqueBlock>> = if self.binary {
+			// the binary format starts with the number of blocks.
+			let mut input = IoReader(input);
+			let count = u64::decode(&mut input).map_err(|e| invalid(&e))?;
+			(0..count)
+				.map(|_| SignedBlock::decode(&mut input))
+				.collect::<Result<_, _>>()
+				.map_err(|e| invalid(&e))?
+		} else {
+			serde_json::Deserializer::from_reader(input)
+				.into_iter()
+				.collect::<Result<_, _>>()
+				.map_err(|e| invalid(&e))?
+		};
+		blocks
+			.into_iter()
+			.map(|signed| Block::decode(&mut &signed.block.encode()[..]))
+			.collect::<Result<_, _>>()
+			.map_err(|e| invalid(&e).into())
+	}
+}
+
+/// The whole top-level state at `hash`, which must still be in the database.
+fn parent_state<BE, C>(client: &C, hash: H256) -> sc_cli::Result<Storage>
+where
+	BE: Backend<OpaqueBlock>,
+	C: StorageProvider<OpaqueBlock, BE>,
+{
+	let unavailable = |e: sp_blockchain::Error| {
+		format!("State of parent block {:?} is not available: {}", hash, e)
+	};
+	let mut top = BTreeMap::new();
+	for key in client.storage_keys(hash, None, None).map_err(unavailable)? {
+		if let Some(value) = client.storage(hash, &key).map_err(unavailable)? {
+			top.insert(key.0, value.0);
+		}
+	}
+	Ok(Storage { top, children_default: Default::default() })
+}
+
+fn phase_name(phase: Phase) -> String {
+	match phase {
+		Phase::Initialization => "initialization".into(),
+		Phase::ApplyExtrinsic(index) => format!("extrinsic {}", index),
+		Phase::Finalization => "finalization".into(),
+	}
+}
+
+fn describe(failure: &Failure) -> String {
+	match failure {
+		Failure::Diverged { phase } =>
+			format!("authoring and import diverge at {}", phase_name(*phase)),
+		Failure::Panicked { phase, message } =>
+			format!("{} panicked: {}", phase_name(*phase), message),
+		Failure::InvalidExtrinsic { index, error } =>
+			format!("extrinsic {} is invalid: {}", index, error),
+		Failure::StateRootMismatch { expected, actual } => format!(
+			"state root is {:?} instead of {:?}, the block was authored with other code or on \
+			 another state",
+			actual, expected
+		),
+		Failure::ExtrinsicsRootMismatch { expected, actual } =>
+			format!("extrinsics root is {:?} instead of {:?}", actual, expected),
+	}
+}
+
+/// The storage keys of this runtime are a readable name, followed by the encoded key of a map if
+/// any, e.g. `BalancesMap ++ encode(account)`. The names are those of the metadata.
+fn format_key(key: &[u8]) -> String {
+	let names = runtime::metadata::pallets()
+		.into_iter()
+		.flat_map(|pallet| pallet.storage.into_iter().flat_map(|storage| storage.entries))
+		.map(|entry| entry.name.as_bytes())
+		.chain([well_known_keys::CODE, well_known_keys::HEAP_PAGES]);
+	match names.filter(|name| key.starts_with(name)).max_by_key(|name| name.len()) {
+		Some(name) if name.len() == key.len() => String::from_utf8_lossy(name).into_owned(),
+		Some(name) => format!(
+			"{} ++ 0x{}",
+			String::from_utf8_lossy(name),
+			HexDisplay::from(&&key[name.len()..])
+		),
+		None => format!("0x{}", HexDisplay::from(&key)),
+	}
+}
+
+fn format_value(value: &[u8]) -> String {
+	if value.len() > MAX_VALUE_LEN {
+		format!("0x{}… ({} bytes)", HexDisplay::from(&&value[..MAX_VALUE_LEN]), value.len())
+	} else {
+		format!("0x{}", HexDisplay::from(&value))
+	}
+}
+
+fn print_step(out: &mut impl Write, step: &Step) -> io::Result<()> {
+	let outcome = match &step.outcome {
+		Outcome::Ok => "ok".to_string(),
+		Outcome::DispatchFailed(error) => format!("dispatch failed: {}", error),
+		Outcome::Invalid(error) => format!("invalid: {}", error),
+		Outcome::Panicked(message) => format!("panicked: {}", message),
+	};
+	writeln!(out, "  {}: {}", phase_name(step.phase), outcome)?;
+	for KeyChange { key, before, after } in &step.changes {
+		let key = format_key(key);
+		match (before, after) {
+			(None, Some(after)) => writeln!(out, "    + {} = {}", key, format_value(after))?,
+			(Some(before), Some(after)) => writeln!(
+				out,
+				"    ~ {} = {} -> {}",
+				key,
+				format_value(before),
+				format_value(after)
+			)?,
+			(Some(_), None) => writeln!(out, "    - {}", key)?,
+			(None, None) => {},
+		}
+	}
+	Ok(())
+}
+
+/// Print the steps of the import of the block, and where authoring differs from it, if it does.
+fn print_text(out: &mut impl Write, replay: &BlockReplay) -> io::Result<()> {
+	let status = replay
+		.failure
+		.as_ref()
+		.map_or("imports".into(), |failure| format!("fails to import, {}", describe(failure)));
+	writeln!(out, "#{} {:?}: {}", replay.number, replay.hash, status)?;
+	for step in &replay.import.steps {
+		print_step(out, step)?;
+	}
+	if let Some(Failure::Diverged { phase }) = &replay.failure {
+		writeln!(out, "  while authoring, instead:")?;
+		if let Some(step) = replay.authoring.steps.iter().find(|step| step.phase == *phase) {
+			print_step(out, step)?;
+		}
+	}
+	Ok(())
+}
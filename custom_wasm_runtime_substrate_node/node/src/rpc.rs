@@ -0,0 +1,92 @@
+//! The RPC extensions of the node, on top of the ones that `sc-service` provides.
+
+use jsonrpsee::{
+	core::RpcResult,
+	proc_macros::rpc,
+	types::error::{CallError, ErrorObject},
+	RpcModule,
+};
+use runtime::{
+	opaque::Block,
+	shared::{AccountBalance, AccountId, Balance},
+	StateQueryApi as StateQueryRuntimeApi,
+};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_runtime::traits::Block as BlockT;
+use std::sync::Arc;
+
+/// Error code of a failed runtime API call.
+const RUNTIME_ERROR: i32 = 1;
+
+/// Read the state of the runtime at block `at`, or the best block if not given.
+#[rpc(server)]
+pub trait StateQueryApi<BlockHash> {
+	#[method(name = "frameless_accountBalance")]
+	fn account_balance(&self, who: AccountId, at: Option<BlockHash>) -> RpcResult<AccountBalance>;
+
+	#[method(name = "frameless_totalIssuance")]
+	fn total_issuance(&self, at: Option<BlockHash>) -> RpcResult<Balance>;
+
+	#[method(name = "frameless_value")]
+	fn value(&self, at: Option<BlockHash>) -> RpcResult<Option<u32>>;
+}
+
+/// Implements [`StateQueryApiServer`] on top of the `StateQueryApi` runtime API.
+pub struct StateQuery<C> {
+	client: Arc<C>,
+}
+
+impl<C> StateQuery<C> {
+	/// Query the state through `client`.
+	pub fn new(client: Arc<C>) -> Self {
+		Self { client }
+	}
+}
+
+fn runtime_error(error: impl std::fmt::Display) -> jsonrpsee::core::Error {
+	CallError::Custom(ErrorObject::owned(
+		RUNTIME_ERROR,
+		"Unable to query the runtime state.",
+		Some(error.to_string()),
+	))
+	.into()
+}
+
+impl<C> StateQueryApiServer<<Block as BlockT>::Hash> for StateQuery<C>
+where
+	C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+	C::Api: StateQueryRuntimeApi<Block>,
+{
+	fn account_balance(
+		&self,
+		who: AccountId,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> RpcResult<AccountBalance> {
+		let at = at.unwrap_or_else(|| self.client.info().best_hash);
+		self.client.runtime_api().account_balance(at, who).map_err(runtime_error)
+	}
+
+	fn total_issuance(&self, at: Option<<Block as BlockT>::Hash>) -> RpcResult<Balance> {
+		let at = at.unwrap_or_else(|| self.client.info().best_hash);
+		self.client.runtime_api().total_issuance(at).map_err(runtime_error)
+	}
+
+	fn value(&self, at: Option<<Block as BlockT>::Hash>) -> RpcResult<Option<u32>> {
+		let at = at.unwrap_or_else(|| self.client.info().best_hash);
+		self.client.runtime_api().value(at).map_err(runtime_error)
+	}
+}
+
+/// All the RPC extensions of the node.
+pub fn create_full<C>(
+	client: Arc<C>,
+) -> Result<RpcModule<()>, Box<dyn std::error::Error + Send + Sync>>
+where
+	C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+	C::Api: StateQueryRuntimeApi<Block>,
+{
+	let mut module = RpcModule::new(());
+	module.merge(StateQuery::new(client).into_rpc())?;
+	Ok(module)
+}
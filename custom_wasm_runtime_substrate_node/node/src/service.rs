@@ -3,6 +3,8 @@ pub use sc_executor::NativeElseWasmExecutor;
 use sc_service::{error::Error as ServiceError, Configuration, TaskManager};
 use sc_telemetry::{Telemetry, TelemetryWorker};
 use sc_transaction_pool_api::OffchainTransactionPoolFactory;
+use sp_blockchain::HeaderBackend;
+use sp_consensus_aura::sr25519::AuthorityPair as AuraPair;
 use std::sync::Arc;
 
 use crate::cli::Consensus;
@@ -31,9 +33,44 @@ pub(crate) type FullClient =
 	sc_service::TFullClient<Block, RuntimeApi, NativeElseWasmExecutor<ExecutorDispatch>>;
 type FullBackend = sc_service::TFullBackend<Block>;
 type FullSelectChain = sc_consensus::LongestChain<FullBackend, Block>;
+type FullGrandpaBlockImport =
+	sc_consensus_grandpa::GrandpaBlockImport<FullBackend, Block, FullClient, FullSelectChain>;
+type FullGrandpaLink = sc_consensus_grandpa::LinkHalf<Block, FullClient, FullSelectChain>;
+
+/// The minimum period of blocks on which GRANDPA justifications are imported and generated.
+const GRANDPA_JUSTIFICATION_PERIOD: u32 = 512;
+
+/// The inherent data providers of a block authored with Aura at the current time.
+fn aura_inherent_data_providers(
+	slot_duration: sp_consensus_aura::SlotDuration,
+) -> (sp_consensus_aura::inherents::InherentDataProvider, sp_timestamp::InherentDataProvider) {
+	let timestamp = sp_timestamp::InherentDataProvider::from_system_time();
+	let slot = sp_consensus_aura::inherents::InherentDataProvider::from_timestamp_and_slot_duration(
+		*timestamp,
+		slot_duration,
+	);
+	(slot, timestamp)
+}
+
+/// The slot duration of the chain, which must be that of `--consensus aura-<ms>`.
+fn aura_slot_duration(
+	client: &FullClient,
+	expected: u64,
+) -> Result<sp_consensus_aura::SlotDuration, ServiceError> {
+	let slot_duration = sc_consensus_aura::slot_duration(client)?;
+	if slot_duration.as_millis() != expected {
+		return Err(ServiceError::Other(format!(
+			"The chain has a slot duration of {}ms, but the node was started with aura-{}",
+			slot_duration.as_millis(),
+			expected
+		)))
+	}
+	Ok(slot_duration)
+}
 
 pub fn new_partial(
 	config: &Configuration,
+	consensus: &Consensus,
 ) -> Result<
 	sc_service::PartialComponents<
 		FullClient,
@@ -41,7 +78,7 @@ pub fn new_partial(
 		FullSelectChain,
 		sc_consensus::DefaultImportQueue<Block, FullClient>,
 		sc_transaction_pool::FullPool<Block, FullClient>,
-		Option<Telemetry>,
+		(Option<Telemetry>, Option<(FullGrandpaBlockImport, FullGrandpaLink)>),
 	>,
 	ServiceError,
 > {
@@ -81,11 +118,43 @@ pub fn new_partial(
 		client.clone(),
 	);
 
-	let import_queue = sc_consensus_manual_seal::import_queue(
-		Box::new(client.clone()),
-		&task_manager.spawn_essential_handle(),
-		config.prometheus_registry(),
-	);
+	let (import_queue, grandpa) = match consensus {
+		Consensus::Aura(slot_duration) => {
+			let (grandpa_block_import, grandpa_link) = sc_consensus_grandpa::block_import(
+				client.clone(),
+				GRANDPA_JUSTIFICATION_PERIOD,
+				&client,
+				select_chain.clone(),
+				telemetry.as_ref().map(|x| x.handle()),
+			)?;
+
+			let slot_duration = aura_slot_duration(&client, *slot_duration)?;
+			let import_queue = sc_consensus_aura::import_queue::<AuraPair, _, _, _, _, _>(
+				sc_consensus_aura::ImportQueueParams {
+					block_import: grandpa_block_import.clone(),
+					justification_import: Some(Box::new(grandpa_block_import.clone())),
+					client: client.clone(),
+					create_inherent_data_providers: move |_, ()| async move {
+						Ok(aura_inherent_data_providers(slot_duration))
+					},
+					spawner: &task_manager.spawn_essential_handle(),
+					registry: config.prometheus_registry(),
+					check_for_equivocation: Default::default(),
+					telemetry: telemetry.as_ref().map(|x| x.handle()),
+					compatibility_mode: Default::default(),
+				},
+			)?;
+			(import_queue, Some((grandpa_block_import, grandpa_link)))
+		},
+		Consensus::ManualSeal(_) | Consensus::InstantSeal => {
+			let import_queue = sc_consensus_manual_seal::import_queue(
+				Box::new(client.clone()),
+				&task_manager.spawn_essential_handle(),
+				config.prometheus_registry(),
+			);
+			(import_queue, None)
+		},
+	};
 
 	Ok(sc_service::PartialComponents {
 		client,
@@ -95,7 +164,7 @@ pub fn new_partial(
 		keystore_container,
 		select_chain,
 		transaction_pool,
-		other: (telemetry),
+		other: (telemetry, grandpa),
 	})
 }
 
@@ -109,10 +178,26 @@ pub fn new_full(config: Configuration, consensus: Consensus) -> Result<TaskManag
 		keystore_container,
 		select_chain,
 		transaction_pool,
-		other: mut telemetry,
-	} = new_partial(&config)?;
+		other: (mut telemetry, grandpa),
+	} = new_partial(&config, &consensus)?;
 
-	let net_config = sc_network::config::FullNetworkConfiguration::new(&config.network);
+	let mut net_config = sc_network::config::FullNetworkConfiguration::new(&config.network);
+	let mut warp_sync_params = None;
+	let grandpa_protocol_name = sc_consensus_grandpa::protocol_standard_name(
+		&client.block_hash(0).ok().flatten().expect("Genesis block exists; qed"),
+		&config.chain_spec,
+	);
+	if let Some((_, grandpa_link)) = &grandpa {
+		net_config.add_notification_protocol(sc_consensus_grandpa::grandpa_peers_set_config(
+			grandpa_protocol_name.clone(),
+		));
+		let warp_sync = Arc::new(sc_consensus_grandpa::warp_proof::NetworkProvider::new(
+			backend.clone(),
+			grandpa_link.shared_authority_set().clone(),
+			Vec::default(),
+		));
+		warp_sync_params = Some(sc_service::WarpSyncParams::WithProvider(warp_sync));
+	}
 
 	let (network, system_rpc_tx, tx_handler_controller, network_starter, sync_service) =
 		sc_service::build_network(sc_service::BuildNetworkParams {
@@ -123,7 +208,7 @@ pub fn new_full(config: Configuration, consensus: Consensus) -> Result<TaskManag
 			import_queue,
 			net_config,
 			block_announce_validator_builder: None,
-			warp_sync_params: None,
+			warp_sync_params,
 		})?;
 
 	use futures::FutureExt;
@@ -151,12 +236,19 @@ pub fn new_full(config: Configuration, consensus: Consensus) -> Result<TaskManag
 
 	}
 
-	let rpc_extensions_builder = { Box::new(move |_, _| Ok(jsonrpsee::RpcModule::new(()))) };
+	let rpc_extensions_builder = {
+		let client = client.clone();
+		Box::new(move |_, _| crate::rpc::create_full(client.clone()).map_err(Into::into))
+	};
 
 	let prometheus_registry = config.prometheus_registry().cloned();
+	let role = config.role.clone();
+	let force_authoring = config.force_authoring;
+	let enable_grandpa = !config.disable_grandpa;
+	let name = config.network.node_name.clone();
 
 	let _rpc_handlers = sc_service::spawn_tasks(sc_service::SpawnTasksParams {
-		network,
+		network: network.clone(),
 		client: client.clone(),
 		keystore: keystore_container.keystore(),
 		task_manager: &mut task_manager,
@@ -165,7 +257,7 @@ pub fn new_full(config: Configuration, consensus: Consensus) -> Result<TaskManag
 		backend,
 		system_rpc_tx,
 		tx_handler_controller,
-		sync_service,
+		sync_service: sync_service.clone(),
 		config,
 		telemetry: telemetry.as_mut(),
 	})?;
@@ -235,8 +327,229 @@ pub fn new_full(config: Configuration, consensus: Consensus) -> Result<TaskManag
 				authorship_future,
 			);
 		},
+		Consensus::Aura(slot_duration) => {
+			let (block_import, grandpa_link) =
+				grandpa.expect("new_partial sets up GRANDPA along with Aura; qed");
+
+			if role.is_authority() {
+				let slot_duration = aura_slot_duration(&client, slot_duration)?;
+				let aura = sc_consensus_aura::start_aura::<AuraPair, _, _, _, _, _, _, _, _, _, _>(
+					sc_consensus_aura::StartAuraParams {
+						slot_duration,
+						client,
+						select_chain,
+						block_import,
+						proposer_factory: proposer,
+						create_inherent_data_providers: move |_, ()| async move {
+							Ok(aura_inherent_data_providers(slot_duration))
+						},
+						force_authoring,
+						backoff_authoring_blocks: None::<()>,
+						keystore: keystore_container.keystore(),
+						sync_oracle: sync_service.clone(),
+						justification_sync_link: sync_service.clone(),
+						block_proposal_slot_portion: sc_consensus_aura::SlotProportion::new(
+							2f32 / 3f32,
+						),
+						max_block_proposal_slot_portion: None,
+						telemetry: telemetry.as_ref().map(|x| x.handle()),
+						compatibility_mode: Default::default(),
+					},
+				)?;
+
+				task_manager.spawn_essential_handle().spawn_blocking(
+					"aura",
+					Some("block-authoring"),
+					aura,
+				);
+			}
+
+			if enable_grandpa {
+				let keystore =
+					if role.is_authority() { Some(keystore_container.keystore()) } else { None };
+				let config = sc_consensus_grandpa::Config {
+					gossip_duration: std::time::Duration::from_millis(333),
+					justification_generation_period: GRANDPA_JUSTIFICATION_PERIOD,
+					name: Some(name),
+					observer_enabled: false,
+					keystore,
+					local_role: role,
+					telemetry: telemetry.as_ref().map(|x| x.handle()),
+					protocol_name: grandpa_protocol_name,
+				};
+				let voter =
+					sc_consensus_grandpa::run_grandpa_voter(sc_consensus_grandpa::GrandpaParams {
+						config,
+						link: grandpa_link,
+						network,
+						sync: Arc::new(sync_service),
+						voting_rule: sc_consensus_grandpa::VotingRulesBuilder::default().build(),
+						prometheus_registry,
+						shared_voter_state: sc_consensus_grandpa::SharedVoterState::empty(),
+						telemetry: telemetry.as_ref().map(|x| x.handle()),
+						offchain_tx_pool_factory: OffchainTransactionPoolFactory::new(
+							transaction_pool,
+						),
+					})?;
+
+				task_manager
+					.spawn_essential_handle()
+					.spawn_blocking("grandpa-voter", None, voter);
+			}
+		},
 	}
 
 	network_starter.start_network();
 	Ok(task_manager)
 }
+
+#[cfg(test)]
+mod tests {
+	use parity_scale_codec::{Decode, Encode};
+	use runtime::shared::{
+		AccountBalance, Block, Extrinsic, RuntimeCall, RuntimeCallExt, SystemCall,
+	};
+	use sc_transaction_pool::{ChainApi, Options, Pool};
+	use sc_transaction_pool_api::error::Error as PoolError;
+	use sp_core::H256;
+	use sp_runtime::{
+		generic::BlockId,
+		traits::{BlakeTwo256, Block as BlockT, Hash, NumberFor},
+		transaction_validity::{TransactionSource, TransactionValidity},
+		Storage,
+	};
+	use std::sync::Arc;
+
+	/// A [`ChainApi`] that validates transactions by calling into the native runtime, on top of a
+	/// fixed state, instead of going through a full client.
+	struct RuntimeChainApi {
+		state: Storage,
+	}
+
+	impl ChainApi for RuntimeChainApi {
+		type Block = Block;
+		type Error = PoolError;
+		type ValidationFuture = futures::future::Ready<Result<TransactionValidity, PoolError>>;
+		type BodyFuture = futures::future::Ready<Result<Option<Vec<Extrinsic>>, PoolError>>;
+
+		fn validate_transaction(
+			&self,
+			_at: &BlockId<Block>,
+			source: TransactionSource,
+			uxt: Extrinsic,
+		) -> Self::ValidationFuture {
+			let input = (source, uxt, H256::default()).encode();
+			let validity = sp_io::TestExternalities::new(self.state.clone()).execute_with(|| {
+				runtime::api::dispatch("TaggedTransactionQueue_validate_transaction", &input)
+					.and_then(|output| TransactionValidity::decode(&mut &*output).ok())
+					.expect("validate_transaction is part of the runtime api; qed")
+			});
+			futures::future::ready(Ok(validity))
+		}
+
+		fn block_id_to_number(
+			&self,
+			at: &BlockId<Block>,
+		) -> Result<Option<NumberFor<Block>>, PoolError> {
+			Ok(match at {
+				BlockId::Number(number) => Some(*number),
+				BlockId::Hash(_) => Some(0),
+			})
+		}
+
+		fn block_id_to_hash(&self, at: &BlockId<Block>) -> Result<Option<H256>, PoolError> {
+			Ok(match at {
+				BlockId::Number(_) => Some(Default::default()),
+				BlockId::Hash(hash) => Some(*hash),
+			})
+		}
+
+		fn hash_and_length(&self, uxt: &Extrinsic) -> (H256, usize) {
+			let encoded = uxt.encode();
+			(BlakeTwo256::hash(&encoded), encoded.len())
+		}
+
+		fn block_body(&self, _at: H256) -> Self::BodyFuture {
+			futures::future::ready(Ok(None))
+		}
+
+		fn block_header(&self, _at: H256) -> Result<Option<<Block as BlockT>::Header>, PoolError> {
+			Ok(None)
+		}
+
+		fn tree_route(
+			&self,
+			_from: H256,
+			_to: H256,
+		) -> Result<sp_blockchain::TreeRoute<Block>, PoolError> {
+			Err(PoolError::RuntimeApi("the pool is never maintained in these tests".into()))
+		}
+	}
+
+	fn set_value(value: u32, nonce: u32) -> Extrinsic {
+		let signer = sp_keyring::AccountKeyring::Alice;
+		let call = RuntimeCallExt {
+			call: RuntimeCall::System(SystemCall::Set { value }),
+			nonce,
+			tip: None,
+		};
+		let signature = signer.sign(&call.encode());
+		Extrinsic::new(call, Some((signer.public(), signature, ()))).unwrap()
+	}
+
+	fn pool_with_alice_at_nonce(nonce: u32) -> Pool<RuntimeChainApi> {
+		let alice = sp_keyring::AccountKeyring::Alice;
+		let mut state = Storage::default();
+		state.top.insert(
+			[b"BalancesMap", alice.public().encode().as_slice()].concat(),
+			AccountBalance { free: 100, reserved: 0, nonce }.encode(),
+		);
+		Pool::new(Options::default(), true.into(), Arc::new(RuntimeChainApi { state }))
+	}
+
+	fn ready_nonces(pool: &Pool<RuntimeChainApi>) -> Vec<u32> {
+		pool.validated_pool().ready().map(|tx| tx.data.function.nonce).collect()
+	}
+
+	#[test]
+	fn out_of_order_transactions_are_queued_until_ready() {
+		let pool = pool_with_alice_at_nonce(0);
+		let at = BlockId::number(0);
+		let submit = |ext| {
+			futures::executor::block_on(pool.submit_one(&at, TransactionSource::External, ext))
+		};
+
+		// nonces 2 and 1 arrive first, and have to wait for 0.
+		submit(set_value(44, 2)).unwrap();
+		submit(set_value(43, 1)).unwrap();
+		assert_eq!(pool.validated_pool().status().ready, 0);
+		assert_eq!(pool.validated_pool().status().future, 2);
+
+		// once 0 arrives, the whole chain is promoted, in nonce order.
+		submit(set_value(42, 0)).unwrap();
+		assert_eq!(pool.validated_pool().status().ready, 3);
+		assert_eq!(pool.validated_pool().status().future, 0);
+		assert_eq!(ready_nonces(&pool), vec![0, 1, 2]);
+	}
+
+	#[test]
+	fn gap_in_nonces_stays_in_future_queue() {
+		let pool = pool_with_alice_at_nonce(1);
+		let at = BlockId::number(0);
+		let submit = |ext| {
+			futures::executor::block_on(pool.submit_one(&at, TransactionSource::External, ext))
+		};
+
+		// nonce 0 is already used.
+		assert!(submit(set_value(42, 0)).is_err());
+
+		submit(set_value(43, 1)).unwrap();
+		submit(set_value(45, 3)).unwrap();
+		assert_eq!(ready_nonces(&pool), vec![1]);
+		assert_eq!(pool.validated_pool().status().future, 1);
+
+		submit(set_value(44, 2)).unwrap();
+		assert_eq!(ready_nonces(&pool), vec![1, 2, 3]);
+		assert_eq!(pool.validated_pool().status().future, 0);
+	}
+}
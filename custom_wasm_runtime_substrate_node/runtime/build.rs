@@ -6,5 +6,16 @@ fn main() {
 			.export_heap_base()
 			.import_memory()
 			.build();
+
+		// the same runtime with a higher spec version, to test upgrades with. Only built on demand,
+		// as it doubles the time it takes to build the runtime.
+		#[cfg(feature = "with-upgrade-test-runtime")]
+		substrate_wasm_builder::WasmBuilder::new()
+			.with_current_project()
+			.export_heap_base()
+			.import_memory()
+			.set_file_name("wasm_binary_upgrade_test.rs")
+			.enable_feature("upgrade-test")
+			.build();
 	}
 }
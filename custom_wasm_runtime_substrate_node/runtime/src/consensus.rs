@@ -0,0 +1,96 @@
+//! The consensus module: the authorities that take turns to author blocks with Aura, and those that
+//! finalize them with GRANDPA.
+//!
+//! The node checks that each block is sealed by the author of its slot, see
+//! [`Module::slot_author`]. All that is left to the runtime is to make sure that slots only ever
+//! increase, and that they match the timestamp of their block. Blocks without an Aura pre-runtime
+//! digest, such as those of manual seal, are not checked.
+//!
+//! The authority sets are fixed at genesis, so the GRANDPA set id is always zero.
+
+use crate::{
+	dispatch::{DispatchResult, Hooks},
+	shared::{AuraId, BlockNumber, GrandpaId, Moment, Slot, DEFAULT_SLOT_DURATION},
+	storage::{storage_prefix, StorageValue},
+	system,
+};
+use sp_consensus_aura::AURA_ENGINE_ID;
+use sp_runtime::DispatchError;
+use sp_std::prelude::*;
+
+storage_prefix!(AuraAuthoritiesPrefix, b"AuraAuthorities");
+storage_prefix!(GrandpaAuthoritiesPrefix, b"GrandpaAuthorities");
+storage_prefix!(SlotDurationPrefix, b"SlotDuration");
+storage_prefix!(CurrentSlotPrefix, b"CurrentSlot");
+
+/// The authorities that author blocks, in the order in which they take turns.
+pub type AuraAuthorities = StorageValue<AuraAuthoritiesPrefix, Vec<AuraId>>;
+/// The authorities that vote on finality, each with a weight of one.
+pub type GrandpaAuthorities = StorageValue<GrandpaAuthoritiesPrefix, Vec<GrandpaId>>;
+/// The duration of a slot, in milliseconds. [`DEFAULT_SLOT_DURATION`] if it does not exist.
+pub type SlotDuration = StorageValue<SlotDurationPrefix, Moment>;
+/// The slot of the last block that was authored with Aura.
+pub type CurrentSlot = StorageValue<CurrentSlotPrefix, Slot>;
+
+/// Returned when the timestamp of a block does not fall in the slot that it claims.
+pub const SLOT_TIMESTAMP_MISMATCH: DispatchError =
+	DispatchError::Other("Timestamp does not match the slot of the block");
+
+sp_runtime::impl_opaque_keys! {
+	/// The keys that each authority keeps in the keystore of its node.
+	pub struct SessionKeys {
+		pub aura: AuraId,
+		pub grandpa: GrandpaId,
+	}
+}
+
+/// The consensus module.
+pub struct Module;
+
+impl Hooks for Module {
+	fn on_initialize(_n: BlockNumber) {
+		if let Some(slot) = Module::block_slot() {
+			assert!(slot > CurrentSlot::get_or_default(), "Slot must increase");
+			CurrentSlot::set(&slot);
+		}
+	}
+}
+
+impl Module {
+	/// The slot that the block being executed claims in its Aura pre-runtime digest, if any.
+	pub fn block_slot() -> Option<Slot> {
+		system::CurrentHeader::get()?
+			.digest
+			.convert_first(|item| item.pre_runtime_try_to::<Slot>(&AURA_ENGINE_ID))
+	}
+
+	/// The duration of a slot, in milliseconds.
+	pub fn slot_duration() -> Moment {
+		SlotDuration::get().unwrap_or(DEFAULT_SLOT_DURATION)
+	}
+
+	/// The authority that may author a block in `slot`, if there are any authorities at all.
+	///
+	/// This is the same round-robin that the Aura client uses to check the seal of a block.
+	pub fn slot_author(slot: Slot) -> Option<AuraId> {
+		let authorities = AuraAuthorities::get_or_default();
+		if authorities.is_empty() {
+			return None
+		}
+		authorities.get((slot % authorities.len() as u64) as usize).cloned()
+	}
+
+	/// The GRANDPA authorities, along with their weights.
+	pub fn grandpa_authorities() -> Vec<(GrandpaId, u64)> {
+		GrandpaAuthorities::get_or_default().into_iter().map(|id| (id, 1)).collect()
+	}
+
+	/// Check that `now`, the timestamp that the block being executed is about to set, falls in the
+	/// slot that the block claims.
+	pub fn check_timestamp(now: Moment) -> DispatchResult {
+		match Self::block_slot() {
+			Some(slot) if now / Self::slot_duration() != slot => Err(SLOT_TIMESTAMP_MISMATCH),
+			_ => Ok(()),
+		}
+	}
+}
@@ -0,0 +1,332 @@
+//! The currency module: balances, total issuance, and tips.
+//!
+//! Transfers can also vest over a range of blocks, or be scheduled at a future block. In both
+//! cases, the funds wait in the reserved balance of an account as a held deposit, so that they
+//! always count towards [`TotalIssuance`] and are never mistaken for bonded funds.
+
+use crate::{
+	dispatch::{DispatchResult, Dispatchable, Hooks},
+	shared::{
+		AccountBalance, AccountId, Balance, BlockNumber, CurrencyCall, RuntimeEvent,
+		ScheduledTransfer, VestingSchedule, MAX_SCHEDULED_PER_BLOCK, MAX_VESTING_SCHEDULES,
+		MINIMUM_BALANCE, TREASURY,
+	},
+	storage::{self, storage_prefix, StorageMap, StorageValue},
+	system,
+};
+use sp_runtime::{ArithmeticError, DispatchError};
+use sp_std::prelude::*;
+
+storage_prefix!(BalancesMapPrefix, b"BalancesMap");
+storage_prefix!(TotalIssuancePrefix, b"TotalIssuance");
+storage_prefix!(HeldDepositsPrefix, b"HeldDeposits");
+storage_prefix!(VestingSchedulesPrefix, b"VestingSchedules");
+storage_prefix!(ScheduledTransfersPrefix, b"ScheduledTransfers");
+
+/// The balance of each account, kept at `BalancesMap ++ encode(account)`.
+pub type BalancesMap = StorageMap<BalancesMapPrefix, AccountId, AccountBalance>;
+/// The sum of all balances in the system, kept at `TotalIssuance`.
+pub type TotalIssuance = StorageValue<TotalIssuancePrefix, Balance>;
+/// The part of the reserved balance of each account that is held as a deposit, rather than bonded,
+/// kept at `HeldDeposits ++ encode(account)`. This includes funds that are vesting, or waiting for
+/// a scheduled transfer.
+pub type HeldDeposits = StorageMap<HeldDepositsPrefix, AccountId, Balance>;
+/// The vesting schedules of each account, kept at `VestingSchedules ++ encode(account)`.
+pub type VestingSchedules = StorageMap<VestingSchedulesPrefix, AccountId, Vec<VestingSchedule>>;
+/// The transfers to execute at the beginning of each block, kept at `ScheduledTransfers ++
+/// encode(block)`.
+pub type ScheduledTransfers =
+	StorageMap<ScheduledTransfersPrefix, BlockNumber, Vec<ScheduledTransfer>>;
+
+/// Returned when an operation would leave an account with less than [`MINIMUM_BALANCE`].
+pub const BELOW_MINIMUM: DispatchError =
+	DispatchError::Other("Free balance is below the minimum balance");
+/// Returned when [`CurrencyCall::TransferAll`] is called by an account with reserved balance.
+pub const HAS_RESERVED: DispatchError = DispatchError::Other("Account has reserved balance");
+/// Returned when a vesting schedule locks less than [`MINIMUM_BALANCE`], or has no duration.
+pub const INVALID_SCHEDULE: DispatchError = DispatchError::Other("Invalid vesting schedule");
+/// Returned when an account already has [`MAX_VESTING_SCHEDULES`] vesting schedules.
+pub const TOO_MANY_SCHEDULES: DispatchError = DispatchError::Other("Too many vesting schedules");
+/// Returned when nothing has vested since the last [`CurrencyCall::Vest`].
+pub const NOTHING_VESTED: DispatchError = DispatchError::Other("No vested funds to release");
+/// Returned when scheduling a transfer at a block that is not in the future.
+pub const SCHEDULED_IN_PAST: DispatchError =
+	DispatchError::Other("Transfer must be scheduled after the current block");
+/// Returned when [`MAX_SCHEDULED_PER_BLOCK`] transfers are already scheduled at a block.
+pub const TOO_MANY_SCHEDULED: DispatchError =
+	DispatchError::Other("Too many transfers scheduled at this block");
+
+/// The currency module.
+pub struct Module;
+
+impl Hooks for Module {
+	fn on_initialize(n: BlockNumber) {
+		for ScheduledTransfer { from, dest, amount } in
+			ScheduledTransfers::take(&n).unwrap_or_default()
+		{
+			let result = Self::release_deposit(&from, amount)
+				.and_then(|_| storage::transactional(|| Self::transfer(&from, dest, amount)));
+			if let Err(error) = result {
+				system::Module::deposit_event(RuntimeEvent::ScheduledTransferFailed {
+					from,
+					to: dest,
+					amount,
+					error,
+				});
+			}
+		}
+	}
+}
+
+impl Module {
+	/// Remove `amount` from the free balance of `who`, which must retain at least
+	/// [`MINIMUM_BALANCE`] afterwards.
+	pub fn withdraw(who: &AccountId, amount: Balance) -> DispatchResult {
+		BalancesMap::try_mutate(who, |account| {
+			account.free = account
+				.free
+				.checked_sub(amount)
+				.filter(|free| *free >= MINIMUM_BALANCE)
+				.ok_or(BELOW_MINIMUM)?;
+			Ok(())
+		})
+	}
+
+	/// Add `amount` to the free balance of `who`, creating the account if needed. Fails if `who`
+	/// would still not have [`MINIMUM_BALANCE`] afterwards.
+	pub fn deposit_creating(who: &AccountId, amount: Balance) -> DispatchResult {
+		BalancesMap::try_mutate(who, |account| {
+			let free = account.free.checked_add(amount).ok_or(ArithmeticError::Overflow)?;
+			if free < MINIMUM_BALANCE {
+				return Err(BELOW_MINIMUM)
+			}
+			account.free = free;
+			Ok(())
+		})
+	}
+
+	/// Move `amount` from the free to the reserved balance of `who`, which must retain at least
+	/// [`MINIMUM_BALANCE`] of free balance afterwards.
+	pub fn reserve(who: &AccountId, amount: Balance) -> DispatchResult {
+		BalancesMap::try_mutate(who, |account| {
+			let free = account
+				.free
+				.checked_sub(amount)
+				.filter(|free| *free >= MINIMUM_BALANCE)
+				.ok_or(BELOW_MINIMUM)?;
+			let reserved = account.reserved.checked_add(amount).ok_or(ArithmeticError::Overflow)?;
+			account.free = free;
+			account.reserved = reserved;
+			Ok(())
+		})
+	}
+
+	/// Move `amount` from the reserved back to the free balance of `who`.
+	pub fn unreserve(who: &AccountId, amount: Balance) -> DispatchResult {
+		BalancesMap::try_mutate(who, |account| {
+			let reserved =
+				account.reserved.checked_sub(amount).ok_or(ArithmeticError::Underflow)?;
+			let free = account.free.checked_add(amount).ok_or(ArithmeticError::Overflow)?;
+			if free < MINIMUM_BALANCE {
+				return Err(BELOW_MINIMUM)
+			}
+			account.free = free;
+			account.reserved = reserved;
+			Ok(())
+		})
+	}
+
+	/// Reserve `amount` from `who` as a deposit, which is tracked in [`HeldDeposits`] so that it is
+	/// not mistaken for bonded funds.
+	pub fn hold_deposit(who: &AccountId, amount: Balance) -> DispatchResult {
+		Self::reserve(who, amount)?;
+		Self::add_held(who, amount)
+	}
+
+	fn add_held(who: &AccountId, amount: Balance) -> DispatchResult {
+		HeldDeposits::try_mutate(who, |held| {
+			*held = held.checked_add(amount).ok_or(ArithmeticError::Overflow)?;
+			Ok(())
+		})
+	}
+
+	/// Release a deposit of `amount` that was previously held with [`Self::hold_deposit`].
+	pub fn release_deposit(who: &AccountId, amount: Balance) -> DispatchResult {
+		HeldDeposits::mutate_exists(who, |held| {
+			let remaining =
+				held.unwrap_or_default().checked_sub(amount).ok_or(ArithmeticError::Underflow)?;
+			*held = Some(remaining).filter(|remaining| *remaining > 0);
+			Ok::<_, DispatchError>(())
+		})?;
+		Self::unreserve(who, amount)
+	}
+
+	/// Move `amount` from `who` to [`TREASURY`].
+	///
+	/// If `amount` is not enough to create the treasury account, it is burnt instead, and total
+	/// issuance is reduced accordingly. This deposits [`RuntimeEvent::TipBurned`].
+	pub fn pay_to_treasury(who: &AccountId, amount: Balance) -> DispatchResult {
+		Self::withdraw(who, amount)?;
+		let mut burnt = false;
+		BalancesMap::mutate(&AccountId::from_raw(TREASURY), |treasury| {
+			match treasury.free.checked_add(amount) {
+				Some(free) if free >= MINIMUM_BALANCE => treasury.free = free,
+				_ => burnt = true,
+			}
+		});
+		if burnt {
+			TotalIssuance::mutate(|issuance| *issuance = issuance.saturating_sub(amount));
+			system::Module::deposit_event(RuntimeEvent::TipBurned { who: *who, amount });
+		}
+		Ok(())
+	}
+
+	fn mint(sender: &AccountId, dest: AccountId, amount: Balance) -> DispatchResult {
+		system::ensure_sudo(sender)?;
+		TotalIssuance::try_mutate(|issuance| {
+			*issuance = issuance.checked_add(amount).ok_or(ArithmeticError::Overflow)?;
+			Ok::<_, DispatchError>(())
+		})?;
+		Self::deposit_creating(&dest, amount)?;
+		system::Module::deposit_event(RuntimeEvent::Minted { to: dest, amount });
+		Ok(())
+	}
+
+	fn transfer(sender: &AccountId, dest: AccountId, amount: Balance) -> DispatchResult {
+		Self::withdraw(sender, amount)?;
+		Self::deposit_creating(&dest, amount)?;
+		system::Module::deposit_event(RuntimeEvent::Transferred {
+			from: *sender,
+			to: dest,
+			amount,
+		});
+		Ok(())
+	}
+
+	/// The part of `schedule` that has vested by block `now`, including what was already released.
+	fn vested(schedule: &VestingSchedule, now: BlockNumber) -> Balance {
+		let elapsed = now.saturating_sub(schedule.start).min(schedule.duration) as Balance;
+		let duration = schedule.duration as Balance;
+		// `locked * elapsed / duration`, split so that it cannot overflow.
+		schedule.locked / duration * elapsed + schedule.locked % duration * elapsed / duration
+	}
+
+	fn vested_transfer(
+		sender: &AccountId,
+		dest: AccountId,
+		amount: Balance,
+		start: BlockNumber,
+		duration: BlockNumber,
+	) -> DispatchResult {
+		// at least the minimum balance, so that `dest` can always vest all of it in the end, and
+		// its schedules cannot be filled up with dust.
+		if amount < MINIMUM_BALANCE || duration == 0 {
+			return Err(INVALID_SCHEDULE)
+		}
+		VestingSchedules::try_mutate(&dest, |schedules| {
+			if schedules.len() >= MAX_VESTING_SCHEDULES as usize {
+				return Err(TOO_MANY_SCHEDULES)
+			}
+			schedules.push(VestingSchedule { locked: amount, released: 0, start, duration });
+			Ok(())
+		})?;
+		// none of `amount` is free at first, so it goes straight to the reserved balance of `dest`,
+		// creating it if needed.
+		Self::withdraw(sender, amount)?;
+		BalancesMap::try_mutate(&dest, |account| {
+			account.reserved =
+				account.reserved.checked_add(amount).ok_or(ArithmeticError::Overflow)?;
+			Ok::<_, DispatchError>(())
+		})?;
+		Self::add_held(&dest, amount)?;
+		system::Module::deposit_event(RuntimeEvent::Transferred {
+			from: *sender,
+			to: dest,
+			amount,
+		});
+		Ok(())
+	}
+
+	fn vest(sender: &AccountId) -> DispatchResult {
+		let now = system::Module::block_number();
+		let mut amount: Balance = 0;
+		VestingSchedules::mutate_exists(sender, |maybe_schedules| {
+			let mut schedules = maybe_schedules.take().unwrap_or_default();
+			for schedule in schedules.iter_mut() {
+				let vested = Self::vested(schedule, now);
+				amount = amount.saturating_add(vested.saturating_sub(schedule.released));
+				schedule.released = vested;
+			}
+			schedules.retain(|schedule| schedule.released < schedule.locked);
+			*maybe_schedules = Some(schedules).filter(|schedules| !schedules.is_empty());
+		});
+		if amount == 0 {
+			return Err(NOTHING_VESTED)
+		}
+		Self::release_deposit(sender, amount)?;
+		system::Module::deposit_event(RuntimeEvent::Vested { who: *sender, amount });
+		Ok(())
+	}
+
+	fn schedule_transfer(
+		sender: &AccountId,
+		dest: AccountId,
+		amount: Balance,
+		when: BlockNumber,
+	) -> DispatchResult {
+		if when <= system::Module::block_number() {
+			return Err(SCHEDULED_IN_PAST)
+		}
+		ScheduledTransfers::try_mutate(&when, |scheduled| {
+			if scheduled.len() >= MAX_SCHEDULED_PER_BLOCK as usize {
+				return Err(TOO_MANY_SCHEDULED)
+			}
+			scheduled.push(ScheduledTransfer { from: *sender, dest, amount });
+			Ok(())
+		})?;
+		Self::hold_deposit(sender, amount)?;
+		system::Module::deposit_event(RuntimeEvent::TransferScheduled {
+			from: *sender,
+			to: dest,
+			amount,
+			when,
+		});
+		Ok(())
+	}
+
+	fn transfer_all(sender: &AccountId, dest: AccountId) -> DispatchResult {
+		let account = BalancesMap::get_or_default(sender);
+		if account.reserved > 0 {
+			return Err(HAS_RESERVED)
+		}
+		if dest == *sender {
+			return Ok(())
+		}
+
+		// the sender is destroyed, along with its nonce.
+		BalancesMap::kill(sender);
+		Self::deposit_creating(&dest, account.free)?;
+		system::Module::deposit_event(RuntimeEvent::Transferred {
+			from: *sender,
+			to: dest,
+			amount: account.free,
+		});
+		system::Module::deposit_event(RuntimeEvent::AccountReaped { who: *sender });
+		Ok(())
+	}
+}
+
+impl Dispatchable for CurrencyCall {
+	fn dispatch(self, sender: &AccountId) -> DispatchResult {
+		match self {
+			CurrencyCall::Mint { dest, amount } => Module::mint(sender, dest, amount),
+			CurrencyCall::Transfer { dest, amount } => Module::transfer(sender, dest, amount),
+			CurrencyCall::TransferAll { dest } => Module::transfer_all(sender, dest),
+			CurrencyCall::VestedTransfer { dest, amount, start, duration } =>
+				Module::vested_transfer(sender, dest, amount, start, duration),
+			CurrencyCall::Vest => Module::vest(sender),
+			CurrencyCall::ScheduleTransfer { dest, amount, when } =>
+				Module::schedule_transfer(sender, dest, amount, when),
+		}
+	}
+}
@@ -0,0 +1,71 @@
+//! The dispatch and hooks abstractions that all modules of the runtime implement.
+
+use crate::shared::{AccountId, BlockNumber, RuntimeCall};
+use sp_runtime::DispatchError;
+
+/// The result of dispatching a call.
+pub type DispatchResult = Result<(), DispatchError>;
+
+/// Something that can be dispatched on behalf of a signed `sender`.
+///
+/// The outer runtime takes care of signatures, nonces and tips. Implementations only need to care
+/// about the logic of the call itself. If they return an error, all of their storage changes are
+/// rolled back.
+pub trait Dispatchable {
+	/// Dispatch `self`, on behalf of `sender`.
+	fn dispatch(self, sender: &AccountId) -> DispatchResult;
+}
+
+impl Dispatchable for RuntimeCall {
+	fn dispatch(self, sender: &AccountId) -> DispatchResult {
+		match self {
+			RuntimeCall::Currency(call) => call.dispatch(sender),
+			RuntimeCall::Staking(call) => call.dispatch(sender),
+			RuntimeCall::System(call) => call.dispatch(sender),
+			RuntimeCall::Multisig(call) => call.dispatch(sender),
+			RuntimeCall::Proxy(call) => call.dispatch(sender),
+		}
+	}
+}
+
+/// Hooks that a module can use to execute some logic at the beginning and end of each block.
+///
+/// These are called in both block authoring and block import, so they must be deterministic.
+pub trait Hooks {
+	/// Called at the beginning of block `_n`, before any extrinsic is applied.
+	fn on_initialize(_n: BlockNumber) {}
+
+	/// Called at the end of block `_n`, after all extrinsics are applied, and before the state root
+	/// is computed.
+	fn on_finalize(_n: BlockNumber) {}
+
+	/// Called once, at the beginning of the first block after the runtime was upgraded, before
+	/// [`Hooks::on_initialize`]. This is where storage migrations go.
+	fn on_runtime_upgrade() {}
+}
+
+macro_rules! impl_hooks_for_tuples {
+	($($module:ident),+) => {
+		impl<$($module: Hooks),+> Hooks for ($($module,)+) {
+			fn on_initialize(n: BlockNumber) {
+				$($module::on_initialize(n);)+
+			}
+
+			fn on_finalize(n: BlockNumber) {
+				$($module::on_finalize(n);)+
+			}
+
+			fn on_runtime_upgrade() {
+				$($module::on_runtime_upgrade();)+
+			}
+		}
+	};
+}
+
+impl_hooks_for_tuples!(A);
+impl_hooks_for_tuples!(A, B);
+impl_hooks_for_tuples!(A, B, C);
+impl_hooks_for_tuples!(A, B, C, D);
+impl_hooks_for_tuples!(A, B, C, D, E);
+impl_hooks_for_tuples!(A, B, C, D, E, F);
+impl_hooks_for_tuples!(A, B, C, D, E, F, G);
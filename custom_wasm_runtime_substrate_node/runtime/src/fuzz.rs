@@ -0,0 +1,215 @@
+//! Property-based tests of the currency invariants.
+//!
+//! Rather than hand-written scenarios, these run random sequences of signed extrinsics from a
+//! handful of accounts, across several blocks, and check after every step that:
+//!
+//! * the sum of all balances, free and reserved, is [`currency::TotalIssuance`].
+//! * no account has a free balance between zero and [`MINIMUM_BALANCE`].
+//! * nonces never decrease, and go up by exactly one for each applied extrinsic, whether its
+//!   dispatch succeeds or not. The only exception is an account that is reaped, which starts over
+//!   from zero.
+//!
+//! Signing extrinsics is slow in debug builds, so only [`DEFAULT_CASES`] cases run by default. Run
+//! more with e.g. `PROPTEST_CASES=10000 cargo test -p runtime --release fuzz`.
+
+use crate::{
+	currency,
+	shared::{
+		AccountBalance, AccountId, Balance, CurrencyCall, RuntimeCall, RuntimeEvent, StakingCall,
+		MINIMUM_BALANCE,
+	},
+	storage::StoragePrefix,
+	tests::{events, header_at, new_test_ext, signed_ext},
+	Runtime, RuntimeGenesisConfig,
+};
+use parity_scale_codec::Decode;
+use proptest::prelude::*;
+use sp_keyring::AccountKeyring;
+use sp_runtime::transaction_validity::{InvalidTransaction, TransactionValidityError};
+use sp_std::collections::btree_map::BTreeMap;
+
+/// The accounts that send extrinsics, and receive funds. Alice is also the sudo account.
+const ACCOUNTS: [AccountKeyring; 4] =
+	[AccountKeyring::Alice, AccountKeyring::Bob, AccountKeyring::Charlie, AccountKeyring::Dave];
+
+/// The number of cases to run, unless `PROPTEST_CASES` is set.
+const DEFAULT_CASES: u32 = 16;
+
+/// The nonce of an extrinsic, relative to that of its sender.
+#[derive(Debug, Clone, Copy)]
+enum Nonce {
+	Current,
+	Stale,
+	Future,
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+	/// Apply an extrinsic signed by `signer`.
+	Apply { signer: AccountKeyring, call: RuntimeCall, nonce: Nonce, tip: Option<Balance> },
+	/// Finalize the current block, and start the next one.
+	NextBlock,
+}
+
+fn account() -> impl Strategy<Value = AccountKeyring> {
+	prop::sample::select(&ACCOUNTS[..])
+}
+
+/// Mostly amounts around [`MINIMUM_BALANCE`] and the genesis balances, but also some that overflow.
+fn amount() -> impl Strategy<Value = Balance> {
+	prop_oneof![
+		4 => 0..MINIMUM_BALANCE * 3,
+		4 => 0..1_000 as Balance,
+		1 => Just(Balance::MAX),
+	]
+}
+
+fn call() -> impl Strategy<Value = RuntimeCall> {
+	let dest = || account().prop_map(|account| account.public());
+	prop_oneof![
+		1 => (dest(), amount()).prop_map(|(dest, amount)| {
+			RuntimeCall::Currency(CurrencyCall::Mint { dest, amount })
+		}),
+		3 => (dest(), amount()).prop_map(|(dest, amount)| {
+			RuntimeCall::Currency(CurrencyCall::Transfer { dest, amount })
+		}),
+		1 => dest().prop_map(|dest| RuntimeCall::Currency(CurrencyCall::TransferAll { dest })),
+		1 => amount().prop_map(|amount| RuntimeCall::Staking(StakingCall::Bond { amount })),
+	]
+}
+
+fn nonce() -> impl Strategy<Value = Nonce> {
+	prop_oneof![8 => Just(Nonce::Current), 1 => Just(Nonce::Stale), 1 => Just(Nonce::Future)]
+}
+
+fn step() -> impl Strategy<Value = Step> {
+	prop_oneof![
+		10 => (account(), call(), nonce(), prop::option::of(0..MINIMUM_BALANCE * 2)).prop_map(
+			|(signer, call, nonce, tip)| Step::Apply { signer, call, nonce, tip }
+		),
+		1 => Just(Step::NextBlock),
+	]
+}
+
+/// The genesis balance of each account in [`ACCOUNTS`], where zero means that it does not exist.
+fn genesis() -> impl Strategy<Value = Vec<Balance>> {
+	prop::collection::vec(prop_oneof![Just(0), MINIMUM_BALANCE..1_000], ACCOUNTS.len())
+}
+
+/// All accounts in [`currency::BalancesMap`], by walking its keys.
+fn all_balances() -> BTreeMap<AccountId, AccountBalance> {
+	let prefix = currency::BalancesMapPrefix::PREFIX;
+	let mut balances = BTreeMap::new();
+	let mut key = prefix.to_vec();
+	while let Some(next) = sp_io::storage::next_key(&key).filter(|next| next.starts_with(prefix)) {
+		let who = AccountId::decode(&mut &next[prefix.len()..]).expect("key ends with an account");
+		balances.insert(who, currency::BalancesMap::get(&who).expect("key exists"));
+		key = next;
+	}
+	balances
+}
+
+fn reaped() -> Vec<AccountId> {
+	events()
+		.into_iter()
+		.filter_map(|(_, event)| match event {
+			RuntimeEvent::AccountReaped { who } => Some(who),
+			_ => None,
+		})
+		.collect()
+}
+
+/// Check the invariants that must hold after every step.
+fn check_invariants() -> Result<(), TestCaseError> {
+	let balances = all_balances();
+	let total = balances
+		.values()
+		.try_fold(0 as Balance, |total, account| {
+			total.checked_add(account.free)?.checked_add(account.reserved)
+		})
+		.ok_or_else(|| TestCaseError::fail("sum of balances overflows"))?;
+	prop_assert_eq!(total, currency::TotalIssuance::get_or_default());
+	for (who, account) in &balances {
+		prop_assert!(
+			account.free == 0 || account.free >= MINIMUM_BALANCE,
+			"{:?} has free balance {} below the minimum",
+			who,
+			account.free
+		);
+	}
+	Ok(())
+}
+
+/// Apply `step`, and check that the nonce of its sender behaved, along with all other invariants.
+fn apply(number: &mut u32, step: Step) -> Result<(), TestCaseError> {
+	let Step::Apply { signer, call, nonce, tip } = step else {
+		Runtime::do_finalize_block();
+		*number += 1;
+		Runtime::do_initialize_block(&header_at(*number));
+		return check_invariants()
+	};
+
+	let who = signer.public();
+	let before = currency::BalancesMap::get_or_default(&who).nonce;
+	let nonce = match nonce {
+		Nonce::Current => before,
+		Nonce::Stale if before > 0 => before - 1,
+		Nonce::Stale | Nonce::Future => before + 1,
+	};
+	let reaped_before = reaped().len();
+	let outcome = Runtime::do_apply_extrinsic(signed_ext(signer, call, nonce, tip));
+	let after = currency::BalancesMap::get_or_default(&who).nonce;
+
+	match outcome {
+		Err(TransactionValidityError::Invalid(
+			InvalidTransaction::Stale | InvalidTransaction::Future,
+		)) => prop_assert_ne!(nonce, before),
+		_ => prop_assert_eq!(nonce, before, "extrinsic with a wrong nonce was applied"),
+	}
+	if reaped()[reaped_before..].contains(&who) {
+		prop_assert_eq!(after, 0);
+	} else if outcome.is_ok() {
+		prop_assert_eq!(after, before + 1);
+	} else {
+		prop_assert_eq!(after, before);
+	}
+	check_invariants()
+}
+
+fn config() -> ProptestConfig {
+	// the default configuration reads `PROPTEST_CASES`.
+	let config = ProptestConfig::default();
+	match std::env::var_os("PROPTEST_CASES") {
+		Some(_) => config,
+		None => ProptestConfig { cases: DEFAULT_CASES, ..config },
+	}
+}
+
+proptest! {
+	#![proptest_config(config())]
+
+	#[test]
+	fn currency_invariants_hold(
+		genesis in genesis(),
+		steps in prop::collection::vec(step(), 1..48),
+	) {
+		let balances = ACCOUNTS
+			.iter()
+			.zip(genesis)
+			.filter(|(_, balance)| *balance > 0)
+			.map(|(account, balance)| (account.public(), balance))
+			.collect();
+		let config = RuntimeGenesisConfig { balances, ..Default::default() };
+
+		new_test_ext(config).execute_with(|| {
+			check_invariants()?;
+			let mut number = 1;
+			Runtime::do_initialize_block(&header_at(number));
+			for step in steps {
+				apply(&mut number, step)?;
+			}
+			Runtime::do_finalize_block();
+			check_invariants()
+		})?;
+	}
+}
@@ -63,7 +63,8 @@
 //! node, and interacting with it via `curl`, `wscat` or a similar tool. See `encode_examples` test.
 //!
 //! > Most of [`shared::SystemCall`] instances are for you to use for learning. Try and upgrade your
-//! > chain using [`shared::SystemCall::Upgrade`]!
+//! > chain using [`shared::SystemCall::AuthorizeUpgrade`] and
+//! > [`shared::SystemCall::ApplyAuthorizedUpgrade`]!
 //!
 //! ## Main Task
 //!
@@ -101,7 +102,13 @@
 //!
 //! The final [`shared::Extrinsic`]'s `Call`, namely [`shared::RuntimeCallExt`] contains `tip`
 //! field. As the name suggests, this is some additional funds that are sent by the user to chain.
-//! Other than this optional tip, all other extrinsics are free.
+//!
+//! On top of the optional tip, every extrinsic pays a fee of `base_fee + byte_fee * encoded_len +
+//! weight_fee * weight(call)`, where the parameters are the [`shared::FeeConfig`] kept at
+//! `FeeConfig`, and the weight of each call is given by
+//! [`transaction_payment::Module::call_weight`]. If no fee config exists in storage, extrinsics are
+//! free. The fee is paid together with the tip, following the exact same rules, and wallets can
+//! estimate it with the `query_fee_info` runtime API.
 //!
 //! Tipped extrinsics are prioritized over non-tipped ones through the virtue of a higher
 //! `priority`. This is further explained in `validate_transaction` section below.
@@ -171,7 +178,8 @@
 //! In all other cases, outer `Result` is `Ok`.
 //!
 //! * If the inner dispatch is failing, your return value should look like `Ok(Err(_))`, and we
-//!   don't care which variant of `DispatchError` you return.
+//!   don't care which variant of `DispatchError` you return. The fee and tip are still paid, and
+//!   the nonce is still used up.
 //!
 //! ### `TaggedTransactionQueue::validate_transaction`
 //!
@@ -185,12 +193,17 @@
 //! * Return `Err` with [`sp_runtime::transaction_validity::TransactionValidityError::Invalid`] and
 //!   [`sp_runtime::transaction_validity::InvalidTransaction::Payment`] if the extrinsic cannot pay
 //!   for its declared tip.
-//! * Return `Err` with [`sp_runtime::transaction_validity::InvalidTransaction::Future`] or `Stale`
-//!   if the nonce is too high or too low.
+//! * Return `Err` with [`sp_runtime::transaction_validity::InvalidTransaction::Stale`] if the nonce
+//!   is too low.
+//!
+//! Unlike `apply_extrinsic`, a nonce that is too high is not an error here. Each transaction
+//! `provides` the tag `(sender, nonce)`, and a transaction with a future nonce additionally
+//! `requires` `(sender, nonce - 1)`, so the pool keeps it in its future queue until its
+//! predecessor arrives.
 //!
-//! Moreover, if tip is provided (and can paid at the time), the
-//! [`sp_runtime::transaction_validity::ValidTransaction::priority`] must be set to the tip value
-//! (use a simple saturated conversion if needed).
+//! Moreover, the [`sp_runtime::transaction_validity::ValidTransaction::priority`] is a base
+//! priority that depends on the call type, plus the tip value, if one is provided (and can be paid
+//! at the time).
 //!
 //! ### `Core_execute_block`
 //!
@@ -315,9 +328,31 @@
 #[cfg(feature = "std")]
 include!(concat!(env!("OUT_DIR"), "/wasm_binary.rs"));
 
+/// The same runtime, compiled with the `upgrade-test` feature, which only bumps its
+/// `spec_version`. Used to test runtime upgrades against a real Wasm blob.
+///
+/// Building it compiles the runtime to Wasm a second time, so it is only built with the
+/// `with-upgrade-test-runtime` feature, e.g. `cargo test -p runtime -F with-upgrade-test-runtime`.
+#[cfg(all(test, feature = "with-upgrade-test-runtime"))]
+mod upgrade_test_runtime {
+	include!(concat!(env!("OUT_DIR"), "/wasm_binary_upgrade_test.rs"));
+}
+
 const LOG_TARGET: &'static str = "frameless";
 
+pub mod consensus;
+pub mod currency;
+pub mod dispatch;
+pub mod metadata;
+pub mod multisig;
+pub mod proxy;
+#[cfg(feature = "std")]
+pub mod replay;
 pub mod shared;
+pub mod staking;
+pub mod storage;
+pub mod system;
+pub mod transaction_payment;
 
 use log::info;
 use parity_scale_codec::{Decode, Encode};
@@ -327,11 +362,12 @@ use sp_api::impl_runtime_apis;
 use sp_runtime::{
 	create_runtime_str,
 	generic::{self},
-	traits::{BlakeTwo256, Block as BlockT},
+	traits::{BlakeTwo256, Block as BlockT, SaturatedConversion},
 	transaction_validity::{
-		self, InvalidTransaction, TransactionSource, TransactionValidity, ValidTransaction,
+		InvalidTransaction, TransactionLongevity, TransactionPriority, TransactionSource,
+		TransactionValidity, TransactionValidityError, ValidTransaction,
 	},
-	ApplyExtrinsicResult, DispatchError,
+	ApplyExtrinsicResult,
 };
 use sp_std::prelude::*;
 #[cfg(feature = "std")]
@@ -348,11 +384,33 @@ use sp_version::NativeVersion;
 
 use sp_version::RuntimeVersion;
 
-use crate::shared::{
-	AccountBalance, CurrencyCall, RuntimeCall, StakingCall, SystemCall, EXTRINSICS_KEY, HEADER_KEY,
-	VALUE_KEY,
+use crate::{
+	dispatch::{Dispatchable, Hooks},
+	shared::{AccountId, CurrencyCall, RuntimeCall, SystemCall, MINIMUM_BALANCE, SUDO},
 };
 
+/// All modules of the runtime, in the order in which their [`Hooks`] are called.
+type AllModules = (
+	system::Module,
+	consensus::Module,
+	currency::Module,
+	staking::Module,
+	multisig::Module,
+	proxy::Module,
+	transaction_payment::Module,
+);
+
+/// Number of blocks for which a validated transaction stays valid in the pool before it is
+/// re-validated.
+const TRANSACTION_LONGEVITY: TransactionLongevity = 64;
+
+/// Base priority of calls that only [`shared::SUDO`] can dispatch, when sent by it.
+const SUDO_PRIORITY: TransactionPriority = TransactionPriority::MAX / 2;
+/// Base priority of all calls that are not otherwise special-cased.
+const DEFAULT_PRIORITY: TransactionPriority = 1;
+/// Base priority of [`shared::SystemCall::Remark`].
+const REMARK_PRIORITY: TransactionPriority = 0;
+
 /// Opaque types. This is what the lectures referred to as `ClientBlock`. Notice how
 /// `OpaqueExtrinsic` is merely a `Vec<u8>`.
 pub mod opaque {
@@ -366,6 +424,7 @@ pub mod opaque {
 }
 
 /// This runtime version.
+#[cfg(not(feature = "upgrade-test"))]
 #[sp_version::runtime_version]
 pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("frameless-runtime"),
@@ -378,15 +437,81 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	state_version: 1,
 };
 
+/// This runtime version, as an upgrade of the one above. Only used to build the Wasm blob that the
+/// upgrade tests upgrade to.
+#[cfg(feature = "upgrade-test")]
+#[sp_version::runtime_version]
+pub const VERSION: RuntimeVersion = RuntimeVersion {
+	spec_name: create_runtime_str!("frameless-runtime"),
+	impl_name: create_runtime_str!("frameless-runtime"),
+	authoring_version: 1,
+	spec_version: 2,
+	impl_version: 1,
+	apis: RUNTIME_API_VERSIONS,
+	transaction_version: 1,
+	state_version: 1,
+};
+
 /// The version information used to identify this runtime when compiled natively.
 #[cfg(feature = "std")]
 pub fn native_version() -> NativeVersion {
 	NativeVersion { runtime_version: VERSION, can_author_with: Default::default() }
 }
 
+sp_api::decl_runtime_apis! {
+	/// The runtime API that lets wallets estimate the fee of a transaction before submitting it.
+	pub trait TransactionPaymentApi {
+		/// The fee of `uxt`, which is `len` bytes long once encoded, excluding its tip.
+		fn query_fee_info(uxt: <Block as BlockT>::Extrinsic, len: u32) -> shared::FeeDetails;
+	}
+
+	/// The runtime API that lets clients read the state of the runtime without computing raw
+	/// storage keys.
+	pub trait StateQueryApi {
+		/// The balance of `who`, which is all zeros if the account does not exist.
+		fn account_balance(who: AccountId) -> shared::AccountBalance;
+		/// The sum of all balances in the system.
+		fn total_issuance() -> shared::Balance;
+		/// The value set by [`SystemCall::Set`], if any.
+		fn value() -> Option<u32>;
+	}
+}
+
 /// The type that provides the genesis storage values for a new chain.
-#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize, Default))]
-pub struct RuntimeGenesisConfig;
+///
+/// All of its fields are optional in the JSON chain spec, and default to an empty chain.
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase", deny_unknown_fields, default))]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuntimeGenesisConfig {
+	/// The accounts that exist at genesis, and their initial free balance. Each must be at least
+	/// [`MINIMUM_BALANCE`].
+	pub balances: Vec<(AccountId, shared::Balance)>,
+	/// The initial stakes, bonded out of the balances above. Each staker must keep at least
+	/// [`MINIMUM_BALANCE`] of free balance.
+	pub stakes: Vec<(AccountId, shared::Balance)>,
+	/// The initial value at [`shared::VALUE_KEY`], if any.
+	pub value: Option<u32>,
+	/// The fee parameters. If not set, transactions are free.
+	pub fee_config: Option<shared::FeeConfig>,
+	/// The Aura and GRANDPA keys of each authority, in the order in which they author blocks.
+	pub authorities: Vec<(shared::AuraId, shared::GrandpaId)>,
+	/// The duration of a slot, in milliseconds. If not set, [`shared::DEFAULT_SLOT_DURATION`].
+	pub slot_duration: Option<shared::Moment>,
+}
+
+#[cfg(feature = "std")]
+impl RuntimeGenesisConfig {
+	/// A config where each of `endowed` starts with `balance`, and fees are set to
+	/// [`shared::DEFAULT_FEE_CONFIG`].
+	pub fn with_endowed(endowed: Vec<AccountId>, balance: shared::Balance) -> Self {
+		Self {
+			balances: endowed.into_iter().map(|who| (who, balance)).collect(),
+			fee_config: Some(shared::DEFAULT_FEE_CONFIG),
+			..Default::default()
+		}
+	}
+}
 
 #[cfg(feature = "std")]
 impl BuildStorage for RuntimeGenesisConfig {
@@ -394,13 +519,74 @@ impl BuildStorage for RuntimeGenesisConfig {
 		// make sure to not remove this, as it might break the node code.
 		storage.top.insert(well_known_keys::CODE.into(), WASM_BINARY.unwrap().to_vec());
 
-		// if you want more data in your genesis, add it here.
+		let mut accounts = std::collections::BTreeMap::<AccountId, shared::AccountBalance>::new();
+		let mut total_issuance: shared::Balance = 0;
+		for (who, free) in &self.balances {
+			if *free < MINIMUM_BALANCE {
+				return Err(format!("genesis balance of {} is below the minimum balance", who))
+			}
+			if accounts
+				.insert(*who, shared::AccountBalance { free: *free, ..Default::default() })
+				.is_some()
+			{
+				return Err(format!("duplicate genesis balance for {}", who))
+			}
+			total_issuance = total_issuance
+				.checked_add(*free)
+				.ok_or_else(|| "genesis total issuance overflows".to_string())?;
+		}
+
+		for (who, stake) in &self.stakes {
+			let account = accounts
+				.get_mut(who)
+				.ok_or_else(|| format!("genesis staker {} has no balance", who))?;
+			account.free = account
+				.free
+				.checked_sub(*stake)
+				.filter(|free| *free >= MINIMUM_BALANCE)
+				.ok_or_else(|| {
+					format!("genesis stake of {} leaves it below the minimum balance", who)
+				})?;
+			account.reserved += stake;
+		}
+
+		for (who, account) in accounts {
+			storage.top.insert(currency::BalancesMap::key(&who), account.encode());
+		}
+		storage.top.insert(currency::TotalIssuance::key(), total_issuance.encode());
+		if let Some(value) = self.value {
+			storage.top.insert(system::Value::key(), value.encode());
+		}
+		if let Some(fee_config) = self.fee_config {
+			storage
+				.top
+				.insert(transaction_payment::FeeConfigValue::key(), fee_config.encode());
+		}
+
+		let (aura, grandpa): (Vec<_>, Vec<_>) = self.authorities.iter().cloned().unzip();
+		fn unique<K: Ord>(keys: &[K]) -> bool {
+			keys.iter().collect::<std::collections::BTreeSet<_>>().len() == keys.len()
+		}
+		if !unique(&aura) || !unique(&grandpa) {
+			return Err("duplicate genesis authority".to_string())
+		}
+		if !self.authorities.is_empty() {
+			storage.top.insert(consensus::AuraAuthorities::key(), aura.encode());
+			storage.top.insert(consensus::GrandpaAuthorities::key(), grandpa.encode());
+		}
+		match self.slot_duration {
+			Some(0) => return Err("genesis slot duration must not be zero".to_string()),
+			Some(slot_duration) => {
+				storage.top.insert(consensus::SlotDuration::key(), slot_duration.encode());
+			},
+			None => {},
+		}
 		Ok(())
 	}
 }
 
 /// The main struct in this module. In frame this comes from `construct_runtime!` macro.
-#[derive(Debug, Encode, Decode, PartialEq, Eq, Clone)]
+#[derive(Debug, Encode, Decode, scale_info::TypeInfo, PartialEq, Eq, Clone)]
 pub struct Runtime;
 
 impl Runtime {
@@ -418,11 +604,14 @@ impl Runtime {
 		}
 	}
 
-	fn get_state<T: Decode>(key: &[u8]) -> Option<T> {
+	pub(crate) fn get_state<T: Decode>(key: &[u8]) -> Option<T> {
 		sp_io::storage::get(key).and_then(|d| T::decode(&mut &*d).ok())
 	}
 
-	fn mutate_state<T: Decode + Encode + Default>(key: &[u8], update: impl FnOnce(&mut T)) {
+	pub(crate) fn mutate_state<T: Decode + Encode + Default>(
+		key: &[u8],
+		update: impl FnOnce(&mut T),
+	) {
 		let mut value = Self::get_state(key).unwrap_or_default();
 		update(&mut value);
 		sp_io::storage::set(key, &value.encode());
@@ -433,23 +622,30 @@ impl Runtime {
 			target: LOG_TARGET,
 			"Entering initialize_block. header: {:?} / version: {:?}", header, VERSION.spec_version
 		);
-		sp_io::storage::set(&HEADER_KEY, &header.encode());
-		sp_io::storage::clear(&EXTRINSICS_KEY);
+		system::CurrentHeader::set(header);
+		system::Extrinsics::kill();
+		system::Module::reset_events();
+		system::ExecutionPhase::set(&shared::Phase::Initialization);
+
+		if system::Module::maybe_run_migrations::<AllModules>() {
+			info!(target: LOG_TARGET, "Ran migrations of spec version {}", VERSION.spec_version);
+		}
+		<AllModules as Hooks>::on_initialize(header.number);
 	}
 
 	fn do_finalize_block() -> <Block as BlockT>::Header {
-		info!(target: LOG_TARGET, "Entering finalize_block."); // Seb's Code
-													   // fetch the header that was given to us at the beginning of the block.
-		let mut header = Self::get_state::<<Block as BlockT>::Header>(HEADER_KEY)
-			.expect("We initialized with header, it never got mutated, qed"); // Starter Code, but changed to `mut` by seb
-																  // and make sure to _remove_ it.
-		sp_io::storage::clear(&HEADER_KEY); // Starter Code
+		info!(target: LOG_TARGET, "Entering finalize_block.");
+		// fetch the header that was given to us at the beginning of the block, and make sure to
+		// _remove_ it.
+		let mut header = system::CurrentHeader::take()
+			.expect("We initialized with header, it never got mutated, qed");
 
-		// TODO: set correct state root and extrinsics root, as described in the corresponding test
-		// case.
+		system::ExecutionPhase::set(&shared::Phase::Finalization);
+		<AllModules as Hooks>::on_finalize(header.number);
+		system::ExecutionPhase::kill();
 
 		// Set extrinsic root
-		let extrinsics = Self::get_state::<Vec<Vec<u8>>>(EXTRINSICS_KEY).unwrap_or_default();
+		let extrinsics = system::Extrinsics::get().unwrap_or_default();
 		header.extrinsics_root =
 			BlakeTwo256::ordered_trie_root(extrinsics, sp_runtime::StateVersion::V0);
 
@@ -465,33 +661,116 @@ impl Runtime {
 
 	/// Your code path to execute a block that has been previously authored.
 	///
-	/// Study this carefully, but you probably don't need to change it, other than providing a
-	/// proper `do_apply_extrinsic`.
+	/// This must go through the exact same steps as block authoring, so that both come to the same
+	/// state root.
 	fn do_execute_block(block: Block) {
 		info!(target: LOG_TARGET, "Entering execute_block. block: {:?}", block);
-		sp_io::storage::clear(&EXTRINSICS_KEY);
+		Self::initialize_imported_block(&block);
 
 		for extrinsic in block.clone().extrinsics {
 			let _outcome = Runtime::do_apply_extrinsic(extrinsic)
 				.expect("A block author has provided us with an invalid block; bailing; qed");
 		}
 
-		// check state root. Clean the state prior to asking for the root.
-		sp_io::storage::clear(&HEADER_KEY);
+		// NOTE: if we forget to check the state root, how can you mess with the blockchain?
+		let (state_root, extrinsics_root) = Self::finalize_imported_block(block.header.number);
+		assert_eq!(block.header.state_root, state_root);
+		assert_eq!(block.header.extrinsics_root, extrinsics_root);
+
+		info!(target: LOG_TARGET, "Finishing block import.");
+		Self::print_state();
+	}
+
+	/// The first steps of [`Self::do_execute_block`], before any extrinsic is applied.
+	fn initialize_imported_block(block: &Block) {
+		assert!(
+			block.extrinsics.first().and_then(Self::timestamp_inherent).is_some(),
+			"A block must start with the timestamp inherent"
+		);
+		Self::do_initialize_block(&block.header);
+	}
 
-		// NOTE: if we forget to do this, how can you mess with the blockchain?
+	/// The last steps of [`Self::do_execute_block`], after all extrinsics are applied. Returns the
+	/// state and extrinsics roots that the header of the block must have.
+	fn finalize_imported_block(number: shared::BlockNumber) -> (H256, H256) {
+		system::ExecutionPhase::set(&shared::Phase::Finalization);
+		<AllModules as Hooks>::on_finalize(number);
+		system::ExecutionPhase::kill();
+
+		// Clean the state prior to asking for the root.
+		system::CurrentHeader::kill();
 		let raw_state_root = &sp_io::storage::root(VERSION.state_version())[..];
 		let state_root = H256::decode(&mut &raw_state_root[..]).unwrap();
-		assert_eq!(block.header.state_root, state_root);
 
-		// check extrinsics root
-		let extrinsics = Self::get_state::<Vec<Vec<u8>>>(EXTRINSICS_KEY).unwrap_or_default();
+		let extrinsics = system::Extrinsics::get().unwrap_or_default();
 		let extrinsics_root =
 			BlakeTwo256::ordered_trie_root(extrinsics, sp_runtime::StateVersion::V0);
-		assert_eq!(block.header.extrinsics_root, extrinsics_root);
+		(state_root, extrinsics_root)
+	}
 
-		info!(target: LOG_TARGET, "Finishing block import.");
-		Self::print_state();
+	/// The timestamp set by `ext`, if it is an unsigned [`SystemCall::SetTimestamp`].
+	fn timestamp_inherent(ext: &<Block as BlockT>::Extrinsic) -> Option<shared::Moment> {
+		match ext.function.call {
+			RuntimeCall::System(SystemCall::SetTimestamp { now }) if ext.signature.is_none() =>
+				Some(now),
+			_ => None,
+		}
+	}
+
+	/// Apply the timestamp inherent `ext`, which sets the timestamp to `now`.
+	///
+	/// Unlike signed extrinsics, an inherent that fails to dispatch is not included in the block at
+	/// all, which makes the block invalid.
+	fn apply_timestamp_inherent(
+		ext: <Block as BlockT>::Extrinsic,
+		now: shared::Moment,
+	) -> ApplyExtrinsicResult {
+		// only the first extrinsic of the block can set the timestamp, so it happens exactly once.
+		if system::Extrinsics::exists() {
+			return Err(InvalidTransaction::BadMandatory.into())
+		}
+		consensus::Module::check_timestamp(now)
+			.and_then(|_| system::Module::set_timestamp(now))
+			.map_err(|_| InvalidTransaction::BadMandatory)?;
+		system::Extrinsics::set(&vec![ext.encode()]);
+		Ok(Ok(()))
+	}
+
+	/// The inherents that the author of a block should put at its beginning, given its local clock
+	/// says `local_now`.
+	fn do_inherent_extrinsics(local_now: shared::Moment) -> Vec<<Block as BlockT>::Extrinsic> {
+		let now = system::Module::next_timestamp(local_now);
+		let call = RuntimeCall::System(SystemCall::SetTimestamp { now });
+		vec![shared::Extrinsic::new_unsigned(shared::RuntimeCallExt { call, nonce: 0, tip: None })]
+	}
+
+	/// Check the inherents of `block` against the local clock of the node, which says `local_now`.
+	///
+	/// The timestamp must not be more than [`shared::MAX_TIMESTAMP_DRIFT`] ahead of it. Everything
+	/// else about the inherent is checked when the block is executed.
+	fn do_check_inherents(block: &Block, local_now: shared::Moment) -> Result<(), &'static str> {
+		let now = block
+			.extrinsics
+			.first()
+			.and_then(Self::timestamp_inherent)
+			.ok_or("Block is missing the timestamp inherent")?;
+		if now > local_now.saturating_add(shared::MAX_TIMESTAMP_DRIFT) {
+			return Err("Timestamp is too far in the future")
+		}
+		Ok(())
+	}
+
+	/// Check the signature of `ext`, and return its signer.
+	fn check_signature(
+		ext: &<Block as BlockT>::Extrinsic,
+	) -> Result<AccountId, TransactionValidityError> {
+		// no signature, invalid transaction
+		let (address, signature, _) = ext.signature.as_ref().ok_or(InvalidTransaction::BadProof)?;
+		if !sp_io::crypto::sr25519_verify(signature, &ext.function.encode(), address) {
+			// bad signature
+			return Err(InvalidTransaction::BadProof.into())
+		}
+		Ok(*address)
 	}
 
 	/// Apply a single extrinsic.
@@ -502,645 +781,49 @@ impl Runtime {
 	fn do_apply_extrinsic(ext: <Block as BlockT>::Extrinsic) -> ApplyExtrinsicResult {
 		info!(target: LOG_TARGET, "Entering apply_extrinsic: {:?}", ext);
 
-		// TODO: we don't have a means of dispatch, implement it! You probably want to match on
-		// `ext.function.call`, and start implementing different arms one at a time. Also, this is
-		// called from both authoring and importing. It should "note" any extrinsic that
-		// successfully executes in EXTRINSICS_KEY.
-
-		// use shared::{RuntimeCall, SystemCall, VALUE_KEY};
-		// match ext.function.call {
-		// 	RuntimeCall::System(SystemCall::Set { value }) =>
-		// 		sp_io::storage::set(&VALUE_KEY, &value.encode()),
-		// 	_ => unimplemented!(),
-		// }
-
-		// for nor we note any extrinsic in the block, BUT BE AWARE, you should only do this if this
-		// function is returning `Ok(_)`.
-		// Self::mutate_state::<Vec<Vec<u8>>>(EXTRINSICS_KEY, |current| {
-		// 	current.push(ext.encode());
-		// });
-
 		// The return type of this function, if called in the context of block authoring, means:
 		// Ok(_) => in the block
 		//   Ok(Ok(_)) => in the block, and succeed to do whatever it wanted to do
 		//   Ok(Err(_)) => in the block, and failed to do whatever it wanted to do
 		// Err(_) => not in the block
+		if let Some(now) = Self::timestamp_inherent(&ext) {
+			return Self::apply_timestamp_inherent(ext, now)
+		}
+		let sender = Self::check_signature(&ext)?;
 
-		// TODO: Need to use safe math for all arithmetic operations, but didn't have time to change
-		// it       but I did some manual overflow checks.
-		let payload = ext.function.encode();
-		let signature = ext.signature.clone();
-		let call = ext.function.call.clone();
-		match signature {
-			Some((address, signature, _)) => {
-				if !sp_io::crypto::sr25519_verify(&signature, &payload, &address) {
-					// bad signature
-					Err(sp_runtime::transaction_validity::TransactionValidityError::Invalid(
-						InvalidTransaction::BadProof,
-					))
-				} else {
-					// good signature
-
-					let account_storage_key =
-						[b"BalancesMap", address.encode().as_slice()].concat();
-
-					let mut account_balance =
-						Self::get_state::<AccountBalance>(&account_storage_key).unwrap_or_default();
-
-					//check if nonce is correct
-					if account_balance.nonce < ext.function.nonce {
-						// nonce is too high
-						return Err(transaction_validity::TransactionValidityError::Invalid(
-							InvalidTransaction::Future,
-						))
-					} else if account_balance.nonce > ext.function.nonce {
-						// nonce is too low
-						return Err(transaction_validity::TransactionValidityError::Invalid(
-							InvalidTransaction::Stale,
-						))
-					}
+		let account = currency::BalancesMap::get_or_default(&sender);
+		if account.nonce < ext.function.nonce {
+			return Err(InvalidTransaction::Future.into())
+		} else if account.nonce > ext.function.nonce {
+			return Err(InvalidTransaction::Stale.into())
+		}
 
-					match call {
-						RuntimeCall::Currency(currency_call) => match currency_call {
-							CurrencyCall::Mint { dest, amount } => {
-								// check if account has enough free balance to pay the tip
-								if let Some(tip) = ext.function.tip {
-									if account_balance.free - shared::MINIMUM_BALANCE < tip {
-										// not enough free balance
-										return Err(
-											transaction_validity::TransactionValidityError::Invalid(
-												InvalidTransaction::Payment,
-											),
-										)
-									}
-									// decrement the origin's free balance
-									account_balance.free -= tip;
-									sp_io::storage::set(
-										&account_storage_key,
-										&account_balance.encode(),
-									);
-
-									// update the treasury's storage with added tip
-									Self::mutate_state(
-										&[b"BalancesMap", shared::TREASURY.encode().as_slice()]
-											.concat(),
-										|current: &mut AccountBalance| {
-											if current.free + tip >= shared::MINIMUM_BALANCE {
-												current.free += tip;
-											} else {
-												Self::mutate_state(
-													b"TotalIssuance",
-													|total_issuance: &mut u128| {
-														*total_issuance -= tip;
-													},
-												)
-											}
-										},
-									);
-								}
-
-								//get dest account balance
-								let dest_storage_key =
-									[b"BalancesMap", dest.encode().as_slice()].concat();
-
-								let mut dest_balance =
-									Self::get_state::<AccountBalance>(&dest_storage_key)
-										.unwrap_or_default();
-
-								//check for account balance overflow and TotalIssuance overflow
-								let mut total_issuance =
-									Self::get_state::<u128>(b"TotalIssuance").unwrap_or_default();
-
-								if u128::MAX - dest_balance.free < amount ||
-									u128::MAX - total_issuance < amount
-								{
-									Self::mutate_state::<Vec<Vec<u8>>>(
-										&EXTRINSICS_KEY,
-										|extrinsics| extrinsics.push(ext.encode()),
-									);
-									// execution will cause an overflow
-									return Ok(Err(DispatchError::Arithmetic(
-										sp_runtime::ArithmeticError::Overflow,
-									)))
-								}
-
-								// check if the free balance is below the minimum balance
-								if dest_balance.free + amount < shared::MINIMUM_BALANCE {
-									// not enough free balance
-									Self::mutate_state::<Vec<Vec<u8>>>(
-										&EXTRINSICS_KEY,
-										|extrinsics| extrinsics.push(ext.encode()),
-									);
-									return Ok(Err(DispatchError::Other(
-										"Free balance is below the minimum balance",
-									)))
-								}
-
-								// check if the sender is SUDO
-								if address != sp_core::sr25519::Public::from_raw(shared::SUDO) {
-									Self::mutate_state::<Vec<Vec<u8>>>(
-										&EXTRINSICS_KEY,
-										|extrinsics| extrinsics.push(ext.encode()),
-									);
-									// dest does not have super user access to mint
-									return Ok(Err(DispatchError::BadOrigin))
-								}
-
-								// increment the total issuance
-								total_issuance += amount;
-
-								if dest_storage_key == account_storage_key {
-									// minting to self
-									//increment account's free balance
-									account_balance.free += amount;
-								} else {
-									//minting to others
-									//increment the dest's free balance
-									dest_balance.free += amount;
-									sp_io::storage::set(&dest_storage_key, &dest_balance.encode());
-								}
-
-								// update the storage
-								sp_io::storage::set(b"TotalIssuance", &total_issuance.encode());
-
-								account_balance.nonce += 1;
-								// update the storage for account
-								sp_io::storage::set(
-									&account_storage_key,
-									&account_balance.encode(),
-								);
-							},
-							CurrencyCall::Transfer { dest, amount } => {
-								// check if account has enough free balance to pay the tip
-								if let Some(tip) = ext.function.tip {
-									if account_balance.free - shared::MINIMUM_BALANCE < tip {
-										// not enough free balance
-										return Err(
-											transaction_validity::TransactionValidityError::Invalid(
-												InvalidTransaction::Payment,
-											),
-										)
-									}
-									// decrement the origin's free balance
-									account_balance.free -= tip;
-									sp_io::storage::set(
-										&account_storage_key,
-										&account_balance.encode(),
-									);
-
-									// update the treasury's storage with added tip
-									Self::mutate_state(
-										&[b"BalancesMap", shared::TREASURY.encode().as_slice()]
-											.concat(),
-										|current: &mut AccountBalance| {
-											if current.free + tip >= shared::MINIMUM_BALANCE {
-												current.free += tip;
-											} else {
-												Self::mutate_state(
-													b"TotalIssuance",
-													|total_issuance: &mut u128| {
-														*total_issuance -= tip;
-													},
-												)
-											}
-										},
-									);
-								}
-
-								//get dest account balance
-								let dest_storage_key =
-									[b"BalancesMap", dest.encode().as_slice()].concat();
-
-								let mut dest_balance =
-									Self::get_state::<AccountBalance>(&dest_storage_key)
-										.unwrap_or_default();
-
-								//check for account balance overflow
-								if u128::MAX - dest_balance.free < amount {
-									Self::mutate_state::<Vec<Vec<u8>>>(
-										&EXTRINSICS_KEY,
-										|extrinsics| extrinsics.push(ext.encode()),
-									);
-									// execution will cause an overflow
-									return Ok(Err(DispatchError::Arithmetic(
-										sp_runtime::ArithmeticError::Overflow,
-									)))
-								}
-
-								// check if the free balance is below the minimum balance
-								if account_balance.free - shared::MINIMUM_BALANCE < amount ||
-									dest_balance.free + amount < shared::MINIMUM_BALANCE
-								{
-									Self::mutate_state::<Vec<Vec<u8>>>(
-										&EXTRINSICS_KEY,
-										|extrinsics| extrinsics.push(ext.encode()),
-									);
-									// not enough free balance
-									return Ok(Err(DispatchError::Other(
-										"Free balance is below the minimum balance",
-									)))
-								}
-
-								//check if transfer to self
-								if account_storage_key != dest_storage_key {
-									// decrement the origin's free balance
-									account_balance.free -= amount;
-
-									// increment the dest's free balance
-									dest_balance.free += amount;
-
-									sp_io::storage::set(&dest_storage_key, &dest_balance.encode());
-								}
-
-								account_balance.nonce += 1;
-								// update the storage
-								sp_io::storage::set(
-									&account_storage_key,
-									&account_balance.encode(),
-								);
-							},
-							CurrencyCall::TransferAll { dest } => {
-								// check if account has enough free balance to pay the tip
-								if let Some(tip) = ext.function.tip {
-									if account_balance.free - shared::MINIMUM_BALANCE < tip {
-										// not enough free balance
-										return Err(
-											transaction_validity::TransactionValidityError::Invalid(
-												InvalidTransaction::Payment,
-											),
-										)
-									}
-									// decrement the origin's free balance
-									account_balance.free -= tip;
-									sp_io::storage::set(
-										&account_storage_key,
-										&account_balance.encode(),
-									);
-
-									// update the treasury's storage with added tip
-									Self::mutate_state(
-										&[b"BalancesMap", shared::TREASURY.encode().as_slice()]
-											.concat(),
-										|current: &mut AccountBalance| {
-											if current.free + tip >= shared::MINIMUM_BALANCE {
-												current.free += tip;
-											} else {
-												Self::mutate_state(
-													b"TotalIssuance",
-													|total_issuance: &mut u128| {
-														*total_issuance -= tip;
-													},
-												)
-											}
-										},
-									);
-								}
-
-								//get dest account balance
-								let dest_storage_key =
-									[b"BalancesMap", dest.encode().as_slice()].concat();
-
-								let mut dest_balance =
-									Self::get_state::<AccountBalance>(&dest_storage_key)
-										.unwrap_or_default();
-
-								// check if sender has reserved balance
-								if account_balance.reserved > 0 {
-									Self::mutate_state::<Vec<Vec<u8>>>(
-										&EXTRINSICS_KEY,
-										|extrinsics| extrinsics.push(ext.encode()),
-									);
-									// origin has reserved balance
-									return Ok(Err(DispatchError::BadOrigin))
-								}
-
-								// check arithmatic overflow
-								if u128::MAX - dest_balance.free < account_balance.free {
-									Self::mutate_state::<Vec<Vec<u8>>>(
-										&EXTRINSICS_KEY,
-										|extrinsics| extrinsics.push(ext.encode()),
-									);
-									// execution will cause an overflow
-									return Ok(Err(DispatchError::Arithmetic(
-										sp_runtime::ArithmeticError::Overflow,
-									)))
-								}
-
-								//check if transfer to self
-								if dest_storage_key != account_storage_key {
-									// increment the dest's free balance
-									dest_balance.free += account_balance.free;
-
-									// remove the origin's account
-									sp_io::storage::clear(&account_storage_key);
-									// update the storage
-									sp_io::storage::set(&dest_storage_key, &dest_balance.encode());
-								} else {
-									account_balance.nonce += 1;
-									sp_io::storage::set(
-										&account_storage_key,
-										&account_balance.encode(),
-									);
-								}
-							},
-						},
-						RuntimeCall::Staking(staking_call) => match staking_call {
-							StakingCall::Bond { amount } => {
-								// check if account has enough free balance to pay the tip
-								if let Some(tip) = ext.function.tip {
-									if account_balance.free - shared::MINIMUM_BALANCE < tip {
-										// not enough free balance
-										return Err(
-											transaction_validity::TransactionValidityError::Invalid(
-												InvalidTransaction::Payment,
-											),
-										)
-									}
-									// decrement the origin's free balance
-									account_balance.free -= tip;
-									// update the storage
-									sp_io::storage::set(
-										&account_storage_key,
-										&account_balance.encode(),
-									);
-
-									// update the treasury's storage with added tip
-									Self::mutate_state(
-										&[b"BalancesMap", shared::TREASURY.encode().as_slice()]
-											.concat(),
-										|current: &mut AccountBalance| {
-											if current.free + tip >= shared::MINIMUM_BALANCE {
-												current.free += tip;
-											} else {
-												Self::mutate_state(
-													b"TotalIssuance",
-													|total_issuance: &mut u128| {
-														*total_issuance -= tip;
-													},
-												)
-											}
-										},
-									);
-								}
-
-								//check if the sender has enough free balance
-								if account_balance.free - shared::MINIMUM_BALANCE < amount {
-									Self::mutate_state::<Vec<Vec<u8>>>(
-										&EXTRINSICS_KEY,
-										|extrinsics| extrinsics.push(ext.encode()),
-									);
-									// not enough free balance
-									return Ok(Err(DispatchError::BadOrigin))
-								}
-
-								//check for arithmetic overflow
-								if u128::MAX - account_balance.reserved < amount {
-									Self::mutate_state::<Vec<Vec<u8>>>(
-										&EXTRINSICS_KEY,
-										|extrinsics| extrinsics.push(ext.encode()),
-									);
-									// execution will cause an overflow
-									return Ok(Err(DispatchError::Arithmetic(
-										sp_runtime::ArithmeticError::Overflow,
-									)))
-								}
-
-								// decrement the origin's free balance
-								account_balance.free -= amount;
-
-								// increment the origin's reserved balance
-								account_balance.reserved += amount;
-
-								account_balance.nonce += 1;
-
-								// update the storage
-								sp_io::storage::set(
-									&account_storage_key,
-									&account_balance.encode(),
-								);
-							},
-						},
-						RuntimeCall::System(system_call) => {
-							match system_call {
-								SystemCall::Remark { .. } => {
-									// check if account has enough free balance to pay the tip
-									if let Some(tip) = ext.function.tip {
-										if account_balance.free - shared::MINIMUM_BALANCE < tip {
-											// not enough free balance
-											return Err(transaction_validity::TransactionValidityError::Invalid(
-								InvalidTransaction::Payment,
-							))
-										}
-										// decrement the origin's free balance
-										account_balance.free -= tip;
-										// update the storage
-										sp_io::storage::set(
-											&account_storage_key,
-											&account_balance.encode(),
-										);
-
-										// update the treasury's storage with added tip
-										Self::mutate_state(
-											&[b"BalancesMap", shared::TREASURY.encode().as_slice()]
-												.concat(),
-											|current: &mut AccountBalance| {
-												if current.free + tip >= shared::MINIMUM_BALANCE {
-													current.free += tip;
-												} else {
-													Self::mutate_state(
-														b"TotalIssuance",
-														|total_issuance: &mut u128| {
-															*total_issuance -= tip;
-														},
-													)
-												}
-											},
-										);
-									}
-
-									account_balance.nonce += 1;
-
-									// update the storage
-									sp_io::storage::set(
-										&account_storage_key,
-										&account_balance.encode(),
-									);
-								},
-								SystemCall::SudoRemark { .. } => {
-									// check if account has enough free balance to pay the tip
-									if let Some(tip) = ext.function.tip {
-										if account_balance.free - shared::MINIMUM_BALANCE < tip {
-											// not enough free balance
-											return Err(transaction_validity::TransactionValidityError::Invalid(
-								InvalidTransaction::Payment,
-							))
-										}
-										// decrement the origin's free balance
-										account_balance.free -= tip;
-										// update the storage
-										sp_io::storage::set(
-											&account_storage_key,
-											&account_balance.encode(),
-										);
-
-										// update the treasury's storage with added tip
-										Self::mutate_state(
-											&[b"BalancesMap", shared::TREASURY.encode().as_slice()]
-												.concat(),
-											|current: &mut AccountBalance| {
-												if current.free + tip >= shared::MINIMUM_BALANCE {
-													current.free += tip;
-												} else {
-													Self::mutate_state(
-														b"TotalIssuance",
-														|total_issuance: &mut u128| {
-															*total_issuance -= tip;
-														},
-													)
-												}
-											},
-										);
-									}
-
-									// check if the sender is SUDO
-									if address != sp_core::sr25519::Public::from_raw(shared::SUDO) {
-										// dest does not have super user access to mint
-										Self::mutate_state::<Vec<Vec<u8>>>(
-											&EXTRINSICS_KEY,
-											|extrinsics| extrinsics.push(ext.encode()),
-										);
-										return Ok(Err(DispatchError::BadOrigin))
-									}
-
-									account_balance.nonce += 1;
-
-									// update the storage
-									sp_io::storage::set(
-										&account_storage_key,
-										&account_balance.encode(),
-									);
-								},
-								SystemCall::Set { value } => {
-									// check if account has enough free balance to pay the tip
-									if let Some(tip) = ext.function.tip {
-										if account_balance.free - shared::MINIMUM_BALANCE < tip {
-											// not enough free balance
-											return Err(transaction_validity::TransactionValidityError::Invalid(
-								InvalidTransaction::Payment,
-							))
-										}
-										// decrement the origin's free balance
-										account_balance.free -= tip;
-										// update the storage
-										sp_io::storage::set(
-											&account_storage_key,
-											&account_balance.encode(),
-										);
-
-										// update the treasury's storage with added tip
-										Self::mutate_state(
-											&[b"BalancesMap", shared::TREASURY.encode().as_slice()]
-												.concat(),
-											|current: &mut AccountBalance| {
-												if current.free + tip >= shared::MINIMUM_BALANCE {
-													current.free += tip;
-												} else {
-													Self::mutate_state(
-														b"TotalIssuance",
-														|total_issuance: &mut u128| {
-															*total_issuance -= tip;
-														},
-													)
-												}
-											},
-										);
-									}
-
-									// set the value in storage.
-									sp_io::storage::set(&VALUE_KEY, &value.encode());
-
-									account_balance.nonce += 1;
-
-									// update the storage
-									sp_io::storage::set(
-										&account_storage_key,
-										&account_balance.encode(),
-									);
-								},
-								SystemCall::Upgrade { code } => {
-									// check if account has enough free balance to pay the tip
-									if let Some(tip) = ext.function.tip {
-										if account_balance.free - shared::MINIMUM_BALANCE < tip {
-											// not enough free balance
-											return Err(transaction_validity::TransactionValidityError::Invalid(
-								InvalidTransaction::Payment,
-							))
-										}
-										// decrement the origin's free balance
-										account_balance.free -= tip;
-										sp_io::storage::set(
-											&account_storage_key,
-											&account_balance.encode(),
-										);
-
-										// update the treasury's storage with added tip
-										Self::mutate_state(
-											&[b"BalancesMap", shared::TREASURY.encode().as_slice()]
-												.concat(),
-											|current: &mut AccountBalance| {
-												if current.free + tip >= shared::MINIMUM_BALANCE {
-													current.free += tip;
-												} else {
-													Self::mutate_state(
-														b"TotalIssuance",
-														|total_issuance: &mut u128| {
-															*total_issuance -= tip;
-														},
-													)
-												}
-											},
-										);
-									}
-
-									// check if the sender is SUDO
-									if address != sp_core::sr25519::Public::from_raw(shared::SUDO) {
-										// dest does not have super user access to mint
-										Self::mutate_state::<Vec<Vec<u8>>>(
-											&EXTRINSICS_KEY,
-											|extrinsics| extrinsics.push(ext.encode()),
-										);
-										return Ok(Err(DispatchError::BadOrigin))
-									}
-
-									// set the code in storage.
-									sp_io::storage::set(b":code", &code);
-
-									account_balance.nonce += 1;
-									// update the storage
-									sp_io::storage::set(
-										&account_storage_key,
-										&account_balance.encode(),
-									);
-								},
-							}
-						},
-					}
+		// the fee and tip are paid and the nonce is used up prior to anything else, and neither is
+		// undone if the dispatch fails, so that a failing extrinsic cannot be applied again.
+		let index = system::Extrinsics::get_or_default().len() as u32;
+		system::ExecutionPhase::set(&shared::Phase::ApplyExtrinsic(index));
+		let encoded = ext.encode();
+		let fee =
+			transaction_payment::Module::fee_details(&ext.function.call, encoded.len() as u32)
+				.final_fee();
+		transaction_payment::Module::charge(&sender, fee, ext.function.tip)
+			.map_err(|_| InvalidTransaction::Payment)?;
+		currency::BalancesMap::mutate(&sender, |account| account.nonce += 1);
+
+		// besides the fee and the nonce, a failed dispatch leaves no trace, not even its events.
+		let call = ext.function.call.clone();
+		let outcome = storage::transactional(|| call.dispatch(&sender));
+		system::Module::deposit_event(match outcome {
+			Ok(()) => shared::RuntimeEvent::ExtrinsicSuccess,
+			Err(error) => shared::RuntimeEvent::ExtrinsicFailed { error },
+		});
 
-					// ext execution successful, add to extrinsics.
-					Self::mutate_state::<Vec<Vec<u8>>>(&EXTRINSICS_KEY, |extrinsics| {
-						extrinsics.push(ext.encode())
-					});
-					info!(target: LOG_TARGET, "Finishing applying extrinsic.");
-					Self::print_state();
+		// ext applied, add to extrinsics, regardless of the dispatch outcome.
+		system::Extrinsics::mutate(|extrinsics| extrinsics.push(encoded));
+		info!(target: LOG_TARGET, "Finishing applying extrinsic: {:?}", outcome);
+		Self::print_state();
 
-					Ok(Ok(()))
-				}
-			},
-			// no signature, invalid transaction
-			None => Err(sp_runtime::transaction_validity::TransactionValidityError::Invalid(
-				InvalidTransaction::BadProof,
-			)),
-		}
+		Ok(outcome)
 	}
 
 	fn do_validate_transaction(
@@ -1150,59 +833,67 @@ impl Runtime {
 	) -> TransactionValidity {
 		log::debug!(target: LOG_TARGET,"Entering validate_transaction. tx: {:?}", ext);
 
-		// TODO: we don't have a means of validating, implement it!
-		// NOTE: every transaction must provide _something_, we provide a dummy value here.
+		let address = Self::check_signature(&ext)?;
+		let account_balance = currency::BalancesMap::get_or_default(&address);
+
+		// a nonce that is already used can never become valid again.
+		let nonce = ext.function.nonce;
+		if nonce < account_balance.nonce {
+			return Err(InvalidTransaction::Stale.into())
+		}
+
+		// the fee and tip must be payable without taking the sender below the minimum balance.
+		let tip = ext.function.tip.unwrap_or_default();
+		let fee =
+			transaction_payment::Module::fee_details(&ext.function.call, ext.encoded_size() as u32)
+				.final_fee();
+		if (fee > 0 || ext.function.tip.is_some()) &&
+			account_balance
+				.free
+				.checked_sub(fee.saturating_add(tip))
+				.map_or(true, |remaining| remaining < MINIMUM_BALANCE)
+		{
+			return Err(InvalidTransaction::Payment.into())
+		}
+
+		// a future nonce is still valid, but the pool must keep it in its future queue until the
+		// transaction that provides the previous nonce of the sender is ready.
+		let requires = if nonce > account_balance.nonce {
+			vec![(address, nonce - 1).encode()]
+		} else {
+			vec![]
+		};
+		let provides = vec![(address, nonce).encode()];
+
+		let priority = Self::call_priority(&address, &ext.function.call)
+			.saturating_add(tip.saturated_into::<TransactionPriority>());
 
 		info!(target: LOG_TARGET, "Finishing validating transaction.");
-		Self::print_state();
 
-		let payload = ext.function.encode();
-		match ext.signature {
-			Some((address, signature, _)) => {
-				if !sp_io::crypto::sr25519_verify(&signature, &payload, &address) {
-					// bad signature
-					Err(sp_runtime::transaction_validity::TransactionValidityError::Invalid(
-						sp_runtime::transaction_validity::InvalidTransaction::BadProof,
-					))
-				} else {
-					// good signature
-					let account_storage_key =
-						[b"BalancesMap", address.encode().as_slice()].concat();
-					let account_balance =
-						Self::get_state::<AccountBalance>(&account_storage_key).unwrap_or_default();
-
-					//initialize a valid transaction
-					let mut valid_transaction = ValidTransaction::default();
-
-					// check if account has enough free balance for tip
-					if let Some(tip) = ext.function.tip {
-						if account_balance.free - shared::MINIMUM_BALANCE < tip {
-							// not enough free balance
-							return Err(
-								sp_runtime::transaction_validity::TransactionValidityError::Invalid(
-									InvalidTransaction::Payment,
-								),
-							)
-						}
-						valid_transaction.priority = u64::try_from(tip).unwrap_or(u64::MAX);
-					}
+		Ok(ValidTransaction {
+			priority,
+			requires,
+			provides,
+			longevity: TRANSACTION_LONGEVITY,
+			propagate: true,
+		})
+	}
 
-					// check if the ext has the correct nonce
-					if ext.function.nonce < account_balance.nonce {
-						// nonce is too low
-						return Err(
-							sp_runtime::transaction_validity::TransactionValidityError::Invalid(
-								InvalidTransaction::Stale,
-							),
-						)
-					}
-					Ok(valid_transaction)
-				}
-			},
-			// no signature, invalid transaction
-			None => Err(sp_runtime::transaction_validity::TransactionValidityError::Invalid(
-				sp_runtime::transaction_validity::InvalidTransaction::BadProof,
-			)),
+	/// The base priority of `call` when sent by `sender`, before the tip is added on top.
+	///
+	/// Calls that only [`shared::SUDO`] can dispatch are prioritized over everything else, but only
+	/// if `sender` is actually [`shared::SUDO`]. Plain remarks carry no base priority at all, so
+	/// they only make it into a crowded block if they are tipped.
+	fn call_priority(sender: &AccountId, call: &RuntimeCall) -> TransactionPriority {
+		let is_sudo = *sender == AccountId::from_raw(SUDO);
+		match call {
+			RuntimeCall::Currency(CurrencyCall::Mint { .. }) |
+			RuntimeCall::System(SystemCall::SudoRemark { .. }) |
+			RuntimeCall::System(SystemCall::AuthorizeUpgrade { .. })
+				if is_sudo =>
+				SUDO_PRIORITY,
+			RuntimeCall::System(SystemCall::Remark { .. }) => REMARK_PRIORITY,
+			_ => DEFAULT_PRIORITY,
 		}
 	}
 }
@@ -1231,15 +922,33 @@ impl_runtime_apis! {
 			Self::do_finalize_block()
 		}
 
-		fn inherent_extrinsics(_data: sp_inherents::InherentData) -> Vec<<Block as BlockT>::Extrinsic> {
-			Default::default()
+		fn inherent_extrinsics(data: sp_inherents::InherentData) -> Vec<<Block as BlockT>::Extrinsic> {
+			match data.get_data::<shared::Moment>(&shared::TIMESTAMP_INHERENT_IDENTIFIER) {
+				Ok(Some(local_now)) => Self::do_inherent_extrinsics(local_now),
+				_ => Default::default(),
+			}
 		}
 
 		fn check_inherents(
-			_block: Block,
-			_data: sp_inherents::InherentData
+			block: Block,
+			data: sp_inherents::InherentData
 		) -> sp_inherents::CheckInherentsResult {
-			Default::default()
+			let mut result = sp_inherents::CheckInherentsResult::new();
+			let outcome = data
+				.get_data::<shared::Moment>(&shared::TIMESTAMP_INHERENT_IDENTIFIER)
+				.ok()
+				.flatten()
+				.ok_or("Timestamp inherent data is missing")
+				.and_then(|local_now| Self::do_check_inherents(&block, local_now));
+			if let Err(reason) = outcome {
+				result
+					.put_error(
+						shared::TIMESTAMP_INHERENT_IDENTIFIER,
+						&sp_inherents::MakeFatalError::from(reason),
+					)
+					.expect("a fatal error is only put once; qed");
+			}
+			result
 		}
 	}
 
@@ -1253,43 +962,102 @@ impl_runtime_apis! {
 		}
 	}
 
-	// Ignore everything after this.
+	impl crate::TransactionPaymentApi<Block> for Runtime {
+		fn query_fee_info(uxt: <Block as BlockT>::Extrinsic, len: u32) -> shared::FeeDetails {
+			transaction_payment::Module::fee_details(&uxt.function.call, len)
+		}
+	}
+
+	impl crate::StateQueryApi<Block> for Runtime {
+		fn account_balance(who: AccountId) -> shared::AccountBalance {
+			currency::BalancesMap::get_or_default(&who)
+		}
+
+		fn total_issuance() -> shared::Balance {
+			currency::TotalIssuance::get_or_default()
+		}
+
+		fn value() -> Option<u32> {
+			system::Value::get()
+		}
+	}
 
 	impl sp_api::Metadata<Block> for Runtime {
 		fn metadata() -> OpaqueMetadata {
-			OpaqueMetadata::new(Default::default())
+			OpaqueMetadata::new(metadata::runtime_metadata().into())
 		}
 
-		fn metadata_at_version(_version: u32) -> Option<OpaqueMetadata> {
-			Default::default()
+		fn metadata_at_version(version: u32) -> Option<OpaqueMetadata> {
+			metadata::runtime_metadata_at_version(version).map(|m| OpaqueMetadata::new(m.into()))
 		}
 
 		fn metadata_versions() -> sp_std::vec::Vec<u32> {
-			Default::default()
+			metadata::METADATA_VERSIONS.to_vec()
 		}
 	}
 
-	impl sp_offchain::OffchainWorkerApi<Block> for Runtime {
-		fn offchain_worker(_header: &<Block as BlockT>::Header) {}
+	impl sp_consensus_aura::AuraApi<Block, shared::AuraId> for Runtime {
+		fn slot_duration() -> sp_consensus_aura::SlotDuration {
+			sp_consensus_aura::SlotDuration::from_millis(consensus::Module::slot_duration())
+		}
+
+		fn authorities() -> Vec<shared::AuraId> {
+			consensus::AuraAuthorities::get_or_default()
+		}
+	}
+
+	impl sp_consensus_grandpa::GrandpaApi<Block> for Runtime {
+		fn grandpa_authorities() -> sp_consensus_grandpa::AuthorityList {
+			consensus::Module::grandpa_authorities()
+		}
+
+		fn current_set_id() -> sp_consensus_grandpa::SetId {
+			0
+		}
+
+		fn submit_report_equivocation_unsigned_extrinsic(
+			_equivocation_proof: sp_consensus_grandpa::EquivocationProof<
+				<Block as BlockT>::Hash,
+				shared::BlockNumber,
+			>,
+			_key_owner_proof: sp_consensus_grandpa::OpaqueKeyOwnershipProof,
+		) -> Option<()> {
+			None
+		}
+
+		fn generate_key_ownership_proof(
+			_set_id: sp_consensus_grandpa::SetId,
+			_authority_id: shared::GrandpaId,
+		) -> Option<sp_consensus_grandpa::OpaqueKeyOwnershipProof> {
+			None
+		}
 	}
 
 	impl sp_session::SessionKeys<Block> for Runtime {
-		fn generate_session_keys(_: Option<Vec<u8>>) -> Vec<u8> {
-			Default::default()
+		fn generate_session_keys(seed: Option<Vec<u8>>) -> Vec<u8> {
+			consensus::SessionKeys::generate(seed)
 		}
 
 		fn decode_session_keys(
-			_: Vec<u8>,
+			encoded: Vec<u8>,
 		) -> Option<Vec<(Vec<u8>, sp_core::crypto::KeyTypeId)>> {
-			Default::default()
+			consensus::SessionKeys::decode_into_raw_public_keys(&encoded)
 		}
 	}
+
+	// Ignore everything after this.
+
+	impl sp_offchain::OffchainWorkerApi<Block> for Runtime {
+		fn offchain_worker(_header: &<Block as BlockT>::Header) {}
+	}
 }
 
+#[cfg(test)]
+mod fuzz;
 #[cfg(test)]
 mod tests {
 	use super::*;
-	use crate::shared::RuntimeCallExt;
+	use crate::shared::{AccountBalance, RuntimeCallExt, EXTRINSICS_KEY, HEADER_KEY};
 	use parity_scale_codec::Encode;
 	use shared::{Extrinsic, RuntimeCall, VALUE_KEY};
 	use sp_core::hexdisplay::HexDisplay;
@@ -1297,6 +1065,7 @@ mod tests {
 	use sp_runtime::{
 		traits::Extrinsic as _,
 		transaction_validity::{InvalidTransaction, TransactionValidityError},
+		DispatchError,
 	};
 
 	fn set_value_call(value: u32, nonce: u32) -> RuntimeCallExt {
@@ -1324,6 +1093,32 @@ mod tests {
 		Extrinsic::new(call, Some((signer.public(), signature, ()))).unwrap()
 	}
 
+	pub(crate) fn signed_ext(
+		signer: sp_keyring::AccountKeyring,
+		call: RuntimeCall,
+		nonce: u32,
+		tip: Option<u128>,
+	) -> Extrinsic {
+		let call = RuntimeCallExt { call, nonce, tip };
+		let signature = signer.sign(&call.encode());
+		Extrinsic::new(call, Some((signer.public(), signature, ()))).unwrap()
+	}
+
+	pub(crate) fn header_at(number: u32) -> shared::Header {
+		shared::Header {
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+			parent_hash: Default::default(),
+			number,
+			state_root: Default::default(),
+		}
+	}
+
+	/// Externalities with the genesis storage of `config`.
+	pub(crate) fn new_test_ext(config: RuntimeGenesisConfig) -> TestExternalities {
+		config.build_storage().unwrap().into()
+	}
+
 	/// Return the list of extrinsics that are noted in the `EXTRINSICS_KEY`.
 	fn noted_extrinsics() -> Vec<Vec<u8>> {
 		sp_io::storage::get(EXTRINSICS_KEY)
@@ -1473,6 +1268,10 @@ mod tests {
 			Runtime::do_initialize_block(&header);
 			drop(header);
 
+			let inherents = Runtime::do_inherent_extrinsics(1_000);
+			for inherent in inherents.clone() {
+				Runtime::do_apply_extrinsic(inherent).unwrap().unwrap();
+			}
 			Runtime::do_apply_extrinsic(ext1.clone()).unwrap().unwrap();
 			Runtime::do_apply_extrinsic(ext2.clone()).unwrap().unwrap();
 			Runtime::do_apply_extrinsic(ext3.clone()).unwrap().unwrap();
@@ -1484,7 +1283,7 @@ mod tests {
 				"header must have been cleared from storage"
 			);
 			let extrinsics = noted_extrinsics();
-			assert_eq!(extrinsics.len(), 3, "incorrect extrinsics_key recorded in state");
+			assert_eq!(extrinsics.len(), 4, "incorrect extrinsics_key recorded in state");
 
 			let expected_state_root = {
 				let raw_state_root = &sp_io::storage::root(Default::default())[..];
@@ -1502,7 +1301,7 @@ mod tests {
 				"block finalization should set correct extrinsics root in header"
 			);
 
-			Block { extrinsics: vec![ext1, ext2, ext3], header }
+			Block { extrinsics: [inherents, vec![ext1, ext2, ext3]].concat(), header }
 		});
 
 		// now re-importing it.
@@ -1766,7 +1565,13 @@ mod tests {
 					ext,
 					Default::default()
 				),
-				Ok(ValidTransaction::default())
+				Ok(ValidTransaction {
+					priority: DEFAULT_PRIORITY,
+					requires: vec![],
+					provides: vec![(alice.public(), 0u32).encode()],
+					longevity: TRANSACTION_LONGEVITY,
+					propagate: true,
+				})
 			);
 		});
 	}
@@ -1812,7 +1617,13 @@ mod tests {
 					ext.clone(),
 					Default::default()
 				),
-				Ok(ValidTransaction::default())
+				Ok(ValidTransaction {
+					priority: DEFAULT_PRIORITY,
+					requires: vec![],
+					provides: vec![(bob.public(), 0u32).encode()],
+					longevity: TRANSACTION_LONGEVITY,
+					propagate: true,
+				})
 			);
 
 			assert_eq!(Runtime::do_apply_extrinsic(ext).unwrap(), Err(DispatchError::BadOrigin));
@@ -2461,7 +2272,7 @@ mod tests {
 				Runtime::get_state::<AccountBalance>(
 					&[b"BalancesMap", bob.public().encode().as_slice()].concat()
 				),
-				Some(AccountBalance { free: 90, reserved: 0, nonce: 0 })
+				Some(AccountBalance { free: 90, reserved: 0, nonce: 1 })
 			);
 
 			assert_eq!(
@@ -2572,7 +2383,7 @@ mod tests {
 				Runtime::get_state::<AccountBalance>(
 					&[b"BalancesMap", bob.public().encode().as_slice()].concat()
 				),
-				Some(AccountBalance { free: 100, reserved: 0, nonce: 0 })
+				Some(AccountBalance { free: 100, reserved: 0, nonce: 1 })
 			);
 
 			assert_eq!(Runtime::get_state::<u128>(b"TotalIssuance").unwrap(), 100);
@@ -2594,4 +2405,1708 @@ mod tests {
 			);
 		});
 	}
+
+	#[test]
+	fn validate_future_nonce_requires_previous_nonce() {
+		let alice = sp_keyring::AccountKeyring::Alice;
+		TestExternalities::new_empty().execute_with(|| {
+			sp_io::storage::set(
+				&[b"BalancesMap", alice.public().encode().as_slice()].concat(),
+				&AccountBalance { free: 100, reserved: 0, nonce: 1 }.encode(),
+			);
+
+			let valid = Runtime::do_validate_transaction(
+				TransactionSource::External,
+				signed_set_value(42, 3),
+				Default::default(),
+			)
+			.unwrap();
+			assert_eq!(valid.requires, vec![(alice.public(), 2u32).encode()]);
+			assert_eq!(valid.provides, vec![(alice.public(), 3u32).encode()]);
+			assert_eq!(valid.longevity, TRANSACTION_LONGEVITY);
+
+			// the current nonce requires nothing.
+			let valid = Runtime::do_validate_transaction(
+				TransactionSource::External,
+				signed_set_value(42, 1),
+				Default::default(),
+			)
+			.unwrap();
+			assert!(valid.requires.is_empty());
+			assert_eq!(valid.provides, vec![(alice.public(), 1u32).encode()]);
+
+			// but a used one is stale.
+			assert_eq!(
+				Runtime::do_validate_transaction(
+					TransactionSource::External,
+					signed_set_value(42, 0),
+					Default::default(),
+				)
+				.unwrap_err(),
+				TransactionValidityError::Invalid(InvalidTransaction::Stale)
+			);
+		});
+	}
+
+	#[test]
+	fn validate_tip_from_account_below_minimum_balance() {
+		let bob = sp_keyring::AccountKeyring::Bob;
+		TestExternalities::new_empty().execute_with(|| {
+			// bob does not exist, and has nothing to pay the tip with.
+			let ext = signed_ext(
+				bob,
+				RuntimeCall::System(shared::SystemCall::Remark { data: vec![] }),
+				0,
+				Some(5),
+			);
+			assert_eq!(
+				Runtime::do_validate_transaction(
+					TransactionSource::External,
+					ext,
+					Default::default()
+				)
+				.unwrap_err(),
+				TransactionValidityError::Invalid(InvalidTransaction::Payment)
+			);
+
+			// bob can pay the tip, but not without going below the minimum balance.
+			sp_io::storage::set(
+				&[b"BalancesMap", bob.public().encode().as_slice()].concat(),
+				&AccountBalance { free: 14, reserved: 0, nonce: 0 }.encode(),
+			);
+			let ext = signed_ext(
+				bob,
+				RuntimeCall::System(shared::SystemCall::Remark { data: vec![] }),
+				0,
+				Some(5),
+			);
+			assert_eq!(
+				Runtime::do_validate_transaction(
+					TransactionSource::External,
+					ext,
+					Default::default()
+				)
+				.unwrap_err(),
+				TransactionValidityError::Invalid(InvalidTransaction::Payment)
+			);
+		});
+	}
+
+	#[test]
+	fn validate_priority_combines_tip_and_call_type() {
+		let alice = sp_keyring::AccountKeyring::Alice;
+		let bob = sp_keyring::AccountKeyring::Bob;
+		TestExternalities::new_empty().execute_with(|| {
+			for who in [alice, bob] {
+				sp_io::storage::set(
+					&[b"BalancesMap", who.public().encode().as_slice()].concat(),
+					&AccountBalance { free: 100, reserved: 0, nonce: 0 }.encode(),
+				);
+			}
+			let priority = |ext| {
+				Runtime::do_validate_transaction(
+					TransactionSource::External,
+					ext,
+					Default::default(),
+				)
+				.unwrap()
+				.priority
+			};
+			let remark = RuntimeCall::System(shared::SystemCall::Remark { data: vec![] });
+			let sudo_remark = RuntimeCall::System(shared::SystemCall::SudoRemark { data: vec![] });
+			let transfer = RuntimeCall::Currency(shared::CurrencyCall::Transfer {
+				dest: alice.public(),
+				amount: 20,
+			});
+
+			assert_eq!(priority(signed_ext(bob, remark.clone(), 0, None)), REMARK_PRIORITY);
+			assert_eq!(priority(signed_ext(bob, remark, 0, Some(10))), REMARK_PRIORITY + 10);
+			assert_eq!(priority(signed_ext(bob, transfer, 0, Some(10))), DEFAULT_PRIORITY + 10);
+
+			// sudo calls are only prioritized when they come from sudo.
+			assert_eq!(
+				priority(signed_ext(bob, sudo_remark.clone(), 0, Some(10))),
+				DEFAULT_PRIORITY + 10
+			);
+			assert_eq!(priority(signed_ext(alice, sudo_remark, 0, Some(10))), SUDO_PRIORITY + 10);
+		});
+	}
+
+	#[docify::export]
+	#[test]
+	fn alice_mints_100_to_bob_bob_stakes_50_and_unbonds_20() {
+		let alice = sp_keyring::AccountKeyring::Alice;
+		let bob = sp_keyring::AccountKeyring::Bob;
+		TestExternalities::new_empty().execute_with(|| {
+			sp_io::storage::set(b"TotalIssuance", &0u128.encode());
+			let ext_mint = signed_ext(
+				alice,
+				RuntimeCall::Currency(shared::CurrencyCall::Mint {
+					dest: bob.public(),
+					amount: 100,
+				}),
+				0,
+				None,
+			);
+			let ext_bond = signed_ext(
+				bob,
+				RuntimeCall::Staking(shared::StakingCall::Bond { amount: 50 }),
+				0,
+				None,
+			);
+			let ext_unbond = signed_ext(
+				bob,
+				RuntimeCall::Staking(shared::StakingCall::Unbond { amount: 20 }),
+				1,
+				None,
+			);
+
+			Runtime::do_initialize_block(&header_at(0));
+
+			Runtime::do_apply_extrinsic(ext_mint).unwrap().unwrap();
+			Runtime::do_apply_extrinsic(ext_bond).unwrap().unwrap();
+			Runtime::do_apply_extrinsic(ext_unbond).unwrap().unwrap();
+
+			let header = Runtime::do_finalize_block();
+			let extrinsics = noted_extrinsics();
+			assert_eq!(extrinsics.len(), 3, "incorrect extrinsics_key recorded in state");
+
+			// unbonding funds remain reserved until withdrawn.
+			assert_eq!(
+				Runtime::get_state::<AccountBalance>(
+					&[b"BalancesMap", bob.public().encode().as_slice()].concat()
+				),
+				Some(AccountBalance { free: 50, reserved: 50, nonce: 2 })
+			);
+			assert_eq!(
+				Runtime::get_state::<Vec<shared::UnlockChunk>>(
+					&[b"UnbondingQueue", bob.public().encode().as_slice()].concat()
+				),
+				Some(vec![shared::UnlockChunk { value: 20, unlock_at: shared::BONDING_DURATION }])
+			);
+			assert_eq!(staking::Module::bonded(&bob.public()), 30);
+			assert_eq!(Runtime::get_state::<u128>(b"TotalIssuance").unwrap(), 100);
+
+			let expected_state_root = {
+				let raw_state_root = &sp_io::storage::root(Default::default())[..];
+				H256::decode(&mut &raw_state_root[..]).unwrap()
+			};
+			assert_eq!(
+				header.state_root, expected_state_root,
+				"block finalization should set correct state root in header"
+			);
+		});
+	}
+
+	#[docify::export]
+	#[test]
+	fn alice_mints_100_to_bob_bob_stakes_50_and_unbonds_60() {
+		let alice = sp_keyring::AccountKeyring::Alice;
+		let bob = sp_keyring::AccountKeyring::Bob;
+		TestExternalities::new_empty().execute_with(|| {
+			sp_io::storage::set(b"TotalIssuance", &0u128.encode());
+			let ext_mint = signed_ext(
+				alice,
+				RuntimeCall::Currency(shared::CurrencyCall::Mint {
+					dest: bob.public(),
+					amount: 100,
+				}),
+				0,
+				None,
+			);
+			let ext_bond = signed_ext(
+				bob,
+				RuntimeCall::Staking(shared::StakingCall::Bond { amount: 50 }),
+				0,
+				None,
+			);
+			let ext_unbond = signed_ext(
+				bob,
+				RuntimeCall::Staking(shared::StakingCall::Unbond { amount: 60 }),
+				1,
+				None,
+			);
+
+			Runtime::do_initialize_block(&header_at(0));
+
+			Runtime::do_apply_extrinsic(ext_mint).unwrap().unwrap();
+			Runtime::do_apply_extrinsic(ext_bond).unwrap().unwrap();
+			Runtime::do_apply_extrinsic(ext_unbond).unwrap().unwrap_err();
+
+			Runtime::do_finalize_block();
+			let extrinsics = noted_extrinsics();
+			assert_eq!(extrinsics.len(), 3, "incorrect extrinsics_key recorded in state");
+
+			// the failed unbond still uses up its nonce.
+			assert_eq!(
+				Runtime::get_state::<AccountBalance>(
+					&[b"BalancesMap", bob.public().encode().as_slice()].concat()
+				),
+				Some(AccountBalance { free: 50, reserved: 50, nonce: 2 })
+			);
+			assert_eq!(
+				Runtime::get_state::<Vec<shared::UnlockChunk>>(
+					&[b"UnbondingQueue", bob.public().encode().as_slice()].concat()
+				),
+				None
+			);
+		});
+	}
+
+	#[docify::export]
+	#[test]
+	fn alice_mints_100_to_bob_bob_stakes_50_and_withdraws_20_after_bonding_duration() {
+		let alice = sp_keyring::AccountKeyring::Alice;
+		let bob = sp_keyring::AccountKeyring::Bob;
+		TestExternalities::new_empty().execute_with(|| {
+			sp_io::storage::set(b"TotalIssuance", &0u128.encode());
+			let ext_mint = signed_ext(
+				alice,
+				RuntimeCall::Currency(shared::CurrencyCall::Mint {
+					dest: bob.public(),
+					amount: 100,
+				}),
+				0,
+				None,
+			);
+			let ext_bond = signed_ext(
+				bob,
+				RuntimeCall::Staking(shared::StakingCall::Bond { amount: 50 }),
+				0,
+				None,
+			);
+			let ext_unbond = signed_ext(
+				bob,
+				RuntimeCall::Staking(shared::StakingCall::Unbond { amount: 20 }),
+				1,
+				None,
+			);
+			let withdraw = |nonce| {
+				signed_ext(
+					bob,
+					RuntimeCall::Staking(shared::StakingCall::WithdrawUnbonded),
+					nonce,
+					None,
+				)
+			};
+
+			Runtime::do_initialize_block(&header_at(1));
+			Runtime::do_apply_extrinsic(ext_mint).unwrap().unwrap();
+			Runtime::do_apply_extrinsic(ext_bond).unwrap().unwrap();
+			Runtime::do_apply_extrinsic(ext_unbond).unwrap().unwrap();
+			Runtime::do_finalize_block();
+
+			// one block too early, nothing can be withdrawn, but the nonce is used up.
+			Runtime::do_initialize_block(&header_at(shared::BONDING_DURATION));
+			Runtime::do_apply_extrinsic(withdraw(2)).unwrap().unwrap_err();
+			Runtime::do_finalize_block();
+
+			assert_eq!(
+				Runtime::get_state::<AccountBalance>(
+					&[b"BalancesMap", bob.public().encode().as_slice()].concat()
+				),
+				Some(AccountBalance { free: 50, reserved: 50, nonce: 3 })
+			);
+
+			Runtime::do_initialize_block(&header_at(shared::BONDING_DURATION + 1));
+			Runtime::do_apply_extrinsic(withdraw(3)).unwrap().unwrap();
+			let header = Runtime::do_finalize_block();
+
+			assert_eq!(
+				Runtime::get_state::<AccountBalance>(
+					&[b"BalancesMap", bob.public().encode().as_slice()].concat()
+				),
+				Some(AccountBalance { free: 70, reserved: 30, nonce: 4 })
+			);
+			assert_eq!(
+				Runtime::get_state::<Vec<shared::UnlockChunk>>(
+					&[b"UnbondingQueue", bob.public().encode().as_slice()].concat()
+				),
+				None
+			);
+			assert_eq!(Runtime::get_state::<u128>(b"TotalIssuance").unwrap(), 100);
+
+			let expected_state_root = {
+				let raw_state_root = &sp_io::storage::root(Default::default())[..];
+				H256::decode(&mut &raw_state_root[..]).unwrap()
+			};
+			assert_eq!(
+				header.state_root, expected_state_root,
+				"block finalization should set correct state root in header"
+			);
+		});
+	}
+
+	#[docify::export]
+	#[test]
+	fn alice_and_bob_approve_and_execute_transfer_from_2_of_3_multisig() {
+		let alice = sp_keyring::AccountKeyring::Alice;
+		let bob = sp_keyring::AccountKeyring::Bob;
+		let charlie = sp_keyring::AccountKeyring::Charlie;
+		let dave = sp_keyring::AccountKeyring::Dave;
+		TestExternalities::new_empty().execute_with(|| {
+			let signatories = vec![charlie.public(), bob.public(), alice.public()];
+			let multisig = multisig::Module::multisig_account_id(&signatories, 2);
+			let transfer = RuntimeCall::Currency(shared::CurrencyCall::Transfer {
+				dest: dave.public(),
+				amount: 30,
+			});
+			let call_hash = multisig::Module::call_hash(&transfer);
+			let balance = |who: AccountId| {
+				Runtime::get_state::<AccountBalance>(
+					&[b"BalancesMap", who.encode().as_slice()].concat(),
+				)
+			};
+
+			Runtime::do_initialize_block(&header_at(1));
+			let mint = |dest, nonce| {
+				signed_ext(
+					alice,
+					RuntimeCall::Currency(shared::CurrencyCall::Mint { dest, amount: 100 }),
+					nonce,
+					None,
+				)
+			};
+			Runtime::do_apply_extrinsic(mint(multisig, 0)).unwrap().unwrap();
+			Runtime::do_apply_extrinsic(mint(bob.public(), 1)).unwrap().unwrap();
+			Runtime::do_apply_extrinsic(signed_ext(
+				bob,
+				RuntimeCall::Multisig(shared::MultisigCall::Create { signatories, threshold: 2 }),
+				0,
+				None,
+			))
+			.unwrap()
+			.unwrap();
+
+			let approve = |signer, nonce| {
+				signed_ext(
+					signer,
+					RuntimeCall::Multisig(shared::MultisigCall::Approve { multisig, call_hash }),
+					nonce,
+					None,
+				)
+			};
+			let execute = |nonce| {
+				signed_ext(
+					bob,
+					RuntimeCall::Multisig(shared::MultisigCall::Execute {
+						multisig,
+						call: Box::new(transfer.clone()),
+					}),
+					nonce,
+					None,
+				)
+			};
+
+			// bob opens the operation, and pays the deposit.
+			Runtime::do_apply_extrinsic(approve(bob, 1)).unwrap().unwrap();
+			assert_eq!(
+				balance(bob.public()),
+				Some(AccountBalance {
+					free: 100 - shared::MULTISIG_DEPOSIT,
+					reserved: shared::MULTISIG_DEPOSIT,
+					nonce: 2
+				})
+			);
+			assert_eq!(staking::Module::bonded(&bob.public()), 0);
+
+			// a second approval by bob does not count, and one approval is not enough. Both still
+			// use up a nonce of bob.
+			assert_eq!(
+				Runtime::do_apply_extrinsic(approve(bob, 2)).unwrap(),
+				Err(multisig::ALREADY_APPROVED)
+			);
+			assert_eq!(
+				Runtime::do_apply_extrinsic(execute(3)).unwrap(),
+				Err(multisig::NOT_ENOUGH_APPROVALS)
+			);
+
+			Runtime::do_apply_extrinsic(approve(alice, 2)).unwrap().unwrap();
+			Runtime::do_apply_extrinsic(execute(4)).unwrap().unwrap();
+			Runtime::do_finalize_block();
+
+			assert_eq!(balance(multisig), Some(AccountBalance { free: 70, reserved: 0, nonce: 0 }));
+			assert_eq!(
+				balance(dave.public()),
+				Some(AccountBalance { free: 30, reserved: 0, nonce: 0 })
+			);
+			assert_eq!(
+				balance(bob.public()),
+				Some(AccountBalance { free: 100, reserved: 0, nonce: 5 })
+			);
+			assert_eq!(
+				Runtime::get_state::<shared::MultisigOperation>(
+					&[b"MultisigOperations", (multisig, call_hash).encode().as_slice()].concat()
+				),
+				None
+			);
+			assert_eq!(Runtime::get_state::<u128>(b"TotalIssuance").unwrap(), 200);
+		});
+	}
+
+	#[docify::export]
+	#[test]
+	fn bob_stakes_for_alice_through_staking_proxy_but_cannot_transfer() {
+		let alice = sp_keyring::AccountKeyring::Alice;
+		let bob = sp_keyring::AccountKeyring::Bob;
+		TestExternalities::new_empty().execute_with(|| {
+			let proxy = |call, nonce| {
+				signed_ext(
+					bob,
+					RuntimeCall::Proxy(shared::ProxyCall::Proxy {
+						real: alice.public(),
+						call: Box::new(call),
+					}),
+					nonce,
+					None,
+				)
+			};
+			let bond = RuntimeCall::Staking(shared::StakingCall::Bond { amount: 50 });
+			let transfer = RuntimeCall::Currency(shared::CurrencyCall::Transfer {
+				dest: bob.public(),
+				amount: 10,
+			});
+
+			Runtime::do_initialize_block(&header_at(1));
+			Runtime::do_apply_extrinsic(signed_ext(
+				alice,
+				RuntimeCall::Currency(shared::CurrencyCall::Mint {
+					dest: alice.public(),
+					amount: 100,
+				}),
+				0,
+				None,
+			))
+			.unwrap()
+			.unwrap();
+
+			// bob is not a proxy of alice yet, and uses up a nonce for trying.
+			assert_eq!(
+				Runtime::do_apply_extrinsic(proxy(bond.clone(), 0)).unwrap(),
+				Err(proxy::NOT_PROXY)
+			);
+
+			Runtime::do_apply_extrinsic(signed_ext(
+				alice,
+				RuntimeCall::Proxy(shared::ProxyCall::AddProxy {
+					delegate: bob.public(),
+					proxy_type: shared::ProxyType::Staking,
+				}),
+				1,
+				None,
+			))
+			.unwrap()
+			.unwrap();
+
+			Runtime::do_apply_extrinsic(proxy(bond, 1)).unwrap().unwrap();
+			assert_eq!(
+				Runtime::do_apply_extrinsic(proxy(transfer, 2)).unwrap(),
+				Err(proxy::CALL_FILTERED)
+			);
+			Runtime::do_finalize_block();
+
+			// alice's nonce is untouched by the proxied calls, and the deposit is not bonded.
+			assert_eq!(
+				Runtime::get_state::<AccountBalance>(
+					&[b"BalancesMap", alice.public().encode().as_slice()].concat()
+				),
+				Some(AccountBalance {
+					free: 50 - shared::PROXY_DEPOSIT,
+					reserved: 50 + shared::PROXY_DEPOSIT,
+					nonce: 2
+				})
+			);
+			assert_eq!(staking::Module::bonded(&alice.public()), 50);
+			assert_eq!(
+				Runtime::get_state::<AccountBalance>(
+					&[b"BalancesMap", bob.public().encode().as_slice()].concat()
+				),
+				Some(AccountBalance { free: 0, reserved: 0, nonce: 3 })
+			);
+		});
+	}
+
+	#[docify::export]
+	#[test]
+	fn bob_pays_base_length_and_weight_fee_to_treasury_for_transfer() {
+		let bob = sp_keyring::AccountKeyring::Bob;
+		let charlie = sp_keyring::AccountKeyring::Charlie;
+		new_test_ext(RuntimeGenesisConfig::with_endowed(vec![bob.public()], 1000)).execute_with(
+			|| {
+				let ext = signed_ext(
+					bob,
+					RuntimeCall::Currency(shared::CurrencyCall::Transfer {
+						dest: charlie.public(),
+						amount: 100,
+					}),
+					0,
+					Some(5),
+				);
+				let len = ext.encode().len() as u128;
+				let fee = transaction_payment::Module::fee_details(&ext.function.call, len as u32);
+				assert_eq!(
+					fee,
+					shared::FeeDetails {
+						weight: 50,
+						base_fee: 10,
+						length_fee: len,
+						weight_fee: 50
+					}
+				);
+
+				Runtime::do_initialize_block(&header_at(1));
+				Runtime::do_apply_extrinsic(ext).unwrap().unwrap();
+				Runtime::do_finalize_block();
+
+				let balance = |who: AccountId| {
+					Runtime::get_state::<AccountBalance>(
+						&[b"BalancesMap", who.encode().as_slice()].concat(),
+					)
+				};
+				assert_eq!(
+					balance(bob.public()),
+					Some(AccountBalance {
+						free: 1000 - 100 - fee.final_fee() - 5,
+						reserved: 0,
+						nonce: 1
+					})
+				);
+				assert_eq!(
+					balance(charlie.public()),
+					Some(AccountBalance { free: 100, reserved: 0, nonce: 0 })
+				);
+				assert_eq!(
+					balance(AccountId::from_raw(shared::TREASURY)),
+					Some(AccountBalance { free: fee.final_fee() + 5, reserved: 0, nonce: 0 })
+				);
+				assert_eq!(Runtime::get_state::<u128>(b"TotalIssuance").unwrap(), 1000);
+			},
+		);
+	}
+
+	#[test]
+	fn failed_transfer_still_pays_fee_and_uses_up_nonce() {
+		let bob = sp_keyring::AccountKeyring::Bob;
+		let charlie = sp_keyring::AccountKeyring::Charlie;
+		new_test_ext(RuntimeGenesisConfig::with_endowed(vec![bob.public()], 1000)).execute_with(
+			|| {
+				// bob cannot afford this transfer.
+				let ext = signed_ext(
+					bob,
+					RuntimeCall::Currency(shared::CurrencyCall::Transfer {
+						dest: charlie.public(),
+						amount: 2000,
+					}),
+					0,
+					Some(5),
+				);
+				let fee = transaction_payment::Module::fee_details(
+					&ext.function.call,
+					ext.encode().len() as u32,
+				)
+				.final_fee();
+				assert!(fee > 0);
+
+				Runtime::do_initialize_block(&header_at(1));
+				assert!(Runtime::do_apply_extrinsic(ext).unwrap().is_err());
+				Runtime::do_finalize_block();
+
+				let balance = |who: AccountId| {
+					Runtime::get_state::<AccountBalance>(
+						&[b"BalancesMap", who.encode().as_slice()].concat(),
+					)
+				};
+				assert_eq!(
+					balance(bob.public()),
+					Some(AccountBalance { free: 1000 - fee - 5, reserved: 0, nonce: 1 })
+				);
+				assert_eq!(balance(charlie.public()), None);
+				assert_eq!(
+					balance(AccountId::from_raw(shared::TREASURY)),
+					Some(AccountBalance { free: fee + 5, reserved: 0, nonce: 0 })
+				);
+				assert_eq!(Runtime::get_state::<u128>(b"TotalIssuance").unwrap(), 1000);
+			},
+		);
+	}
+
+	#[test]
+	fn validate_remark_with_fee_above_free_balance() {
+		let bob = sp_keyring::AccountKeyring::Bob;
+		new_test_ext(RuntimeGenesisConfig::with_endowed(vec![bob.public()], 500)).execute_with(
+			|| {
+				let remark = |size| {
+					signed_ext(
+						bob,
+						RuntimeCall::System(shared::SystemCall::Remark { data: vec![0; size] }),
+						0,
+						None,
+					)
+				};
+
+				// a small remark is affordable, even without a tip.
+				assert!(Runtime::do_validate_transaction(
+					TransactionSource::External,
+					remark(10),
+					Default::default()
+				)
+				.is_ok());
+
+				// a big one is not, since each byte has a price.
+				assert_eq!(
+					Runtime::do_validate_transaction(
+						TransactionSource::External,
+						remark(1000),
+						Default::default()
+					)
+					.unwrap_err(),
+					TransactionValidityError::Invalid(InvalidTransaction::Payment)
+				);
+				assert_eq!(
+					Runtime::do_apply_extrinsic(remark(1000)).unwrap_err(),
+					TransactionValidityError::Invalid(InvalidTransaction::Payment)
+				);
+			},
+		);
+	}
+
+	/// The phase and event of all events of the current block.
+	pub(crate) fn events() -> Vec<(shared::Phase, shared::RuntimeEvent)> {
+		Runtime::get_state::<Vec<shared::EventRecord>>(shared::EVENTS_KEY)
+			.unwrap_or_default()
+			.into_iter()
+			.map(|record| (record.phase, record.event))
+			.collect()
+	}
+
+	#[test]
+	fn events_are_deposited_per_extrinsic_and_cleared_in_next_block() {
+		use shared::{Phase::ApplyExtrinsic, RuntimeEvent};
+		let alice = sp_keyring::AccountKeyring::Alice;
+		let bob = sp_keyring::AccountKeyring::Bob;
+		let charlie = sp_keyring::AccountKeyring::Charlie;
+		let config =
+			RuntimeGenesisConfig { balances: vec![(alice.public(), 1000)], ..Default::default() };
+
+		new_test_ext(config).execute_with(|| {
+			Runtime::do_initialize_block(&header_at(1));
+			let inherents = Runtime::do_inherent_extrinsics(1_000);
+			Runtime::do_apply_extrinsic(inherents[0].clone()).unwrap().unwrap();
+			let transfer = |signer, dest: AccountId, amount| {
+				let call = RuntimeCall::Currency(CurrencyCall::Transfer { dest, amount });
+				signed_ext(signer, call, 0, None)
+			};
+			Runtime::do_apply_extrinsic(transfer(alice, bob.public(), 100))
+				.unwrap()
+				.unwrap();
+			// bob cannot afford this, so the transfer is reverted along with its events.
+			assert!(Runtime::do_apply_extrinsic(transfer(bob, charlie.public(), 1000))
+				.unwrap()
+				.is_err());
+			Runtime::do_finalize_block();
+
+			assert_eq!(
+				events(),
+				vec![
+					(
+						ApplyExtrinsic(1),
+						RuntimeEvent::Transferred {
+							from: alice.public(),
+							to: bob.public(),
+							amount: 100
+						}
+					),
+					(ApplyExtrinsic(1), RuntimeEvent::ExtrinsicSuccess),
+					(
+						ApplyExtrinsic(2),
+						// the message of `DispatchError::Other` is not encoded.
+						RuntimeEvent::ExtrinsicFailed { error: DispatchError::Other("") }
+					),
+				]
+			);
+			// the transfer is indexed under both of its accounts.
+			let topic = |who: AccountId| H256(who.0);
+			assert_eq!(system::EventTopics::get(&topic(alice.public())), Some(vec![0]));
+			assert_eq!(system::EventTopics::get(&topic(bob.public())), Some(vec![0]));
+			assert_eq!(system::EventTopics::get(&topic(charlie.public())), None);
+			assert!(!system::ExecutionPhase::exists());
+
+			// the events remain until the next block starts.
+			Runtime::do_initialize_block(&header_at(2));
+			assert_eq!(events(), vec![]);
+			assert_eq!(system::EventTopics::get(&topic(alice.public())), None);
+		});
+	}
+
+	#[test]
+	fn failed_dispatch_uses_up_the_nonce() {
+		let alice = sp_keyring::AccountKeyring::Alice;
+		let bob = sp_keyring::AccountKeyring::Bob;
+		let config =
+			RuntimeGenesisConfig { balances: vec![(alice.public(), 1000)], ..Default::default() };
+
+		new_test_ext(config).execute_with(|| {
+			Runtime::do_initialize_block(&header_at(1));
+			let inherents = Runtime::do_inherent_extrinsics(1_000);
+			Runtime::do_apply_extrinsic(inherents[0].clone()).unwrap().unwrap();
+			// alice cannot afford this, but still pays for trying.
+			let call = RuntimeCall::Currency(CurrencyCall::Transfer {
+				dest: bob.public(),
+				amount: 10_000,
+			});
+			let ext = signed_ext(alice, call, 0, Some(1));
+			assert!(Runtime::do_apply_extrinsic(ext.clone()).unwrap().is_err());
+			let account = currency::BalancesMap::get_or_default(&alice.public());
+			assert_eq!(account.nonce, 1);
+			assert!(account.free < 1000);
+
+			// so the same extrinsic can neither be applied nor validated again.
+			assert_eq!(
+				Runtime::do_apply_extrinsic(ext.clone()).unwrap_err(),
+				TransactionValidityError::Invalid(InvalidTransaction::Stale)
+			);
+			assert_eq!(
+				Runtime::do_validate_transaction(
+					TransactionSource::External,
+					ext,
+					Default::default()
+				)
+				.unwrap_err(),
+				TransactionValidityError::Invalid(InvalidTransaction::Stale)
+			);
+			assert_eq!(currency::BalancesMap::get_or_default(&alice.public()), account);
+		});
+	}
+
+	#[test]
+	fn events_of_reaped_account_burnt_tip_and_bond() {
+		use shared::{Phase::ApplyExtrinsic, RuntimeEvent};
+		let alice = sp_keyring::AccountKeyring::Alice;
+		let bob = sp_keyring::AccountKeyring::Bob;
+		let config =
+			RuntimeGenesisConfig { balances: vec![(alice.public(), 1000)], ..Default::default() };
+
+		new_test_ext(config).execute_with(|| {
+			Runtime::do_initialize_block(&header_at(1));
+			// a tip below the minimum balance cannot create the treasury, so it is burnt.
+			let transfer_all =
+				RuntimeCall::Currency(CurrencyCall::TransferAll { dest: bob.public() });
+			Runtime::do_apply_extrinsic(signed_ext(alice, transfer_all, 0, Some(5)))
+				.unwrap()
+				.unwrap();
+			let bond = RuntimeCall::Staking(shared::StakingCall::Bond { amount: 100 });
+			Runtime::do_apply_extrinsic(signed_ext(bob, bond, 0, None)).unwrap().unwrap();
+			Runtime::do_finalize_block();
+
+			assert_eq!(
+				events(),
+				vec![
+					(ApplyExtrinsic(0), RuntimeEvent::TipBurned { who: alice.public(), amount: 5 }),
+					(
+						ApplyExtrinsic(0),
+						RuntimeEvent::TransactionFeePaid { who: alice.public(), fee: 0, tip: 5 }
+					),
+					(
+						ApplyExtrinsic(0),
+						RuntimeEvent::Transferred {
+							from: alice.public(),
+							to: bob.public(),
+							amount: 995
+						}
+					),
+					(ApplyExtrinsic(0), RuntimeEvent::AccountReaped { who: alice.public() }),
+					(ApplyExtrinsic(0), RuntimeEvent::ExtrinsicSuccess),
+					(ApplyExtrinsic(1), RuntimeEvent::Bonded { who: bob.public(), amount: 100 }),
+					(ApplyExtrinsic(1), RuntimeEvent::ExtrinsicSuccess),
+				]
+			);
+		});
+	}
+
+	#[test]
+	fn batch_stops_at_first_failure_and_batch_all_rolls_back() {
+		let alice = sp_keyring::AccountKeyring::Alice;
+		let bob = sp_keyring::AccountKeyring::Bob.public();
+		let charlie = sp_keyring::AccountKeyring::Charlie.public();
+		let transfer =
+			|dest, amount| RuntimeCall::Currency(CurrencyCall::Transfer { dest, amount });
+		let bond = RuntimeCall::Staking(shared::StakingCall::Bond { amount: 100 });
+		let config =
+			RuntimeGenesisConfig { balances: vec![(alice.public(), 1000)], ..Default::default() };
+
+		new_test_ext(config).execute_with(|| {
+			Runtime::do_initialize_block(&header_at(1));
+			// the second transfer cannot create charlie, so the third is not dispatched.
+			let batch = RuntimeCall::System(SystemCall::Batch {
+				calls: vec![transfer(bob, 100), transfer(charlie, 5), transfer(bob, 100)],
+			});
+			Runtime::do_apply_extrinsic(signed_ext(alice, batch, 0, Some(20)))
+				.unwrap()
+				.unwrap();
+			assert!(events().iter().any(|(_, event)| matches!(
+				event,
+				shared::RuntimeEvent::BatchInterrupted { index: 1, .. }
+			)));
+
+			// the same with `BatchAll` leaves no trace, besides using up the nonce.
+			let batch_all = RuntimeCall::System(SystemCall::BatchAll {
+				calls: vec![transfer(bob, 100), transfer(charlie, 5)],
+			});
+			assert_eq!(
+				Runtime::do_apply_extrinsic(signed_ext(alice, batch_all, 1, None)).unwrap(),
+				Err(currency::BELOW_MINIMUM)
+			);
+
+			let batch_all = RuntimeCall::System(SystemCall::BatchAll {
+				calls: vec![transfer(bob, 100), bond.clone()],
+			});
+			Runtime::do_apply_extrinsic(signed_ext(alice, batch_all, 2, None))
+				.unwrap()
+				.unwrap();
+			Runtime::do_finalize_block();
+
+			// the tip was paid once, and the nonce went up once per batch, failed or not.
+			let balance = |who: AccountId| currency::BalancesMap::get(&who);
+			assert_eq!(
+				balance(alice.public()),
+				Some(AccountBalance { free: 1000 - 200 - 20 - 100, reserved: 100, nonce: 3 })
+			);
+			assert_eq!(balance(bob), Some(AccountBalance { free: 200, reserved: 0, nonce: 0 }));
+			assert_eq!(balance(charlie), None);
+			assert_eq!(balance(AccountId::from_raw(shared::TREASURY)).unwrap().free, 20);
+		});
+	}
+
+	#[test]
+	fn batch_length_and_nesting_are_limited() {
+		let alice = sp_keyring::AccountKeyring::Alice.public();
+		let remark = RuntimeCall::System(SystemCall::Remark { data: vec![] });
+		let batch = |calls| RuntimeCall::System(SystemCall::Batch { calls });
+		let nested = |depth| (0..depth).fold(remark.clone(), |call, _| batch(vec![call]));
+
+		TestExternalities::new_empty().execute_with(|| {
+			let calls = vec![remark.clone(); shared::MAX_BATCH_LENGTH as usize];
+			assert_eq!(batch(calls.clone()).dispatch(&alice), Ok(()));
+			let too_many = [calls, vec![remark.clone()]].concat();
+			assert_eq!(batch(too_many.clone()).dispatch(&alice), Err(system::TOO_MANY_CALLS));
+			// the length of nested batches is checked upfront as well.
+			assert_eq!(
+				batch(vec![remark.clone(), batch(too_many)]).dispatch(&alice),
+				Err(system::TOO_MANY_CALLS)
+			);
+
+			assert_eq!(nested(shared::MAX_BATCH_DEPTH).dispatch(&alice), Ok(()));
+			assert_eq!(
+				nested(shared::MAX_BATCH_DEPTH + 1).dispatch(&alice),
+				Err(system::TOO_DEEPLY_NESTED)
+			);
+			// calls that dispatch another call do not hide the batches in it.
+			let proxied = RuntimeCall::Proxy(shared::ProxyCall::Proxy {
+				real: alice,
+				call: Box::new(nested(shared::MAX_BATCH_DEPTH)),
+			});
+			assert_eq!(batch(vec![proxied]).dispatch(&alice), Err(system::TOO_DEEPLY_NESTED));
+		});
+
+		// a proxy can only batch the calls that it is allowed to dispatch on its own.
+		let bond = RuntimeCall::Staking(shared::StakingCall::Bond { amount: 10 });
+		let transfer = RuntimeCall::Currency(CurrencyCall::Transfer { dest: alice, amount: 10 });
+		assert!(shared::ProxyType::Staking.allows(&batch(vec![bond.clone(), bond.clone()])));
+		assert!(!shared::ProxyType::Staking.allows(&batch(vec![bond, batch(vec![transfer])])));
+	}
+
+	#[test]
+	fn alice_vests_300_to_bob_over_6_blocks() {
+		let alice = sp_keyring::AccountKeyring::Alice;
+		let bob = sp_keyring::AccountKeyring::Bob;
+		let charlie = sp_keyring::AccountKeyring::Charlie.public();
+		let config = RuntimeGenesisConfig {
+			balances: vec![(alice.public(), 1000), (bob.public(), 20)],
+			..Default::default()
+		};
+		let vested_transfer = |dest, amount| {
+			RuntimeCall::Currency(CurrencyCall::VestedTransfer {
+				dest,
+				amount,
+				start: 1,
+				duration: 6,
+			})
+		};
+		let vest = |nonce| signed_ext(bob, RuntimeCall::Currency(CurrencyCall::Vest), nonce, None);
+		let balance = |who: AccountId| currency::BalancesMap::get(&who);
+
+		new_test_ext(config).execute_with(|| {
+			Runtime::do_initialize_block(&header_at(1));
+			Runtime::do_apply_extrinsic(signed_ext(
+				alice,
+				vested_transfer(bob.public(), 300),
+				0,
+				None,
+			))
+			.unwrap()
+			.unwrap();
+			// none of the amount is free at first, but it still creates charlie.
+			Runtime::do_apply_extrinsic(signed_ext(alice, vested_transfer(charlie, 300), 1, None))
+				.unwrap()
+				.unwrap();
+			// nothing has vested yet, but bob still uses up a nonce for trying.
+			assert_eq!(
+				Runtime::do_apply_extrinsic(vest(0)).unwrap(),
+				Err(currency::NOTHING_VESTED)
+			);
+			Runtime::do_finalize_block();
+
+			assert_eq!(
+				balance(bob.public()),
+				Some(AccountBalance { free: 20, reserved: 300, nonce: 1 })
+			);
+			assert_eq!(balance(charlie), Some(AccountBalance { free: 0, reserved: 300, nonce: 0 }));
+			assert_eq!(currency::HeldDeposits::get(&charlie), Some(300));
+			// vesting funds are not bonded, and cannot be unbonded.
+			assert_eq!(staking::Module::bonded(&bob.public()), 0);
+
+			Runtime::do_initialize_block(&header_at(3));
+			Runtime::do_apply_extrinsic(vest(1)).unwrap().unwrap();
+			Runtime::do_finalize_block();
+
+			assert_eq!(
+				balance(bob.public()),
+				Some(AccountBalance { free: 120, reserved: 200, nonce: 2 })
+			);
+			assert_eq!(
+				events().last(),
+				Some(&(shared::Phase::ApplyExtrinsic(0), shared::RuntimeEvent::ExtrinsicSuccess))
+			);
+
+			// past the end of the schedule, the rest is released and the schedule removed.
+			Runtime::do_initialize_block(&header_at(10));
+			Runtime::do_apply_extrinsic(vest(2)).unwrap().unwrap();
+			Runtime::do_finalize_block();
+
+			assert_eq!(
+				balance(bob.public()),
+				Some(AccountBalance { free: 320, reserved: 0, nonce: 3 })
+			);
+			assert_eq!(currency::VestingSchedules::get(&bob.public()), None);
+			assert_eq!(currency::HeldDeposits::get(&bob.public()), None);
+			assert_eq!(currency::TotalIssuance::get(), Some(1020));
+
+			// charlie vests as well, despite starting without any free balance.
+			assert_eq!(CurrencyCall::Vest.dispatch(&charlie), Ok(()));
+			assert_eq!(balance(charlie), Some(AccountBalance { free: 300, reserved: 0, nonce: 0 }));
+			assert_eq!(currency::HeldDeposits::get(&charlie), None);
+		});
+	}
+
+	#[test]
+	fn vesting_schedules_are_validated_and_limited() {
+		let alice = sp_keyring::AccountKeyring::Alice.public();
+		let bob = sp_keyring::AccountKeyring::Bob.public();
+		let config =
+			RuntimeGenesisConfig { balances: vec![(alice, 1000), (bob, 20)], ..Default::default() };
+		let vested_transfer = |amount, duration| CurrencyCall::VestedTransfer {
+			dest: bob,
+			amount,
+			start: 0,
+			duration,
+		};
+
+		new_test_ext(config).execute_with(|| {
+			assert_eq!(vested_transfer(0, 10).dispatch(&alice), Err(currency::INVALID_SCHEDULE));
+			assert_eq!(vested_transfer(10, 0).dispatch(&alice), Err(currency::INVALID_SCHEDULE));
+			// dust cannot take up the schedules of bob.
+			assert_eq!(
+				vested_transfer(MINIMUM_BALANCE - 1, 10).dispatch(&alice),
+				Err(currency::INVALID_SCHEDULE)
+			);
+			assert_eq!(currency::VestingSchedules::get(&bob), None);
+			for _ in 0..shared::MAX_VESTING_SCHEDULES {
+				assert_eq!(vested_transfer(MINIMUM_BALANCE, 10).dispatch(&alice), Ok(()));
+			}
+			assert_eq!(
+				vested_transfer(MINIMUM_BALANCE, 10).dispatch(&alice),
+				Err(currency::TOO_MANY_SCHEDULES)
+			);
+		});
+	}
+
+	#[test]
+	fn alice_schedules_transfers_to_bob_and_charlie_at_block_3() {
+		use shared::{Phase::Initialization, RuntimeEvent};
+		let alice = sp_keyring::AccountKeyring::Alice;
+		let bob = sp_keyring::AccountKeyring::Bob.public();
+		let charlie = sp_keyring::AccountKeyring::Charlie.public();
+		let config =
+			RuntimeGenesisConfig { balances: vec![(alice.public(), 1000)], ..Default::default() };
+		let schedule = |dest, amount, when| {
+			RuntimeCall::Currency(CurrencyCall::ScheduleTransfer { dest, amount, when })
+		};
+		let balance = |who: AccountId| currency::BalancesMap::get(&who);
+
+		new_test_ext(config).execute_with(|| {
+			Runtime::do_initialize_block(&header_at(2));
+			// the current block is too late.
+			assert_eq!(
+				Runtime::do_apply_extrinsic(signed_ext(alice, schedule(bob, 100, 2), 0, None))
+					.unwrap(),
+				Err(currency::SCHEDULED_IN_PAST)
+			);
+			Runtime::do_apply_extrinsic(signed_ext(alice, schedule(bob, 100, 3), 1, None))
+				.unwrap()
+				.unwrap();
+			// this one cannot create charlie once it executes.
+			Runtime::do_apply_extrinsic(signed_ext(alice, schedule(charlie, 5, 3), 2, None))
+				.unwrap()
+				.unwrap();
+			Runtime::do_finalize_block();
+
+			assert_eq!(
+				balance(alice.public()),
+				Some(AccountBalance { free: 895, reserved: 105, nonce: 3 })
+			);
+			assert_eq!(balance(bob), None);
+
+			Runtime::do_initialize_block(&header_at(3));
+			Runtime::do_finalize_block();
+
+			assert_eq!(
+				balance(alice.public()),
+				Some(AccountBalance { free: 900, reserved: 0, nonce: 3 })
+			);
+			assert_eq!(balance(bob), Some(AccountBalance { free: 100, reserved: 0, nonce: 0 }));
+			assert_eq!(balance(charlie), None);
+			assert_eq!(currency::ScheduledTransfers::get(&3), None);
+			assert_eq!(currency::HeldDeposits::get(&alice.public()), None);
+			assert_eq!(currency::TotalIssuance::get(), Some(1000));
+			assert_eq!(
+				events(),
+				vec![
+					(
+						Initialization,
+						RuntimeEvent::Transferred { from: alice.public(), to: bob, amount: 100 }
+					),
+					(
+						Initialization,
+						RuntimeEvent::ScheduledTransferFailed {
+							from: alice.public(),
+							to: charlie,
+							amount: 5,
+							error: DispatchError::Other(""),
+						}
+					),
+				]
+			);
+		});
+	}
+
+	/// Genesis where alice has 1000, and a block on top of it with the timestamp inherent and a
+	/// transfer of 100 from alice to bob.
+	fn genesis_and_transfer_block() -> (Storage, Block) {
+		let alice = sp_keyring::AccountKeyring::Alice;
+		let bob = sp_keyring::AccountKeyring::Bob.public();
+		let config =
+			RuntimeGenesisConfig { balances: vec![(alice.public(), 1000)], ..Default::default() };
+		let genesis = config.build_storage().unwrap();
+		let transfer = RuntimeCall::Currency(CurrencyCall::Transfer { dest: bob, amount: 100 });
+		let transfer = signed_ext(alice, transfer, 0, None);
+
+		let block = TestExternalities::new(genesis.clone()).execute_with(|| {
+			Runtime::do_initialize_block(&header_at(1));
+			let inherents = Runtime::do_inherent_extrinsics(1_000);
+			Runtime::do_apply_extrinsic(inherents[0].clone()).unwrap().unwrap();
+			Runtime::do_apply_extrinsic(transfer.clone()).unwrap().unwrap();
+			let header = Runtime::do_finalize_block();
+			Block { extrinsics: [inherents, vec![transfer]].concat(), header }
+		});
+		(genesis, block)
+	}
+
+	#[test]
+	fn replay_records_the_changes_of_each_extrinsic() {
+		use replay::Outcome;
+		let alice = sp_keyring::AccountKeyring::Alice.public();
+		let bob = sp_keyring::AccountKeyring::Bob.public();
+		let (genesis, block) = genesis_and_transfer_block();
+
+		let replay = replay::replay_block(&genesis, &block);
+		assert_eq!(replay.failure, None);
+		assert_eq!(replay.import.state_root, Some(block.header.state_root));
+		assert_eq!(replay.authoring.state_root, Some(block.header.state_root));
+
+		let phases = replay.import.steps.iter().map(|step| step.phase).collect::<Vec<_>>();
+		assert_eq!(
+			phases,
+			vec![
+				shared::Phase::Initialization,
+				shared::Phase::ApplyExtrinsic(0),
+				shared::Phase::ApplyExtrinsic(1),
+				shared::Phase::Finalization
+			]
+		);
+		let transfer = &replay.import.steps[2];
+		assert_eq!(transfer.outcome, Outcome::Ok);
+		let keys = transfer.changes.iter().map(|change| change.key.to_vec()).collect::<Vec<_>>();
+		assert!(keys.contains(&currency::BalancesMap::key(&alice)));
+		assert!(keys.contains(&currency::BalancesMap::key(&bob)));
+		assert!(keys.contains(&EXTRINSICS_KEY.to_vec()));
+
+		// the next block can be replayed on top of the state that this one leaves.
+		let bob_balance = AccountBalance { free: 100, reserved: 0, nonce: 0 };
+		assert_eq!(
+			replay.post_state().top.get(&currency::BalancesMap::key(&bob)),
+			Some(&bob_balance.encode())
+		);
+	}
+
+	#[test]
+	fn replay_finds_why_a_block_fails_to_import() {
+		use replay::{Failure, Outcome};
+		let (genesis, block) = genesis_and_transfer_block();
+
+		let mut tampered = block.clone();
+		tampered.header.state_root = H256::repeat_byte(1);
+		let replay = replay::replay_block(&genesis, &tampered);
+		assert_eq!(
+			replay.failure,
+			Some(Failure::StateRootMismatch {
+				expected: H256::repeat_byte(1),
+				actual: block.header.state_root
+			})
+		);
+		let json = serde_json::to_value(&replay).unwrap();
+		assert_eq!(json["failure"]["kind"], "stateRootMismatch");
+
+		// import asserts that the block starts with the timestamp inherent, authoring does not.
+		let mut no_inherent = block.clone();
+		no_inherent.extrinsics.remove(0);
+		let replay = replay::replay_block(&genesis, &no_inherent);
+		assert_eq!(
+			replay.failure,
+			Some(Failure::Diverged { phase: shared::Phase::Initialization })
+		);
+		assert_eq!(replay.import.steps.len(), 1);
+		assert_eq!(
+			replay.import.steps[0].outcome,
+			Outcome::Panicked("A block must start with the timestamp inherent".into())
+		);
+		assert_eq!(replay.import.state_root, None);
+
+		// the transfer cannot be applied twice.
+		let mut replayed = block.clone();
+		replayed.extrinsics.push(block.extrinsics[1].clone());
+		assert_eq!(
+			replay::replay_block(&genesis, &replayed).failure,
+			Some(Failure::InvalidExtrinsic {
+				index: 2,
+				error: format!("{:?}", TransactionValidityError::from(InvalidTransaction::Stale))
+			})
+		);
+	}
+
+	#[test]
+	fn genesis_config_endows_stakes_and_sets_value() {
+		let alice = sp_keyring::AccountKeyring::Alice;
+		let bob = sp_keyring::AccountKeyring::Bob;
+		let config = RuntimeGenesisConfig {
+			stakes: vec![(bob.public(), 40)],
+			value: Some(42),
+			..RuntimeGenesisConfig::with_endowed(vec![alice.public(), bob.public()], 100)
+		};
+
+		new_test_ext(config).execute_with(|| {
+			assert_eq!(
+				Runtime::get_state::<AccountBalance>(
+					&[b"BalancesMap", alice.public().encode().as_slice()].concat()
+				),
+				Some(AccountBalance { free: 100, reserved: 0, nonce: 0 })
+			);
+			assert_eq!(
+				Runtime::get_state::<AccountBalance>(
+					&[b"BalancesMap", bob.public().encode().as_slice()].concat()
+				),
+				Some(AccountBalance { free: 60, reserved: 40, nonce: 0 })
+			);
+			assert_eq!(staking::Module::bonded(&bob.public()), 40);
+			assert_eq!(Runtime::get_state::<u128>(b"TotalIssuance"), Some(200));
+			assert_eq!(Runtime::get_state::<u32>(VALUE_KEY), Some(42));
+			assert_eq!(
+				Runtime::get_state::<shared::FeeConfig>(b"FeeConfig"),
+				Some(shared::DEFAULT_FEE_CONFIG)
+			);
+		});
+	}
+
+	#[test]
+	fn genesis_config_rejects_invalid_balances_and_stakes() {
+		let alice = sp_keyring::AccountKeyring::Alice.public();
+		let bob = sp_keyring::AccountKeyring::Bob.public();
+		let build = |balances, stakes| {
+			RuntimeGenesisConfig { balances, stakes, ..Default::default() }.build_storage()
+		};
+
+		assert!(build(vec![(alice, 100)], vec![(alice, 90)]).is_ok());
+		assert!(build(vec![(alice, MINIMUM_BALANCE - 1)], vec![]).is_err());
+		assert!(build(vec![(alice, 100), (alice, 100)], vec![]).is_err());
+		assert!(build(vec![(alice, 100)], vec![(alice, 91)]).is_err());
+		assert!(build(vec![(alice, 100)], vec![(bob, 10)]).is_err());
+	}
+
+	#[test]
+	fn genesis_config_from_json() {
+		let config: RuntimeGenesisConfig = serde_json::from_str(
+			r#"{
+				"balances": [["5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY", 1000]],
+				"value": 7
+			}"#,
+		)
+		.unwrap();
+		assert_eq!(
+			config,
+			RuntimeGenesisConfig {
+				balances: vec![(sp_keyring::AccountKeyring::Alice.public(), 1000)],
+				value: Some(7),
+				..Default::default()
+			}
+		);
+	}
+
+	#[test]
+	fn metadata_describes_calls_and_storage() {
+		use frame_metadata::{v14::StorageEntryType, RuntimeMetadata, RuntimeMetadataPrefixed};
+
+		let encoded: Vec<u8> = metadata::runtime_metadata().into();
+		let RuntimeMetadataPrefixed(_, RuntimeMetadata::V14(metadata)) =
+			RuntimeMetadataPrefixed::decode(&mut &encoded[..]).unwrap()
+		else {
+			panic!("metadata should be V14")
+		};
+
+		// each module is a pallet, indexed like the variants of `RuntimeCall`.
+		for (index, name) in
+			["Currency", "Staking", "System", "Multisig", "Proxy"].iter().enumerate()
+		{
+			let pallet = metadata.pallets.iter().find(|p| p.name == *name).unwrap();
+			assert_eq!(pallet.index as usize, index);
+			assert!(pallet.calls.is_some());
+		}
+
+		// the storage of each account is described, and its value type resolves to
+		// `AccountBalance`.
+		let currency = metadata.pallets.iter().find(|p| p.name == "Currency").unwrap();
+		let balances = currency
+			.storage
+			.as_ref()
+			.unwrap()
+			.entries
+			.iter()
+			.find(|e| e.name == "BalancesMap")
+			.unwrap();
+		let StorageEntryType::Map { value, .. } = &balances.ty else { panic!("should be a map") };
+		let value = metadata.types.resolve(value.id).unwrap();
+		assert_eq!(value.path.ident(), Some("AccountBalance".to_string()));
+
+		// all events are those of `System`.
+		let system = metadata.pallets.iter().find(|p| p.name == "System").unwrap();
+		let event = metadata.types.resolve(system.event.as_ref().unwrap().ty.id).unwrap();
+		assert_eq!(event.path.ident(), Some("RuntimeEvent".to_string()));
+
+		// the extrinsic type is described as well, and reaches `RuntimeCallExt`.
+		assert!(metadata
+			.types
+			.types
+			.iter()
+			.any(|t| t.ty.path.ident() == Some("RuntimeCallExt".to_string())));
+	}
+
+	#[test]
+	fn timestamp_inherent_must_be_first_and_increasing() {
+		let alice = sp_keyring::AccountKeyring::Alice;
+		let set_timestamp = |now| {
+			shared::Extrinsic::new_unsigned(RuntimeCallExt {
+				call: RuntimeCall::System(shared::SystemCall::SetTimestamp { now }),
+				nonce: 0,
+				tip: None,
+			})
+		};
+		TestExternalities::new_empty().execute_with(|| {
+			Runtime::do_initialize_block(&header_at(1));
+			Runtime::do_apply_extrinsic(set_timestamp(1_000)).unwrap().unwrap();
+			assert_eq!(system::Module::now(), 1_000);
+
+			// a second one in the same block is rejected.
+			assert_eq!(
+				Runtime::do_apply_extrinsic(set_timestamp(2_000)).unwrap_err(),
+				TransactionValidityError::Invalid(InvalidTransaction::BadMandatory)
+			);
+			// and so is a signed one.
+			let signed = signed_ext(
+				alice,
+				RuntimeCall::System(shared::SystemCall::SetTimestamp { now: 2_000 }),
+				0,
+				None,
+			);
+			assert_eq!(Runtime::do_apply_extrinsic(signed).unwrap(), Err(DispatchError::BadOrigin));
+			Runtime::do_finalize_block();
+
+			// the next block cannot go back in time, but the author never proposes that.
+			Runtime::do_initialize_block(&header_at(2));
+			assert_eq!(
+				Runtime::do_apply_extrinsic(set_timestamp(1_000)).unwrap_err(),
+				TransactionValidityError::Invalid(InvalidTransaction::BadMandatory)
+			);
+			let inherents = Runtime::do_inherent_extrinsics(500);
+			assert_eq!(inherents, vec![set_timestamp(1_000 + shared::MINIMUM_PERIOD)]);
+			Runtime::do_apply_extrinsic(inherents[0].clone()).unwrap().unwrap();
+			Runtime::do_finalize_block();
+
+			assert_eq!(
+				Runtime::get_state::<u64>(shared::TIMESTAMP_KEY),
+				Some(1_000 + shared::MINIMUM_PERIOD)
+			);
+		});
+	}
+
+	#[test]
+	fn check_inherents_rejects_missing_or_drifting_timestamp() {
+		let block = |extrinsics| Block { header: header_at(1), extrinsics };
+		TestExternalities::new_empty().execute_with(|| {
+			let inherents = Runtime::do_inherent_extrinsics(100_000);
+			assert_eq!(Runtime::do_check_inherents(&block(inherents.clone()), 100_000), Ok(()));
+			assert!(
+				Runtime::do_check_inherents(&block(vec![signed_set_value(1, 0)]), 100_000).is_err()
+			);
+
+			// the author's clock is ahead of ours, by more than the allowed drift.
+			let local_now = 100_000 - shared::MAX_TIMESTAMP_DRIFT - 1;
+			assert!(Runtime::do_check_inherents(&block(inherents), local_now).is_err());
+		});
+	}
+
+	fn authority(seed: &str) -> (shared::AuraId, shared::GrandpaId) {
+		use sp_core::Pair;
+		let uri = format!("//{}", seed);
+		(
+			sp_core::sr25519::Pair::from_string(&uri, None).unwrap().public().into(),
+			sp_core::ed25519::Pair::from_string(&uri, None).unwrap().public().into(),
+		)
+	}
+
+	#[test]
+	fn genesis_config_sets_authorities_that_rotate_by_slot() {
+		let authorities = vec![authority("Alice"), authority("Bob"), authority("Charlie")];
+		let config = RuntimeGenesisConfig {
+			authorities: authorities.clone(),
+			slot_duration: Some(2_000),
+			..Default::default()
+		};
+
+		new_test_ext(config).execute_with(|| {
+			assert_eq!(consensus::Module::slot_duration(), 2_000);
+			for slot in 0..6 {
+				assert_eq!(
+					consensus::Module::slot_author(slot),
+					Some(authorities[slot as usize % 3].0.clone())
+				);
+			}
+			assert_eq!(
+				consensus::Module::grandpa_authorities(),
+				authorities.iter().map(|(_, grandpa)| (grandpa.clone(), 1)).collect::<Vec<_>>()
+			);
+
+			// the session keys of an authority decode into its aura and grandpa keys.
+			let (aura, grandpa) = authorities[1].clone();
+			let keys = consensus::SessionKeys { aura, grandpa }.encode();
+			let decoded = consensus::SessionKeys::decode_into_raw_public_keys(&keys).unwrap();
+			assert_eq!(decoded.len(), 2);
+			assert_eq!(decoded[0].1, sp_core::crypto::KeyTypeId(*b"aura"));
+			assert_eq!(decoded[1].1, sp_core::crypto::KeyTypeId(*b"gran"));
+		});
+
+		// without any, no one can author and the default slot duration applies.
+		new_test_ext(Default::default()).execute_with(|| {
+			assert_eq!(consensus::Module::slot_author(0), None);
+			assert_eq!(consensus::Module::slot_duration(), shared::DEFAULT_SLOT_DURATION);
+		});
+
+		let build = |authorities, slot_duration| {
+			RuntimeGenesisConfig { authorities, slot_duration, ..Default::default() }
+				.build_storage()
+		};
+		assert!(build(vec![authority("Alice"), authority("Alice")], None).is_err());
+		assert!(build(vec![authority("Alice")], Some(0)).is_err());
+	}
+
+	#[test]
+	fn aura_slot_must_increase_and_match_timestamp() {
+		let header_at_slot = |number, slot: shared::Slot| {
+			let mut header = header_at(number);
+			header.digest.push(sp_runtime::DigestItem::PreRuntime(
+				sp_consensus_aura::AURA_ENGINE_ID,
+				slot.encode(),
+			));
+			header
+		};
+		let config = RuntimeGenesisConfig { slot_duration: Some(1_000), ..Default::default() };
+
+		new_test_ext(config.clone()).execute_with(|| {
+			Runtime::do_initialize_block(&header_at_slot(1, 5));
+			assert_eq!(consensus::CurrentSlot::get(), Some(5));
+			// the timestamp must fall in slot 5.
+			let inherents = Runtime::do_inherent_extrinsics(6_000);
+			assert_eq!(
+				Runtime::do_apply_extrinsic(inherents[0].clone()).unwrap_err(),
+				TransactionValidityError::Invalid(InvalidTransaction::BadMandatory)
+			);
+			let inherents = Runtime::do_inherent_extrinsics(5_500);
+			Runtime::do_apply_extrinsic(inherents[0].clone()).unwrap().unwrap();
+			Runtime::do_finalize_block();
+
+			// blocks without a slot, as sealed manually, are not checked.
+			Runtime::do_initialize_block(&header_at(2));
+			let inherents = Runtime::do_inherent_extrinsics(60_000);
+			Runtime::do_apply_extrinsic(inherents[0].clone()).unwrap().unwrap();
+			Runtime::do_finalize_block();
+			assert_eq!(consensus::CurrentSlot::get(), Some(5));
+		});
+
+		new_test_ext(config).execute_with(|| {
+			Runtime::do_initialize_block(&header_at_slot(1, 5));
+			Runtime::do_finalize_block();
+			let replayed = std::panic::catch_unwind(|| {
+				Runtime::do_initialize_block(&header_at_slot(2, 5));
+			});
+			assert!(replayed.is_err());
+		});
+	}
+
+	/// Reads the version of "code" that is nothing but an encoded [`RuntimeVersion`], so that
+	/// upgrades can be tested without compiling a runtime for each version.
+	struct ReadEncodedVersion;
+
+	impl sp_core::traits::ReadRuntimeVersion for ReadEncodedVersion {
+		fn read_runtime_version(
+			&self,
+			code: &[u8],
+			_: &mut dyn sp_core::traits::Externalities,
+		) -> Result<Vec<u8>, String> {
+			RuntimeVersion::decode(&mut &code[..])
+				.map(|_| code.to_vec())
+				.map_err(|e| e.to_string())
+		}
+	}
+
+	fn version_with(spec_name: &'static str, spec_version: u32) -> Vec<u8> {
+		RuntimeVersion { spec_name: create_runtime_str!(spec_name), spec_version, ..VERSION }
+			.encode()
+	}
+
+	/// Alice, as [`SUDO`], authorizing `code` with `nonce` and then applying it with the next one.
+	fn authorize_and_apply(code: &[u8], nonce: u32) -> [Extrinsic; 2] {
+		let alice = sp_keyring::AccountKeyring::Alice;
+		let code_hash = sp_io::hashing::blake2_256(code);
+		[
+			signed_ext(
+				alice,
+				RuntimeCall::System(shared::SystemCall::AuthorizeUpgrade { code_hash }),
+				nonce,
+				None,
+			),
+			signed_ext(
+				alice,
+				RuntimeCall::System(shared::SystemCall::ApplyAuthorizedUpgrade {
+					code: code.to_vec(),
+				}),
+				nonce + 1,
+				None,
+			),
+		]
+	}
+
+	fn upgrade_ext() -> TestExternalities {
+		let mut ext = TestExternalities::new_empty();
+		ext.register_extension(sp_core::traits::ReadRuntimeVersionExt::new(ReadEncodedVersion));
+		ext
+	}
+
+	#[test]
+	fn upgrade_must_be_authorized_by_sudo_and_match_code_hash() {
+		let alice = sp_keyring::AccountKeyring::Alice;
+		let bob = sp_keyring::AccountKeyring::Bob;
+		let code = version_with("frameless-runtime", VERSION.spec_version + 1);
+		let authorize = |signer, code_hash, nonce| {
+			signed_ext(
+				signer,
+				RuntimeCall::System(shared::SystemCall::AuthorizeUpgrade { code_hash }),
+				nonce,
+				None,
+			)
+		};
+		let apply = |code: &[u8], nonce| {
+			signed_ext(
+				bob,
+				RuntimeCall::System(shared::SystemCall::ApplyAuthorizedUpgrade {
+					code: code.to_vec(),
+				}),
+				nonce,
+				None,
+			)
+		};
+
+		upgrade_ext().execute_with(|| {
+			// nothing is authorized yet. Each failed attempt of bob still uses up his nonce.
+			assert_eq!(
+				Runtime::do_apply_extrinsic(apply(&code, 0)).unwrap(),
+				Err(system::UPGRADE_NOT_AUTHORIZED)
+			);
+
+			// only sudo can authorize.
+			let code_hash = sp_io::hashing::blake2_256(&code);
+			assert_eq!(
+				Runtime::do_apply_extrinsic(authorize(bob, code_hash, 1)).unwrap(),
+				Err(DispatchError::BadOrigin)
+			);
+			Runtime::do_apply_extrinsic(authorize(alice, code_hash, 0)).unwrap().unwrap();
+
+			// other code than the authorized one cannot be applied.
+			let other = version_with("frameless-runtime", VERSION.spec_version + 2);
+			assert_eq!(
+				Runtime::do_apply_extrinsic(apply(&other, 2)).unwrap(),
+				Err(system::UPGRADE_NOT_AUTHORIZED)
+			);
+
+			// but anyone can apply the authorized code, only once.
+			Runtime::do_apply_extrinsic(apply(&code, 3)).unwrap().unwrap();
+			assert_eq!(sp_io::storage::get(well_known_keys::CODE).unwrap().to_vec(), code);
+			assert_eq!(system::PendingUpgrade::get(), Some(VERSION.spec_version + 1));
+			assert_eq!(system::AuthorizedUpgrade::get(), None);
+			assert_eq!(
+				Runtime::do_apply_extrinsic(apply(&code, 4)).unwrap(),
+				Err(system::UPGRADE_NOT_AUTHORIZED)
+			);
+		});
+	}
+
+	#[test]
+	fn system_calls_keep_their_indices() {
+		// index 3 was `Upgrade`, which is gone.
+		let calls = [
+			(shared::SystemCall::Set { value: 0 }, 2),
+			(shared::SystemCall::AuthorizeUpgrade { code_hash: [0; 32] }, 4),
+			(shared::SystemCall::ApplyAuthorizedUpgrade { code: vec![] }, 5),
+			(shared::SystemCall::SetTimestamp { now: 0 }, 6),
+			(shared::SystemCall::Batch { calls: vec![] }, 7),
+			(shared::SystemCall::BatchAll { calls: vec![] }, 8),
+		];
+		for (call, index) in calls {
+			assert_eq!(call.encode()[0], index, "{:?}", call);
+		}
+		assert!(shared::SystemCall::decode(&mut &[3u8, 0][..]).is_err());
+	}
+
+	#[test]
+	fn upgrade_rejects_invalid_code_other_chain_and_downgrade() {
+		upgrade_ext().execute_with(|| {
+			let code_before = sp_io::storage::get(well_known_keys::CODE);
+			let cases = [
+				(b"not a runtime".to_vec(), system::INVALID_CODE),
+				(
+					version_with("other-runtime", VERSION.spec_version + 1),
+					system::SPEC_NAME_MISMATCH,
+				),
+				(
+					version_with("frameless-runtime", VERSION.spec_version),
+					system::SPEC_VERSION_NOT_INCREASED,
+				),
+				(
+					version_with("frameless-runtime", VERSION.spec_version - 1),
+					system::SPEC_VERSION_NOT_INCREASED,
+				),
+			];
+			for (index, (code, error)) in cases.into_iter().enumerate() {
+				let [authorize, apply] = authorize_and_apply(&code, 2 * index as u32);
+				Runtime::do_apply_extrinsic(authorize).unwrap().unwrap();
+				assert_eq!(Runtime::do_apply_extrinsic(apply).unwrap(), Err(error));
+			}
+			assert_eq!(sp_io::storage::get(well_known_keys::CODE), code_before);
+			assert!(!system::PendingUpgrade::exists());
+		});
+	}
+
+	#[test]
+	fn migrations_run_once_in_the_block_after_upgrade() {
+		struct Migration;
+		impl Hooks for Migration {
+			fn on_runtime_upgrade() {
+				sp_io::storage::set(
+					b"migrated",
+					&(Runtime::get_state::<u32>(b"migrated").unwrap_or(0) + 1).encode(),
+				);
+			}
+		}
+
+		upgrade_ext().execute_with(|| {
+			assert!(!system::Module::maybe_run_migrations::<Migration>());
+
+			let code = version_with("frameless-runtime", VERSION.spec_version + 1);
+			Runtime::do_initialize_block(&header_at(1));
+			for ext in authorize_and_apply(&code, 0) {
+				Runtime::do_apply_extrinsic(ext).unwrap().unwrap();
+			}
+			Runtime::do_finalize_block();
+
+			assert!(system::Module::maybe_run_migrations::<Migration>());
+			assert!(!system::Module::maybe_run_migrations::<Migration>());
+			assert_eq!(Runtime::get_state::<u32>(b"migrated"), Some(1));
+		});
+	}
+
+	#[test]
+	#[cfg(feature = "with-upgrade-test-runtime")]
+	fn upgrade_to_compiled_runtime_with_higher_spec_version() {
+		let alice = sp_keyring::AccountKeyring::Alice;
+		let bob = sp_keyring::AccountKeyring::Bob;
+		let current = WASM_BINARY.unwrap().to_vec();
+		let upgraded = upgrade_test_runtime::WASM_BINARY.unwrap().to_vec();
+
+		let executor =
+			sc_executor::WasmExecutor::<sp_io::SubstrateHostFunctions>::builder().build();
+		let mut ext = TestExternalities::new_empty();
+		ext.register_extension(sp_core::traits::ReadRuntimeVersionExt::new(executor));
+
+		ext.execute_with(|| {
+			let authorize = |code: &[u8], nonce| {
+				signed_ext(
+					alice,
+					RuntimeCall::System(shared::SystemCall::AuthorizeUpgrade {
+						code_hash: sp_io::hashing::blake2_256(code),
+					}),
+					nonce,
+					None,
+				)
+			};
+			let apply = |code: &[u8], nonce| {
+				signed_ext(
+					bob,
+					RuntimeCall::System(shared::SystemCall::ApplyAuthorizedUpgrade {
+						code: code.to_vec(),
+					}),
+					nonce,
+					None,
+				)
+			};
+
+			// the runtime itself is not an upgrade of itself.
+			Runtime::do_apply_extrinsic(authorize(&current, 0)).unwrap().unwrap();
+			assert_eq!(
+				Runtime::do_apply_extrinsic(apply(&current, 0)).unwrap(),
+				Err(system::SPEC_VERSION_NOT_INCREASED)
+			);
+
+			// but the one with a higher spec version is. The failed attempt used up bob's nonce.
+			Runtime::do_apply_extrinsic(authorize(&upgraded, 1)).unwrap().unwrap();
+			Runtime::do_apply_extrinsic(apply(&upgraded, 1)).unwrap().unwrap();
+			assert_eq!(sp_io::storage::get(well_known_keys::CODE).unwrap().to_vec(), upgraded);
+			assert_eq!(system::PendingUpgrade::get(), Some(VERSION.spec_version + 1));
+		});
+	}
 }
@@ -0,0 +1,357 @@
+//! The metadata of the runtime, in the `V14` format of `frame-metadata`.
+//!
+//! Each module of the runtime is described as a pallet, whose index is that of its variant in
+//! [`RuntimeCall`], so that clients can build and decode extrinsics from the type registry alone.
+//!
+//! Note that the storage of this runtime is not laid out like that of FRAME: each storage item
+//! lives under its raw name (e.g. `BalancesMap ++ encode(account)`), without any hashing. The
+//! entries below reflect this with the name of each item being its raw key, and the
+//! [`StorageHasher::Identity`] hasher. Clients that assume `twox128` prefixes should use the
+//! `StateQueryApi` runtime API instead.
+
+use crate::{
+	shared::{
+		AccountBalance, AccountId, AuraId, Balance, BlockNumber, CallHash, CurrencyCall,
+		EventRecord, Extrinsic, FeeConfig, GrandpaId, Header, Moment, Multisig, MultisigCall,
+		MultisigOperation, Phase, ProxyCall, ProxyDefinition, RuntimeEvent, ScheduledTransfer,
+		Slot, StakingCall, SystemCall, UnlockChunk, VestingSchedule, BONDING_DURATION,
+		DEFAULT_SLOT_DURATION, MAX_BATCH_DEPTH, MAX_BATCH_LENGTH, MAX_PROXIES,
+		MAX_SCHEDULED_PER_BLOCK, MAX_SIGNATORIES, MAX_TIMESTAMP_DRIFT, MAX_UNLOCKING_CHUNKS,
+		MAX_VESTING_SCHEDULES, MINIMUM_BALANCE, MINIMUM_PERIOD, MULTISIG_DEPOSIT, PROXY_DEPOSIT,
+	},
+	Runtime,
+};
+use frame_metadata::{
+	v14::{
+		ExtrinsicMetadata, PalletCallMetadata, PalletConstantMetadata, PalletEventMetadata,
+		PalletMetadata, PalletStorageMetadata, RuntimeMetadataV14, StorageEntryMetadata,
+		StorageEntryModifier, StorageEntryType, StorageHasher,
+	},
+	RuntimeMetadata, RuntimeMetadataPrefixed, META_RESERVED,
+};
+use parity_scale_codec::Encode;
+use scale_info::{meta_type, TypeInfo};
+use sp_core::H256;
+use sp_std::prelude::*;
+
+/// The metadata versions that this runtime can provide.
+pub const METADATA_VERSIONS: [u32; 1] = [14];
+
+/// A storage value that exists under `name`.
+fn value<V: TypeInfo + 'static>(
+	name: &'static str,
+	modifier: StorageEntryModifier,
+	default: Vec<u8>,
+	docs: &'static str,
+) -> StorageEntryMetadata {
+	StorageEntryMetadata {
+		name,
+		modifier,
+		ty: StorageEntryType::Plain(meta_type::<V>()),
+		default,
+		docs: vec![docs],
+	}
+}
+
+/// A storage map that lives under `name ++ encode(key)`.
+fn map<K: TypeInfo + 'static, V: TypeInfo + 'static>(
+	name: &'static str,
+	modifier: StorageEntryModifier,
+	default: Vec<u8>,
+	docs: &'static str,
+) -> StorageEntryMetadata {
+	StorageEntryMetadata {
+		name,
+		modifier,
+		ty: StorageEntryType::Map {
+			hashers: vec![StorageHasher::Identity],
+			key: meta_type::<K>(),
+			value: meta_type::<V>(),
+		},
+		default,
+		docs: vec![docs],
+	}
+}
+
+fn constant<T: TypeInfo + Encode + 'static>(
+	name: &'static str,
+	value: T,
+	docs: &'static str,
+) -> PalletConstantMetadata {
+	PalletConstantMetadata { name, ty: meta_type::<T>(), value: value.encode(), docs: vec![docs] }
+}
+
+fn pallet(
+	name: &'static str,
+	index: u8,
+	calls: Option<PalletCallMetadata>,
+	entries: Vec<StorageEntryMetadata>,
+	constants: Vec<PalletConstantMetadata>,
+) -> PalletMetadata {
+	// all events of the runtime are variants of a single `RuntimeEvent`, deposited by `System`.
+	let event = (name == "System").then(|| PalletEventMetadata { ty: meta_type::<RuntimeEvent>() });
+	PalletMetadata {
+		name,
+		storage: Some(PalletStorageMetadata { prefix: name, entries }),
+		calls,
+		event,
+		constants,
+		error: None,
+		index,
+	}
+}
+
+fn calls<C: TypeInfo + 'static>() -> Option<PalletCallMetadata> {
+	Some(PalletCallMetadata { ty: meta_type::<C>() })
+}
+
+/// The metadata of all modules of the runtime.
+pub fn pallets() -> Vec<PalletMetadata> {
+	use StorageEntryModifier::Optional;
+	vec![
+		pallet(
+			"Currency",
+			0,
+			calls::<CurrencyCall>(),
+			vec![
+				map::<AccountId, AccountBalance>(
+					"BalancesMap",
+					StorageEntryModifier::Default,
+					AccountBalance::default().encode(),
+					"The balance of each account.",
+				),
+				value::<Balance>(
+					"TotalIssuance",
+					StorageEntryModifier::Default,
+					Balance::default().encode(),
+					"The sum of all balances in the system.",
+				),
+				map::<AccountId, Balance>(
+					"HeldDeposits",
+					StorageEntryModifier::Default,
+					Balance::default().encode(),
+					"The part of the reserved balance of each account that is held as a deposit.",
+				),
+				map::<AccountId, Vec<VestingSchedule>>(
+					"VestingSchedules",
+					StorageEntryModifier::Default,
+					Vec::<VestingSchedule>::new().encode(),
+					"The vesting schedules of each account.",
+				),
+				map::<BlockNumber, Vec<ScheduledTransfer>>(
+					"ScheduledTransfers",
+					StorageEntryModifier::Default,
+					Vec::<ScheduledTransfer>::new().encode(),
+					"The transfers to execute at the beginning of each block.",
+				),
+			],
+			vec![
+				constant(
+					"MinimumBalance",
+					MINIMUM_BALANCE,
+					"The minimum free balance of an account.",
+				),
+				constant(
+					"MaxVestingSchedules",
+					MAX_VESTING_SCHEDULES,
+					"Maximum number of vesting schedules of an account.",
+				),
+				constant(
+					"MaxScheduledPerBlock",
+					MAX_SCHEDULED_PER_BLOCK,
+					"Maximum number of transfers that can be scheduled at the same block.",
+				),
+			],
+		),
+		pallet(
+			"Staking",
+			1,
+			calls::<StakingCall>(),
+			vec![map::<AccountId, Vec<UnlockChunk>>(
+				"UnbondingQueue",
+				StorageEntryModifier::Default,
+				Vec::<UnlockChunk>::new().encode(),
+				"The chunks of each account that are being unbonded.",
+			)],
+			vec![
+				constant(
+					"BondingDuration",
+					BONDING_DURATION,
+					"Number of blocks that unbonded funds remain reserved for.",
+				),
+				constant(
+					"MaxUnlockingChunks",
+					MAX_UNLOCKING_CHUNKS,
+					"Maximum number of chunks in the unbonding queue of an account.",
+				),
+			],
+		),
+		pallet(
+			"System",
+			2,
+			calls::<SystemCall>(),
+			vec![
+				value::<u32>("value", Optional, vec![0], "The value set by `SystemCall::Set`."),
+				value::<Header>(
+					"header",
+					Optional,
+					vec![0],
+					"The header of the block being authored.",
+				),
+				value::<Vec<Vec<u8>>>(
+					"extrinsics",
+					StorageEntryModifier::Default,
+					Vec::<Vec<u8>>::new().encode(),
+					"All extrinsics applied in the current block, in order.",
+				),
+				value::<Moment>(
+					"timestamp",
+					Optional,
+					vec![0],
+					"The timestamp of the current block, in milliseconds.",
+				),
+				value::<Vec<EventRecord>>(
+					"events",
+					StorageEntryModifier::Default,
+					Vec::<EventRecord>::new().encode(),
+					"All events of the current block, or of the last one outside of block execution.",
+				),
+				map::<H256, Vec<u32>>(
+					"EventTopics",
+					StorageEntryModifier::Default,
+					Vec::<u32>::new().encode(),
+					"The indices in `events` of the events with each topic.",
+				),
+				value::<Phase>(
+					"ExecutionPhase",
+					Optional,
+					vec![0],
+					"The part of the block that is being executed.",
+				),
+			],
+			vec![
+				constant(
+					"MinimumPeriod",
+					MINIMUM_PERIOD,
+					"The minimum number of milliseconds between two blocks.",
+				),
+				constant(
+					"MaxTimestampDrift",
+					MAX_TIMESTAMP_DRIFT,
+					"The maximum number of milliseconds a block can be ahead of the local clock.",
+				),
+				constant("MaxBatchLength", MAX_BATCH_LENGTH, "Maximum number of calls in a batch."),
+				constant(
+					"MaxBatchDepth",
+					MAX_BATCH_DEPTH,
+					"Maximum number of batches that can be nested in one another.",
+				),
+			],
+		),
+		pallet(
+			"Multisig",
+			3,
+			calls::<MultisigCall>(),
+			vec![
+				map::<AccountId, Multisig>(
+					"Multisigs",
+					Optional,
+					vec![0],
+					"The registered multisig accounts.",
+				),
+				map::<(AccountId, CallHash), MultisigOperation>(
+					"MultisigOperations",
+					Optional,
+					vec![0],
+					"The calls that are waiting for approvals.",
+				),
+			],
+			vec![
+				constant(
+					"MaxSignatories",
+					MAX_SIGNATORIES,
+					"Maximum number of signatories of a multisig.",
+				),
+				constant(
+					"MultisigDeposit",
+					MULTISIG_DEPOSIT,
+					"The deposit reserved for each open operation.",
+				),
+			],
+		),
+		pallet(
+			"Proxy",
+			4,
+			calls::<ProxyCall>(),
+			vec![map::<AccountId, Vec<ProxyDefinition>>(
+				"Proxies",
+				StorageEntryModifier::Default,
+				Vec::<ProxyDefinition>::new().encode(),
+				"The proxies of each account.",
+			)],
+			vec![
+				constant("MaxProxies", MAX_PROXIES, "Maximum number of proxies of an account."),
+				constant("ProxyDeposit", PROXY_DEPOSIT, "The deposit reserved for each proxy."),
+			],
+		),
+		pallet(
+			"TransactionPayment",
+			5,
+			None,
+			vec![value::<FeeConfig>(
+				"FeeConfig",
+				Optional,
+				vec![0],
+				"The parameters of the transaction fee. Transactions are free if this does not exist.",
+			)],
+			vec![],
+		),
+		pallet(
+			"Consensus",
+			6,
+			None,
+			vec![
+				value::<Vec<AuraId>>(
+					"AuraAuthorities",
+					StorageEntryModifier::Default,
+					Vec::<AuraId>::new().encode(),
+					"The authorities that author blocks, in the order in which they take turns.",
+				),
+				value::<Vec<GrandpaId>>(
+					"GrandpaAuthorities",
+					StorageEntryModifier::Default,
+					Vec::<GrandpaId>::new().encode(),
+					"The authorities that vote on finality, each with a weight of one.",
+				),
+				value::<Moment>(
+					"SlotDuration",
+					StorageEntryModifier::Default,
+					DEFAULT_SLOT_DURATION.encode(),
+					"The duration of a slot, in milliseconds.",
+				),
+				value::<Slot>(
+					"CurrentSlot",
+					StorageEntryModifier::Default,
+					Slot::default().encode(),
+					"The slot of the last block that was authored with Aura.",
+				),
+			],
+			vec![],
+		),
+	]
+}
+
+/// The metadata of the runtime.
+pub fn runtime_metadata() -> RuntimeMetadataPrefixed {
+	// the call of the extrinsic is `RuntimeCallExt`, which wraps `RuntimeCall` along with the nonce
+	// and tip, so there are no signed extensions.
+	let extrinsic =
+		ExtrinsicMetadata { ty: meta_type::<Extrinsic>(), version: 4, signed_extensions: vec![] };
+	let metadata = RuntimeMetadataV14::new(pallets(), extrinsic, meta_type::<Runtime>());
+	RuntimeMetadataPrefixed(META_RESERVED, RuntimeMetadata::V14(metadata))
+}
+
+/// The metadata of the runtime at `version`, if supported.
+pub fn runtime_metadata_at_version(version: u32) -> Option<RuntimeMetadataPrefixed> {
+	METADATA_VERSIONS.contains(&version).then(runtime_metadata)
+}
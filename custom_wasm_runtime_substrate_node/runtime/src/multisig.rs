@@ -0,0 +1,158 @@
+//! The multisig module: M-of-N accounts that dispatch calls once enough of their signatories have
+//! approved them.
+//!
+//! A multisig account has no key of its own. Its id is derived from its signatories and threshold,
+//! so anyone can compute it, and fund it, before it is even created.
+
+use crate::{
+	currency,
+	dispatch::{DispatchResult, Dispatchable, Hooks},
+	shared::{
+		AccountId, CallHash, Multisig, MultisigCall, MultisigOperation, RuntimeCall,
+		MAX_SIGNATORIES, MULTISIG_DEPOSIT,
+	},
+	storage::{storage_prefix, StorageMap},
+};
+use parity_scale_codec::Encode;
+use sp_runtime::DispatchError;
+use sp_std::prelude::*;
+
+storage_prefix!(MultisigsPrefix, b"Multisigs");
+storage_prefix!(MultisigOperationsPrefix, b"MultisigOperations");
+
+/// The registered multisig accounts, kept at `Multisigs ++ encode(multisig)`.
+pub type Multisigs = StorageMap<MultisigsPrefix, AccountId, Multisig>;
+/// The calls that are waiting for approvals, kept at `MultisigOperations ++ encode((multisig,
+/// call_hash))`.
+pub type MultisigOperations =
+	StorageMap<MultisigOperationsPrefix, (AccountId, CallHash), MultisigOperation>;
+
+/// Returned when creating a multisig with an invalid set of signatories or threshold.
+pub const INVALID_MULTISIG: DispatchError =
+	DispatchError::Other("Invalid signatories or threshold");
+/// Returned when creating a multisig that already exists.
+pub const MULTISIG_EXISTS: DispatchError = DispatchError::Other("Multisig already exists");
+/// Returned when referring to a multisig that does not exist.
+pub const UNKNOWN_MULTISIG: DispatchError = DispatchError::Other("Unknown multisig");
+/// Returned when the sender is not a signatory of the multisig.
+pub const NOT_SIGNATORY: DispatchError = DispatchError::Other("Sender is not a signatory");
+/// Returned when a signatory approves the same call twice.
+pub const ALREADY_APPROVED: DispatchError = DispatchError::Other("Call already approved");
+/// Returned when referring to a call that has no open operation.
+pub const UNKNOWN_OPERATION: DispatchError = DispatchError::Other("Unknown multisig operation");
+/// Returned when executing a call that does not have enough approvals yet.
+pub const NOT_ENOUGH_APPROVALS: DispatchError = DispatchError::Other("Not enough approvals");
+/// Returned when cancelling an operation that was opened by someone else.
+pub const NOT_DEPOSITOR: DispatchError = DispatchError::Other("Sender is not the depositor");
+
+/// The multisig module.
+pub struct Module;
+
+impl Hooks for Module {}
+
+impl Module {
+	/// The account id of the multisig of `signatories`, with `threshold`.
+	///
+	/// The order of `signatories` does not matter.
+	pub fn multisig_account_id(signatories: &[AccountId], threshold: u16) -> AccountId {
+		let mut signatories = signatories.to_vec();
+		signatories.sort();
+		let entropy =
+			(b"multisig", signatories, threshold).using_encoded(sp_io::hashing::blake2_256);
+		AccountId::from_raw(entropy)
+	}
+
+	/// The hash of `call`, as approved with [`MultisigCall::Approve`].
+	pub fn call_hash(call: &RuntimeCall) -> CallHash {
+		call.using_encoded(sp_io::hashing::blake2_256)
+	}
+
+	fn ensure_signatory(multisig: &AccountId, who: &AccountId) -> DispatchResult {
+		let multisig = Multisigs::get(multisig).ok_or(UNKNOWN_MULTISIG)?;
+		if multisig.signatories.binary_search(who).is_err() {
+			return Err(NOT_SIGNATORY)
+		}
+		Ok(())
+	}
+
+	fn create(mut signatories: Vec<AccountId>, threshold: u16) -> DispatchResult {
+		signatories.sort();
+		let len = signatories.len();
+		signatories.dedup();
+		if signatories.len() != len ||
+			len > MAX_SIGNATORIES as usize ||
+			threshold < 2 ||
+			threshold as usize > len
+		{
+			return Err(INVALID_MULTISIG)
+		}
+
+		let multisig = Self::multisig_account_id(&signatories, threshold);
+		if Multisigs::contains_key(&multisig) {
+			return Err(MULTISIG_EXISTS)
+		}
+		Multisigs::set(&multisig, &Multisig { signatories, threshold });
+		Ok(())
+	}
+
+	fn approve(sender: &AccountId, multisig: AccountId, call_hash: CallHash) -> DispatchResult {
+		Self::ensure_signatory(&multisig, sender)?;
+		let key = (multisig, call_hash);
+		let mut operation = match MultisigOperations::get(&key) {
+			Some(operation) => operation,
+			None => {
+				currency::Module::hold_deposit(sender, MULTISIG_DEPOSIT)?;
+				MultisigOperation {
+					approvals: Vec::new(),
+					depositor: *sender,
+					deposit: MULTISIG_DEPOSIT,
+				}
+			},
+		};
+
+		match operation.approvals.binary_search(sender) {
+			Ok(_) => return Err(ALREADY_APPROVED),
+			Err(index) => operation.approvals.insert(index, *sender),
+		}
+		MultisigOperations::set(&key, &operation);
+		Ok(())
+	}
+
+	fn execute(sender: &AccountId, multisig: AccountId, call: RuntimeCall) -> DispatchResult {
+		Self::ensure_signatory(&multisig, sender)?;
+		let threshold = Multisigs::get(&multisig).ok_or(UNKNOWN_MULTISIG)?.threshold;
+		let key = (multisig, Self::call_hash(&call));
+		let operation = MultisigOperations::get(&key).ok_or(UNKNOWN_OPERATION)?;
+		if operation.approvals.len() < threshold as usize {
+			return Err(NOT_ENOUGH_APPROVALS)
+		}
+
+		MultisigOperations::kill(&key);
+		currency::Module::release_deposit(&operation.depositor, operation.deposit)?;
+		call.dispatch(&multisig)
+	}
+
+	fn cancel(sender: &AccountId, multisig: AccountId, call_hash: CallHash) -> DispatchResult {
+		let key = (multisig, call_hash);
+		let operation = MultisigOperations::get(&key).ok_or(UNKNOWN_OPERATION)?;
+		if operation.depositor != *sender {
+			return Err(NOT_DEPOSITOR)
+		}
+		MultisigOperations::kill(&key);
+		currency::Module::release_deposit(sender, operation.deposit)
+	}
+}
+
+impl Dispatchable for MultisigCall {
+	fn dispatch(self, sender: &AccountId) -> DispatchResult {
+		match self {
+			MultisigCall::Create { signatories, threshold } =>
+				Module::create(signatories, threshold),
+			MultisigCall::Approve { multisig, call_hash } =>
+				Module::approve(sender, multisig, call_hash),
+			MultisigCall::Execute { multisig, call } => Module::execute(sender, multisig, *call),
+			MultisigCall::Cancel { multisig, call_hash } =>
+				Module::cancel(sender, multisig, call_hash),
+		}
+	}
+}
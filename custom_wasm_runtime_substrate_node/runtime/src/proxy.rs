@@ -0,0 +1,107 @@
+//! The proxy module: accounts that can dispatch a subset of calls on behalf of another account.
+
+use crate::{
+	currency,
+	dispatch::{DispatchResult, Dispatchable, Hooks},
+	shared::{
+		AccountId, ProxyCall, ProxyDefinition, ProxyType, RuntimeCall, SystemCall, MAX_PROXIES,
+		PROXY_DEPOSIT,
+	},
+	storage::{storage_prefix, StorageMap},
+};
+use sp_runtime::DispatchError;
+use sp_std::prelude::*;
+
+storage_prefix!(ProxiesPrefix, b"Proxies");
+
+/// The proxies of each account, kept at `Proxies ++ encode(account)`.
+pub type Proxies = StorageMap<ProxiesPrefix, AccountId, Vec<ProxyDefinition>>;
+
+/// Returned when adding a proxy that already exists.
+pub const DUPLICATE_PROXY: DispatchError = DispatchError::Other("Proxy already exists");
+/// Returned when an account already has [`MAX_PROXIES`] proxies.
+pub const TOO_MANY_PROXIES: DispatchError = DispatchError::Other("Too many proxies");
+/// Returned when the sender is not a proxy of the account.
+pub const NOT_PROXY: DispatchError = DispatchError::Other("Sender is not a proxy");
+/// Returned when the proxy type of the sender does not allow the call.
+pub const CALL_FILTERED: DispatchError = DispatchError::Other("Call not allowed by proxy type");
+
+impl ProxyType {
+	/// Whether a proxy of this type is allowed to dispatch `call`.
+	///
+	/// A batch is allowed if all of its calls are, so that it cannot be used to get around the
+	/// filter.
+	pub fn allows(&self, call: &RuntimeCall) -> bool {
+		match (self, call) {
+			(ProxyType::Any, _) => true,
+			(
+				_,
+				RuntimeCall::System(SystemCall::Batch { calls } | SystemCall::BatchAll { calls }),
+			) => calls.iter().all(|call| self.allows(call)),
+			(ProxyType::NonTransfer, call) => matches!(
+				call,
+				RuntimeCall::Staking(_) |
+					RuntimeCall::System(SystemCall::Remark { .. }) |
+					RuntimeCall::System(SystemCall::Set { .. })
+			),
+			(ProxyType::Staking, call) => matches!(call, RuntimeCall::Staking(_)),
+		}
+	}
+}
+
+/// The proxy module.
+pub struct Module;
+
+impl Hooks for Module {}
+
+impl Module {
+	fn add_proxy(sender: &AccountId, delegate: AccountId, proxy_type: ProxyType) -> DispatchResult {
+		let mut proxies = Proxies::get_or_default(sender);
+		if proxies.iter().any(|proxy| proxy.delegate == delegate) {
+			return Err(DUPLICATE_PROXY)
+		}
+		if proxies.len() >= MAX_PROXIES as usize {
+			return Err(TOO_MANY_PROXIES)
+		}
+
+		currency::Module::hold_deposit(sender, PROXY_DEPOSIT)?;
+		proxies.push(ProxyDefinition { delegate, proxy_type, deposit: PROXY_DEPOSIT });
+		Proxies::set(sender, &proxies);
+		Ok(())
+	}
+
+	fn remove_proxy(sender: &AccountId, delegate: AccountId) -> DispatchResult {
+		let mut proxies = Proxies::get_or_default(sender);
+		let index = proxies.iter().position(|proxy| proxy.delegate == delegate).ok_or(NOT_PROXY)?;
+		let removed = proxies.remove(index);
+
+		if proxies.is_empty() {
+			Proxies::kill(sender);
+		} else {
+			Proxies::set(sender, &proxies);
+		}
+		currency::Module::release_deposit(sender, removed.deposit)
+	}
+
+	fn proxy(sender: &AccountId, real: AccountId, call: RuntimeCall) -> DispatchResult {
+		let proxy = Proxies::get_or_default(&real)
+			.into_iter()
+			.find(|proxy| proxy.delegate == *sender)
+			.ok_or(NOT_PROXY)?;
+		if !proxy.proxy_type.allows(&call) {
+			return Err(CALL_FILTERED)
+		}
+		call.dispatch(&real)
+	}
+}
+
+impl Dispatchable for ProxyCall {
+	fn dispatch(self, sender: &AccountId) -> DispatchResult {
+		match self {
+			ProxyCall::AddProxy { delegate, proxy_type } =>
+				Module::add_proxy(sender, delegate, proxy_type),
+			ProxyCall::RemoveProxy { delegate } => Module::remove_proxy(sender, delegate),
+			ProxyCall::Proxy { real, call } => Module::proxy(sender, real, *call),
+		}
+	}
+}
@@ -0,0 +1,276 @@
+//! Re-execution of blocks, one step at a time, to find out why a block fails to import.
+//!
+//! When a block does not match the state root in its header, all that import shows is a failed
+//! assertion in `execute_block`. [`replay_block`] executes the block again on top of the state of
+//! its parent, twice: once as it is authored, with `initialize_block`, `apply_extrinsic` and
+//! `finalize_block`, and once as it is imported. The changes of each step are found by walking the
+//! whole state with `next_key` before and after it, as `print_state` does.
+//!
+//! The first step at which the two paths do not make the same changes is where authoring and import
+//! diverge. If they never do, but the header still expects another state root, then the block was
+//! authored with different code, or on top of a different state.
+//!
+//! This is only available with the `std` feature, for the `replay-blocks` command of the node.
+
+use crate::{
+	shared::{Block, Header, Phase, HEADER_KEY},
+	Runtime,
+};
+use serde::Serialize;
+use sp_core::{Bytes, H256};
+use sp_runtime::{traits::Header as _, ApplyExtrinsicResult, Storage};
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	panic::{self, AssertUnwindSafe},
+};
+
+/// The whole top-level state, by key.
+pub type State = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// The change of the value of a key, where `None` means that the key does not exist.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KeyChange {
+	pub key: Bytes,
+	pub before: Option<Bytes>,
+	pub after: Option<Bytes>,
+}
+
+/// How a step of a block went.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Outcome {
+	/// The step completed. For an extrinsic, its dispatch succeeded.
+	Ok,
+	/// The extrinsic is in the block, but its dispatch failed with this error.
+	DispatchFailed(String),
+	/// The extrinsic cannot be in the block at all, for this reason.
+	Invalid(String),
+	/// The step panicked with this message, and nothing after it was executed.
+	Panicked(String),
+}
+
+impl From<ApplyExtrinsicResult> for Outcome {
+	fn from(result: ApplyExtrinsicResult) -> Self {
+		match result {
+			Ok(Ok(())) => Outcome::Ok,
+			Ok(Err(error)) => Outcome::DispatchFailed(format!("{:?}", error)),
+			Err(error) => Outcome::Invalid(format!("{:?}", error)),
+		}
+	}
+}
+
+/// One step of the execution of a block, along with its changes to the state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Step {
+	/// The part of the block that this step executes.
+	pub phase: Phase,
+	pub outcome: Outcome,
+	pub changes: Vec<KeyChange>,
+}
+
+/// The execution of a block along one of its two paths.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Execution {
+	pub steps: Vec<Step>,
+	/// The state root that this path comes to, unless one of its steps panicked.
+	pub state_root: Option<H256>,
+	/// The extrinsics root that this path comes to, unless one of its steps panicked.
+	pub extrinsics_root: Option<H256>,
+	/// The state after the last step.
+	#[serde(skip)]
+	pub state: State,
+}
+
+/// The reason why a block fails to import.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum Failure {
+	/// Authoring and import first make different changes, or come to a different outcome, at this
+	/// step.
+	Diverged { phase: Phase },
+	/// Both authoring and import panicked at this step.
+	Panicked { phase: Phase, message: String },
+	/// The extrinsic at `index` is not valid, so it cannot be in the block.
+	InvalidExtrinsic { index: u32, error: String },
+	/// Authoring and import agree, but the header expects another state root.
+	StateRootMismatch { expected: H256, actual: H256 },
+	/// Authoring and import agree, but the header expects another extrinsics root.
+	ExtrinsicsRootMismatch { expected: H256, actual: H256 },
+}
+
+/// The replay of a block, as returned by [`replay_block`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockReplay {
+	pub number: u32,
+	pub hash: H256,
+	pub authoring: Execution,
+	pub import: Execution,
+	/// Why the block fails to import, if it does.
+	pub failure: Option<Failure>,
+}
+
+impl BlockReplay {
+	/// The state after the import of the block, on top of which its child can be replayed.
+	pub fn post_state(&self) -> Storage {
+		Storage { top: self.import.state.clone(), children_default: Default::default() }
+	}
+}
+
+/// Re-execute `block` on top of `parent`, the state of its parent, as it is authored and as it is
+/// imported.
+pub fn replay_block(parent: &Storage, block: &Block) -> BlockReplay {
+	let authoring = execute(parent, block, Path::Authoring);
+	let import = execute(parent, block, Path::Import);
+	let failure = failure(block, &authoring, &import);
+	BlockReplay {
+		number: block.header.number,
+		hash: block.header.hash(),
+		authoring,
+		import,
+		failure,
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Path {
+	Authoring,
+	Import,
+}
+
+/// All keys and values of the current state.
+fn snapshot() -> State {
+	let mut state = State::new();
+	let mut key = vec![];
+	while let Some(next) = sp_io::storage::next_key(&key) {
+		let value = sp_io::storage::get(&next).expect("next_key only returns existing keys; qed");
+		state.insert(next.clone(), value.to_vec());
+		key = next;
+	}
+	state
+}
+
+fn diff(before: &State, after: &State) -> Vec<KeyChange> {
+	let keys: BTreeSet<_> = before.keys().chain(after.keys()).collect();
+	keys.into_iter()
+		.filter(|key| before.get(*key) != after.get(*key))
+		.map(|key| KeyChange {
+			key: key.clone().into(),
+			before: before.get(key).cloned().map(Into::into),
+			after: after.get(key).cloned().map(Into::into),
+		})
+		.collect()
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+	payload
+		.downcast_ref::<&str>()
+		.map(|message| message.to_string())
+		.or_else(|| payload.downcast_ref::<String>().cloned())
+		.unwrap_or_else(|| "unknown panic".into())
+}
+
+impl Execution {
+	/// Run `f` as the step of `phase`, and record its changes. Returns whether it did not panic.
+	fn step(&mut self, phase: Phase, f: impl FnOnce() -> Outcome) -> bool {
+		let outcome = panic::catch_unwind(AssertUnwindSafe(f))
+			.unwrap_or_else(|payload| Outcome::Panicked(panic_message(payload)));
+		let state = snapshot();
+		let changes = diff(&self.state, &state);
+		self.state = state;
+		let completed = !matches!(outcome, Outcome::Panicked(_));
+		self.steps.push(Step { phase, outcome, changes });
+		completed
+	}
+}
+
+fn execute(parent: &Storage, block: &Block, path: Path) -> Execution {
+	sp_io::TestExternalities::new(parent.clone()).execute_with(|| {
+		let mut execution = Execution { state: snapshot(), ..Default::default() };
+		let initialized = execution.step(Phase::Initialization, || {
+			match path {
+				// the block builder starts from a header without roots.
+				Path::Authoring => Runtime::do_initialize_block(&Header::new(
+					block.header.number,
+					Default::default(),
+					Default::default(),
+					block.header.parent_hash,
+					block.header.digest.clone(),
+				)),
+				Path::Import => Runtime::initialize_imported_block(block),
+			}
+			Outcome::Ok
+		});
+		if !initialized {
+			return execution
+		}
+
+		for (index, extrinsic) in block.extrinsics.iter().enumerate() {
+			let applied = execution.step(Phase::ApplyExtrinsic(index as u32), || {
+				Runtime::do_apply_extrinsic(extrinsic.clone()).into()
+			});
+			if !applied {
+				return execution
+			}
+		}
+
+		let mut roots = None;
+		execution.step(Phase::Finalization, || {
+			roots = Some(match path {
+				Path::Authoring => {
+					let header = Runtime::do_finalize_block();
+					(header.state_root, header.extrinsics_root)
+				},
+				Path::Import => Runtime::finalize_imported_block(block.header.number),
+			});
+			Outcome::Ok
+		});
+		(execution.state_root, execution.extrinsics_root) = roots.unzip();
+		execution
+	})
+}
+
+/// Whether the two paths took `authored` and `imported` the same way. The header of the block
+/// differs between the two, since the block builder does not know its roots yet, so it is ignored.
+fn same_step(authored: &Step, imported: &Step) -> bool {
+	fn changes(step: &Step) -> impl Iterator<Item = &KeyChange> {
+		step.changes.iter().filter(|change| &change.key[..] != HEADER_KEY)
+	}
+	authored.phase == imported.phase &&
+		authored.outcome == imported.outcome &&
+		changes(authored).eq(changes(imported))
+}
+
+fn failure(block: &Block, authoring: &Execution, import: &Execution) -> Option<Failure> {
+	// a path only stops early at a step that panicked, which the other path took differently
+	// unless it panicked in the same way.
+	let mut steps = authoring.steps.iter().zip(&import.steps);
+	if let Some((_, imported)) = steps.find(|(authored, imported)| !same_step(authored, imported)) {
+		return Some(Failure::Diverged { phase: imported.phase })
+	}
+	for step in &import.steps {
+		match (&step.outcome, step.phase) {
+			(Outcome::Panicked(message), phase) =>
+				return Some(Failure::Panicked { phase, message: message.clone() }),
+			(Outcome::Invalid(error), Phase::ApplyExtrinsic(index)) =>
+				return Some(Failure::InvalidExtrinsic { index, error: error.clone() }),
+			_ => {},
+		}
+	}
+
+	let (state_root, extrinsics_root) = (import.state_root?, import.extrinsics_root?);
+	if state_root != block.header.state_root {
+		return Some(Failure::StateRootMismatch {
+			expected: block.header.state_root,
+			actual: state_root,
+		})
+	}
+	if extrinsics_root != block.header.extrinsics_root {
+		return Some(Failure::ExtrinsicsRootMismatch {
+			expected: block.header.extrinsics_root,
+			actual: extrinsics_root,
+		})
+	}
+	None
+}
@@ -9,12 +9,23 @@ use sp_std::prelude::*;
 pub type Balance = u128;
 /// The block number type.
 pub type BlockNumber = u32;
+/// The timestamp type, in milliseconds since the unix epoch.
+pub type Moment = u64;
+/// The weight type, an abstract measure of the execution cost of a call.
+pub type Weight = u64;
 /// Signature type. We use `sr25519` crypto.
 pub type Signature = sp_core::sr25519::Signature;
 /// Account id type is the public key. We use `sr25519` crypto.
 ///
 /// be aware of using the right crypto type when using `sp-keyring` crate.
 pub type AccountId = sp_core::sr25519::Public;
+/// The key with which an authority signs the blocks that it authors with Aura.
+pub type AuraId = sp_consensus_aura::sr25519::AuthorityId;
+/// The key with which an authority votes on finality with GRANDPA.
+pub type GrandpaId = sp_consensus_grandpa::AuthorityId;
+/// The number of a slot of Aura, which is the timestamp of its beginning divided by the slot
+/// duration.
+pub type Slot = u64;
 
 /// The account id who's allowed to mint, and call `SudoRemark`. This is the sr25519 representation
 /// of `Alice` in `sp-keyring`.
@@ -30,6 +41,48 @@ pub const MINIMUM_BALANCE: Balance = 10;
 ///
 /// Hex: 0x76616c7565
 pub const VALUE_KEY: &[u8] = b"value";
+/// Number of blocks that unbonded funds remain reserved for, before they can be withdrawn with
+/// [`StakingCall::WithdrawUnbonded`].
+pub const BONDING_DURATION: BlockNumber = 28;
+/// Maximum number of [`UnlockChunk`]s that an account can have in its unbonding queue at any point
+/// in time.
+pub const MAX_UNLOCKING_CHUNKS: u32 = 32;
+/// Maximum number of signatories of a multisig account.
+pub const MAX_SIGNATORIES: u32 = 16;
+/// The deposit that is reserved from the first approver of a multisig operation, until the
+/// operation is executed or cancelled.
+pub const MULTISIG_DEPOSIT: Balance = 5;
+/// Maximum number of proxies that an account can have.
+pub const MAX_PROXIES: u32 = 8;
+/// The deposit that is reserved from an account for each of its proxies, until the proxy is
+/// removed.
+pub const PROXY_DEPOSIT: Balance = 5;
+/// Maximum number of vesting schedules that an account can have.
+pub const MAX_VESTING_SCHEDULES: u32 = 8;
+/// Maximum number of transfers that can be scheduled at the same block.
+pub const MAX_SCHEDULED_PER_BLOCK: u32 = 16;
+/// Maximum number of calls in a [`SystemCall::Batch`] or [`SystemCall::BatchAll`].
+pub const MAX_BATCH_LENGTH: u32 = 64;
+/// Maximum number of batches that can be nested in one another, including the outermost one.
+pub const MAX_BATCH_DEPTH: u32 = 3;
+/// The fee parameters that are written to genesis. See [`FeeConfig`].
+pub const DEFAULT_FEE_CONFIG: FeeConfig = FeeConfig { base_fee: 10, byte_fee: 1, weight_fee: 1 };
+/// The key under which the timestamp of the current block is kept, as set by
+/// [`SystemCall::SetTimestamp`].
+///
+/// Hex: 0x74696d657374616d70
+pub const TIMESTAMP_KEY: &[u8] = b"timestamp";
+/// The minimum number of milliseconds between the timestamps of two consecutive blocks.
+pub const MINIMUM_PERIOD: Moment = 1;
+/// The maximum number of milliseconds that the timestamp of a block can be ahead of the local
+/// clock of the node that imports it.
+pub const MAX_TIMESTAMP_DRIFT: Moment = 30_000;
+/// The identifier of the timestamp inherent data. This is the same as `sp_timestamp`'s, so that its
+/// `InherentDataProvider` can be used by the node as-is.
+pub const TIMESTAMP_INHERENT_IDENTIFIER: [u8; 8] = *b"timstap0";
+/// The slot duration that is written to genesis if the chain spec does not set one, in
+/// milliseconds.
+pub const DEFAULT_SLOT_DURATION: Moment = 6_000;
 /// Temporary key used to store the header. This should always be clear at the end of the block.
 ///
 /// Hex: 0x686561646572
@@ -38,6 +91,13 @@ pub const HEADER_KEY: &[u8] = b"header";
 ///
 /// Should always remain at the end of the block, and be cleared at the beginning of the next block.
 pub const EXTRINSICS_KEY: &[u8] = b"extrinsics";
+/// Key used to store all events of the current block, as a `Vec<EventRecord>`.
+///
+/// Cleared at the beginning of each block, so that it holds the events of the last block once it
+/// is finished.
+///
+/// Hex: 0x6576656e7473
+pub const EVENTS_KEY: &[u8] = b"events";
 
 #[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
@@ -47,6 +107,27 @@ pub enum StakingCall {
 	/// This results in `amount` being moved from their free balance to their reserved balance. See
 	/// [`AccountBalance`].
 	Bond { amount: Balance },
+	/// Schedule `amount` of the sender's bonded funds to be unbonded.
+	///
+	/// The funds remain in the sender's reserved balance for [`BONDING_DURATION`] blocks, after
+	/// which they can be moved back to the free balance with [`StakingCall::WithdrawUnbonded`].
+	/// The pending chunks are kept in the unbonding queue, under `UnbondingQueue ++
+	/// encode(account)`, as a `Vec<UnlockChunk>`.
+	///
+	/// ### Errors
+	///
+	/// * If the sender does not have `amount` bonded, that is not already being unbonded.
+	/// * If the sender already has [`MAX_UNLOCKING_CHUNKS`] pending chunks.
+	Unbond { amount: Balance },
+	/// Move all of the sender's unbonded funds whose bonding period has passed from their reserved
+	/// balance back to their free balance.
+	///
+	/// ### Errors
+	///
+	/// * If the sender has no funds that can be withdrawn yet.
+	/// * If any type of arithmetic operation overflows.
+	/// * If the sender's free balance would still not pass the bar of [`MINIMUM_BALANCE`].
+	WithdrawUnbonded,
 }
 
 #[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
@@ -60,10 +141,56 @@ pub enum SystemCall {
 	SudoRemark { data: sp_std::prelude::Vec<u8> },
 	/// Set the value under [`VALUE_KEY`] to `value`.
 	Set { value: u32 },
-	/// Upgrade the runtime to the given code.
+	// Index 3 was a one-step `Upgrade`, removed in favour of the two calls below. The indices of
+	// the calls after it are pinned, so that extrinsics encoded before still decode to the same
+	// call.
+	/// Authorize an upgrade to the code whose `blake2_256` hash is `code_hash`. Can only be called
+	/// by [`SUDO`]. This replaces any previously authorized upgrade.
+	#[codec(index = 4)]
+	AuthorizeUpgrade { code_hash: CodeHash },
+	/// Upgrade the runtime to `code`, which must match the hash authorized by
+	/// [`SystemCall::AuthorizeUpgrade`]. Can be called by anyone, so that [`SUDO`] only has to
+	/// sign a hash, rather than a whole runtime.
+	///
+	/// The new code is applied from the next block onwards, at the beginning of which the
+	/// `on_runtime_upgrade` migration hooks of all modules run.
 	///
-	/// This is only for you to play around with, and no graded test will use it.
-	Upgrade { code: sp_std::prelude::Vec<u8> },
+	/// ### Errors
+	///
+	/// * If no upgrade is authorized, or the hash of `code` does not match it.
+	/// * If `code` is not a valid runtime, i.e. its version cannot be read.
+	/// * If its `spec_name` is not the same as the current one.
+	/// * If its `spec_version` is not greater than the current one.
+	#[codec(index = 5)]
+	ApplyAuthorizedUpgrade { code: sp_std::prelude::Vec<u8> },
+	/// Set the timestamp of the current block to `now`, kept under [`TIMESTAMP_KEY`].
+	///
+	/// This is an inherent: it is only valid unsigned, as the first extrinsic of a block, and
+	/// every block must contain exactly one. It is created by the block author from the
+	/// `timstap0` inherent data.
+	///
+	/// ### Errors
+	///
+	/// * If it is signed, or not the first extrinsic of the block.
+	/// * If `now` is less than [`MINIMUM_PERIOD`] after the timestamp of the previous block.
+	#[codec(index = 6)]
+	SetTimestamp { now: Moment },
+	/// Dispatch `calls` in order, on behalf of the sender, stopping at the first one that fails.
+	///
+	/// Each call is dispatched in its own storage transaction: the calls before the failed one
+	/// keep their changes, and the batch itself succeeds. [`RuntimeEvent::BatchInterrupted`] tells
+	/// which call failed, and [`RuntimeEvent::BatchCompleted`] that none did.
+	///
+	/// ### Errors
+	///
+	/// * If there are more than [`MAX_BATCH_LENGTH`] calls, in this or any nested batch.
+	/// * If batches are nested more than [`MAX_BATCH_DEPTH`] deep.
+	#[codec(index = 7)]
+	Batch { calls: Vec<RuntimeCall> },
+	/// Same as [`SystemCall::Batch`], but all of `calls` must succeed: if one fails, the changes
+	/// of all of them are rolled back, and the batch fails with its error.
+	#[codec(index = 8)]
+	BatchAll { calls: Vec<RuntimeCall> },
 }
 
 #[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
@@ -105,6 +232,127 @@ pub enum CurrencyCall {
 	/// Since the sender is a valid account, with more than [`MINIMUM_BALANCE`], the recipient
 	/// is also guaranteed to have at least [`MINIMUM_BALANCE`].
 	TransferAll { dest: AccountId },
+	/// Transfer `amount` to `dest`, where it is locked in its reserved balance, and vests linearly
+	/// over the `duration` blocks after block `start`. The vested part is moved to the free
+	/// balance of `dest` with [`CurrencyCall::Vest`].
+	///
+	/// The schedule is kept under `VestingSchedules ++ encode(dest)`, as a [`VestingSchedule`].
+	///
+	/// `dest` is created if it does not exist yet, with all of `amount` reserved and no free
+	/// balance.
+	///
+	/// ### Errors
+	///
+	/// * If `amount` is below [`MINIMUM_BALANCE`], or `duration` is zero.
+	/// * If `dest` already has [`MAX_VESTING_SCHEDULES`] schedules.
+	/// * If the sender would finish without [`MINIMUM_BALANCE`] of free balance left.
+	VestedTransfer { dest: AccountId, amount: Balance, start: BlockNumber, duration: BlockNumber },
+	/// Move the part of the sender's vesting schedules that has vested by now from their reserved
+	/// balance to their free balance. Schedules that have fully vested are removed.
+	///
+	/// ### Errors
+	///
+	/// * If nothing has vested since the last time.
+	Vest,
+	/// Transfer `amount` to `dest` at the beginning of block `when`. Until then, `amount` is kept
+	/// in the sender's reserved balance.
+	///
+	/// The transfer is queued under `ScheduledTransfers ++ encode(when)`. If it fails when it is
+	/// executed, `amount` is returned to the free balance of the sender.
+	///
+	/// ### Errors
+	///
+	/// * If `when` is not after the current block.
+	/// * If [`MAX_SCHEDULED_PER_BLOCK`] transfers are already scheduled at `when`.
+	/// * If the sender would finish without [`MINIMUM_BALANCE`] of free balance left.
+	ScheduleTransfer { dest: AccountId, amount: Balance, when: BlockNumber },
+}
+
+/// The hash of a runtime's code, as used by [`SystemCall::AuthorizeUpgrade`]. This is the
+/// `blake2_256` hash of the code.
+pub type CodeHash = [u8; 32];
+
+/// The hash of a [`RuntimeCall`], as used by [`MultisigCall`]. This is the `blake2_256` hash of
+/// the encoded call.
+pub type CallHash = [u8; 32];
+
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
+pub enum MultisigCall {
+	/// Register the M-of-N multisig account of `signatories`, with the given `threshold`.
+	///
+	/// The account id of the multisig is deterministic: it is the `blake2_256` hash of
+	/// `(b"multisig", sorted_signatories, threshold)`. It is kept under `Multisigs ++
+	/// encode(multisig)` as a [`Multisig`].
+	///
+	/// ### Errors
+	///
+	/// * If `threshold` is less than 2, or more than the number of signatories.
+	/// * If there are more than [`MAX_SIGNATORIES`] signatories, or any duplicates.
+	/// * If the multisig already exists.
+	Create { signatories: Vec<AccountId>, threshold: u16 },
+	/// Approve the call with `call_hash`, to be dispatched from `multisig`.
+	///
+	/// The first approval of a call opens a [`MultisigOperation`], and reserves
+	/// [`MULTISIG_DEPOSIT`] from the sender.
+	///
+	/// ### Errors
+	///
+	/// * If the sender is not a signatory of `multisig`.
+	/// * If the sender has already approved this call.
+	/// * If the sender cannot reserve the deposit, while keeping [`MINIMUM_BALANCE`].
+	Approve { multisig: AccountId, call_hash: CallHash },
+	/// Dispatch `call` from `multisig`, once it has been approved by enough signatories. The
+	/// deposit is returned to whoever opened the operation.
+	///
+	/// ### Errors
+	///
+	/// * If the sender is not a signatory of `multisig`.
+	/// * If the call does not have enough approvals.
+	/// * If the dispatch of `call` itself fails.
+	Execute { multisig: AccountId, call: Box<RuntimeCall> },
+	/// Cancel the operation with `call_hash`, and return its deposit. Only the sender that opened
+	/// the operation can cancel it.
+	Cancel { multisig: AccountId, call_hash: CallHash },
+}
+
+/// The types of calls that a proxy is allowed to make on behalf of the account it proxies.
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ProxyType {
+	/// Any call.
+	Any,
+	/// Any call that cannot move funds out of the account, nor manage its proxies.
+	NonTransfer,
+	/// Only [`StakingCall`]s.
+	Staking,
+}
+
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Encode, Decode, TypeInfo, Debug, PartialEq, Eq, Clone)]
+pub enum ProxyCall {
+	/// Allow `delegate` to dispatch calls of `proxy_type` on behalf of the sender.
+	///
+	/// This reserves [`PROXY_DEPOSIT`] from the sender. The proxies of an account are kept under
+	/// `Proxies ++ encode(account)` as a `Vec<ProxyDefinition>`.
+	///
+	/// ### Errors
+	///
+	/// * If `delegate` is already a proxy of the sender.
+	/// * If the sender already has [`MAX_PROXIES`] proxies.
+	/// * If the sender cannot reserve the deposit, while keeping [`MINIMUM_BALANCE`].
+	AddProxy { delegate: AccountId, proxy_type: ProxyType },
+	/// Remove `delegate` from the proxies of the sender, and return its deposit.
+	RemoveProxy { delegate: AccountId },
+	/// Dispatch `call` on behalf of `real`, of which the sender must be a proxy.
+	///
+	/// The nonce and tip are those of the sender, not `real`.
+	///
+	/// ### Errors
+	///
+	/// * If the sender is not a proxy of `real`, or its [`ProxyType`] does not allow `call`.
+	/// * If the dispatch of `call` itself fails.
+	Proxy { real: AccountId, call: Box<RuntimeCall> },
 }
 
 /// The outer runtime call.
@@ -114,6 +362,8 @@ pub enum RuntimeCall {
 	Currency(CurrencyCall),
 	Staking(StakingCall),
 	System(SystemCall),
+	Multisig(MultisigCall),
+	Proxy(ProxyCall),
 }
 
 /// Extended, final runtime call.
@@ -151,15 +401,213 @@ pub type Block = generic::Block<Header, Extrinsic>;
 ///
 /// If an account has no reserved balance, and its free balance falls to zero, it is considered
 /// destroyed.
-#[derive(Debug, Clone, Encode, Decode, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq, Default)]
 pub struct AccountBalance {
 	/// The free balance that they have. This can be transferred.
 	pub free: Balance,
 	/// The reserved balance that they have. This CANNOT be transferred.
 	pub reserved: Balance,
-	/// The nonce of the account. Increment every time an account successfully transacts.
+	/// The nonce of the account. Increment every time an extrinsic of the account is applied,
+	/// whether its dispatch succeeds or not.
 	///
 	/// Once an account is created, it should have a nonce of 0. By the end of the transaction,
 	/// this value is increment to 1.
 	pub nonce: u32,
 }
+
+/// A vesting schedule, kept under `VestingSchedules ++ encode(account)`.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq, Default)]
+pub struct VestingSchedule {
+	/// The amount that vests over the whole schedule.
+	pub locked: Balance,
+	/// The part of `locked` that was already moved to the free balance.
+	pub released: Balance,
+	/// The block after which funds start vesting.
+	pub start: BlockNumber,
+	/// The number of blocks over which `locked` vests, linearly.
+	pub duration: BlockNumber,
+}
+
+/// A transfer that is scheduled at some block, kept under `ScheduledTransfers ++ encode(block)`.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+pub struct ScheduledTransfer {
+	/// The sender, who holds `amount` in their reserved balance until then.
+	pub from: AccountId,
+	/// The recipient.
+	pub dest: AccountId,
+	/// The amount to transfer.
+	pub amount: Balance,
+}
+
+/// A chunk of funds that is being unbonded, kept under `UnbondingQueue ++ encode(account)`.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq, Default)]
+pub struct UnlockChunk {
+	/// The amount of funds being unbonded.
+	pub value: Balance,
+	/// The block number at which `value` can be withdrawn.
+	pub unlock_at: BlockNumber,
+}
+
+/// A multisig account, kept under `Multisigs ++ encode(multisig)`.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+pub struct Multisig {
+	/// The signatories of the multisig, sorted.
+	pub signatories: Vec<AccountId>,
+	/// The number of approvals needed to dispatch a call.
+	pub threshold: u16,
+}
+
+/// A call that is waiting for approvals of a multisig, kept under `MultisigOperations ++
+/// encode((multisig, call_hash))`.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+pub struct MultisigOperation {
+	/// The signatories that have approved the call so far.
+	pub approvals: Vec<AccountId>,
+	/// The signatory that opened the operation, and paid its deposit.
+	pub depositor: AccountId,
+	/// The deposit that is reserved from `depositor`.
+	pub deposit: Balance,
+}
+
+/// A proxy of an account, kept in the `Vec` under `Proxies ++ encode(account)`.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+pub struct ProxyDefinition {
+	/// The account that can dispatch calls on behalf of the proxied account.
+	pub delegate: AccountId,
+	/// The type of calls that `delegate` can dispatch.
+	pub proxy_type: ProxyType,
+	/// The deposit that is reserved from the proxied account.
+	pub deposit: Balance,
+}
+
+/// The parameters of the transaction fee, kept under `FeeConfig`.
+///
+/// The fee of a transaction is `base_fee + byte_fee * encoded_len + weight_fee * weight(call)`,
+/// and is paid along with the tip, before dispatch. If no config exists in storage, transactions
+/// are free.
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, Encode, Decode, TypeInfo, PartialEq, Eq, Default)]
+pub struct FeeConfig {
+	/// The fee paid by every transaction.
+	pub base_fee: Balance,
+	/// The fee paid for each byte of the encoded extrinsic.
+	pub byte_fee: Balance,
+	/// The fee paid for each unit of [`Weight`] of the call.
+	pub weight_fee: Balance,
+}
+
+/// The breakdown of the fee of a transaction, as returned by the `query_fee_info` runtime API.
+///
+/// This does not include the tip.
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq, Default)]
+pub struct FeeDetails {
+	/// The weight of the call.
+	pub weight: Weight,
+	/// The part of the fee that every transaction pays.
+	pub base_fee: Balance,
+	/// The part of the fee that is due to the length of the extrinsic.
+	pub length_fee: Balance,
+	/// The part of the fee that is due to the weight of the call.
+	pub weight_fee: Balance,
+}
+
+impl FeeDetails {
+	/// The total fee, without the tip.
+	pub fn final_fee(&self) -> Balance {
+		self.base_fee.saturating_add(self.length_fee).saturating_add(self.weight_fee)
+	}
+}
+
+/// Something that happened in the runtime, for clients to learn about the outcome of the
+/// extrinsics of a block. All events of the current block are kept under [`EVENTS_KEY`].
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+pub enum RuntimeEvent {
+	/// An extrinsic was dispatched successfully.
+	ExtrinsicSuccess,
+	/// The dispatch of an extrinsic failed, and all of its changes were reverted. Its fee and tip
+	/// were still paid.
+	ExtrinsicFailed { error: sp_runtime::DispatchError },
+	/// `who` paid `fee` and `tip` for an extrinsic.
+	TransactionFeePaid { who: AccountId, fee: Balance, tip: Balance },
+	/// The fee and tip that `who` paid, `amount` in total, were burnt, because they were not
+	/// enough to create the [`TREASURY`] account.
+	TipBurned { who: AccountId, amount: Balance },
+	/// `amount` was minted to `to`.
+	Minted { to: AccountId, amount: Balance },
+	/// `amount` was transferred from `from` to `to`.
+	Transferred { from: AccountId, to: AccountId, amount: Balance },
+	/// `amount` vested, and was moved to the free balance of `who`.
+	Vested { who: AccountId, amount: Balance },
+	/// A transfer of `amount` from `from` to `to` was scheduled at block `when`.
+	TransferScheduled { from: AccountId, to: AccountId, amount: Balance, when: BlockNumber },
+	/// A scheduled transfer of `amount` from `from` to `to` failed with `error`, and `amount`
+	/// was returned to the free balance of `from`.
+	ScheduledTransferFailed {
+		from: AccountId,
+		to: AccountId,
+		amount: Balance,
+		error: sp_runtime::DispatchError,
+	},
+	/// `who` was destroyed, along with its nonce.
+	AccountReaped { who: AccountId },
+	/// `who` bonded `amount`.
+	Bonded { who: AccountId, amount: Balance },
+	/// `who` scheduled `amount` of its bonded funds to be unbonded.
+	Unbonded { who: AccountId, amount: Balance },
+	/// `who` moved `amount` of unbonded funds back to its free balance.
+	Withdrawn { who: AccountId, amount: Balance },
+	/// The call at `index` of a [`SystemCall::Batch`] failed with `error`, so the calls after it
+	/// were not dispatched.
+	BatchInterrupted { index: u32, error: sp_runtime::DispatchError },
+	/// All calls of a batch were dispatched successfully.
+	BatchCompleted,
+}
+
+impl RuntimeEvent {
+	/// The topics of this event, under which it is indexed: one for each account that it involves.
+	pub fn topics(&self) -> Vec<sp_core::H256> {
+		let accounts = match self {
+			RuntimeEvent::ExtrinsicSuccess |
+			RuntimeEvent::ExtrinsicFailed { .. } |
+			RuntimeEvent::BatchInterrupted { .. } |
+			RuntimeEvent::BatchCompleted => vec![],
+			RuntimeEvent::Transferred { from, to, .. } |
+			RuntimeEvent::TransferScheduled { from, to, .. } |
+			RuntimeEvent::ScheduledTransferFailed { from, to, .. } => vec![from, to],
+			RuntimeEvent::Minted { to: who, .. } |
+			RuntimeEvent::Vested { who, .. } |
+			RuntimeEvent::TransactionFeePaid { who, .. } |
+			RuntimeEvent::TipBurned { who, .. } |
+			RuntimeEvent::AccountReaped { who } |
+			RuntimeEvent::Bonded { who, .. } |
+			RuntimeEvent::Unbonded { who, .. } |
+			RuntimeEvent::Withdrawn { who, .. } => vec![who],
+		};
+		accounts.into_iter().map(|who| sp_core::H256(who.0)).collect()
+	}
+}
+
+/// The part of a block in which an event was deposited.
+#[cfg_attr(feature = "std", derive(serde::Serialize))]
+#[derive(Debug, Clone, Copy, Encode, Decode, TypeInfo, PartialEq, Eq)]
+pub enum Phase {
+	/// While applying the extrinsic at this index of the block.
+	ApplyExtrinsic(u32),
+	/// In the `on_finalize` hooks.
+	Finalization,
+	/// In the `on_initialize` hooks, or outside of block execution.
+	Initialization,
+}
+
+/// An event, along with where it happened and its topics.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+pub struct EventRecord {
+	/// The part of the block in which the event was deposited.
+	pub phase: Phase,
+	/// The event itself.
+	pub event: RuntimeEvent,
+	/// The topics of the event, see [`RuntimeEvent::topics`].
+	pub topics: Vec<sp_core::H256>,
+}
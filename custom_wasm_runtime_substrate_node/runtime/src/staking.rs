@@ -0,0 +1,112 @@
+//! The staking module, built on top of the reserved balance of [`crate::currency`].
+//!
+//! All funds that an account has bonded are kept in its reserved balance. Unbonding them does not
+//! release them right away: they are added to the account's unbonding queue instead, and can only
+//! be withdrawn back to the free balance once [`BONDING_DURATION`] blocks have passed.
+
+use crate::{
+	currency::{self, BalancesMap, HeldDeposits},
+	dispatch::{DispatchResult, Dispatchable, Hooks},
+	shared::{
+		AccountId, Balance, RuntimeEvent, StakingCall, UnlockChunk, BONDING_DURATION,
+		MAX_UNLOCKING_CHUNKS,
+	},
+	storage::{storage_prefix, StorageMap},
+	system,
+};
+use sp_runtime::{ArithmeticError, DispatchError};
+use sp_std::prelude::*;
+
+storage_prefix!(UnbondingQueuePrefix, b"UnbondingQueue");
+
+/// The chunks of each account that are being unbonded, kept at `UnbondingQueue ++
+/// encode(account)`.
+pub type UnbondingQueue = StorageMap<UnbondingQueuePrefix, AccountId, Vec<UnlockChunk>>;
+
+/// Returned when unbonding more than what is bonded.
+pub const INSUFFICIENT_BOND: DispatchError = DispatchError::Other("Not enough bonded funds");
+/// Returned when the unbonding queue of an account is full.
+pub const TOO_MANY_CHUNKS: DispatchError = DispatchError::Other("Too many unlocking chunks");
+/// Returned when there is nothing to withdraw.
+pub const NOTHING_UNLOCKED: DispatchError = DispatchError::Other("No unbonded funds to withdraw");
+
+/// The staking module.
+pub struct Module;
+
+impl Hooks for Module {}
+
+impl Module {
+	/// The sum of all chunks in `queue`.
+	fn total(queue: &[UnlockChunk]) -> Result<Balance, DispatchError> {
+		queue
+			.iter()
+			.try_fold(0 as Balance, |total, chunk| total.checked_add(chunk.value))
+			.ok_or(ArithmeticError::Overflow.into())
+	}
+
+	/// The funds of `who` that are bonded, and not already being unbonded.
+	///
+	/// Deposits held by other modules live in the reserved balance as well, and are not counted.
+	pub fn bonded(who: &AccountId) -> Balance {
+		let reserved = BalancesMap::get_or_default(who).reserved;
+		let deposits = HeldDeposits::get_or_default(who);
+		let unlocking = Self::total(&UnbondingQueue::get_or_default(who)).unwrap_or(Balance::MAX);
+		reserved.saturating_sub(deposits).saturating_sub(unlocking)
+	}
+
+	fn bond(sender: &AccountId, amount: Balance) -> DispatchResult {
+		currency::Module::reserve(sender, amount)?;
+		system::Module::deposit_event(RuntimeEvent::Bonded { who: *sender, amount });
+		Ok(())
+	}
+
+	fn unbond(sender: &AccountId, amount: Balance) -> DispatchResult {
+		if amount == 0 || amount > Self::bonded(sender) {
+			return Err(INSUFFICIENT_BOND)
+		}
+		let unlock_at = system::Module::block_number()
+			.checked_add(BONDING_DURATION)
+			.ok_or(ArithmeticError::Overflow)?;
+
+		UnbondingQueue::try_mutate(sender, |queue| {
+			if queue.len() >= MAX_UNLOCKING_CHUNKS as usize {
+				return Err(TOO_MANY_CHUNKS)
+			}
+			queue.push(UnlockChunk { value: amount, unlock_at });
+			Ok(())
+		})?;
+		system::Module::deposit_event(RuntimeEvent::Unbonded { who: *sender, amount });
+		Ok(())
+	}
+
+	fn withdraw_unbonded(sender: &AccountId) -> DispatchResult {
+		let now = system::Module::block_number();
+		let (unlocked, pending): (Vec<_>, Vec<_>) = UnbondingQueue::get_or_default(sender)
+			.into_iter()
+			.partition(|chunk| chunk.unlock_at <= now);
+		if unlocked.is_empty() {
+			return Err(NOTHING_UNLOCKED)
+		}
+		let amount = Self::total(&unlocked)?;
+
+		currency::Module::unreserve(sender, amount)?;
+
+		if pending.is_empty() {
+			UnbondingQueue::kill(sender);
+		} else {
+			UnbondingQueue::set(sender, &pending);
+		}
+		system::Module::deposit_event(RuntimeEvent::Withdrawn { who: *sender, amount });
+		Ok(())
+	}
+}
+
+impl Dispatchable for StakingCall {
+	fn dispatch(self, sender: &AccountId) -> DispatchResult {
+		match self {
+			StakingCall::Bond { amount } => Module::bond(sender, amount),
+			StakingCall::Unbond { amount } => Module::unbond(sender, amount),
+			StakingCall::WithdrawUnbonded => Module::withdraw_unbonded(sender),
+		}
+	}
+}
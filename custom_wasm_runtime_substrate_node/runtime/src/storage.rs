@@ -0,0 +1,176 @@
+//! Typed storage abstractions, built on top of [`Runtime::get_state`] and
+//! [`Runtime::mutate_state`].
+//!
+//! A [`StorageValue`] lives under a single key, while a [`StorageMap`] lives under
+//! `prefix ++ encode(key)`, exactly like `BalancesMap` is laid out in `mini_substrate`.
+
+use crate::Runtime;
+use parity_scale_codec::{Decode, Encode};
+use sp_std::{marker::PhantomData, prelude::*};
+
+/// Something that provides the raw key (or key prefix) of a storage item.
+pub trait StoragePrefix {
+	/// The raw bytes of the key.
+	const PREFIX: &'static [u8];
+}
+
+/// Declare a type that implements [`StoragePrefix`].
+///
+/// ```ignore
+/// storage_prefix!(TotalIssuancePrefix, b"TotalIssuance");
+/// ```
+macro_rules! storage_prefix {
+	($name:ident, $prefix:expr) => {
+		#[doc = concat!("Storage prefix `", stringify!($prefix), "`.")]
+		pub struct $name;
+		impl $crate::storage::StoragePrefix for $name {
+			const PREFIX: &'static [u8] = $prefix;
+		}
+	};
+}
+pub(crate) use storage_prefix;
+
+/// A single value of type `V`, stored under the key provided by `P`.
+pub struct StorageValue<P, V>(PhantomData<(P, V)>);
+
+impl<P: StoragePrefix, V: Encode + Decode> StorageValue<P, V> {
+	/// The raw storage key of this value.
+	pub fn key() -> Vec<u8> {
+		P::PREFIX.to_vec()
+	}
+
+	/// Get the value, if it exists in storage.
+	pub fn get() -> Option<V> {
+		Runtime::get_state(P::PREFIX)
+	}
+
+	/// Whether the value exists in storage.
+	pub fn exists() -> bool {
+		sp_io::storage::exists(P::PREFIX)
+	}
+
+	/// Overwrite the value.
+	pub fn set(value: &V) {
+		sp_io::storage::set(P::PREFIX, &value.encode());
+	}
+
+	/// Remove the value from storage.
+	pub fn kill() {
+		sp_io::storage::clear(P::PREFIX);
+	}
+
+	/// Remove the value from storage, and return it.
+	pub fn take() -> Option<V> {
+		let value = Self::get();
+		Self::kill();
+		value
+	}
+}
+
+impl<P: StoragePrefix, V: Encode + Decode + Default> StorageValue<P, V> {
+	/// Get the value, or its default if it does not exist in storage.
+	pub fn get_or_default() -> V {
+		Self::get().unwrap_or_default()
+	}
+
+	/// Mutate the value in place. If it does not exist, `update` is given its default, and the
+	/// result is always written back.
+	pub fn mutate(update: impl FnOnce(&mut V)) {
+		Runtime::mutate_state(P::PREFIX, update)
+	}
+
+	/// Same as [`Self::mutate`], but nothing is written if `update` returns an error.
+	pub fn try_mutate<R, E>(update: impl FnOnce(&mut V) -> Result<R, E>) -> Result<R, E> {
+		let mut value = Self::get_or_default();
+		let outcome = update(&mut value)?;
+		Self::set(&value);
+		Ok(outcome)
+	}
+}
+
+/// A mapping from `K` to `V`, where each value is stored under `P ++ encode(key)`.
+pub struct StorageMap<P, K, V>(PhantomData<(P, K, V)>);
+
+impl<P: StoragePrefix, K: Encode, V: Encode + Decode> StorageMap<P, K, V> {
+	/// The raw storage key of the value of `key`.
+	pub fn key(key: &K) -> Vec<u8> {
+		[P::PREFIX, key.encode().as_slice()].concat()
+	}
+
+	/// Get the value of `key`, if it exists in storage.
+	pub fn get(key: &K) -> Option<V> {
+		Runtime::get_state(&Self::key(key))
+	}
+
+	/// Whether any value is stored for `key`.
+	pub fn contains_key(key: &K) -> bool {
+		sp_io::storage::exists(&Self::key(key))
+	}
+
+	/// Overwrite the value of `key`.
+	pub fn set(key: &K, value: &V) {
+		sp_io::storage::set(&Self::key(key), &value.encode());
+	}
+
+	/// Remove the value of `key` from storage.
+	pub fn kill(key: &K) {
+		sp_io::storage::clear(&Self::key(key));
+	}
+
+	/// Remove the value of `key` from storage, and return it.
+	pub fn take(key: &K) -> Option<V> {
+		let value = Self::get(key);
+		Self::kill(key);
+		value
+	}
+
+	/// Remove all values of this map from storage.
+	pub fn clear() {
+		let _ = sp_io::storage::clear_prefix(P::PREFIX, None);
+	}
+
+	/// Mutate the value of `key`, with full control over whether it exists or not. Returning
+	/// `None` in `update` removes the value from storage.
+	pub fn mutate_exists<R>(key: &K, update: impl FnOnce(&mut Option<V>) -> R) -> R {
+		let mut value = Self::get(key);
+		let outcome = update(&mut value);
+		match value {
+			Some(value) => Self::set(key, &value),
+			None => Self::kill(key),
+		}
+		outcome
+	}
+}
+
+impl<P: StoragePrefix, K: Encode, V: Encode + Decode + Default> StorageMap<P, K, V> {
+	/// Get the value of `key`, or its default if it does not exist in storage.
+	pub fn get_or_default(key: &K) -> V {
+		Self::get(key).unwrap_or_default()
+	}
+
+	/// Mutate the value of `key` in place. If it does not exist, `update` is given its default,
+	/// and the result is always written back.
+	pub fn mutate(key: &K, update: impl FnOnce(&mut V)) {
+		Runtime::mutate_state(&Self::key(key), update)
+	}
+
+	/// Same as [`Self::mutate`], but nothing is written if `update` returns an error.
+	pub fn try_mutate<R, E>(key: &K, update: impl FnOnce(&mut V) -> Result<R, E>) -> Result<R, E> {
+		let mut value = Self::get_or_default(key);
+		let outcome = update(&mut value)?;
+		Self::set(key, &value);
+		Ok(outcome)
+	}
+}
+
+/// Execute `f` in a new storage transaction. All of its changes are committed if it returns `Ok`,
+/// and rolled back otherwise.
+pub fn transactional<R, E>(f: impl FnOnce() -> Result<R, E>) -> Result<R, E> {
+	sp_io::storage::start_transaction();
+	let outcome = f();
+	match outcome {
+		Ok(_) => sp_io::storage::commit_transaction(),
+		Err(_) => sp_io::storage::rollback_transaction(),
+	}
+	outcome
+}
@@ -0,0 +1,253 @@
+//! The system module: block bookkeeping, and calls that are not related to balances.
+
+use crate::{
+	dispatch::{DispatchResult, Dispatchable, Hooks},
+	shared::{
+		AccountId, BlockNumber, CodeHash, EventRecord, Header, Moment, MultisigCall, Phase,
+		ProxyCall, RuntimeCall, RuntimeEvent, SystemCall, EVENTS_KEY, EXTRINSICS_KEY, HEADER_KEY,
+		MAX_BATCH_DEPTH, MAX_BATCH_LENGTH, MINIMUM_PERIOD, SUDO, TIMESTAMP_KEY, VALUE_KEY,
+	},
+	storage::{self, storage_prefix, StorageMap, StorageValue},
+	VERSION,
+};
+use parity_scale_codec::Decode;
+use sp_core::H256;
+use sp_runtime::DispatchError;
+use sp_std::prelude::*;
+use sp_storage::well_known_keys;
+use sp_version::RuntimeVersion;
+
+storage_prefix!(ValuePrefix, VALUE_KEY);
+storage_prefix!(HeaderPrefix, HEADER_KEY);
+storage_prefix!(ExtrinsicsPrefix, EXTRINSICS_KEY);
+storage_prefix!(TimestampPrefix, TIMESTAMP_KEY);
+storage_prefix!(AuthorizedUpgradePrefix, b"AuthorizedUpgrade");
+storage_prefix!(PendingUpgradePrefix, b"PendingUpgrade");
+storage_prefix!(EventsPrefix, EVENTS_KEY);
+storage_prefix!(EventTopicsPrefix, b"EventTopics");
+storage_prefix!(ExecutionPhasePrefix, b"ExecutionPhase");
+
+/// The value set by [`SystemCall::Set`].
+pub type Value = StorageValue<ValuePrefix, u32>;
+/// The header of the block being authored. Only exists during block authoring.
+pub type CurrentHeader = StorageValue<HeaderPrefix, Header>;
+/// All extrinsics applied in the current block, in order.
+pub type Extrinsics = StorageValue<ExtrinsicsPrefix, Vec<Vec<u8>>>;
+/// The timestamp of the current block, or of the last one outside of block execution.
+pub type Timestamp = StorageValue<TimestampPrefix, Moment>;
+/// The hash of the code that [`SystemCall::ApplyAuthorizedUpgrade`] may upgrade to.
+pub type AuthorizedUpgrade = StorageValue<AuthorizedUpgradePrefix, CodeHash>;
+/// The `spec_version` of the code that was just applied, whose migrations run at the beginning of
+/// the next block.
+pub type PendingUpgrade = StorageValue<PendingUpgradePrefix, u32>;
+/// All events of the current block, or of the last one outside of block execution.
+pub type Events = StorageValue<EventsPrefix, Vec<EventRecord>>;
+/// The indices in [`Events`] of the events with each topic, kept at `EventTopics ++
+/// encode(topic)`.
+pub type EventTopics = StorageMap<EventTopicsPrefix, H256, Vec<u32>>;
+/// The part of the block that is being executed. Only exists during block execution.
+pub type ExecutionPhase = StorageValue<ExecutionPhasePrefix, Phase>;
+
+/// Returned when a timestamp is set too early after that of the previous block.
+pub const TIMESTAMP_TOO_EARLY: DispatchError =
+	DispatchError::Other("Timestamp must increment by at least the minimum period");
+/// Returned when applying code that was not authorized.
+pub const UPGRADE_NOT_AUTHORIZED: DispatchError =
+	DispatchError::Other("Code hash does not match the authorized upgrade");
+/// Returned when applying code whose runtime version cannot be read.
+pub const INVALID_CODE: DispatchError = DispatchError::Other("Code is not a valid runtime");
+/// Returned when applying code of a different chain.
+pub const SPEC_NAME_MISMATCH: DispatchError =
+	DispatchError::Other("Code has a different spec name");
+/// Returned when applying code that is not a newer version of the current runtime.
+pub const SPEC_VERSION_NOT_INCREASED: DispatchError =
+	DispatchError::Other("Code spec version must be greater than the current one");
+/// Returned when a batch has more than [`MAX_BATCH_LENGTH`] calls.
+pub const TOO_MANY_CALLS: DispatchError = DispatchError::Other("Batch has too many calls");
+/// Returned when batches are nested more than [`MAX_BATCH_DEPTH`] deep.
+pub const TOO_DEEPLY_NESTED: DispatchError = DispatchError::Other("Batches are nested too deeply");
+
+/// Ensure that `who` is [`SUDO`].
+pub fn ensure_sudo(who: &AccountId) -> DispatchResult {
+	if *who == AccountId::from_raw(SUDO) {
+		Ok(())
+	} else {
+		Err(DispatchError::BadOrigin)
+	}
+}
+
+/// The system module.
+pub struct Module;
+
+impl Hooks for Module {}
+
+impl Module {
+	/// The number of the block that is being executed, or zero outside of block execution.
+	pub fn block_number() -> BlockNumber {
+		CurrentHeader::get().map_or(0, |header| header.number)
+	}
+
+	/// The timestamp of the current block, or zero if none was ever set.
+	pub fn now() -> Moment {
+		Timestamp::get_or_default()
+	}
+
+	/// The timestamp that the author of the next block should use, given its local clock says
+	/// `local_now`. This is never less than [`MINIMUM_PERIOD`] after the current one.
+	pub fn next_timestamp(local_now: Moment) -> Moment {
+		local_now.max(Self::minimum_next_timestamp())
+	}
+
+	/// Set the timestamp of the current block. This is the logic of [`SystemCall::SetTimestamp`];
+	/// checking that it is an unsigned, first extrinsic is up to the caller.
+	pub fn set_timestamp(now: Moment) -> DispatchResult {
+		if Timestamp::exists() && now < Self::minimum_next_timestamp() {
+			return Err(TIMESTAMP_TOO_EARLY)
+		}
+		Timestamp::set(&now);
+		Ok(())
+	}
+
+	/// Deposit `event` in the current phase of the block, and index it under its topics.
+	pub fn deposit_event(event: RuntimeEvent) {
+		let phase = ExecutionPhase::get().unwrap_or(Phase::Initialization);
+		let topics = event.topics();
+		let mut index = 0;
+		Events::mutate(|events| {
+			index = events.len() as u32;
+			events.push(EventRecord { phase, event, topics: topics.clone() });
+		});
+		for topic in &topics {
+			EventTopics::mutate(topic, |indices| indices.push(index));
+		}
+	}
+
+	/// Remove the events of the previous block, along with their topics.
+	pub fn reset_events() {
+		Events::kill();
+		EventTopics::clear();
+	}
+
+	/// Run the migrations of the code that was applied in the previous block, if any. Returns
+	/// whether they ran.
+	///
+	/// This must be called at the very beginning of a block, before any other hook.
+	pub fn maybe_run_migrations<M: Hooks>() -> bool {
+		let Some(spec_version) = PendingUpgrade::take() else { return false };
+		if spec_version != VERSION.spec_version {
+			log::warn!(
+				target: crate::LOG_TARGET,
+				"Upgraded to spec version {}, but running {}",
+				spec_version,
+				VERSION.spec_version
+			);
+		}
+		M::on_runtime_upgrade();
+		true
+	}
+
+	/// Check that `code` is a valid upgrade of the current runtime, then write it to `:code`.
+	fn apply_upgrade(code: Vec<u8>) -> DispatchResult {
+		let version = sp_io::misc::runtime_version(&code)
+			.and_then(|version| RuntimeVersion::decode(&mut &version[..]).ok())
+			.ok_or(INVALID_CODE)?;
+		if version.spec_name != VERSION.spec_name {
+			return Err(SPEC_NAME_MISMATCH)
+		}
+		if version.spec_version <= VERSION.spec_version {
+			return Err(SPEC_VERSION_NOT_INCREASED)
+		}
+
+		sp_io::storage::set(well_known_keys::CODE, &code);
+		PendingUpgrade::set(&version.spec_version);
+		Ok(())
+	}
+
+	fn apply_authorized_upgrade(code: Vec<u8>) -> DispatchResult {
+		let authorized = AuthorizedUpgrade::get().ok_or(UPGRADE_NOT_AUTHORIZED)?;
+		if sp_io::hashing::blake2_256(&code) != authorized {
+			return Err(UPGRADE_NOT_AUTHORIZED)
+		}
+		Self::apply_upgrade(code)?;
+		AuthorizedUpgrade::kill();
+		Ok(())
+	}
+
+	/// The number of batches nested in `calls`, including the batch of `calls` itself. Fails if
+	/// any of them has more than [`MAX_BATCH_LENGTH`] calls.
+	fn batch_depth(calls: &[RuntimeCall]) -> Result<u32, DispatchError> {
+		if calls.len() > MAX_BATCH_LENGTH as usize {
+			return Err(TOO_MANY_CALLS)
+		}
+		let nested = calls.iter().try_fold(0, |depth, call| {
+			Ok::<_, DispatchError>(depth.max(Self::call_depth(call)?))
+		})?;
+		Ok(nested + 1)
+	}
+
+	/// The number of batches nested in `call`, including `call` itself if it is a batch. Calls
+	/// that dispatch another call are looked through.
+	fn call_depth(call: &RuntimeCall) -> Result<u32, DispatchError> {
+		match call {
+			RuntimeCall::System(SystemCall::Batch { calls } | SystemCall::BatchAll { calls }) =>
+				Self::batch_depth(calls),
+			RuntimeCall::Proxy(ProxyCall::Proxy { call, .. }) |
+			RuntimeCall::Multisig(MultisigCall::Execute { call, .. }) => Self::call_depth(call),
+			_ => Ok(0),
+		}
+	}
+
+	fn ensure_batch_limits(calls: &[RuntimeCall]) -> DispatchResult {
+		if Self::batch_depth(calls)? > MAX_BATCH_DEPTH {
+			return Err(TOO_DEEPLY_NESTED)
+		}
+		Ok(())
+	}
+
+	fn batch(sender: &AccountId, calls: Vec<RuntimeCall>) -> DispatchResult {
+		Self::ensure_batch_limits(&calls)?;
+		for (index, call) in calls.into_iter().enumerate() {
+			if let Err(error) = storage::transactional(|| call.dispatch(sender)) {
+				let index = index as u32;
+				Self::deposit_event(RuntimeEvent::BatchInterrupted { index, error });
+				return Ok(())
+			}
+		}
+		Self::deposit_event(RuntimeEvent::BatchCompleted);
+		Ok(())
+	}
+
+	fn batch_all(sender: &AccountId, calls: Vec<RuntimeCall>) -> DispatchResult {
+		Self::ensure_batch_limits(&calls)?;
+		storage::transactional(|| calls.into_iter().try_for_each(|call| call.dispatch(sender)))?;
+		Self::deposit_event(RuntimeEvent::BatchCompleted);
+		Ok(())
+	}
+
+	fn minimum_next_timestamp() -> Moment {
+		Timestamp::get().map_or(0, |previous| previous.saturating_add(MINIMUM_PERIOD))
+	}
+}
+
+impl Dispatchable for SystemCall {
+	fn dispatch(self, sender: &AccountId) -> DispatchResult {
+		match self {
+			SystemCall::Remark { .. } => Ok(()),
+			SystemCall::SudoRemark { .. } => ensure_sudo(sender),
+			SystemCall::Set { value } => {
+				Value::set(&value);
+				Ok(())
+			},
+			SystemCall::AuthorizeUpgrade { code_hash } => {
+				ensure_sudo(sender)?;
+				AuthorizedUpgrade::set(&code_hash);
+				Ok(())
+			},
+			SystemCall::ApplyAuthorizedUpgrade { code } => Module::apply_authorized_upgrade(code),
+			// only the block author can set the timestamp, through an unsigned inherent.
+			SystemCall::SetTimestamp { .. } => Err(DispatchError::BadOrigin),
+			SystemCall::Batch { calls } => Module::batch(sender, calls),
+			SystemCall::BatchAll { calls } => Module::batch_all(sender, calls),
+		}
+	}
+}
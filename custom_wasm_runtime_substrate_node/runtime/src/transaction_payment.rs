@@ -0,0 +1,89 @@
+//! The transaction payment module: the weight of each call, and the fee that is charged for it.
+
+use crate::{
+	currency,
+	dispatch::{DispatchResult, Hooks},
+	shared::{
+		AccountId, Balance, CurrencyCall, FeeConfig, FeeDetails, MultisigCall, ProxyCall,
+		RuntimeCall, RuntimeEvent, StakingCall, SystemCall, Weight,
+	},
+	storage::{storage_prefix, StorageValue},
+	system,
+};
+
+storage_prefix!(FeeConfigPrefix, b"FeeConfig");
+
+/// The parameters of the transaction fee, kept at `FeeConfig`. Transactions are free if this does
+/// not exist.
+pub type FeeConfigValue = StorageValue<FeeConfigPrefix, FeeConfig>;
+
+/// The transaction payment module.
+pub struct Module;
+
+impl Hooks for Module {}
+
+impl Module {
+	/// The weight of `call`. Calls that dispatch another call also include the weight of the inner
+	/// call.
+	pub fn call_weight(call: &RuntimeCall) -> Weight {
+		match call {
+			RuntimeCall::Currency(call) => match call {
+				CurrencyCall::Mint { .. } | CurrencyCall::Transfer { .. } => 50,
+				CurrencyCall::TransferAll { .. } | CurrencyCall::Vest => 60,
+				CurrencyCall::VestedTransfer { .. } => 80,
+				CurrencyCall::ScheduleTransfer { .. } => 70,
+			},
+			RuntimeCall::Staking(call) => match call {
+				StakingCall::Bond { .. } => 60,
+				StakingCall::Unbond { .. } | StakingCall::WithdrawUnbonded => 80,
+			},
+			RuntimeCall::System(call) => match call {
+				SystemCall::Remark { .. } | SystemCall::SudoRemark { .. } => 10,
+				SystemCall::Set { .. } => 20,
+				SystemCall::ApplyAuthorizedUpgrade { .. } => 1_000,
+				SystemCall::AuthorizeUpgrade { .. } => 20,
+				SystemCall::SetTimestamp { .. } => 10,
+				SystemCall::Batch { calls } | SystemCall::BatchAll { calls } => calls
+					.iter()
+					.fold(10u64, |weight, call| weight.saturating_add(Self::call_weight(call))),
+			},
+			RuntimeCall::Multisig(call) => match call {
+				MultisigCall::Create { signatories, .. } => 50 + 10 * signatories.len() as Weight,
+				MultisigCall::Approve { .. } => 60,
+				MultisigCall::Execute { call, .. } => 60u64.saturating_add(Self::call_weight(call)),
+				MultisigCall::Cancel { .. } => 40,
+			},
+			RuntimeCall::Proxy(call) => match call {
+				ProxyCall::AddProxy { .. } => 60,
+				ProxyCall::RemoveProxy { .. } => 40,
+				ProxyCall::Proxy { call, .. } => 30u64.saturating_add(Self::call_weight(call)),
+			},
+		}
+	}
+
+	/// The fee of `call`, in an extrinsic that is `len` bytes long, with the current
+	/// [`FeeConfigValue`].
+	pub fn fee_details(call: &RuntimeCall, len: u32) -> FeeDetails {
+		let config = FeeConfigValue::get_or_default();
+		let weight = Self::call_weight(call);
+		FeeDetails {
+			weight,
+			base_fee: config.base_fee,
+			length_fee: config.byte_fee.saturating_mul(len as Balance),
+			weight_fee: config.weight_fee.saturating_mul(weight as Balance),
+		}
+	}
+
+	/// Charge `fee` and `tip` from `who`, and route them to the treasury.
+	///
+	/// Nothing is charged if the transaction is free and carries no tip.
+	pub fn charge(who: &AccountId, fee: Balance, tip: Option<Balance>) -> DispatchResult {
+		if fee == 0 && tip.is_none() {
+			return Ok(())
+		}
+		let tip = tip.unwrap_or_default();
+		currency::Module::pay_to_treasury(who, fee.saturating_add(tip))?;
+		system::Module::deposit_event(RuntimeEvent::TransactionFeePaid { who: *who, fee, tip });
+		Ok(())
+	}
+}
@@ -0,0 +1,52 @@
+//! Where `dex_app_chain_node` keeps the reserves of its DEX pools.
+//!
+//! The DEX pallet holds the assets of each pool in an account derived from the ids of its two
+//! assets, and `pallet-assets` (the `Assets` pallet of the runtime) keeps the balance of every
+//! account in every asset in its `Account` double map, keyed by the asset id and the account, both
+//! hashed with `Blake2_128Concat`.
+
+use ink::env::hash::{Blake2x128, Blake2x256, HashOutput};
+use ink::prelude::vec::Vec;
+use scale::{Decode, Encode};
+
+pub type AssetId = u32;
+pub type AssetBalance = u128;
+
+/// The prefix of the keys of `Assets::Account`: `twox_128(b"Assets") ++ twox_128(b"Account")`.
+pub const ASSETS_ACCOUNT_PREFIX: [u8; 32] = [
+    0x68, 0x2a, 0x59, 0xd5, 0x1a, 0xb9, 0xe4, 0x8a, 0x8c, 0x8c, 0xc4, 0x18, 0xff, 0x97, 0x08, 0xd2,
+    0xb9, 0x9d, 0x88, 0x0e, 0xc6, 0x81, 0x79, 0x9c, 0x0c, 0xf3, 0x0e, 0x88, 0x86, 0x37, 0x1d, 0xa9,
+];
+
+/// The id of the pool of two assets: the pair of their ids, the lowest first.
+pub fn pool_id(asset_a: AssetId, asset_b: AssetId) -> (AssetId, AssetId) {
+    (asset_a.min(asset_b), asset_a.max(asset_b))
+}
+
+/// The account that holds the reserves of the pool of two assets, as `get_pool_account` of the
+/// DEX pallet derives it: the Blake2-256 hash of the encoded pool id.
+pub fn pool_account(asset_a: AssetId, asset_b: AssetId) -> [u8; 32] {
+    let mut account = <Blake2x256 as HashOutput>::Type::default();
+    ink::env::hash_encoded::<Blake2x256, _>(&pool_id(asset_a, asset_b), &mut account);
+    account
+}
+
+/// The storage key of the balance of `account` in `asset`.
+pub fn asset_account_key(asset: AssetId, account: &[u8; 32]) -> Vec<u8> {
+    let mut key = ASSETS_ACCOUNT_PREFIX.to_vec();
+    blake2_128_concat(&asset.encode(), &mut key);
+    blake2_128_concat(account, &mut key);
+    key
+}
+
+/// The balance in an encoded `pallet_assets::AssetAccount`, which is its first field.
+pub fn decode_balance(mut asset_account: &[u8]) -> Option<AssetBalance> {
+    AssetBalance::decode(&mut asset_account).ok()
+}
+
+fn blake2_128_concat(input: &[u8], key: &mut Vec<u8>) {
+    let mut hash = <Blake2x128 as HashOutput>::Type::default();
+    ink::env::hash_bytes::<Blake2x128>(input, &mut hash);
+    key.extend_from_slice(&hash);
+    key.extend_from_slice(input);
+}
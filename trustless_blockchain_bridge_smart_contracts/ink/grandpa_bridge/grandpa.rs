@@ -0,0 +1,164 @@
+//! The Substrate and GRANDPA types that the bridge checks, laid out so that they have the same
+//! SCALE encoding as their counterparts on the source chain, whose block numbers are `u32`:
+//!
+//! - [`Header`] as `sp_runtime::generic::Header<u32, BlakeTwo256>`,
+//! - [`Justification`] as `sp_consensus_grandpa::GrandpaJustification`, made of the
+//!   `finality_grandpa` commit types with ed25519 signatures and authority ids,
+//! - [`ConsensusLog`] as `sp_consensus_grandpa::ConsensusLog<u32>`, which `pallet-grandpa`
+//!   deposits in the digest of headers.
+//!
+//! This way, they are decoded straight from what the node returns, without depending on
+//! `sp-runtime`, which needs host functions that contracts do not have.
+
+use ink::env::hash::{Blake2x256, HashOutput};
+use ink::prelude::vec::Vec;
+use scale::Encode;
+
+pub type HashValue = [u8; 32];
+pub type BlockNumber = u32;
+/// The ed25519 public key of a GRANDPA authority.
+pub type AuthorityId = [u8; 32];
+pub type AuthorityWeight = u64;
+pub type AuthorityList = Vec<(AuthorityId, AuthorityWeight)>;
+/// Every change of the authority set increments its id.
+pub type SetId = u64;
+pub type Signature = [u8; 64];
+
+/// The id of GRANDPA's consensus logs in header digests.
+pub const GRANDPA_ENGINE_ID: [u8; 4] = *b"FRNK";
+
+/// An item of the digest of a header, as `sp_runtime::generic::DigestItem`.
+#[derive(Debug, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum DigestItem {
+    #[codec(index = 6)]
+    PreRuntime([u8; 4], Vec<u8>),
+    #[codec(index = 4)]
+    Consensus([u8; 4], Vec<u8>),
+    #[codec(index = 5)]
+    Seal([u8; 4], Vec<u8>),
+    #[codec(index = 0)]
+    Other(Vec<u8>),
+    #[codec(index = 8)]
+    RuntimeEnvironmentUpdated,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, scale::Decode, scale::Encode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub struct Digest {
+    pub logs: Vec<DigestItem>,
+}
+
+/// A header of the source chain.
+#[derive(Debug, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub struct Header {
+    pub parent_hash: HashValue,
+    #[codec(compact)]
+    pub number: BlockNumber,
+    pub state_root: HashValue,
+    pub extrinsics_root: HashValue,
+    pub digest: Digest,
+}
+
+impl Header {
+    /// The hash of the header, the Blake2-256 hash of its encoding.
+    pub fn hash(&self) -> HashValue {
+        let mut hash = <Blake2x256 as HashOutput>::Type::default();
+        ink::env::hash_encoded::<Blake2x256, _>(self, &mut hash);
+        hash
+    }
+
+    /// The GRANDPA consensus logs in the digest, as they are encoded.
+    pub fn grandpa_logs(&self) -> impl Iterator<Item = &[u8]> {
+        self.digest.logs.iter().filter_map(|item| match item {
+            DigestItem::Consensus(GRANDPA_ENGINE_ID, log) => Some(&log[..]),
+            _ => None,
+        })
+    }
+}
+
+/// A change of the authority set, enacted `delay` blocks after the header that schedules it.
+#[derive(Debug, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub struct ScheduledChange {
+    pub next_authorities: AuthorityList,
+    pub delay: BlockNumber,
+}
+
+/// A consensus log of GRANDPA in the digest of a header.
+#[derive(Debug, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum ConsensusLog {
+    #[codec(index = 1)]
+    ScheduledChange(ScheduledChange),
+    /// A change that does not wait for the header that schedules it to be finalized, when
+    /// finality has stalled. The number is the median last finalized block.
+    #[codec(index = 2)]
+    ForcedChange(BlockNumber, ScheduledChange),
+    /// The authority with this index in the set is disabled.
+    #[codec(index = 3)]
+    OnDisabled(u64),
+    #[codec(index = 4)]
+    Pause(BlockNumber),
+    #[codec(index = 5)]
+    Resume(BlockNumber),
+}
+
+/// A vote of an authority for a block and its ancestors.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub struct Precommit {
+    pub target_hash: HashValue,
+    pub target_number: BlockNumber,
+}
+
+impl Precommit {
+    /// What the authority signs: the precommit as a GRANDPA message, along with the round and the
+    /// authority set it was cast in.
+    pub fn signing_payload(&self, round: u64, set_id: SetId) -> Vec<u8> {
+        /// The index of precommits in `finality_grandpa::Message`.
+        const PRECOMMIT: u8 = 1;
+        (PRECOMMIT, self, round, set_id).encode()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub struct SignedPrecommit {
+    pub precommit: Precommit,
+    pub signature: Signature,
+    pub id: AuthorityId,
+}
+
+/// Precommits for a block, or for descendants of it.
+#[derive(Debug, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub struct Commit {
+    pub target_hash: HashValue,
+    pub target_number: BlockNumber,
+    pub precommits: Vec<SignedPrecommit>,
+}
+
+/// The proof that a block is final: a commit of the authority set in some round, along with the
+/// headers between the target of the commit and the targets of its precommits.
+#[derive(Debug, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub struct Justification {
+    pub round: u64,
+    pub commit: Commit,
+    pub votes_ancestries: Vec<Header>,
+}
+
+/// Whether `signature` is the ed25519 signature of `message` by `id`.
+pub fn verify_signature(message: &[u8], signature: &Signature, id: &AuthorityId) -> bool {
+    ed25519_zebra::VerificationKey::try_from(*id)
+        .and_then(|key| key.verify(&ed25519_zebra::Signature::from(*signature), message))
+        .is_ok()
+}
+
+/// The weight that precommits must add up to for a commit to be valid: more than two thirds of
+/// the total weight of the set.
+pub fn threshold(total_weight: AuthorityWeight) -> AuthorityWeight {
+    total_weight - total_weight.saturating_sub(1) / 3
+}
@@ -0,0 +1,902 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub mod dex;
+pub mod grandpa;
+pub mod trie;
+
+pub use self::grandpa_bridge::{
+    AuthoritySet, Error, FinalizedHeader, GrandpaBridge, GrandpaBridgeRef,
+};
+
+#[ink::contract]
+mod grandpa_bridge {
+    use crate::dex::{self, AssetBalance, AssetId};
+    use crate::grandpa::{
+        self, AuthorityId, AuthorityList, AuthorityWeight, BlockNumber as SourceBlockNumber,
+        Commit, ConsensusLog, HashValue, Header, Justification, Precommit, SetId,
+    };
+    use crate::trie;
+    use ink::prelude::collections::{BTreeMap, BTreeSet};
+    use ink::prelude::vec::Vec;
+    use ink::storage::Mapping;
+    use scale::DecodeAll;
+
+    /// The GRANDPA authorities that finalize the source chain, each with its voting weight.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo)
+    )]
+    pub struct AuthoritySet {
+        pub authorities: AuthorityList,
+        pub set_id: SetId,
+    }
+
+    impl AuthoritySet {
+        /// The set, unless it has no weight at all, or more than fits in a weight.
+        fn new(authorities: AuthorityList, set_id: SetId) -> Option<Self> {
+            let set = Self {
+                authorities,
+                set_id,
+            };
+            match set.total_weight() {
+                Some(weight) if weight > 0 => Some(set),
+                _ => None,
+            }
+        }
+
+        fn total_weight(&self) -> Option<AuthorityWeight> {
+            self.authorities
+                .iter()
+                .try_fold(0u64, |total, (_, weight)| total.checked_add(*weight))
+        }
+
+        fn weight_of(&self, id: &AuthorityId) -> Option<AuthorityWeight> {
+            self.authorities
+                .iter()
+                .find(|(authority, _)| authority == id)
+                .map(|(_, weight)| *weight)
+        }
+    }
+
+    /// What the bridge keeps of a finalized header.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo)
+    )]
+    pub struct FinalizedHeader {
+        pub number: SourceBlockNumber,
+        /// The root of the state of the source chain after the block, which storage proofs are
+        /// checked against
+        pub state_root: HashValue,
+    }
+
+    #[ink(storage)]
+    pub struct GrandpaBridge {
+        /// The authority set that signs the justifications of the next headers.
+        authority_set: AuthoritySet,
+
+        /// The finalized headers that were submitted, by hash.
+        finalized: Mapping<HashValue, FinalizedHeader>,
+
+        /// The hash of the submitted finalized header at each block number.
+        finalized_hashes: Mapping<SourceBlockNumber, HashValue>,
+
+        /// The hash of the highest finalized header.
+        best_finalized: HashValue,
+    }
+
+    /// Errors that can occur upon calling this contract.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub enum Error {
+        /// The header is not above the highest finalized header
+        OldHeader,
+        /// The commit of the justification is not for the submitted header
+        JustificationTargetMismatch,
+        /// A precommit is signed by an authority outside the current set
+        UnknownAuthority,
+        /// An authority signed more than one precommit
+        DuplicateVote,
+        /// The signature of a precommit is not valid
+        InvalidSignature,
+        /// A precommit is not for the target of the commit, nor for a descendant of it
+        /// that the ancestries of the justification lead up to
+        PrecommitNotDescendant,
+        /// The precommits do not add up to more than two thirds of the weight of the set
+        NotEnoughWeight,
+        /// A GRANDPA consensus log of the header cannot be decoded, or its new set has no weight
+        InvalidAuthoritySetChange,
+        /// The header schedules a delayed or forced authority set change, which the bridge cannot
+        /// follow
+        UnsupportedAuthoritySetChange,
+        /// The header is not a finalized header of the bridge
+        UnknownHeader,
+        /// The storage proof is not valid for the state root of the header
+        InvalidStorageProof,
+        /// The proven storage value is not of the expected type
+        InvalidStorageValue,
+    }
+
+    /// Type alias for the contract's `Result` type.
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    /// A header was proven final.
+    #[ink(event)]
+    pub struct HeaderFinalized {
+        #[ink(topic)]
+        block_hash: HashValue,
+        block_number: SourceBlockNumber,
+    }
+
+    /// The authority set changed, and signs the justifications of the next headers.
+    #[ink(event)]
+    pub struct AuthoritySetChanged {
+        set_id: SetId,
+        authorities: AuthorityList,
+    }
+
+    /// An on-chain light client for a Substrate source chain that finalizes its blocks with
+    /// GRANDPA, such as `dex_app_chain_node`.
+    ///
+    /// Rather than following every header, the bridge accepts any header above its highest one
+    /// along with a justification, the proof that the current authority set finalized it. Since a
+    /// header that changes the set cannot be skipped without the justifications of the next
+    /// headers being signed by a set the bridge does not know, the bridge follows every change.
+    /// Users can then prove what is in the state of the finalized headers.
+    impl GrandpaBridge {
+        /// Initialize the light client with a finalized "checkpoint" header, and the authority
+        /// set that finalizes the headers after it.
+        ///
+        /// Panics if the set has no weight.
+        #[ink(constructor)]
+        pub fn new(checkpoint: Header, authorities: AuthorityList, set_id: SetId) -> Self {
+            let authority_set = AuthoritySet::new(authorities, set_id)
+                .expect("the authority set must have some weight");
+            let hash = checkpoint.hash();
+            let mut finalized = Mapping::default();
+            finalized.insert(
+                hash,
+                &FinalizedHeader {
+                    number: checkpoint.number,
+                    state_root: checkpoint.state_root,
+                },
+            );
+            let mut finalized_hashes = Mapping::default();
+            finalized_hashes.insert(checkpoint.number, &hash);
+            Self {
+                authority_set,
+                finalized,
+                finalized_hashes,
+                best_finalized: hash,
+            }
+        }
+
+        /// Submit a header, along with the justification that the current authority set
+        /// finalized it. In order for the header to be accepted, these conditions must be met:
+        /// 1. The header must be above the highest finalized header of the bridge
+        /// 2. The commit of the justification must be for the header
+        /// 3. Each precommit must be signed by a different authority of the set, for the round of
+        ///    the justification, and for the header or a descendant of it, which the ancestries of
+        ///    the justification lead up to
+        /// 4. The authorities that signed must have more than two thirds of the weight of the set
+        ///
+        /// If the header schedules a change of the authority set, it is enacted right away, as
+        /// `pallet-grandpa` does for changes without delay. Delayed and forced changes are
+        /// rejected.
+        #[ink(message)]
+        pub fn submit_finality_proof(
+            &mut self,
+            header: Header,
+            justification: Justification,
+        ) -> Result<()> {
+            // 1. The header must be above the highest finalized header
+            let best = self.finalized.get(self.best_finalized).unwrap();
+            if header.number <= best.number {
+                return Err(Error::OldHeader);
+            }
+
+            // 2. to 4.
+            let hash = header.hash();
+            self.verify_justification(hash, header.number, &justification)?;
+            let change = self.authority_set_change(&header)?;
+
+            self.finalized.insert(
+                hash,
+                &FinalizedHeader {
+                    number: header.number,
+                    state_root: header.state_root,
+                },
+            );
+            self.finalized_hashes.insert(header.number, &hash);
+            self.best_finalized = hash;
+            self.env().emit_event(HeaderFinalized {
+                block_hash: hash,
+                block_number: header.number,
+            });
+
+            if let Some(authority_set) = change {
+                self.env().emit_event(AuthoritySetChanged {
+                    set_id: authority_set.set_id,
+                    authorities: authority_set.authorities.clone(),
+                });
+                self.authority_set = authority_set;
+            }
+            Ok(())
+        }
+
+        fn verify_justification(
+            &self,
+            hash: HashValue,
+            number: SourceBlockNumber,
+            justification: &Justification,
+        ) -> Result<()> {
+            // 2. The commit of the justification must be for the header
+            let commit = &justification.commit;
+            if commit.target_hash != hash || commit.target_number != number {
+                return Err(Error::JustificationTargetMismatch);
+            }
+
+            // 3. Each precommit must be signed by a different authority of the set, for the
+            // header or a descendant of it
+            let ancestry: BTreeMap<HashValue, (HashValue, SourceBlockNumber)> = justification
+                .votes_ancestries
+                .iter()
+                .map(|header| (header.hash(), (header.parent_hash, header.number)))
+                .collect();
+            let set = &self.authority_set;
+            let mut voted = BTreeSet::new();
+            let mut weight: AuthorityWeight = 0;
+            for signed in &commit.precommits {
+                let authority_weight = set.weight_of(&signed.id).ok_or(Error::UnknownAuthority)?;
+                if !voted.insert(signed.id) {
+                    return Err(Error::DuplicateVote);
+                }
+                if !Self::descends_from_target(&signed.precommit, commit, &ancestry) {
+                    return Err(Error::PrecommitNotDescendant);
+                }
+                let payload = signed
+                    .precommit
+                    .signing_payload(justification.round, set.set_id);
+                if !grandpa::verify_signature(&payload, &signed.signature, &signed.id) {
+                    return Err(Error::InvalidSignature);
+                }
+                weight = weight.saturating_add(authority_weight);
+            }
+
+            // 4. The authorities that signed must have more than two thirds of the weight
+            let total_weight = set.total_weight().unwrap_or(AuthorityWeight::MAX);
+            if weight < grandpa::threshold(total_weight) {
+                return Err(Error::NotEnoughWeight);
+            }
+            Ok(())
+        }
+
+        /// Whether the target of `precommit` is the target of `commit`, or one of its descendants
+        /// that `ancestry` leads up to it from, with consistent numbers.
+        fn descends_from_target(
+            precommit: &Precommit,
+            commit: &Commit,
+            ancestry: &BTreeMap<HashValue, (HashValue, SourceBlockNumber)>,
+        ) -> bool {
+            let mut hash = precommit.target_hash;
+            let mut number = precommit.target_number;
+            while hash != commit.target_hash {
+                match ancestry.get(&hash) {
+                    Some(&(parent, header_number))
+                        if header_number == number && number > commit.target_number =>
+                    {
+                        hash = parent;
+                        number -= 1;
+                    }
+                    _ => return false,
+                }
+            }
+            number == commit.target_number
+        }
+
+        /// The authority set that the header enacts, if it schedules a change without delay.
+        fn authority_set_change(&self, header: &Header) -> Result<Option<AuthoritySet>> {
+            let mut change = None;
+            for mut log in header.grandpa_logs() {
+                match ConsensusLog::decode_all(&mut log)
+                    .map_err(|_| Error::InvalidAuthoritySetChange)?
+                {
+                    ConsensusLog::ScheduledChange(scheduled)
+                        if scheduled.delay == 0 && change.is_none() =>
+                    {
+                        change = Some(
+                            AuthoritySet::new(
+                                scheduled.next_authorities,
+                                self.authority_set.set_id + 1,
+                            )
+                            .ok_or(Error::InvalidAuthoritySetChange)?,
+                        );
+                    }
+                    ConsensusLog::ScheduledChange(_) | ConsensusLog::ForcedChange(..) => {
+                        return Err(Error::UnsupportedAuthoritySetChange);
+                    }
+                    ConsensusLog::OnDisabled(_)
+                    | ConsensusLog::Pause(_)
+                    | ConsensusLog::Resume(_) => {}
+                }
+            }
+            Ok(change)
+        }
+
+        /// Read the value at `key` in the state of a finalized header, given a storage proof of
+        /// it, as returned by the `state_getReadProof` RPC of the source chain. Returns `None` if
+        /// the proof shows that there is no value at `key`.
+        #[ink(message)]
+        pub fn read_storage(
+            &self,
+            header_hash: HashValue,
+            key: Vec<u8>,
+            proof: Vec<Vec<u8>>,
+        ) -> Result<Option<Vec<u8>>> {
+            let header = self
+                .finalized
+                .get(header_hash)
+                .ok_or(Error::UnknownHeader)?;
+            trie::read_proof(&header.state_root, &proof, &key)
+                .map_err(|_| Error::InvalidStorageProof)
+        }
+
+        /// The reserves of the DEX pool of two assets in the state of a finalized header: the
+        /// balances of the pool account in `asset_a` and `asset_b`, in that order. The proof must
+        /// cover the keys of both balances, see [`GrandpaBridge::pool_reserve_keys`].
+        #[ink(message)]
+        pub fn pool_reserves(
+            &self,
+            header_hash: HashValue,
+            asset_a: AssetId,
+            asset_b: AssetId,
+            proof: Vec<Vec<u8>>,
+        ) -> Result<(AssetBalance, AssetBalance)> {
+            let [key_a, key_b] = Self::pool_reserve_keys(asset_a, asset_b);
+            let reserve = |key: Vec<u8>| match self.read_storage(header_hash, key, proof.clone())? {
+                // An account without any of the asset has no entry.
+                None => Ok(0),
+                Some(asset_account) => {
+                    dex::decode_balance(&asset_account).ok_or(Error::InvalidStorageValue)
+                }
+            };
+            Ok((reserve(key_a)?, reserve(key_b)?))
+        }
+
+        /// The storage keys of the reserves of the DEX pool of two assets, which a proof for
+        /// `pool_reserves` must cover.
+        pub fn pool_reserve_keys(asset_a: AssetId, asset_b: AssetId) -> [Vec<u8>; 2] {
+            let pool_account = dex::pool_account(asset_a, asset_b);
+            [
+                dex::asset_account_key(asset_a, &pool_account),
+                dex::asset_account_key(asset_b, &pool_account),
+            ]
+        }
+
+        /// The hash and number of the highest finalized header.
+        #[ink(message)]
+        pub fn best_finalized(&self) -> (HashValue, SourceBlockNumber) {
+            let best = self.finalized.get(self.best_finalized).unwrap();
+            (self.best_finalized, best.number)
+        }
+
+        /// The finalized header with this hash, if it was submitted.
+        #[ink(message)]
+        pub fn finalized_header(&self, header_hash: HashValue) -> Option<FinalizedHeader> {
+            self.finalized.get(header_hash)
+        }
+
+        /// The hash of the finalized header at `number`, if it was submitted.
+        #[ink(message)]
+        pub fn finalized_hash_at(&self, number: SourceBlockNumber) -> Option<HashValue> {
+            self.finalized_hashes.get(number)
+        }
+
+        /// The authority set that signs the justifications of the next headers.
+        #[ink(message)]
+        pub fn authority_set(&self) -> AuthoritySet {
+            self.authority_set.clone()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::grandpa::{DigestItem, ScheduledChange, GRANDPA_ENGINE_ID};
+        use scale::{Decode, Encode};
+        use sp_core::{ed25519, hashing, Blake2Hasher, Pair, H256};
+        use sp_runtime::traits::{BlakeTwo256, Header as _};
+        use sp_trie::{
+            recorder::Recorder, LayoutV1, Trie, TrieDBBuilder, TrieDBMutBuilder, TrieMut,
+        };
+
+        /// The header type of `dex_app_chain_node`.
+        type SpHeader = sp_runtime::generic::Header<u32, BlakeTwo256>;
+        type SpCommit = finality_grandpa::Commit<H256, u32, ed25519::Signature, ed25519::Public>;
+        type Event = <GrandpaBridge as ink::reflect::ContractEventBase>::Type;
+
+        fn authority(name: &str) -> ed25519::Pair {
+            ed25519::Pair::from_string(&format!("//{}", name), None).unwrap()
+        }
+
+        fn authorities(names: &[&str]) -> AuthorityList {
+            names
+                .iter()
+                .map(|name| (authority(name).public().0, 1))
+                .collect()
+        }
+
+        /// Convert a header as the node builds it to the header of the bridge.
+        fn header(header: &SpHeader) -> Header {
+            Header::decode(&mut &header.encode()[..]).unwrap()
+        }
+
+        fn sp_header(
+            parent: &SpHeader,
+            state_root: H256,
+            logs: Vec<sp_runtime::DigestItem>,
+        ) -> SpHeader {
+            SpHeader::new(
+                parent.number + 1,
+                H256::repeat_byte(0xe0),
+                state_root,
+                parent.hash(),
+                sp_runtime::Digest { logs },
+            )
+        }
+
+        fn genesis() -> SpHeader {
+            SpHeader::new(
+                0,
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                Default::default(),
+            )
+        }
+
+        /// A justification of `target` in which `signers` precommit for `precommit_target`, a
+        /// descendant of it, with `ancestries` in between, as the node encodes it.
+        fn justification_for(
+            target: &SpHeader,
+            precommit_target: &SpHeader,
+            ancestries: Vec<SpHeader>,
+            signers: &[&str],
+            round: u64,
+            set_id: SetId,
+        ) -> Justification {
+            let precommit =
+                finality_grandpa::Precommit::new(precommit_target.hash(), precommit_target.number);
+            // `sp_consensus_grandpa::localized_payload`
+            let payload = (
+                finality_grandpa::Message::Precommit(precommit.clone()),
+                round,
+                set_id,
+            )
+                .encode();
+            let commit = SpCommit {
+                target_hash: target.hash(),
+                target_number: target.number,
+                precommits: signers
+                    .iter()
+                    .map(|name| finality_grandpa::SignedPrecommit {
+                        precommit: precommit.clone(),
+                        signature: authority(name).sign(&payload),
+                        id: authority(name).public(),
+                    })
+                    .collect(),
+            };
+            // `sp_consensus_grandpa::GrandpaJustification`
+            let encoded = (round, commit, ancestries).encode();
+            Justification::decode(&mut &encoded[..]).unwrap()
+        }
+
+        fn justification(target: &SpHeader, signers: &[&str], set_id: SetId) -> Justification {
+            justification_for(target, target, Vec::new(), signers, 1, set_id)
+        }
+
+        /// Deploy the bridge with `genesis` as its checkpoint, finalized by Alice, Bob, Charlie
+        /// and Dave, so that any three of them finalize the next headers.
+        fn deploy_bridge(genesis: &SpHeader) -> GrandpaBridge {
+            GrandpaBridge::new(
+                header(genesis),
+                authorities(&["Alice", "Bob", "Charlie", "Dave"]),
+                0,
+            )
+        }
+
+        fn recorded_events() -> Vec<Event> {
+            ink::env::test::recorded_events()
+                .map(|event| <Event as Decode>::decode(&mut &event.data[..]).unwrap())
+                .collect()
+        }
+
+        #[ink::test]
+        fn test_header_matches_sp_runtime() {
+            let digest = Vec::from([
+                sp_runtime::DigestItem::PreRuntime(*b"aura", Vec::from([1, 2])),
+                sp_runtime::DigestItem::Consensus(GRANDPA_ENGINE_ID, Vec::from([3])),
+                sp_runtime::DigestItem::Seal(*b"aura", Vec::from([4; 64])),
+                sp_runtime::DigestItem::Other(Vec::from([5])),
+                sp_runtime::DigestItem::RuntimeEnvironmentUpdated,
+            ]);
+            let sp_header = sp_header(&genesis(), H256::repeat_byte(7), digest);
+            let header = header(&sp_header);
+            assert_eq!(header.number, 1);
+            assert_eq!(header.state_root, [7; 32]);
+            assert_eq!(
+                header.digest.logs[0],
+                DigestItem::PreRuntime(*b"aura", Vec::from([1, 2]))
+            );
+            assert_eq!(
+                header.grandpa_logs().collect::<Vec<_>>(),
+                Vec::from([&[3u8][..]])
+            );
+            assert_eq!(header.encode(), sp_header.encode());
+            assert_eq!(header.hash(), sp_header.hash().0);
+        }
+
+        #[ink::test]
+        fn test_submit_finality_proof() {
+            let genesis = genesis();
+            let mut bridge = deploy_bridge(&genesis);
+            let a = sp_header(&genesis, H256::repeat_byte(1), Vec::new());
+            let b = sp_header(&a, H256::repeat_byte(2), Vec::new());
+
+            assert_eq!(
+                bridge.submit_finality_proof(header(&b), justification(&b, &["Alice", "Bob"], 0)),
+                Err(Error::NotEnoughWeight)
+            );
+            assert_eq!(
+                bridge.submit_finality_proof(
+                    header(&b),
+                    justification(&b, &["Alice", "Bob", "Dave"], 0)
+                ),
+                Ok(())
+            );
+            assert_eq!(bridge.best_finalized(), (b.hash().0, 2));
+            assert_eq!(bridge.finalized_hash_at(2), Some(b.hash().0));
+            assert_eq!(bridge.finalized_hash_at(1), None);
+            assert_eq!(
+                bridge.finalized_header(b.hash().0),
+                Some(FinalizedHeader {
+                    number: 2,
+                    state_root: [2; 32]
+                })
+            );
+            assert!(matches!(
+                recorded_events()[..],
+                [Event::HeaderFinalized(HeaderFinalized { block_hash, block_number: 2 })]
+                    if block_hash == b.hash().0
+            ));
+
+            // Headers that are not above the highest finalized one are rejected.
+            for old in [&a, &b] {
+                assert_eq!(
+                    bridge.submit_finality_proof(
+                        header(old),
+                        justification(old, &["Alice", "Bob", "Charlie"], 0)
+                    ),
+                    Err(Error::OldHeader)
+                );
+            }
+        }
+
+        #[ink::test]
+        fn test_invalid_justifications() {
+            let genesis = genesis();
+            let mut bridge = deploy_bridge(&genesis);
+            let a = sp_header(&genesis, H256::repeat_byte(1), Vec::new());
+            let b = sp_header(&a, H256::repeat_byte(2), Vec::new());
+            let c = sp_header(&b, H256::repeat_byte(3), Vec::new());
+            let signers = ["Alice", "Bob", "Charlie"];
+            let mut submit = |justification: Justification| {
+                bridge.submit_finality_proof(header(&a), justification)
+            };
+
+            assert_eq!(
+                submit(justification(&b, &signers, 0)),
+                Err(Error::JustificationTargetMismatch)
+            );
+            assert_eq!(
+                submit(justification(&a, &["Alice", "Bob", "Eve"], 0)),
+                Err(Error::UnknownAuthority)
+            );
+            assert_eq!(
+                submit(justification(&a, &["Alice", "Bob", "Bob"], 0)),
+                Err(Error::DuplicateVote)
+            );
+            // Signed for another authority set
+            assert_eq!(
+                submit(justification(&a, &signers, 1)),
+                Err(Error::InvalidSignature)
+            );
+            // Signed for another round than the one of the justification
+            let mut other_round = justification(&a, &signers, 0);
+            other_round.round += 1;
+            assert_eq!(submit(other_round), Err(Error::InvalidSignature));
+
+            // Precommits for descendants of the target need the headers in between.
+            assert_eq!(
+                submit(justification_for(
+                    &a,
+                    &c,
+                    Vec::from([c.clone()]),
+                    &signers,
+                    1,
+                    0
+                )),
+                Err(Error::PrecommitNotDescendant)
+            );
+            assert_eq!(
+                submit(justification_for(
+                    &a,
+                    &c,
+                    Vec::from([c.clone(), b.clone()]),
+                    &signers,
+                    1,
+                    0
+                )),
+                Ok(())
+            );
+            assert_eq!(bridge.best_finalized(), (a.hash().0, 1));
+        }
+
+        #[ink::test]
+        fn test_authority_set_changes() {
+            let genesis = genesis();
+            let mut bridge = deploy_bridge(&genesis);
+            let next_authorities = authorities(&["Eve", "Ferdie"]);
+            let change = |delay| {
+                let log = ConsensusLog::ScheduledChange(ScheduledChange {
+                    next_authorities: next_authorities.clone(),
+                    delay,
+                });
+                sp_runtime::DigestItem::Consensus(GRANDPA_ENGINE_ID, log.encode())
+            };
+            // As `sp_consensus_grandpa::ConsensusLog::ScheduledChange` encodes it
+            let mut expected = Vec::from([1, 2 << 2]);
+            for (id, weight) in &next_authorities {
+                expected.extend_from_slice(id);
+                expected.extend_from_slice(&weight.to_le_bytes());
+            }
+            expected.extend_from_slice(&0u32.to_le_bytes());
+            assert_eq!(
+                change(0),
+                sp_runtime::DigestItem::Consensus(GRANDPA_ENGINE_ID, expected)
+            );
+
+            let delayed = sp_header(&genesis, H256::zero(), Vec::from([change(5)]));
+            assert_eq!(
+                bridge.submit_finality_proof(
+                    header(&delayed),
+                    justification(&delayed, &["Alice", "Bob", "Charlie"], 0)
+                ),
+                Err(Error::UnsupportedAuthoritySetChange)
+            );
+            let garbled = sp_header(
+                &genesis,
+                H256::zero(),
+                Vec::from([sp_runtime::DigestItem::Consensus(
+                    GRANDPA_ENGINE_ID,
+                    Vec::from([1, 4]),
+                )]),
+            );
+            assert_eq!(
+                bridge.submit_finality_proof(
+                    header(&garbled),
+                    justification(&garbled, &["Alice", "Bob", "Charlie"], 0)
+                ),
+                Err(Error::InvalidAuthoritySetChange)
+            );
+
+            // The old set finalizes the header that changes it, and the new one the next ones.
+            let a = sp_header(&genesis, H256::zero(), Vec::from([change(0)]));
+            assert_eq!(
+                bridge.submit_finality_proof(
+                    header(&a),
+                    justification(&a, &["Alice", "Bob", "Charlie"], 0)
+                ),
+                Ok(())
+            );
+            assert_eq!(
+                bridge.authority_set(),
+                AuthoritySet {
+                    authorities: next_authorities.clone(),
+                    set_id: 1
+                }
+            );
+            assert!(matches!(
+                recorded_events().last(),
+                Some(Event::AuthoritySetChanged(AuthoritySetChanged { set_id: 1, authorities }))
+                    if *authorities == next_authorities
+            ));
+
+            let b = sp_header(&a, H256::zero(), Vec::new());
+            assert_eq!(
+                bridge.submit_finality_proof(
+                    header(&b),
+                    justification(&b, &["Alice", "Bob", "Charlie"], 0)
+                ),
+                Err(Error::UnknownAuthority)
+            );
+            assert_eq!(
+                bridge.submit_finality_proof(header(&b), justification(&b, &["Eve", "Ferdie"], 0)),
+                Err(Error::InvalidSignature)
+            );
+            assert_eq!(
+                bridge.submit_finality_proof(header(&b), justification(&b, &["Eve"], 1)),
+                Err(Error::NotEnoughWeight)
+            );
+            assert_eq!(
+                bridge.submit_finality_proof(header(&b), justification(&b, &["Eve", "Ferdie"], 1)),
+                Ok(())
+            );
+        }
+
+        /// Build the state trie of `entries`, as the node does, and return its root along with the
+        /// storage proof of `keys`.
+        fn state_with_proof(
+            entries: &[(Vec<u8>, Vec<u8>)],
+            keys: &[&[u8]],
+        ) -> (H256, Vec<Vec<u8>>) {
+            let mut db = sp_trie::MemoryDB::<Blake2Hasher>::default();
+            let mut root = H256::default();
+            {
+                let mut trie =
+                    TrieDBMutBuilder::<LayoutV1<Blake2Hasher>>::new(&mut db, &mut root).build();
+                for (key, value) in entries {
+                    trie.insert(key, value).unwrap();
+                }
+            }
+            let recorder = Recorder::<Blake2Hasher>::default();
+            {
+                let mut trie_recorder = recorder.as_trie_recorder(root);
+                let trie = TrieDBBuilder::<LayoutV1<Blake2Hasher>>::new(&db, &root)
+                    .with_recorder(&mut trie_recorder)
+                    .build();
+                for key in keys {
+                    trie.get(key).unwrap();
+                }
+            }
+            let proof = recorder.drain_storage_proof().into_iter_nodes().collect();
+            (root, proof)
+        }
+
+        /// Deploy the bridge, and finalize a header with `state_root`, whose hash is returned.
+        fn finalize_state(state_root: H256) -> (GrandpaBridge, HashValue) {
+            let genesis = genesis();
+            let mut bridge = deploy_bridge(&genesis);
+            let a = sp_header(&genesis, state_root, Vec::new());
+            bridge
+                .submit_finality_proof(
+                    header(&a),
+                    justification(&a, &["Alice", "Bob", "Charlie"], 0),
+                )
+                .unwrap();
+            (bridge, a.hash().0)
+        }
+
+        #[ink::test]
+        fn test_read_storage() {
+            // Short keys and values end up inlined in their parents, and values of 33 bytes or
+            // more are hashed.
+            let entries: Vec<(Vec<u8>, Vec<u8>)> = Vec::from([
+                (b"a".to_vec(), b"1".to_vec()),
+                (b"ab".to_vec(), b"2".to_vec()),
+                (b"abc".to_vec(), [3; 40].to_vec()),
+                (b"b".to_vec(), b"4".to_vec()),
+                ([7; 32].to_vec(), [5; 32].to_vec()),
+                ([7; 33].to_vec(), [6; 100].to_vec()),
+            ]);
+            let keys: Vec<&[u8]> = Vec::from([
+                &b"a"[..],
+                b"ab",
+                b"abc",
+                b"b",
+                &[7; 32],
+                &[7; 33],
+                b"abd",
+                b"c",
+            ]);
+            let (root, proof) = state_with_proof(&entries, &keys);
+            let (bridge, hash) = finalize_state(root);
+
+            for (key, value) in &entries {
+                assert_eq!(
+                    bridge.read_storage(hash, key.clone(), proof.clone()),
+                    Ok(Some(value.clone()))
+                );
+            }
+            for key in [b"abd", b"c\0\0"] {
+                let key = key.split(|byte| *byte == 0).next().unwrap().to_vec();
+                assert_eq!(bridge.read_storage(hash, key, proof.clone()), Ok(None));
+            }
+
+            // A proof of other keys proves nothing about this one.
+            let (_, other_proof) = state_with_proof(&entries, &[b"b"]);
+            assert_eq!(
+                bridge.read_storage(hash, b"abc".to_vec(), other_proof),
+                Err(Error::InvalidStorageProof)
+            );
+            // Nor does a tampered proof.
+            let tampered: Vec<Vec<u8>> = proof
+                .iter()
+                .map(|node| {
+                    let mut node = node.clone();
+                    *node.last_mut().unwrap() ^= 1;
+                    node
+                })
+                .collect();
+            assert_eq!(
+                bridge.read_storage(hash, b"a".to_vec(), tampered),
+                Err(Error::InvalidStorageProof)
+            );
+            assert_eq!(
+                bridge.read_storage([9; 32], b"a".to_vec(), proof),
+                Err(Error::UnknownHeader)
+            );
+        }
+
+        /// An encoded `pallet_assets::AssetAccount` with `balance`, which is liquid and kept alive
+        /// by a consumer reference.
+        fn asset_account(balance: AssetBalance) -> Vec<u8> {
+            (balance, 0u8, 0u8).encode()
+        }
+
+        /// The key of `Assets::Account`, as `pallet-assets` derives it.
+        fn asset_account_key(asset: AssetId, account: [u8; 32]) -> Vec<u8> {
+            [
+                &hashing::twox_128(b"Assets")[..],
+                &hashing::twox_128(b"Account"),
+                &hashing::blake2_128(&asset.encode()),
+                &asset.encode(),
+                &hashing::blake2_128(&account),
+                &account,
+            ]
+            .concat()
+        }
+
+        #[ink::test]
+        fn test_pool_reserves() {
+            // The pool of assets 1 and 2 holds 1000 of 1 and 2500 of 2, and the pool of assets 1
+            // and 3 has run out of 3.
+            let pool_12 = hashing::blake2_256(&(1u32, 2u32).encode());
+            let pool_13 = hashing::blake2_256(&(1u32, 3u32).encode());
+            assert_eq!(dex::pool_account(2, 1), pool_12);
+            assert_eq!(
+                GrandpaBridge::pool_reserve_keys(1, 2),
+                [asset_account_key(1, pool_12), asset_account_key(2, pool_12)]
+            );
+            let entries = Vec::from([
+                (asset_account_key(1, pool_12), asset_account(1000)),
+                (asset_account_key(2, pool_12), asset_account(2500)),
+                (asset_account_key(1, pool_13), asset_account(40)),
+                (asset_account_key(1, [0xaa; 32]), asset_account(7)),
+                (b"something else".to_vec(), Vec::from([1])),
+            ]);
+            let [key_1, key_2] = GrandpaBridge::pool_reserve_keys(1, 2);
+            let [key_3_1, key_3_3] = GrandpaBridge::pool_reserve_keys(3, 1);
+            let (root, proof) = state_with_proof(&entries, &[&key_1, &key_2, &key_3_1, &key_3_3]);
+            let (bridge, hash) = finalize_state(root);
+
+            assert_eq!(
+                bridge.pool_reserves(hash, 1, 2, proof.clone()),
+                Ok((1000, 2500))
+            );
+            assert_eq!(
+                bridge.pool_reserves(hash, 2, 1, proof.clone()),
+                Ok((2500, 1000))
+            );
+            assert_eq!(bridge.pool_reserves(hash, 3, 1, proof.clone()), Ok((0, 40)));
+
+            let (_, partial_proof) = state_with_proof(&entries, &[&key_1]);
+            assert_eq!(
+                bridge.pool_reserves(hash, 1, 2, partial_proof),
+                Err(Error::InvalidStorageProof)
+            );
+        }
+    }
+}
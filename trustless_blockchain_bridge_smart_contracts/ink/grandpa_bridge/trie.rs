@@ -0,0 +1,205 @@
+//! Reading values out of the state of a Substrate chain, given its state root and a storage proof.
+//!
+//! The state is a base-16 Patricia-Merkle trie, whose nodes are referred to by the Blake2-256 hash
+//! of their encoding, unless that encoding is shorter than a hash, in which case it is inlined in
+//! its parent. A storage proof, as returned by the `state_getReadProof` RPC, is the set of encoded
+//! nodes on the paths from the root to the keys it proves, in no particular order. Values of 33
+//! bytes or more are hashed too, and the proof then holds them as they are.
+//!
+//! Nodes are encoded as in `sp_trie::NodeCodec`: a header made of the kind of the node and the
+//! number of nibbles of its partial key, the partial key, then for branches a bitmap of their
+//! children, then the value, if any, then the children.
+
+use ink::env::hash::{Blake2x256, HashOutput};
+use ink::prelude::{boxed::Box, collections::BTreeMap, vec::Vec};
+use scale::{Compact, Decode};
+
+type HashValue = [u8; 32];
+
+/// The proof is not valid for this root and key.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct InvalidProof;
+
+type Result<T> = core::result::Result<T, InvalidProof>;
+
+enum Value<'a> {
+    Inline(&'a [u8]),
+    Hashed(&'a [u8]),
+}
+
+enum Child<'a> {
+    Hash(&'a [u8]),
+    Inline(&'a [u8]),
+}
+
+enum Node<'a> {
+    Empty,
+    Leaf {
+        partial: Vec<u8>,
+        value: Value<'a>,
+    },
+    Branch {
+        partial: Vec<u8>,
+        value: Option<Value<'a>>,
+        children: Box<[Option<Child<'a>>; 16]>,
+    },
+}
+
+/// Read the value at `key` in the trie with `root`, or `None` if the proof shows there is none.
+pub fn read_proof(root: &HashValue, proof: &[Vec<u8>], key: &[u8]) -> Result<Option<Vec<u8>>> {
+    let nodes: BTreeMap<HashValue, &[u8]> = proof
+        .iter()
+        .map(|node| (blake2_256(node), &node[..]))
+        .collect();
+    let node_at = |hash: &[u8]| nodes.get(hash).copied().ok_or(InvalidProof);
+    let value = |value: Value| match value {
+        Value::Inline(value) => Ok(value.to_vec()),
+        Value::Hashed(hash) => node_at(hash).map(<[u8]>::to_vec),
+    };
+
+    let key = nibbles(key);
+    let mut key = &key[..];
+    let mut encoded = node_at(root)?;
+    loop {
+        match decode_node(encoded)? {
+            Node::Empty => return Ok(None),
+            Node::Leaf { partial, value: v } => {
+                return if key == partial {
+                    value(v).map(Some)
+                } else {
+                    Ok(None)
+                }
+            }
+            Node::Branch {
+                partial,
+                value: v,
+                mut children,
+            } => {
+                let Some(rest) = key.strip_prefix(&partial[..]) else {
+                    return Ok(None);
+                };
+                let Some((&nibble, rest)) = rest.split_first() else {
+                    return v.map(value).transpose();
+                };
+                encoded = match children[nibble as usize].take() {
+                    None => return Ok(None),
+                    Some(Child::Hash(hash)) => node_at(hash)?,
+                    Some(Child::Inline(node)) => node,
+                };
+                key = rest;
+            }
+        }
+    }
+}
+
+fn blake2_256(input: &[u8]) -> HashValue {
+    let mut hash = <Blake2x256 as HashOutput>::Type::default();
+    ink::env::hash_bytes::<Blake2x256>(input, &mut hash);
+    hash
+}
+
+/// The nibbles of `bytes`, high nibble first.
+fn nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0f])
+        .collect()
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if input.len() < len {
+        return Err(InvalidProof);
+    }
+    let (taken, rest) = input.split_at(len);
+    *input = rest;
+    Ok(taken)
+}
+
+fn take_byte(input: &mut &[u8]) -> Result<u8> {
+    take(input, 1).map(|byte| byte[0])
+}
+
+fn take_with_length<'a>(input: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let len = Compact::<u32>::decode(input).map_err(|_| InvalidProof)?.0;
+    take(input, len as usize)
+}
+
+/// The number of nibbles in the header, whose first byte is `first`, and whose kind took up its
+/// `prefix_bits` highest bits. The lowest bits all set mean that more bytes follow.
+fn nibble_count(first: u8, prefix_bits: u32, input: &mut &[u8]) -> Result<usize> {
+    let max = 0xffu8 >> prefix_bits;
+    let mut count = (first & max) as usize;
+    if count < max as usize {
+        return Ok(count);
+    }
+    count -= 1;
+    loop {
+        let next = take_byte(input)? as usize;
+        if next < 255 {
+            return Ok(count + next + 1);
+        }
+        count += 255;
+    }
+}
+
+/// The partial key of `count` nibbles. An odd count is padded with a zero nibble in front.
+fn partial(count: usize, input: &mut &[u8]) -> Result<Vec<u8>> {
+    let bytes = take(input, count.div_ceil(2))?;
+    let padded = nibbles(bytes);
+    let padding = padded.len() - count;
+    if padded[..padding].iter().any(|nibble| *nibble != 0) {
+        return Err(InvalidProof);
+    }
+    Ok(padded[padding..].to_vec())
+}
+
+fn decode_node(mut input: &[u8]) -> Result<Node<'_>> {
+    let input = &mut input;
+    let first = take_byte(input)?;
+    // The kind of node, whether its value is hashed, and how many bits the kind takes up.
+    let (is_branch, has_value, hashed_value, prefix_bits) = match first >> 6 {
+        0b01 => (false, true, false, 2),
+        0b10 => (true, false, false, 2),
+        0b11 => (true, true, false, 2),
+        _ if first == 0 => return Ok(Node::Empty),
+        _ if first >> 5 == 0b001 => (false, true, true, 3),
+        _ if first >> 4 == 0b0001 => (true, true, true, 4),
+        _ => return Err(InvalidProof),
+    };
+    let count = nibble_count(first, prefix_bits, input)?;
+    let partial = partial(count, input)?;
+    let bitmap = if is_branch {
+        let bytes = take(input, 2)?;
+        u16::from_le_bytes([bytes[0], bytes[1]])
+    } else {
+        0
+    };
+    let value = if !has_value {
+        None
+    } else if hashed_value {
+        Some(Value::Hashed(take(input, 32)?))
+    } else {
+        Some(Value::Inline(take_with_length(input)?))
+    };
+
+    if !is_branch {
+        let value = value.ok_or(InvalidProof)?;
+        return Ok(Node::Leaf { partial, value });
+    }
+    let mut children: Box<[Option<Child>; 16]> = Default::default();
+    for (nibble, child) in children.iter_mut().enumerate() {
+        if bitmap & (1 << nibble) != 0 {
+            let encoded = take_with_length(input)?;
+            *child = Some(if encoded.len() == 32 {
+                Child::Hash(encoded)
+            } else {
+                Child::Inline(encoded)
+            });
+        }
+    }
+    Ok(Node::Branch {
+        partial,
+        value,
+        children,
+    })
+}
@@ -0,0 +1,161 @@
+//! Bitcoin's block header format, proof of work and Merkle trees.
+//!
+//! Bitcoin hashes everything with a double SHA-256, and treats hashes as little-endian 256-bit
+//! numbers, while the bridge compares them as big-endian byte arrays. So all hashes that go in and
+//! out of these functions are in the order that block explorers display them: the reverse of the
+//! order in which they are hashed and serialized in blocks.
+
+use ink::env::hash::{HashOutput, Sha2x256};
+use primitive_types::U256;
+
+/// The highest target allowed on mainnet, as encoded by bits `0x1d00ffff`.
+pub const POW_LIMIT: [u8; 32] = [
+    0, 0, 0, 0, 0xff, 0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0,
+];
+
+/// The fields of an 80-byte Bitcoin block header.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BitcoinHeader {
+    pub version: i32,
+    /// The hash of the previous block, in display order
+    pub prev_hash: [u8; 32],
+    /// The root of the Merkle tree of the txids of the block, in display order
+    pub merkle_root: [u8; 32],
+    /// When the block was mined, in seconds since the Unix epoch
+    pub time: u32,
+    /// The target that the hash of the block must meet, in compact form
+    pub bits: u32,
+    pub nonce: u32,
+}
+
+impl BitcoinHeader {
+    /// Parse a raw header, whose integers are all little-endian.
+    pub fn parse(raw: &[u8; 80]) -> Self {
+        let u32_at = |offset: usize| {
+            u32::from_le_bytes([
+                raw[offset],
+                raw[offset + 1],
+                raw[offset + 2],
+                raw[offset + 3],
+            ])
+        };
+        let hash_at = |offset: usize| {
+            let mut hash = [0u8; 32];
+            hash.copy_from_slice(&raw[offset..offset + 32]);
+            hash.reverse();
+            hash
+        };
+        Self {
+            version: u32_at(0) as i32,
+            prev_hash: hash_at(4),
+            merkle_root: hash_at(36),
+            time: u32_at(68),
+            bits: u32_at(72),
+            nonce: u32_at(76),
+        }
+    }
+
+    /// The hash of a raw header, which is the id of its block, in display order.
+    pub fn hash(raw: &[u8; 80]) -> [u8; 32] {
+        let mut hash = double_sha256(raw);
+        hash.reverse();
+        hash
+    }
+}
+
+fn double_sha256(input: &[u8]) -> [u8; 32] {
+    let mut first = <Sha2x256 as HashOutput>::Type::default();
+    ink::env::hash_bytes::<Sha2x256>(input, &mut first);
+    let mut second = <Sha2x256 as HashOutput>::Type::default();
+    ink::env::hash_bytes::<Sha2x256>(&first, &mut second);
+    second
+}
+
+/// The parent of two nodes of a Bitcoin Merkle tree, all in display order.
+pub fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut pair = [0u8; 64];
+    pair[..32].copy_from_slice(left);
+    pair[32..].copy_from_slice(right);
+    pair[..32].reverse();
+    pair[32..].reverse();
+    let mut hash = double_sha256(&pair);
+    hash.reverse();
+    hash
+}
+
+/// Expand compact `bits` into the target they encode, as a big-endian integer.
+///
+/// The top byte of `bits` is the length of the target in bytes, and the low 23 bits are its most
+/// significant bits. Returns `None` for the negative and overflowing targets that the 24th bit
+/// and large lengths can encode, which no valid header has.
+pub fn expand_bits(bits: u32) -> Option<[u8; 32]> {
+    let size = bits >> 24;
+    let mantissa = bits & 0x007f_ffff;
+    if mantissa != 0
+        && (bits & 0x0080_0000 != 0
+            || size > 34
+            || (mantissa > 0xff && size > 33)
+            || (mantissa > 0xffff && size > 32))
+    {
+        return None;
+    }
+
+    let target = if size <= 3 {
+        U256::from(mantissa >> (8 * (3 - size)))
+    } else {
+        U256::from(mantissa) << (8 * (size - 3))
+    };
+    let mut bytes = [0u8; 32];
+    target.to_big_endian(&mut bytes);
+    Some(bytes)
+}
+
+/// Encode `target` in compact form, rounding it down to its three most significant bytes as
+/// Bitcoin does when it retargets.
+pub fn compact_bits(target: &[u8; 32]) -> u32 {
+    let target = U256::from_big_endian(target);
+    let mut size = (target.bits() as u32).div_ceil(8);
+    let mut mantissa = if size <= 3 {
+        target.low_u32() << (8 * (3 - size))
+    } else {
+        (target >> (8 * (size - 3))).low_u32()
+    };
+    // The 24th bit would make the target negative, so shift it into one more byte instead.
+    if mantissa & 0x0080_0000 != 0 {
+        mantissa >>= 8;
+        size += 1;
+    }
+    (size << 24) | mantissa
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bits_round_trip() {
+        assert_eq!(expand_bits(0x1d00ffff), Some(POW_LIMIT));
+        assert_eq!(compact_bits(&POW_LIMIT), 0x1d00ffff);
+
+        // The target of block 32256, the first retarget of mainnet.
+        let target = expand_bits(0x1d00d86a).unwrap();
+        assert_eq!(&target[..7], &[0, 0, 0, 0, 0xd8, 0x6a, 0]);
+        assert_eq!(compact_bits(&target), 0x1d00d86a);
+
+        // Lower bits than the three most significant bytes are rounded away.
+        let mut rounded = target;
+        rounded[10] = 1;
+        assert_eq!(compact_bits(&rounded), 0x1d00d86a);
+
+        // A mantissa with its top bit set takes one more byte.
+        let mut high = [0u8; 32];
+        high[29] = 0x80;
+        assert_eq!(compact_bits(&high), 0x04008000);
+        assert_eq!(expand_bits(0x04008000), Some(high));
+
+        assert_eq!(expand_bits(0x01003456), Some([0; 32]));
+        assert_eq!(expand_bits(0x04923456), None);
+        assert_eq!(expand_bits(0xff123456), None);
+    }
+}
@@ -2,10 +2,21 @@
 
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
+mod bitcoin;
+
+pub use self::spv_bridge::{
+    Bond, Error, Fraud, HashValue, Header, MerkleProof, OptimisticHeader, OptimisticRule,
+    ParameterUpdate, PendingUpdate, RetargetRule, SiblingNode, SpvBridge, SpvBridgeRef, StateClaim,
+    StoredHeader,
+};
+
 #[ink::contract]
 mod spv_bridge {
+    use crate::bitcoin::{self, BitcoinHeader};
     use ink::env::hash::{HashOutput, Sha2x256};
-    use ink::storage::Mapping;
+    use ink::prelude::vec::Vec;
+    use ink::storage::{traits::ManualKey, Lazy, Mapping};
+    use primitive_types::U256;
 
     pub type HashValue = [u8; 32];
 
@@ -20,39 +31,234 @@ mod spv_bridge {
         height: u64,
         /// The hash of this block's parent
         parent: HashValue,
+        /// When this block was produced, in seconds
+        timestamp: u64,
         /// The merkle tree root of the storage
-        storage_root: u64,
+        storage_root: HashValue,
         /// The merkle tree root of the transactions included in the block
-        transactions_root: u64,
+        transactions_root: HashValue,
         /// The nonce that allows the block's hash to satisfy the proof of work
         pow_nonce: u64,
     }
 
-    /// A STUB of a Merkle Proof
+    impl Header {
+        /// A header with the given fields, for off-chain code that produces headers.
+        pub fn new(
+            height: u64,
+            parent: HashValue,
+            timestamp: u64,
+            storage_root: HashValue,
+            transactions_root: HashValue,
+            pow_nonce: u64,
+        ) -> Self {
+            Self {
+                height,
+                parent,
+                timestamp,
+                storage_root,
+                transactions_root,
+                pow_nonce,
+            }
+        }
+
+        pub fn height(&self) -> u64 {
+            self.height
+        }
+
+        pub fn parent(&self) -> HashValue {
+            self.parent
+        }
+
+        pub fn timestamp(&self) -> u64 {
+            self.timestamp
+        }
+    }
+
+    /// The format of the headers of the source chain. It decides how headers are submitted and
+    /// hashed, and how transactions are proven to be in their blocks.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo)
+    )]
+    pub enum HeaderFormat {
+        /// [`Header`]s, hashed with a single SHA-256 of their SCALE encoding.
+        Scale,
+        /// Raw 80-byte Bitcoin headers, see [`crate::bitcoin`]. Their parent, transactions root
+        /// and time are kept as a [`Header`] without a storage root.
+        Bitcoin,
+    }
+
+    /// A header as stored by the bridge, along with what the bridge derived from its ancestors.
     ///
-    /// Rather than actually reassembling a partial tree of siblings etc, we just have a simple field
-    /// indicating whether the proof should be treated as valid or not.
+    /// Targets and chain work are 256-bit unsigned integers, stored big-endian so that they compare
+    /// the same way as the hashes they are checked against.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo)
+    )]
+    pub struct StoredHeader {
+        header: Header,
+        /// The difficulty threshold that the hash of this header had to meet
+        target: HashValue,
+        /// The total work of the chain from the checkpoint up to and including this header
+        chain_work: HashValue,
+        /// The timestamp of the first block of the retarget window of this header
+        window_start: u64,
+        /// The deposit that the relayer of this header paid, refunded when it is pruned
+        deposit: Balance,
+    }
+
+    /// How the difficulty threshold of the source chain changes over time.
     ///
-    /// We stub the proof system because students already built exactly this in assignment 1.
-    /// For this assignment, there is enough to focus on in the smart contract and blockchain aspects.
+    /// The first block of every window of `window` blocks gets a new threshold: the previous one
+    /// scaled by how long the previous window actually took, compared to the `window * block_time`
+    /// it should have taken. As in Bitcoin, the time a window took is measured from its first block
+    /// to its last one. The adjustment is clamped to a factor of `max_adjustment` either way, and
+    /// the threshold never goes above `max_target`. A `window` of zero keeps the threshold fixed.
     #[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
     #[cfg_attr(
         feature = "std",
         derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo)
     )]
+    pub struct RetargetRule {
+        pub window: u64,
+        pub block_time: u64,
+        pub max_adjustment: u64,
+        pub max_target: HashValue,
+    }
+
+    impl RetargetRule {
+        /// Bitcoin's rule: windows of two weeks' worth of ten minute blocks, adjusted by at most a
+        /// factor of four.
+        pub fn bitcoin(max_target: HashValue) -> Self {
+            Self {
+                window: 2016,
+                block_time: 600,
+                max_adjustment: 4,
+                max_target,
+            }
+        }
+
+        /// Whether the block at `height` is the first of a window, and so gets a new threshold.
+        fn starts_window(&self, height: u64) -> bool {
+            self.window != 0 && height.is_multiple_of(self.window)
+        }
+
+        /// The threshold of the block at `height`, whose parent is `parent`.
+        fn next_target(&self, parent: &StoredHeader, height: u64) -> HashValue {
+            if !self.starts_window(height) {
+                return parent.target;
+            }
+
+            let max_adjustment = self.max_adjustment.max(1);
+            let expected = self.window.saturating_mul(self.block_time).max(1);
+            let actual = parent
+                .header
+                .timestamp
+                .saturating_sub(parent.window_start)
+                .clamp(
+                    expected / max_adjustment,
+                    expected.saturating_mul(max_adjustment),
+                )
+                .max(1);
+
+            let target = U256::from_big_endian(&parent.target).full_mul(actual.into())
+                / U256::from(expected).full_mul(1.into());
+            let target = U256::try_from(target)
+                .unwrap_or(U256::MAX)
+                .min(U256::from_big_endian(&self.max_target));
+            let mut bytes = [0u8; 32];
+            target.to_big_endian(&mut bytes);
+            bytes
+        }
+    }
+
+    /// The expected number of hashes needed to find one below `target`, that is
+    /// `2^256 / (target + 1)`, computed as Bitcoin does without overflowing 256 bits.
+    fn work(target: HashValue) -> U256 {
+        let target = U256::from_big_endian(&target);
+        match target.checked_add(1.into()) {
+            Some(divisor) => !target / divisor + 1,
+            None => 1.into(),
+        }
+    }
+
+    /// A sibling node along the Merkle path from a leaf to the root. It is necessary to specify
+    /// which side the sibling is on, so that the two hashes are combined in the same order as when
+    /// the tree was built.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum SiblingNode {
+        Left(HashValue),
+        Right(HashValue),
+    }
+
+    /// A Merkle proof that a leaf is part of a binary Merkle tree.
+    ///
+    /// The leaves of the tree are the hashes of the transactions of a block, or of the state
+    /// claims of its storage. Each node above them is the `Sha2x256` hash of the concatenation of
+    /// its left and right children. The proof is the list of siblings along the path from the
+    /// leaf up to the root, starting with the sibling of the leaf itself.
+    ///
+    /// Bitcoin's trees hash the concatenation twice instead, see [`bitcoin::hash_pair`]. When a
+    /// layer of its trees has an odd number of nodes, the last one is paired with itself, so in a
+    /// proof of that node, its sibling is itself, on the right.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct MerkleProof {
-        verifies: bool,
+        siblings: Vec<SiblingNode>,
     }
 
     impl MerkleProof {
+        /// A proof made of the given siblings, ordered from the leaf up to the root.
+        pub fn new(siblings: Vec<SiblingNode>) -> Self {
+            Self { siblings }
+        }
+
+        /// Check that `claim` is a leaf of the tree with the given root, by hashing it together
+        /// with each of the siblings of the proof in turn.
         pub fn check_merkle_proof(
-            _claim: HashValue,
-            proof: MerkleProof,
-            _merkle_root: u64,
+            claim: HashValue,
+            proof: &MerkleProof,
+            merkle_root: HashValue,
         ) -> bool {
-            // This is where the actual merkle proof checking logic _would_ go
-            // if we weren't stubbing the proofs. Instead this stub is given.
-            proof.verifies
+            let computed_root = proof
+                .siblings
+                .iter()
+                .fold(claim, |node, sibling| match sibling {
+                    SiblingNode::Left(left) => Self::hash_pair(left, &node),
+                    SiblingNode::Right(right) => Self::hash_pair(&node, right),
+                });
+            computed_root == merkle_root
+        }
+
+        /// Check that `txid` is a leaf of the Bitcoin Merkle tree with the given root, all in
+        /// display order.
+        pub fn check_bitcoin_merkle_proof(
+            txid: HashValue,
+            proof: &MerkleProof,
+            merkle_root: HashValue,
+        ) -> bool {
+            let computed_root = proof
+                .siblings
+                .iter()
+                .fold(txid, |node, sibling| match sibling {
+                    SiblingNode::Left(left) => bitcoin::hash_pair(left, &node),
+                    SiblingNode::Right(right) => bitcoin::hash_pair(&node, right),
+                });
+            computed_root == merkle_root
+        }
+
+        /// Helper function to compute the parent of two nodes of the tree.
+        pub fn hash_pair(left: &HashValue, right: &HashValue) -> HashValue {
+            let mut pair = [0u8; 64];
+            pair[..32].copy_from_slice(left);
+            pair[32..].copy_from_slice(right);
+            let mut hash_value = <Sha2x256 as HashOutput>::Type::default();
+            ink::env::hash_bytes::<Sha2x256>(&pair, &mut hash_value);
+            hash_value
         }
     }
 
@@ -62,8 +268,8 @@ mod spv_bridge {
     /// We model the source chain storage as a key value mapping, like most blockchains.
     /// An instance of this struct would claim that a particular key holds a particular value.
     ///
-    /// For assignment purposes, the storage model doesn't matter so much because we stub the proofs.
-    /// Nonetheless, we give a somewhat realistic model.
+    /// The leaves of the storage tree of a block are the `Sha2x256` hashes of the encoded claims
+    /// that hold in its state, see [`SpvBridge::hash_claim`].
     #[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
     #[cfg_attr(
         feature = "std",
@@ -74,34 +280,186 @@ mod spv_bridge {
         value: u64,
     }
 
+    /// How long a proposed parameter update waits before it can be executed, unless the timelock
+    /// itself is updated: two days, in milliseconds.
+    pub const DEFAULT_TIMELOCK: Timestamp = 2 * 24 * 60 * 60 * 1000;
+
+    /// A change of the parameters of the bridge, which the owner proposes and can only execute
+    /// once the timelock has passed, so that relayers and verifiers can react to it.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo)
+    )]
+    pub enum ParameterUpdate {
+        /// New relay and verify fees. Headers relayed before keep the deposit they were relayed with.
+        Fees {
+            relay_fee: Balance,
+            verify_fee: Balance,
+        },
+        /// A new rule to retarget the PoW threshold of the headers relayed from now on.
+        Retarget(RetargetRule),
+        /// A new finality depth. Lowering it prunes the headers that are now final right away.
+        FinalityDepth(u64),
+        /// A new timelock for the updates proposed from now on, in milliseconds.
+        Timelock(Timestamp),
+        /// Hand the owner role over to another account, e.g. a governance contract.
+        Owner(AccountId),
+        /// Upgrade the code of the contract with `set_code_hash`. The new code must keep the
+        /// storage layout of this one, or migrate it.
+        Code(Hash),
+        /// Enable optimistic headers with this rule, or disable them. Headers already submitted
+        /// optimistically can still be challenged and accepted.
+        Optimistic(Option<OptimisticRule>),
+    }
+
+    /// A proposed parameter update, along with the time from which it can be executed.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo)
+    )]
+    pub struct PendingUpdate {
+        pub update: ParameterUpdate,
+        pub eta: Timestamp,
+    }
+
+    /// How optimistic headers are submitted, see `submit_optimistic_headers`.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo)
+    )]
+    pub struct OptimisticRule {
+        /// The bond that a relayer must have posted to submit optimistic headers
+        pub bond: Balance,
+        /// How long optimistic headers can be challenged after they are submitted, in milliseconds
+        pub challenge_period: Timestamp,
+    }
+
+    /// The bond of a relayer, which is slashed if any of its optimistic headers is proven invalid.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Default, scale::Decode, scale::Encode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo)
+    )]
+    pub struct Bond {
+        pub amount: Balance,
+        /// The end of the challenge window of the last header the relayer submitted. The bond
+        /// cannot be withdrawn before.
+        pub locked_until: Timestamp,
+    }
+
+    /// A header submitted without being checked, which can be challenged until its deadline.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo)
+    )]
+    pub struct OptimisticHeader {
+        pub header: Header,
+        pub relayer: AccountId,
+        /// The relay fee that the relayer paid, held as the deposit of the header
+        pub deposit: Balance,
+        /// The last moment at which the header can be challenged
+        pub challenge_deadline: Timestamp,
+    }
+
+    /// What a challenger claims is wrong with an optimistic header. Each claim is checked against
+    /// the parent of the header, as it would be if the header was submitted with all checks, with
+    /// optimistic ancestors counting as valid.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Fraud {
+        /// The hash of the header does not meet the threshold retargeted from its parent's
+        BadPoW,
+        /// The height of the header is not one more than its parent's
+        WrongHeight,
+        /// The parent of the header is neither in the db nor optimistic, nor are all of its
+        /// ancestors down to the db, and it is above the finalized height, so it was not pruned
+        UnknownParent,
+    }
+
+    /// The storage keys of the fields added to [`SpvBridge`] after its first deployments. They
+    /// live under keys of their own rather than in the root of the storage, so that contracts
+    /// upgraded to this code still decode their root as it is.
+    const OPTIMISTIC_RULE_KEY: u32 = 0x4f50_0001;
+    const OPTIMISTIC_HEADERS_KEY: u32 = 0x4f50_0002;
+    const BONDS_KEY: u32 = 0x4f50_0003;
+
+    /// The storage of the bridge. Upgrades with `set_code_hash` keep it as it is, so its layout
+    /// must only change along with a migration, or be extended with fields under keys of their
+    /// own, see `test_storage_layout_is_compatible`.
     #[ink(storage)]
     pub struct SpvBridge {
         /// The main source chain header database.
         /// Maps header hashes to complete headers.
-        headers: Mapping<HashValue, Header>,
+        headers: Mapping<HashValue, StoredHeader>,
 
-        /// A representation of the canonical source chain.
+        /// A representation of the canonical source chain, the one with the most cumulative work.
         /// Maps block heights to the canonical source block hash at that height.
         /// Updates when a re-org happens
         canon_chain: Mapping<u64, HashValue>,
 
         /// The user who submitted each block hash.
-        /// Fees paid by verifiers will go to this address.
+        /// Fees paid by verifiers will go to this address, and deposits are refunded to it.
         fee_recipient: Mapping<HashValue, AccountId>,
 
+        /// The hashes of all headers in the db at each height, canonical or not,
+        /// so that they can be pruned.
+        headers_at_height: Mapping<u64, Vec<HashValue>>,
+
+        /// Deposits that could not be refunded when their headers were pruned,
+        /// which their relayers can claim with `claim_refund`.
+        refunds: Mapping<AccountId, Balance>,
+
         /// The height of the current best known source chain
         best_height: u64,
 
-        /// The difficulty threshold for the PoW
-        difficulty_threshold: HashValue,
+        /// How many blocks below the tip of the canonical chain a header becomes final.
+        /// Final headers can no longer be re-orged, and all headers below the lowest final one,
+        /// along with its forks, are pruned.
+        finality_depth: u64,
+
+        /// The height of the lowest header in the db, which is canonical and final
+        finalized_height: u64,
+
+        /// How the difficulty threshold for the PoW changes over time
+        retarget: RetargetRule,
+
+        /// The format of the source chain headers
+        format: HeaderFormat,
 
         /// The fee the relayer must pay in order to relay a block on top
-        /// of any protocol level gas fees
+        /// of any protocol level gas fees. It is held as a deposit until the block is pruned.
         relay_fee: Balance,
 
         /// The fee the verifier must pay in order to verify that their
         /// transaction or state claim is canonical on the source chain.
         verify_fee: Balance,
+
+        /// The account that can update the parameters of the bridge, through the timelock, and
+        /// withdraw its surplus. The deployer at first.
+        owner: AccountId,
+
+        /// How long a proposed update waits before it can be executed, in milliseconds
+        timelock: Timestamp,
+
+        /// The parameter update that the owner proposed, if any
+        pending_update: Option<PendingUpdate>,
+
+        /// The deposits of the headers in the db, plus the refunds yet to be claimed.
+        /// The balance of the contract above them is its surplus, which the owner can withdraw.
+        deposits: Balance,
+
+        /// How optimistic headers are submitted, or `None` if they cannot be
+        optimistic: Lazy<Option<OptimisticRule>, ManualKey<OPTIMISTIC_RULE_KEY>>,
+
+        /// Headers submitted optimistically that are not accepted in the db yet, by hash
+        optimistic_headers: Mapping<HashValue, OptimisticHeader, ManualKey<OPTIMISTIC_HEADERS_KEY>>,
+
+        /// The bonds of the relayers of optimistic headers
+        bonds: Mapping<AccountId, Bond, ManualKey<BONDS_KEY>>,
     }
 
     /// Errors that can occur upon calling this contract.
@@ -120,8 +478,42 @@ mod spv_bridge {
         IncorrectHeight,
         /// PoW threshold has not been met
         PoWThresholdNotMet,
+        /// The difficulty bits of a Bitcoin header are not those of the retargeted threshold
+        UnexpectedDifficulty,
+        /// The header was submitted in another format than the source chain's
+        WrongHeaderFormat,
+        /// The header is on a fork that diverges from the canonical chain below its final headers
+        FinalizedFork,
+        /// There is no refund to claim
+        NoRefund,
         /// Attempted reward payment to a relayer failed,
         PaymentFailed,
+        /// Only the owner can do this
+        NotOwner,
+        /// Another parameter update is already pending
+        UpdatePending,
+        /// There is no parameter update to execute or cancel
+        NoPendingUpdate,
+        /// The timelock of the pending update has not passed yet
+        TimelockNotExpired,
+        /// The balance of the contract does not exceed the deposits it holds
+        NothingToWithdraw,
+        /// The code hash of a code upgrade is not that of uploaded code
+        CodeUpgradeFailed,
+        /// Optimistic headers are disabled
+        OptimisticModeDisabled,
+        /// The relayer has not posted the bond that optimistic headers require
+        InsufficientBond,
+        /// The bond is still at stake for headers that can be challenged
+        BondLocked,
+        /// There is no optimistic header with this hash
+        NotOptimistic,
+        /// The challenge window of the optimistic header has passed
+        ChallengeWindowClosed,
+        /// The optimistic header can still be challenged
+        ChallengeWindowOpen,
+        /// The header is not invalid in the way the challenger claims
+        NoFraud,
     }
 
     /// Type alias for the contract's `Result` type.
@@ -136,6 +528,76 @@ mod spv_bridge {
         submitter: AccountId,
     }
 
+    /// The canonical chain switched from `old_tip` to `new_tip`, dropping the `depth` canonical
+    /// headers above `common_ancestor`, the last header that both chains share.
+    #[ink(event)]
+    pub struct Reorged {
+        #[ink(topic)]
+        old_tip: HashValue,
+        #[ink(topic)]
+        new_tip: HashValue,
+        common_ancestor: HashValue,
+        depth: u64,
+    }
+
+    /// The fees of the bridge are now these.
+    #[ink(event)]
+    pub struct FeeUpdated {
+        relay_fee: Balance,
+        verify_fee: Balance,
+    }
+
+    /// Someone submitted a source chain header optimistically, which can be challenged until
+    /// `challenge_deadline`.
+    #[ink(event)]
+    pub struct OptimisticHeaderSubmitted {
+        block_hash: HashValue,
+        block_height: u64,
+        #[ink(topic)]
+        submitter: AccountId,
+        challenge_deadline: Timestamp,
+    }
+
+    /// The `challenger` proved that an optimistic header of `relayer` was invalid, and received the
+    /// bond of the relayer along with the deposit of the header, `slashed` in total.
+    #[ink(event)]
+    pub struct FraudProven {
+        block_hash: HashValue,
+        fraud: Fraud,
+        #[ink(topic)]
+        relayer: AccountId,
+        #[ink(topic)]
+        challenger: AccountId,
+        slashed: Balance,
+    }
+
+    /// The owner proposed `update`, which can be executed from `eta` on.
+    #[ink(event)]
+    pub struct UpdateProposed {
+        update: ParameterUpdate,
+        eta: Timestamp,
+    }
+
+    /// The pending `update` was executed.
+    #[ink(event)]
+    pub struct UpdateExecuted {
+        update: ParameterUpdate,
+    }
+
+    /// The owner cancelled the pending `update`.
+    #[ink(event)]
+    pub struct UpdateCancelled {
+        update: ParameterUpdate,
+    }
+
+    /// The owner withdrew `amount` of the surplus of the bridge to `to`.
+    #[ink(event)]
+    pub struct SurplusWithdrawn {
+        #[ink(topic)]
+        to: AccountId,
+        amount: Balance,
+    }
+
     /// An on-chain light client (or SPV client) for a foreign source chain.
     ///
     /// This contract, inspired by btc-relay, allows users to submit new block headers
@@ -148,11 +610,79 @@ mod spv_bridge {
         /// history. We allow starting from a recent point in the source chain and verifying
         /// thereafter.
         ///
-        /// This constructor allows the contract deployer to specify the recent block from which to start
+        /// This constructor allows the contract deployer to specify the recent block from which to start,
+        /// along with its difficulty threshold and the timestamp of the first block of its retarget
+        /// window (which is its own timestamp if it starts one). The work of the chain before the
+        /// checkpoint is not counted, since only forks after it are compared.
         #[ink(constructor)]
         pub fn new(
             source_genesis_header: Header,
             difficulty: HashValue,
+            window_start: u64,
+            retarget: RetargetRule,
+            finality_depth: u64,
+            init_relay_fee: Balance,
+            init_verify_fee: Balance,
+        ) -> Self {
+            let h = Self::hash_header(source_genesis_header);
+            Self::init(
+                h,
+                source_genesis_header,
+                difficulty,
+                window_start,
+                retarget,
+                HeaderFormat::Scale,
+                finality_depth,
+                init_relay_fee,
+                init_verify_fee,
+            )
+        }
+
+        /// Initialize the on-chain light client for Bitcoin mainnet, with a raw checkpoint header
+        /// at the given height. Headers are then submitted with `submit_bitcoin_header`, and
+        /// retargeted every 2016 blocks as Bitcoin does.
+        #[ink(constructor)]
+        pub fn new_bitcoin(
+            checkpoint: [u8; 80],
+            height: u64,
+            window_start: u64,
+            finality_depth: u64,
+            init_relay_fee: Balance,
+            init_verify_fee: Balance,
+        ) -> Self {
+            let parsed = BitcoinHeader::parse(&checkpoint);
+            let header = Header {
+                height,
+                parent: parsed.prev_hash,
+                timestamp: parsed.time.into(),
+                storage_root: [0; 32],
+                transactions_root: parsed.merkle_root,
+                pow_nonce: parsed.nonce.into(),
+            };
+            let difficulty = bitcoin::expand_bits(parsed.bits)
+                .expect("the checkpoint must have valid difficulty bits");
+            Self::init(
+                BitcoinHeader::hash(&checkpoint),
+                header,
+                difficulty,
+                window_start,
+                RetargetRule::bitcoin(bitcoin::POW_LIMIT),
+                HeaderFormat::Bitcoin,
+                finality_depth,
+                init_relay_fee,
+                init_verify_fee,
+            )
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        fn init(
+            h: HashValue,
+            source_genesis_header: Header,
+            difficulty: HashValue,
+            window_start: u64,
+            retarget: RetargetRule,
+            format: HeaderFormat,
+            finality_depth: u64,
             init_relay_fee: Balance,
             init_verify_fee: Balance,
         ) -> Self {
@@ -161,168 +691,647 @@ mod spv_bridge {
             let mut headers = Mapping::default();
             let mut canon_chain = Mapping::default();
             let mut fee_recipient = Mapping::default();
+            let mut headers_at_height = Mapping::default();
 
-            let difficulty_threshold = difficulty;
             let relay_fee = init_relay_fee;
             let verify_fee = init_verify_fee;
 
-            // Calculate header hash and put header in storage
-            let h = Self::hash_header(source_genesis_header);
-            headers.insert(h, &source_genesis_header);
+            // Put header in storage
+            let mut chain_work = [0u8; 32];
+            work(difficulty).to_big_endian(&mut chain_work);
+            headers.insert(
+                h,
+                &StoredHeader {
+                    header: source_genesis_header,
+                    target: difficulty,
+                    chain_work,
+                    window_start,
+                    deposit: 0,
+                },
+            );
 
             // Update other storages
             let best_height = source_genesis_header.height;
             canon_chain.insert(best_height, &h);
+            headers_at_height.insert(best_height, &Vec::from([h]));
 
             // Record the deployer as the fee recipient for the checkpoint block
             fee_recipient.insert(h, &caller);
 
+            Self::env().emit_event(FeeUpdated {
+                relay_fee,
+                verify_fee,
+            });
+
             Self {
                 headers,
                 canon_chain,
                 fee_recipient,
+                headers_at_height,
+                refunds: Mapping::default(),
                 best_height,
-                difficulty_threshold,
+                finality_depth,
+                finalized_height: best_height,
+                retarget,
+                format,
                 relay_fee,
                 verify_fee,
+                owner: caller,
+                timelock: DEFAULT_TIMELOCK,
+                pending_update: None,
+                deposits: 0,
+                optimistic: Lazy::default(),
+                optimistic_headers: Mapping::default(),
+                bonds: Mapping::default(),
             }
         }
 
         /// Submit a new source chain block header to the bridge for verification.
         /// In order for the new header to be valid, these conditions must be met:
-        /// 0. The relayer must pay the relay fee (which will be locked forever).
+        /// 0. The relayer must pay the relay fee (which is held as a deposit).
         /// 1. The header must not already be in the db
         /// 2. The header's parent must already be in the db
         /// 3. The header's height must be one more than it's parent
-        /// 4. The header's hash must satisfy the PoW threshold
+        /// 4. The header's hash must satisfy the PoW threshold, as retargeted from its parent's
         ///
-        /// Once the block is validated you must determine whether this causes
-        /// a re-org or not, and update storage accordingly.
+        /// Once the block is validated, it causes a re-org if its chain has more cumulative work
+        /// than the current canonical chain, even if it is shorter, unless that chain diverges
+        /// from the canonical one below its final headers. Headers that are no longer needed are
+        /// then pruned, see `finality_depth`.
         ///
-        /// The relay fee does not go to anyone. It is locked up forever; effectively burnt.
+        /// The relay fee is refunded to the relayer when the header is pruned.
         #[ink(message, payable)]
         pub fn submit_new_header(&mut self, header: Header) -> Result<()> {
-            // 0. The relayer must pay the relay fee
-            if self.env().transferred_value() < self.relay_fee {
+            self.submit_headers(Vec::from([header]))
+        }
+
+        /// Submit a segment of a source chain at once, each header being the parent of the next
+        /// one, and the parent of the first one already in the db.
+        ///
+        /// Each header must meet the same conditions as in `submit_new_header`, and the relayer
+        /// must pay the relay fee for each of them. If any header is invalid, none are stored.
+        #[ink(message, payable)]
+        pub fn submit_headers(&mut self, headers: Vec<Header>) -> Result<()> {
+            // 0. The relayer must pay the relay fee for each header
+            let fees = self.relay_fee.saturating_mul(headers.len() as Balance);
+            if self.env().transferred_value() < fees {
                 // Not enough relay_fee, return an error
                 return Err(Error::InsufficientRelayFee);
             }
+            if self.format != HeaderFormat::Scale {
+                return Err(Error::WrongHeaderFormat);
+            }
+
+            let mut segment: Vec<(HashValue, StoredHeader)> = Vec::new();
+            for header in headers {
+                let header_hash = Self::hash_header(header);
+
+                // 1. and 2. The header must be new, and its parent known, either in the db or as
+                // the previous header of the segment
+                let parent = match segment.last() {
+                    Some((parent_hash, parent)) if *parent_hash == header.parent => {
+                        if Self::header_is_known(self, header_hash) {
+                            return Err(Error::HeaderAlreadySubmitted);
+                        }
+                        *parent
+                    }
+                    Some(_) => return Err(Error::UnknownParent),
+                    None => self.check_new_header(header_hash, header.parent)?,
+                };
+
+                // 3. The header's height must be one more than it's parent
+                if parent.header.height + 1 != header.height {
+                    // block height is not one more than its parent, return an error
+                    return Err(Error::IncorrectHeight);
+                }
 
-            let header_hash = Self::hash_header(header);
+                // 4. The header's hash must satisfy the PoW threshold, as retargeted from its
+                // parent's
+                let target = self.retarget.next_target(&parent, header.height);
+                if header_hash >= target {
+                    // block hash does not satisfy the PoW threshold, return an error
+                    return Err(Error::PoWThresholdNotMet);
+                }
 
-            // 1. The header must not already be in the db
-            if Self::header_is_known(&self, header_hash) {
-                // block hash is already in the db, return an error
-                return Err(Error::HeaderAlreadySubmitted);
+                segment.push((header_hash, self.child_of(&parent, header, target)));
             }
 
-            // 2. The header's parent must already be in the db
-            if !Self::header_is_known(&self, header.parent) {
-                // parent hash is not in the db, return an error
-                return Err(Error::UnknownParent);
-            }
+            // submit_headers succeeded, return Ok
+            self.insert_segment(segment, self.env().caller())
+        }
 
-            let mut parent_height = self.headers.get(header.parent).unwrap().height;
-            // 3. The header's height must be one more than it's parent
-            if parent_height + 1 != header.height {
-                // block height is not one more than its parent, return an error
-                return Err(Error::IncorrectHeight);
+        /// Submit a raw 80-byte header of a Bitcoin source chain.
+        ///
+        /// The conditions are the same as for `submit_new_header`, except that the height of the
+        /// header is that of its parent plus one, and that its difficulty bits must encode the
+        /// threshold retargeted from its parent's, rounded as Bitcoin does. Its hash is the double
+        /// SHA-256 of the raw header, in display order.
+        #[ink(message, payable)]
+        pub fn submit_bitcoin_header(&mut self, raw: [u8; 80]) -> Result<()> {
+            // 0. The relayer must pay the relay fee
+            if self.env().transferred_value() < self.relay_fee {
+                // Not enough relay_fee, return an error
+                return Err(Error::InsufficientRelayFee);
+            }
+            if self.format != HeaderFormat::Bitcoin {
+                return Err(Error::WrongHeaderFormat);
             }
 
-            // 4. The header's hash must satisfy the PoW threshold
-            if header_hash >= self.difficulty_threshold {
+            let bitcoin_header = BitcoinHeader::parse(&raw);
+            let header_hash = BitcoinHeader::hash(&raw);
+
+            // 1. and 2. The header must be new, and its parent known
+            let parent = self.check_new_header(header_hash, bitcoin_header.prev_hash)?;
+
+            // 3. The header's height is one more than its parent
+            let header = Header {
+                height: parent.header.height + 1,
+                parent: bitcoin_header.prev_hash,
+                timestamp: bitcoin_header.time.into(),
+                storage_root: [0; 32],
+                transactions_root: bitcoin_header.merkle_root,
+                pow_nonce: bitcoin_header.nonce.into(),
+            };
+
+            // 4. The header's hash must satisfy the PoW threshold that its bits encode, which must
+            // be the one retargeted from its parent's
+            let expected = self.retarget.next_target(&parent, header.height);
+            let target = match bitcoin::expand_bits(bitcoin_header.bits) {
+                Some(target) if bitcoin_header.bits == bitcoin::compact_bits(&expected) => target,
+                _ => return Err(Error::UnexpectedDifficulty),
+            };
+            if header_hash >= target {
                 // block hash does not satisfy the PoW threshold, return an error
                 return Err(Error::PoWThresholdNotMet);
             }
 
-            let new_header_hash = Self::hash_header(header);
-            self.headers.insert(new_header_hash, &header);
+            let stored = self.child_of(&parent, header, target);
+            self.insert_segment(Vec::from([(header_hash, stored)]), self.env().caller())
+        }
 
-            // Check if the new header is higher then the best height of the current canonical chain
-            if header.height > self.best_height {
-                let mut parent_hash = header.parent;
-                while !Self::header_is_canon(&self, parent_hash) {
-                    // Re-org
-                    self.canon_chain.insert(parent_height, &parent_hash);
-                    // update parent hash and height
-                    parent_hash = self.headers.get(parent_hash).unwrap().parent;
-                    parent_height = self.headers.get(parent_hash).unwrap().height;
-                }
-                // Update best height
-                self.best_height = header.height;
+        /// Post a bond, or add to it, so as to submit optimistic headers.
+        #[ink(message, payable)]
+        pub fn post_bond(&mut self) -> Result<()> {
+            let relayer = self.env().caller();
+            let value = self.env().transferred_value();
+            let mut bond = self.bonds.get(relayer).unwrap_or_default();
+            bond.amount = bond.amount.saturating_add(value);
+            self.bonds.insert(relayer, &bond);
+            self.deposits = self.deposits.saturating_add(value);
+            Ok(())
+        }
 
-                // Insert the new header into the canon chain
-                self.canon_chain.insert(header.height, &new_header_hash);
+        /// Withdraw the whole bond of the caller, once none of their optimistic headers can be
+        /// challenged anymore.
+        #[ink(message)]
+        pub fn withdraw_bond(&mut self) -> Result<Balance> {
+            let relayer = self.env().caller();
+            let bond = self.bonds.get(relayer).unwrap_or_default();
+            if self.env().block_timestamp() <= bond.locked_until {
+                return Err(Error::BondLocked);
             }
+            self.bonds.remove(relayer);
+            self.env()
+                .transfer(relayer, bond.amount)
+                .map_err(|_| Error::PaymentFailed)?;
+            self.deposits = self.deposits.saturating_sub(bond.amount);
+            Ok(bond.amount)
+        }
 
-            // Record the relayer as the fee recipient for the new block
-            self.fee_recipient
-                .insert(new_header_hash, &self.env().caller());
+        /// Submit source chain headers optimistically: the relayer, who must have posted the bond
+        /// of the optimistic rule, pays the relay fee for each of them, and they are stored without
+        /// any other check.
+        ///
+        /// Until the challenge window of a header has passed, anyone can prove that it is invalid
+        /// with `challenge_header`, and get the bond of the relayer. Once it has passed, the header
+        /// can be added to the db with `accept_header`. Only then do verifications count it.
+        #[ink(message, payable)]
+        pub fn submit_optimistic_headers(&mut self, headers: Vec<Header>) -> Result<()> {
+            let rule = self
+                .optimistic_rule()
+                .ok_or(Error::OptimisticModeDisabled)?;
+            let fees = self.relay_fee.saturating_mul(headers.len() as Balance);
+            if self.env().transferred_value() < fees {
+                return Err(Error::InsufficientRelayFee);
+            }
+            if self.format != HeaderFormat::Scale {
+                return Err(Error::WrongHeaderFormat);
+            }
+            let relayer = self.env().caller();
+            let mut bond = self.bonds.get(relayer).unwrap_or_default();
+            if bond.amount < rule.bond {
+                return Err(Error::InsufficientBond);
+            }
 
-            // submit_new_header succeeded, return Ok
+            let challenge_deadline = self
+                .env()
+                .block_timestamp()
+                .saturating_add(rule.challenge_period);
+            for header in headers {
+                let header_hash = Self::hash_header(header);
+                if self.header_is_known(header_hash)
+                    || self.optimistic_headers.contains(header_hash)
+                {
+                    return Err(Error::HeaderAlreadySubmitted);
+                }
+                self.optimistic_headers.insert(
+                    header_hash,
+                    &OptimisticHeader {
+                        header,
+                        relayer,
+                        deposit: self.relay_fee,
+                        challenge_deadline,
+                    },
+                );
+                self.deposits = self.deposits.saturating_add(self.relay_fee);
+                self.env().emit_event(OptimisticHeaderSubmitted {
+                    block_hash: header_hash,
+                    block_height: header.height,
+                    submitter: relayer,
+                    challenge_deadline,
+                });
+            }
+            bond.locked_until = challenge_deadline;
+            self.bonds.insert(relayer, &bond);
             Ok(())
         }
 
-        /// Verify that some transaction has occurred on the source chain.
+        /// Prove that an optimistic header is invalid during its challenge window. The header is
+        /// dropped, and the caller gets the bond of its relayer, along with its deposit, and
+        /// returns how much that is.
         ///
-        /// In order for a verification to be successful (to return true), these conditions must be met:
-        /// 0. The verifier must pay the verification fee (which will go to the relayer).
-        /// 1. The block is in the db
-        /// 2. The block is in the best chain
-        /// 3. The block's height in the best chain is at least `min_depth` before the tip of the chain.
-        ///    A min_depth of 0 just means that the header is canon at all.
-        ///    A min_depth of 1 means there is at least one block confirmation afterward.
-        /// 4. The merkle proof must be valid
-        #[ink(message, payable)]
-        pub fn verify_transaction(
+        /// The descendants of the header then have an unknown parent, and can be challenged too.
+        #[ink(message)]
+        pub fn challenge_header(
             &mut self,
-            tx_hash: HashValue,
             header_hash: HashValue,
-            min_depth: u64,
-            p: MerkleProof,
-        ) -> Result<bool> {
-            let verification_fee_payed = self.env().transferred_value();
-
-            // 0. The verifier must pay the verification fee
-            if verification_fee_payed < self.verify_fee {
-                // Not enough verify_fee, return an error
-                return Err(Error::InsufficientVerifyFee);
+            fraud: Fraud,
+        ) -> Result<Balance> {
+            let optimistic = self
+                .optimistic_headers
+                .get(header_hash)
+                .ok_or(Error::NotOptimistic)?;
+            if self.env().block_timestamp() > optimistic.challenge_deadline {
+                return Err(Error::ChallengeWindowClosed);
             }
-
-            // 1. The block is in the db
-            if !Self::header_is_known(&self, header_hash) {
-                // block hash is not in the db, verify tx failed, return false
-                return Ok(false);
+            let header = optimistic.header;
+            let proven = !self.header_is_known(header_hash)
+                && match (fraud, self.provisional_parent(header)) {
+                    // A missing parent at or below the finalized height may have been pruned,
+                    // along with the forks at its height, which is no fraud.
+                    (Fraud::UnknownParent, Err(orphan)) => !self.may_be_pruned(orphan),
+                    (Fraud::WrongHeight, Ok(parent)) => parent.header.height + 1 != header.height,
+                    (Fraud::BadPoW, Ok(parent)) => {
+                        header_hash >= self.retarget.next_target(&parent, header.height)
+                    }
+                    _ => false,
+                };
+            if !proven {
+                return Err(Error::NoFraud);
             }
 
-            // 2. The block is in the best chain
-            if !Self::header_is_canon(&self, header_hash) {
-                // block is not in the current canonical chain, verify tx failed, return false
-                return Ok(false);
-            }
+            let challenger = self.env().caller();
+            let bond = self.bonds.take(optimistic.relayer).unwrap_or_default();
+            let slashed = bond.amount.saturating_add(optimistic.deposit);
+            self.optimistic_headers.remove(header_hash);
+            self.env()
+                .transfer(challenger, slashed)
+                .map_err(|_| Error::PaymentFailed)?;
+            self.deposits = self.deposits.saturating_sub(slashed);
+            self.env().emit_event(FraudProven {
+                block_hash: header_hash,
+                fraud,
+                relayer: optimistic.relayer,
+                challenger,
+                slashed,
+            });
+            Ok(slashed)
+        }
 
-            let header = self.headers.get(header_hash).unwrap();
+        /// Add an optimistic header to the db once its challenge window has passed, as if its
+        /// relayer had submitted it then, except that its PoW is not checked. Its parent must be
+        /// in the db already, so optimistic chains are accepted from their oldest header up.
+        ///
+        /// If the header was submitted with all checks in the meantime, or if its parent was
+        /// pruned as the chain was finalized past it, it is just dropped, and its deposit refunded.
+        #[ink(message)]
+        pub fn accept_header(&mut self, header_hash: HashValue) -> Result<()> {
+            let optimistic = self
+                .optimistic_headers
+                .get(header_hash)
+                .ok_or(Error::NotOptimistic)?;
+            if self.env().block_timestamp() <= optimistic.challenge_deadline {
+                return Err(Error::ChallengeWindowOpen);
+            }
+            let header = optimistic.header;
+            if self.header_is_known(header_hash)
+                || (!self.header_is_known(header.parent) && self.may_be_pruned(header))
+            {
+                self.optimistic_headers.remove(header_hash);
+                self.deposits = self.deposits.saturating_sub(optimistic.deposit);
+                return self
+                    .env()
+                    .transfer(optimistic.relayer, optimistic.deposit)
+                    .map_err(|_| Error::PaymentFailed);
+            }
 
-            // 3. The block's height in the best chain is at least `min_depth` before the tip of the chain.
-            if header.height > self.best_height - min_depth {
-                // block height is not at least min_depth before the tip of the chain, return an error
+            let parent = self.check_new_header(header_hash, header.parent)?;
+            if parent.header.height + 1 != header.height {
                 return Err(Error::IncorrectHeight);
             }
+            let target = self.retarget.next_target(&parent, header.height);
+            let stored = StoredHeader {
+                deposit: optimistic.deposit,
+                ..self.child_of(&parent, header, target)
+            };
+            self.insert_segment(Vec::from([(header_hash, stored)]), optimistic.relayer)?;
+            // The deposit was already held while the header was optimistic.
+            self.deposits = self.deposits.saturating_sub(optimistic.deposit);
+            self.optimistic_headers.remove(header_hash);
+            Ok(())
+        }
 
-            // 4. The merkle proof must be valid
-            if !MerkleProof::check_merkle_proof(tx_hash, p, header.transactions_root) {
-                // invalid merkle proof, verify tx failed, return false
-                return Ok(false);
-            }
+        /// The parent of `header` as stored if it was in the db, where optimistic headers are
+        /// derived from their ancestors as if they were valid. If the parent does not descend
+        /// from the db, the error is the header on the way down whose parent is missing.
+        fn provisional_parent(&self, header: Header) -> core::result::Result<StoredHeader, Header> {
+            let mut child = header;
+            let mut optimistic = Vec::new();
+            let mut stored = loop {
+                if let Some(stored) = self.headers.get(child.parent) {
+                    break stored;
+                }
+                let parent = self
+                    .optimistic_headers
+                    .get(child.parent)
+                    .ok_or(child)?
+                    .header;
+                optimistic.push(parent);
+                child = parent;
+            };
+            for header in optimistic.into_iter().rev() {
+                let target = self.retarget.next_target(&stored, header.height);
+                stored = self.child_of(&stored, header, target);
+            }
+            Ok(stored)
+        }
+
+        /// Whether the parent of `header`, by the height that `header` claims, is at or below the
+        /// finalized height, so that it may have been in the db and pruned since.
+        fn may_be_pruned(&self, header: Header) -> bool {
+            header.height.saturating_sub(1) <= self.finalized_height
+        }
+
+        /// Claim the deposits of pruned headers that could not be refunded at the time.
+        #[ink(message)]
+        pub fn claim_refund(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            let refund = self.refunds.take(caller).ok_or(Error::NoRefund)?;
+            self.env()
+                .transfer(caller, refund)
+                .map_err(|_| Error::PaymentFailed)?;
+            self.deposits = self.deposits.saturating_sub(refund);
+            Ok(())
+        }
+
+        /// Check that a header is not in the db yet, but its parent is, and return the parent.
+        fn check_new_header(
+            &self,
+            header_hash: HashValue,
+            parent_hash: HashValue,
+        ) -> Result<StoredHeader> {
+            // 1. The header must not already be in the db
+            if Self::header_is_known(self, header_hash) {
+                // block hash is already in the db, return an error
+                return Err(Error::HeaderAlreadySubmitted);
+            }
+
+            // 2. The header's parent must already be in the db
+            // parent hash is not in the db, return an error
+            self.headers.get(parent_hash).ok_or(Error::UnknownParent)
+        }
+
+        /// The header to store for `header`, a valid child of `parent` that met `target`.
+        fn child_of(
+            &self,
+            parent: &StoredHeader,
+            header: Header,
+            target: HashValue,
+        ) -> StoredHeader {
+            let window_start = if self.retarget.starts_window(header.height) {
+                header.timestamp
+            } else {
+                parent.window_start
+            };
+            let mut chain_work = [0u8; 32];
+            U256::from_big_endian(&parent.chain_work)
+                .saturating_add(work(target))
+                .to_big_endian(&mut chain_work);
+            StoredHeader {
+                header,
+                target,
+                chain_work,
+                window_start,
+                deposit: self.relay_fee,
+            }
+        }
+
+        /// Store a valid segment of headers, re-org if its chain is now the heaviest, prune the
+        /// headers that are no longer needed, and record the relayer of the segment.
+        fn insert_segment(
+            &mut self,
+            segment: Vec<(HashValue, StoredHeader)>,
+            relayer: AccountId,
+        ) -> Result<()> {
+            let Some((tip_hash, tip)) = segment.last().copied() else {
+                return Ok(());
+            };
+
+            // Check if the new tip has more cumulative work than the tip of the current
+            // canonical chain. Ties go to the chain that was seen first.
+            let best_hash = self.canon_chain.get(self.best_height).unwrap();
+            let reorg = tip.chain_work > self.headers.get(best_hash).unwrap().chain_work;
+            if reorg && !self.descends_from_finalized(segment[0].1.header.parent) {
+                return Err(Error::FinalizedFork);
+            }
+
+            for (header_hash, stored) in &segment {
+                self.headers.insert(header_hash, stored);
+                let mut at_height = self
+                    .headers_at_height
+                    .get(stored.header.height)
+                    .unwrap_or_default();
+                at_height.push(*header_hash);
+                self.headers_at_height
+                    .insert(stored.header.height, &at_height);
+                // Record the relayer as the fee recipient for the new block
+                self.fee_recipient.insert(header_hash, &relayer);
+                self.deposits = self.deposits.saturating_add(stored.deposit);
+                self.env().emit_event(HeaderSubmitted {
+                    block_hash: *header_hash,
+                    block_height: stored.header.height,
+                    submitter: relayer,
+                });
+            }
+
+            if reorg {
+                // Re-org. The new chain may be shorter than the old one, if it is heavier.
+                for height in tip.header.height + 1..=self.best_height {
+                    self.canon_chain.remove(height);
+                }
+                let mut hash = tip_hash;
+                let mut height = tip.header.height;
+                while !Self::header_is_canon(self, hash) {
+                    self.canon_chain.insert(height, &hash);
+                    // update hash and height to the parent's
+                    hash = self.headers.get(hash).unwrap().header.parent;
+                    height -= 1;
+                }
+                // The new chain only extends the old one if the old tip is the common ancestor
+                let depth = self.best_height - height;
+                if depth > 0 {
+                    self.env().emit_event(Reorged {
+                        old_tip: best_hash,
+                        new_tip: tip_hash,
+                        common_ancestor: hash,
+                        depth,
+                    });
+                }
+                // Update best height
+                self.best_height = tip.header.height;
+                self.prune();
+            }
+            Ok(())
+        }
+
+        /// Whether the header with `hash` is in the db, and so are all of its ancestors down to
+        /// the canonical chain. Headers on forks that diverge below the final headers lose their
+        /// ancestors to pruning.
+        fn descends_from_finalized(&self, mut hash: HashValue) -> bool {
+            loop {
+                let Some(stored) = self.headers.get(hash) else {
+                    return false;
+                };
+                if self.canon_chain.get(stored.header.height) == Some(hash) {
+                    return true;
+                }
+                hash = stored.header.parent;
+            }
+        }
+
+        /// Drop all headers more than `finality_depth` below the tip of the canonical chain,
+        /// except the canonical one right at that depth, along with all forks at its height.
+        /// Their deposits are refunded to their relayers.
+        fn prune(&mut self) {
+            let finalized_height = self.best_height.saturating_sub(self.finality_depth);
+            if finalized_height <= self.finalized_height {
+                return;
+            }
+            let finalized_hash = self.canon_chain.get(finalized_height).unwrap();
+            for height in self.finalized_height..=finalized_height {
+                for hash in self.headers_at_height.take(height).unwrap_or_default() {
+                    if hash != finalized_hash {
+                        self.prune_header(hash);
+                    }
+                }
+                if height < finalized_height {
+                    self.canon_chain.remove(height);
+                }
+            }
+            self.headers_at_height
+                .insert(finalized_height, &Vec::from([finalized_hash]));
+            self.finalized_height = finalized_height;
+        }
+
+        fn prune_header(&mut self, hash: HashValue) {
+            let deposit = self.headers.take(hash).map_or(0, |stored| stored.deposit);
+            let Some(relayer) = self.fee_recipient.take(hash) else {
+                return;
+            };
+            if deposit == 0 {
+                return;
+            }
+            if self.env().transfer(relayer, deposit).is_ok() {
+                self.deposits = self.deposits.saturating_sub(deposit);
+            } else {
+                let refund = self.refunds.get(relayer).unwrap_or_default();
+                self.refunds
+                    .insert(relayer, &refund.saturating_add(deposit));
+            }
+        }
+
+        /// Verify that some transaction has occurred on the source chain.
+        ///
+        /// In order for a verification to be successful (to return true), these conditions must be met:
+        /// 0. The verifier must pay the verification fee (which will go to the relayer).
+        /// 1. The block is in the db
+        /// 2. The block is in the best chain
+        /// 3. The block's height in the best chain is at least `min_depth` before the tip of the chain.
+        ///    A min_depth of 0 just means that the header is canon at all.
+        ///    A min_depth of 1 means there is at least one block confirmation afterward.
+        /// 4. The merkle proof must be valid
+        #[ink(message, payable)]
+        pub fn verify_transaction(
+            &mut self,
+            tx_hash: HashValue,
+            header_hash: HashValue,
+            min_depth: u64,
+            p: MerkleProof,
+        ) -> Result<bool> {
+            let verification_fee_payed = self.env().transferred_value();
+
+            // 0. The verifier must pay the verification fee
+            if verification_fee_payed < self.verify_fee {
+                // Not enough verify_fee, return an error
+                return Err(Error::InsufficientVerifyFee);
+            }
+
+            // 1. The block is in the db
+            if !Self::header_is_known(self, header_hash) {
+                // block hash is not in the db, verify tx failed, return false
+                return Ok(false);
+            }
+
+            // 2. The block is in the best chain
+            if !Self::header_is_canon(self, header_hash) {
+                // block is not in the current canonical chain, verify tx failed, return false
+                return Ok(false);
+            }
+
+            let header = self.headers.get(header_hash).unwrap().header;
+
+            // 3. The block's height in the best chain is at least `min_depth` before the tip of the chain.
+            if header.height > self.best_height - min_depth {
+                // block height is not at least min_depth before the tip of the chain, return an error
+                return Err(Error::IncorrectHeight);
+            }
+
+            // 4. The merkle proof must be valid
+            let included = match self.format {
+                HeaderFormat::Scale => {
+                    MerkleProof::check_merkle_proof(tx_hash, &p, header.transactions_root)
+                }
+                HeaderFormat::Bitcoin => {
+                    MerkleProof::check_bitcoin_merkle_proof(tx_hash, &p, header.transactions_root)
+                }
+            };
+            if !included {
+                // invalid merkle proof, verify tx failed, return false
+                return Ok(false);
+            }
 
             // Pay the fee to the relayer
-            if let Ok(_) = self.env().transfer(
-                match self.fee_recipient.get(header_hash) {
-                    Some(x) => x,
-                    None => return Err(Error::PaymentFailed),
-                },
-                verification_fee_payed,
-            ) {
+            if self
+                .env()
+                .transfer(
+                    match self.fee_recipient.get(header_hash) {
+                        Some(x) => x,
+                        None => return Err(Error::PaymentFailed),
+                    },
+                    verification_fee_payed,
+                )
+                .is_ok()
+            {
                 return Ok(true);
             }
 
@@ -341,8 +1350,7 @@ mod spv_bridge {
             min_depth: u64,
             p: MerkleProof,
         ) -> Result<bool> {
-            let mut claim_hash = <Sha2x256 as HashOutput>::Type::default();
-            ink::env::hash_encoded::<Sha2x256, _>(&claim, &mut claim_hash);
+            let claim_hash = Self::hash_claim(claim);
 
             let verification_fee_payed = self.env().transferred_value();
 
@@ -353,18 +1361,18 @@ mod spv_bridge {
             }
 
             // 1. The block is in the db
-            if !Self::header_is_known(&self, block_hash) {
+            if !Self::header_is_known(self, block_hash) {
                 // block hash is not in the db, verify state failed, return false
                 return Ok(false);
             }
 
             // 2. The block is in the best chain
-            if !Self::header_is_canon(&self, block_hash) {
+            if !Self::header_is_canon(self, block_hash) {
                 // block is not in the current canonical chain, verify state failed, return false
                 return Ok(false);
             }
 
-            let header = self.headers.get(block_hash).unwrap();
+            let header = self.headers.get(block_hash).unwrap().header;
             // 3. The block's height in the best chain is at least `min_depth` before the tip of the chain.
             if header.height > self.best_height - min_depth {
                 // block height is not at least min_depth before the tip of the chain, return an error
@@ -372,19 +1380,23 @@ mod spv_bridge {
             }
 
             // 4. The merkle proof must be valid
-            if !MerkleProof::check_merkle_proof(claim_hash, p, header.transactions_root) {
+            if !MerkleProof::check_merkle_proof(claim_hash, &p, header.storage_root) {
                 // invalid merkle proof, verify state failed, return false
                 return Ok(false);
             }
 
             // Pay the fee to the relayer
-            if let Ok(_) = self.env().transfer(
-                match self.fee_recipient.get(block_hash) {
-                    Some(x) => x,
-                    None => return Err(Error::PaymentFailed),
-                },
-                verification_fee_payed,
-            ) {
+            if self
+                .env()
+                .transfer(
+                    match self.fee_recipient.get(block_hash) {
+                        Some(x) => x,
+                        None => return Err(Error::PaymentFailed),
+                    },
+                    verification_fee_payed,
+                )
+                .is_ok()
+            {
                 return Ok(true);
             }
 
@@ -403,23 +1415,185 @@ mod spv_bridge {
             hash_value
         }
 
+        /// Helper function to hash a state claim, as it appears in the leaves of a storage tree.
+        pub fn hash_claim(claim: StateClaim) -> HashValue {
+            let mut hash_value = <Sha2x256 as HashOutput>::Type::default();
+            ink::env::hash_encoded::<Sha2x256, _>(&claim, &mut hash_value);
+            hash_value
+        }
+
         /// A helper function to detect whether a header exists in the storage
         pub fn header_is_known(&self, header_hash: HashValue) -> bool {
+            self.headers.contains(header_hash)
+        }
+
+        /// A helper function to determine whether a header is in the canon chain.
+        /// Unknown headers are not.
+        pub fn header_is_canon(&self, header_hash: HashValue) -> bool {
             match self.headers.get(header_hash) {
-                Some(_) => true,
+                Some(stored) => self.canon_chain.get(stored.header.height) == Some(header_hash),
                 None => false,
             }
         }
 
-        /// A helper function to determine whether a header is in the canon chain
-        pub fn header_is_canon(&self, header_hash: HashValue) -> bool {
-            match self
-                .canon_chain
-                .get(self.headers.get(header_hash).unwrap().height)
-            {
-                Some(canon_hash) => canon_hash == header_hash,
-                None => false,
+        /// The hash and header of the tip of the canonical chain.
+        #[ink(message)]
+        pub fn best_header(&self) -> (HashValue, Header) {
+            let best_hash = self.canon_chain.get(self.best_height).unwrap();
+            (best_hash, self.headers.get(best_hash).unwrap().header)
+        }
+
+        /// The hash of the canonical header at `height`, if there is one in the db.
+        #[ink(message)]
+        pub fn canonical_hash_at(&self, height: u64) -> Option<HashValue> {
+            self.canon_chain.get(height)
+        }
+
+        /// A header in the db, canonical or not, along with its difficulty threshold, the work of
+        /// its chain and its relayer's deposit.
+        #[ink(message)]
+        pub fn header(&self, header_hash: HashValue) -> Option<StoredHeader> {
+            self.headers.get(header_hash)
+        }
+
+        /// How many canonical headers are on top of a canonical header, which is the greatest
+        /// `min_depth` it can be verified with. `None` if the header is not canonical.
+        #[ink(message)]
+        pub fn confirmations(&self, header_hash: HashValue) -> Option<u64> {
+            let height = self.headers.get(header_hash)?.header.height;
+            (self.canon_chain.get(height) == Some(header_hash)).then(|| self.best_height - height)
+        }
+
+        /// The relay fee and the verification fee.
+        #[ink(message)]
+        pub fn get_fees(&self) -> (Balance, Balance) {
+            (self.relay_fee, self.verify_fee)
+        }
+
+        /// The optimistic header with this hash, if it is not accepted nor dropped yet.
+        #[ink(message)]
+        pub fn optimistic_header(&self, header_hash: HashValue) -> Option<OptimisticHeader> {
+            self.optimistic_headers.get(header_hash)
+        }
+
+        /// The rule for optimistic headers, if they are enabled.
+        #[ink(message)]
+        pub fn optimistic_rule(&self) -> Option<OptimisticRule> {
+            self.optimistic.get().flatten()
+        }
+
+        /// The bond that `relayer` posted.
+        #[ink(message)]
+        pub fn bond_of(&self, relayer: AccountId) -> Bond {
+            self.bonds.get(relayer).unwrap_or_default()
+        }
+
+        /// The account that can update the parameters of the bridge.
+        #[ink(message)]
+        pub fn owner(&self) -> AccountId {
+            self.owner
+        }
+
+        /// The parameter update waiting for its timelock to pass, if any.
+        #[ink(message)]
+        pub fn pending_update(&self) -> Option<PendingUpdate> {
+            self.pending_update
+        }
+
+        /// Propose a parameter update, which can be executed once the timelock has passed, and
+        /// return the time from which it can be. Only one update can be pending at a time.
+        #[ink(message)]
+        pub fn propose_update(&mut self, update: ParameterUpdate) -> Result<Timestamp> {
+            self.ensure_owner()?;
+            if self.pending_update.is_some() {
+                return Err(Error::UpdatePending);
+            }
+            let eta = self.env().block_timestamp().saturating_add(self.timelock);
+            self.pending_update = Some(PendingUpdate { update, eta });
+            self.env().emit_event(UpdateProposed { update, eta });
+            Ok(eta)
+        }
+
+        /// Execute the pending parameter update, once its timelock has passed.
+        #[ink(message)]
+        pub fn execute_update(&mut self) -> Result<()> {
+            self.ensure_owner()?;
+            let PendingUpdate { update, eta } =
+                self.pending_update.ok_or(Error::NoPendingUpdate)?;
+            if self.env().block_timestamp() < eta {
+                return Err(Error::TimelockNotExpired);
+            }
+            self.pending_update = None;
+            match update {
+                ParameterUpdate::Fees {
+                    relay_fee,
+                    verify_fee,
+                } => {
+                    self.relay_fee = relay_fee;
+                    self.verify_fee = verify_fee;
+                    self.env().emit_event(FeeUpdated {
+                        relay_fee,
+                        verify_fee,
+                    });
+                }
+                ParameterUpdate::Retarget(rule) => self.retarget = rule,
+                ParameterUpdate::FinalityDepth(depth) => {
+                    self.finality_depth = depth;
+                    self.prune();
+                }
+                ParameterUpdate::Timelock(timelock) => self.timelock = timelock,
+                ParameterUpdate::Owner(owner) => self.owner = owner,
+                ParameterUpdate::Code(code_hash) => self
+                    .env()
+                    .set_code_hash(&code_hash)
+                    .map_err(|_| Error::CodeUpgradeFailed)?,
+                ParameterUpdate::Optimistic(rule) => self.optimistic.set(&rule),
+            }
+            self.env().emit_event(UpdateExecuted { update });
+            Ok(())
+        }
+
+        /// Cancel the pending parameter update.
+        #[ink(message)]
+        pub fn cancel_update(&mut self) -> Result<()> {
+            self.ensure_owner()?;
+            let PendingUpdate { update, .. } =
+                self.pending_update.take().ok_or(Error::NoPendingUpdate)?;
+            self.env().emit_event(UpdateCancelled { update });
+            Ok(())
+        }
+
+        /// The balance of the contract that it does not hold as deposits, nor needs to stay alive.
+        /// It comes from fees paid above what was due, and from direct transfers.
+        #[ink(message)]
+        pub fn surplus(&self) -> Balance {
+            self.env()
+                .balance()
+                .saturating_sub(self.deposits)
+                .saturating_sub(self.env().minimum_balance())
+        }
+
+        /// Withdraw the surplus of the contract to `to`, e.g. a treasury or a relayer reward pool,
+        /// and return how much was withdrawn. Deposits stay in the contract until they are refunded.
+        #[ink(message)]
+        pub fn withdraw_surplus(&mut self, to: AccountId) -> Result<Balance> {
+            self.ensure_owner()?;
+            let amount = self.surplus();
+            if amount == 0 {
+                return Err(Error::NothingToWithdraw);
             }
+            self.env()
+                .transfer(to, amount)
+                .map_err(|_| Error::PaymentFailed)?;
+            self.env().emit_event(SurplusWithdrawn { to, amount });
+            Ok(amount)
+        }
+
+        fn ensure_owner(&self) -> Result<()> {
+            if self.env().caller() != self.owner {
+                return Err(Error::NotOwner);
+            }
+            Ok(())
         }
 
         /// This function is not graded. It is just for collecting feedback.
@@ -445,6 +1619,10 @@ mod spv_bridge {
         ];
         const RELAY_FEE: u128 = 1_000;
         const VERIFY_FEE: u128 = 100;
+        const GENESIS_TIME: u64 = 1_000_000;
+        const BLOCK_TIME: u64 = 600;
+        // Deep enough that no test chain is pruned, unless it deploys with another depth.
+        const FINALITY_DEPTH: u64 = 100;
 
         use super::*;
 
@@ -456,41 +1634,160 @@ mod spv_bridge {
             ink::env::test::set_caller::<Environment>(caller);
         }
 
+        /// Deploy the contract at its own account, rather than Alice's, so that the balances of
+        /// relayers show what the contract pays them.
+        fn set_contract_account() {
+            ink::env::test::set_callee::<Environment>(AccountId::from([0xc0; 32]));
+        }
+
         fn make_child(parent: Header) -> Header {
-            make_child_with_transactions_root(parent, 0)
+            make_child_with_transactions_root(parent, [0; 32])
         }
 
-        fn make_child_with_transactions_root(parent: Header, tx_root: u64) -> Header {
-            let mut child = Header {
+        fn make_child_with_transactions_root(parent: Header, tx_root: HashValue) -> Header {
+            let child = Header {
                 height: parent.height + 1,
                 parent: SpvBridge::hash_header(parent),
-                storage_root: 0,
+                timestamp: parent.timestamp + BLOCK_TIME,
+                storage_root: [0; 32],
                 transactions_root: tx_root,
                 pow_nonce: 1,
             };
+            mine(child, THRESHOLD)
+        }
+
+        /// A child of `parent` produced `block_time` seconds after it, that meets `target`.
+        fn make_child_after(parent: Header, block_time: u64, target: HashValue) -> Header {
+            let child = Header {
+                timestamp: parent.timestamp + block_time,
+                ..make_child(parent)
+            };
+            mine(child, target)
+        }
 
-            while SpvBridge::hash_header(child) >= THRESHOLD {
-                child.pow_nonce = child.pow_nonce + 1;
+        fn mine(mut header: Header, target: HashValue) -> Header {
+            while SpvBridge::hash_header(header) >= target {
+                header.pow_nonce += 1;
             }
+            header
+        }
+
+        /// A target made of the given leading bytes, followed by zeros.
+        fn target(prefix: &[u8]) -> HashValue {
+            let mut target = [0; 32];
+            target[..prefix.len()].copy_from_slice(prefix);
+            target
+        }
+
+        /// The hashes of the transactions of the genesis block.
+        fn transactions() -> Vec<HashValue> {
+            (1..=5).map(|i| [i; 32]).collect()
+        }
+
+        /// The claims that hold in the state of the genesis block.
+        fn state() -> Vec<StateClaim> {
+            (1..=5)
+                .map(|key| StateClaim {
+                    key,
+                    value: key * 10,
+                })
+                .collect()
+        }
+
+        fn state_leaves() -> Vec<HashValue> {
+            state().into_iter().map(SpvBridge::hash_claim).collect()
+        }
+
+        /// Build the Merkle tree of `leaves`, and return its root along with a proof of the leaf
+        /// at `index`. A layer with an odd number of nodes has its last node paired with itself.
+        fn merkle_root_and_proof(leaves: &[HashValue], index: usize) -> (HashValue, MerkleProof) {
+            merkle_tree(leaves, index, MerkleProof::hash_pair)
+        }
+
+        fn bitcoin_merkle_root_and_proof(
+            txids: &[HashValue],
+            index: usize,
+        ) -> (HashValue, MerkleProof) {
+            merkle_tree(txids, index, bitcoin::hash_pair)
+        }
 
-            child
+        fn merkle_tree(
+            leaves: &[HashValue],
+            index: usize,
+            hash_pair: fn(&HashValue, &HashValue) -> HashValue,
+        ) -> (HashValue, MerkleProof) {
+            let mut layer = leaves.to_vec();
+            let mut index = index;
+            let mut siblings = Vec::new();
+            while layer.len() > 1 {
+                if layer.len() % 2 == 1 {
+                    layer.push(layer[layer.len() - 1]);
+                }
+                siblings.push(if index.is_multiple_of(2) {
+                    SiblingNode::Right(layer[index + 1])
+                } else {
+                    SiblingNode::Left(layer[index - 1])
+                });
+                layer = layer
+                    .chunks(2)
+                    .map(|pair| hash_pair(&pair[0], &pair[1]))
+                    .collect();
+                index /= 2;
+            }
+            (layer[0], MerkleProof::new(siblings))
         }
 
         pub fn deploy_bridge(deployer: AccountId) -> (SpvBridge, Header) {
+            deploy_bridge_with_rule(deployer, RetargetRule::bitcoin(THRESHOLD), FINALITY_DEPTH)
+        }
+
+        fn deploy_pruning_bridge(deployer: AccountId, finality_depth: u64) -> (SpvBridge, Header) {
+            deploy_bridge_with_rule(deployer, RetargetRule::bitcoin(THRESHOLD), finality_depth)
+        }
+
+        /// Deploy the bridge with a rule that retargets every 4 blocks of 10 seconds, by at most a
+        /// factor of 4, starting from a genesis at height 100 that starts a window.
+        fn deploy_fast_retargeting_bridge(deployer: AccountId) -> (SpvBridge, Header) {
+            deploy_bridge_with_rule(
+                deployer,
+                RetargetRule {
+                    window: 4,
+                    block_time: 10,
+                    max_adjustment: 4,
+                    max_target: THRESHOLD,
+                },
+                FINALITY_DEPTH,
+            )
+        }
+
+        fn deploy_bridge_with_rule(
+            deployer: AccountId,
+            rule: RetargetRule,
+            finality_depth: u64,
+        ) -> (SpvBridge, Header) {
             set_next_caller(deployer);
+            set_contract_account();
 
             let source_genesis_header = Header {
                 height: 100,
                 parent: [0; 32],
-                storage_root: 0,
-                transactions_root: 0,
+                timestamp: GENESIS_TIME,
+                storage_root: merkle_root_and_proof(&state_leaves(), 0).0,
+                transactions_root: merkle_root_and_proof(&transactions(), 0).0,
                 // The initial block is not checked; not even its pow seal;
                 // We put a non-zero nonce here to make sure this block
                 // isn't the default block.
                 pow_nonce: 1,
             };
-            let spv_bridge =
-                SpvBridge::new(source_genesis_header, THRESHOLD, RELAY_FEE, VERIFY_FEE);
+            let spv_bridge = SpvBridge::new(
+                source_genesis_header,
+                THRESHOLD,
+                GENESIS_TIME,
+                rule,
+                finality_depth,
+                RELAY_FEE,
+                VERIFY_FEE,
+            );
 
             (spv_bridge, source_genesis_header)
         }
@@ -564,7 +1861,7 @@ mod spv_bridge {
             //  \
             //   --C
 
-            let c_header = make_child_with_transactions_root(genesis_header, 1);
+            let c_header = make_child_with_transactions_root(genesis_header, [1; 32]);
             let c_hash = SpvBridge::hash_header(c_header);
             let relay_response =
                 ink::env::pay_with_call!(bridge.submit_new_header(c_header), RELAY_FEE);
@@ -615,9 +1912,9 @@ mod spv_bridge {
             //  \
             //   --C---D
 
-            let c_header = make_child_with_transactions_root(genesis_header, 1);
+            let c_header = make_child_with_transactions_root(genesis_header, [1; 32]);
             let c_hash = SpvBridge::hash_header(c_header);
-            let d_header = make_child_with_transactions_root(c_header, 1);
+            let d_header = make_child_with_transactions_root(c_header, [1; 32]);
             let d_hash = SpvBridge::hash_header(d_header);
             let relay_response =
                 ink::env::pay_with_call!(bridge.submit_new_header(c_header), RELAY_FEE);
@@ -664,14 +1961,10 @@ mod spv_bridge {
                 ink::env::pay_with_call!(bridge.submit_new_header(a_header), RELAY_FEE);
             assert_eq!(relay_response, Ok(()));
 
-            debug_assert_eq!(
+            let (_, proof) = merkle_root_and_proof(&transactions(), 3);
+            assert_eq!(
                 ink::env::pay_with_call!(
-                    bridge.verify_transaction(
-                        [0u8; 32],
-                        genesis_hash,
-                        0,
-                        MerkleProof { verifies: true }
-                    ),
+                    bridge.verify_transaction(transactions()[3], genesis_hash, 0, proof),
                     VERIFY_FEE
                 ),
                 Ok(true)
@@ -693,14 +1986,11 @@ mod spv_bridge {
                 ink::env::pay_with_call!(bridge.submit_new_header(a_header), RELAY_FEE);
             assert_eq!(relay_response, Ok(()));
 
+            // The proof is genuine, but for another transaction than the one claimed.
+            let (_, proof) = merkle_root_and_proof(&transactions(), 3);
             assert_eq!(
                 ink::env::pay_with_call!(
-                    bridge.verify_transaction(
-                        [0u8; 32],
-                        genesis_hash,
-                        0,
-                        MerkleProof { verifies: false }
-                    ),
+                    bridge.verify_transaction([0u8; 32], genesis_hash, 0, proof),
                     VERIFY_FEE
                 ),
                 Ok(false)
@@ -725,10 +2015,10 @@ mod spv_bridge {
             let a_header = make_child(genesis_header);
             let b_header = make_child(a_header);
             let f_header = make_child(b_header);
-            let c_header = make_child_with_transactions_root(genesis_header, 1);
-            let d_header = make_child_with_transactions_root(c_header, 1);
-            let e_header = make_child_with_transactions_root(d_header, 1);
-            let h_header = make_child_with_transactions_root(e_header, 1);
+            let c_header = make_child_with_transactions_root(genesis_header, [1; 32]);
+            let d_header = make_child_with_transactions_root(c_header, [1; 32]);
+            let e_header = make_child_with_transactions_root(d_header, [1; 32]);
+            let h_header = make_child_with_transactions_root(e_header, [1; 32]);
 
             assert_eq!(
                 ink::env::pay_with_call!(bridge.submit_new_header(a_header), RELAY_FEE),
@@ -854,7 +2144,7 @@ mod spv_bridge {
             let (mut bridge, genesis_header) = deploy_bridge(default_accounts.alice);
             let a_header = make_child(genesis_header);
             let b_header = make_child(a_header);
-            let c_header = make_child_with_transactions_root(genesis_header, 1);
+            let c_header = make_child_with_transactions_root(genesis_header, [1; 32]);
 
             assert_eq!(
                 ink::env::pay_with_call!(bridge.submit_new_header(a_header), RELAY_FEE),
@@ -877,10 +2167,10 @@ mod spv_bridge {
             assert_eq!(
                 ink::env::pay_with_call!(
                     bridge.verify_transaction(
-                        [0u8; 32],
+                        transactions()[0],
                         SpvBridge::hash_header(a_header),
                         2,
-                        MerkleProof { verifies: true }
+                        merkle_root_and_proof(&transactions(), 0).1
                     ),
                     VERIFY_FEE
                 ),
@@ -901,7 +2191,7 @@ mod spv_bridge {
             let (mut bridge, genesis_header) = deploy_bridge(default_accounts.alice);
             let a_header = make_child(genesis_header);
             let b_header = make_child(a_header);
-            let c_header = make_child_with_transactions_root(genesis_header, 1);
+            let c_header = make_child_with_transactions_root(genesis_header, [1; 32]);
 
             assert_eq!(
                 ink::env::pay_with_call!(bridge.submit_new_header(a_header), RELAY_FEE),
@@ -921,15 +2211,13 @@ mod spv_bridge {
                 Some(SpvBridge::hash_header(a_header))
             );
 
-            let claim = StateClaim { key: 0, value: 0 };
-
             assert_eq!(
                 ink::env::pay_with_call!(
                     bridge.verify_state(
-                        claim,
+                        state()[0],
                         SpvBridge::hash_header(a_header),
                         2,
-                        MerkleProof { verifies: true }
+                        merkle_root_and_proof(&state_leaves(), 0).1
                     ),
                     VERIFY_FEE
                 ),
@@ -950,7 +2238,7 @@ mod spv_bridge {
             let (mut bridge, genesis_header) = deploy_bridge(default_accounts.alice);
             let a_header = make_child(genesis_header);
             let b_header = make_child(a_header);
-            let c_header = make_child_with_transactions_root(genesis_header, 1);
+            let c_header = make_child_with_transactions_root(genesis_header, [1; 32]);
 
             assert_eq!(
                 ink::env::pay_with_call!(bridge.submit_new_header(a_header), RELAY_FEE),
@@ -970,13 +2258,14 @@ mod spv_bridge {
                 Some(SpvBridge::hash_header(a_header))
             );
 
+            // The proof is genuine, but for the transactions of the genesis block rather than A.
             assert_eq!(
                 ink::env::pay_with_call!(
                     bridge.verify_transaction(
-                        [0u8; 32],
+                        transactions()[0],
                         SpvBridge::hash_header(a_header),
                         0,
-                        MerkleProof { verifies: false }
+                        merkle_root_and_proof(&transactions(), 0).1
                     ),
                     VERIFY_FEE
                 ),
@@ -997,7 +2286,7 @@ mod spv_bridge {
             let (mut bridge, genesis_header) = deploy_bridge(default_accounts.alice);
             let a_header = make_child(genesis_header);
             let b_header = make_child(a_header);
-            let c_header = make_child_with_transactions_root(genesis_header, 1);
+            let c_header = make_child_with_transactions_root(genesis_header, [1; 32]);
 
             assert_eq!(
                 ink::env::pay_with_call!(bridge.submit_new_header(a_header), RELAY_FEE),
@@ -1017,15 +2306,14 @@ mod spv_bridge {
                 Some(SpvBridge::hash_header(a_header))
             );
 
-            let claim = StateClaim { key: 0, value: 0 };
-
+            // The proof is genuine, but for the state of the genesis block rather than A.
             assert_eq!(
                 ink::env::pay_with_call!(
                     bridge.verify_state(
-                        claim,
+                        state()[0],
                         SpvBridge::hash_header(a_header),
                         0,
-                        MerkleProof { verifies: false }
+                        merkle_root_and_proof(&state_leaves(), 0).1
                     ),
                     VERIFY_FEE
                 ),
@@ -1049,11 +2337,10 @@ mod spv_bridge {
                 ink::env::pay_with_call!(bridge.submit_new_header(a_header), RELAY_FEE);
             assert_eq!(relay_response, Ok(()));
 
-            let claim = StateClaim { key: 0, value: 0 };
-
+            let (_, proof) = merkle_root_and_proof(&state_leaves(), 2);
             assert_eq!(
                 ink::env::pay_with_call!(
-                    bridge.verify_state(claim, genesis_hash, 0, MerkleProof { verifies: true }),
+                    bridge.verify_state(state()[2], genesis_hash, 0, proof),
                     VERIFY_FEE
                 ),
                 Ok(true)
@@ -1075,15 +2362,1352 @@ mod spv_bridge {
                 ink::env::pay_with_call!(bridge.submit_new_header(a_header), RELAY_FEE);
             assert_eq!(relay_response, Ok(()));
 
-            let claim = StateClaim { key: 0, value: 0 };
+            // The key is in the state, but holds another value.
+            let claim = StateClaim { key: 3, value: 31 };
+            let (_, proof) = merkle_root_and_proof(&state_leaves(), 2);
+            assert_eq!(
+                ink::env::pay_with_call!(
+                    bridge.verify_state(claim, genesis_hash, 0, proof),
+                    VERIFY_FEE
+                ),
+                Ok(false)
+            );
+        }
+
+        #[ink::test]
+        fn test_state_verification_checks_storage_root() {
+            // G---C, where C's transactions root happens to be the root of G's storage.
+            let default_accounts = default_accounts();
+            set_next_caller(default_accounts.alice);
+
+            let (mut bridge, genesis_header) = deploy_bridge(default_accounts.alice);
+            let (storage_root, proof) = merkle_root_and_proof(&state_leaves(), 1);
+            let c_header = make_child_with_transactions_root(genesis_header, storage_root);
+            let c_hash = SpvBridge::hash_header(c_header);
+
+            let relay_response =
+                ink::env::pay_with_call!(bridge.submit_new_header(c_header), RELAY_FEE);
+            assert_eq!(relay_response, Ok(()));
 
             assert_eq!(
                 ink::env::pay_with_call!(
-                    bridge.verify_state(claim, genesis_hash, 0, MerkleProof { verifies: false }),
+                    bridge.verify_state(state()[1], c_hash, 0, proof),
                     VERIFY_FEE
                 ),
                 Ok(false)
             );
         }
+
+        #[ink::test]
+        fn test_merkle_proofs() {
+            // A tree of a single leaf is just that leaf, with an empty proof.
+            let (root, proof) = merkle_root_and_proof(&transactions()[..1], 0);
+            assert_eq!(root, transactions()[0]);
+            assert!(MerkleProof::check_merkle_proof(
+                transactions()[0],
+                &proof,
+                root
+            ));
+
+            // Every leaf of a tree with an odd number of leaves, including the last one which is
+            // paired with itself.
+            for (index, leaf) in transactions().into_iter().enumerate() {
+                let (root, proof) = merkle_root_and_proof(&transactions(), index);
+                assert!(MerkleProof::check_merkle_proof(leaf, &proof, root));
+                assert!(!MerkleProof::check_merkle_proof([0; 32], &proof, root));
+            }
+
+            // A sibling on the wrong side gives another root.
+            let (root, proof) = merkle_root_and_proof(&transactions(), 0);
+            let mut flipped = proof.clone();
+            flipped.siblings[0] = match flipped.siblings[0] {
+                SiblingNode::Left(hash) => SiblingNode::Right(hash),
+                SiblingNode::Right(hash) => SiblingNode::Left(hash),
+            };
+            assert!(MerkleProof::check_merkle_proof(
+                transactions()[0],
+                &proof,
+                root
+            ));
+            assert!(!MerkleProof::check_merkle_proof(
+                transactions()[0],
+                &flipped,
+                root
+            ));
+        }
+
+        #[ink::test]
+        fn test_difficulty_retargets() {
+            // G---A---B---C---D---E, each 10 seconds after its parent.
+            let default_accounts = default_accounts();
+            let (mut bridge, genesis_header) =
+                deploy_fast_retargeting_bridge(default_accounts.alice);
+            let a_header = make_child_after(genesis_header, 10, THRESHOLD);
+            let b_header = make_child_after(a_header, 10, THRESHOLD);
+            let c_header = make_child_after(b_header, 10, THRESHOLD);
+            for header in [a_header, b_header, c_header] {
+                assert_eq!(
+                    ink::env::pay_with_call!(bridge.submit_new_header(header), RELAY_FEE),
+                    Ok(())
+                );
+            }
+
+            // D starts a new window. The last one took 30 seconds from G to C rather than 40, so
+            // the threshold goes down to 3/4 of what it was.
+            let new_target = target(&[47, 64]);
+            let mut d_header = make_child_after(c_header, 10, THRESHOLD);
+            while SpvBridge::hash_header(d_header) < new_target {
+                d_header.pow_nonce += 1;
+                d_header = mine(d_header, THRESHOLD);
+            }
+            assert_eq!(
+                ink::env::pay_with_call!(bridge.submit_new_header(d_header), RELAY_FEE),
+                Err(Error::PoWThresholdNotMet)
+            );
+
+            let d_header = mine(d_header, new_target);
+            assert_eq!(
+                ink::env::pay_with_call!(bridge.submit_new_header(d_header), RELAY_FEE),
+                Ok(())
+            );
+            let e_header = make_child_after(d_header, 10, new_target);
+            assert_eq!(
+                ink::env::pay_with_call!(bridge.submit_new_header(e_header), RELAY_FEE),
+                Ok(())
+            );
+
+            for (header, expected_target) in [(d_header, new_target), (e_header, new_target)] {
+                let stored = bridge.headers.get(SpvBridge::hash_header(header)).unwrap();
+                assert_eq!(stored.target, expected_target);
+                assert_eq!(stored.window_start, d_header.timestamp);
+            }
+        }
+
+        #[ink::test]
+        fn test_difficulty_adjustment_is_clamped() {
+            // G---A---B---C---D, one second apart
+            //  \
+            //   --E---F---H---I, a thousand seconds apart
+            let default_accounts = default_accounts();
+            let (mut bridge, genesis_header) =
+                deploy_fast_retargeting_bridge(default_accounts.alice);
+
+            let a_header = make_child_after(genesis_header, 1, THRESHOLD);
+            let b_header = make_child_after(a_header, 1, THRESHOLD);
+            let c_header = make_child_after(b_header, 1, THRESHOLD);
+            // 3 seconds rather than 40, but the threshold only goes down by a factor of 4.
+            let d_header = make_child_after(c_header, 1, target(&[15, 192]));
+
+            let e_header = make_child_after(genesis_header, 1_000, THRESHOLD);
+            let f_header = make_child_after(e_header, 1_000, THRESHOLD);
+            let h_header = make_child_after(f_header, 1_000, THRESHOLD);
+            // 3000 seconds rather than 40, but the threshold never goes above the maximum.
+            let i_header = make_child_after(h_header, 1_000, THRESHOLD);
+
+            for header in [
+                a_header, b_header, c_header, d_header, e_header, f_header, h_header, i_header,
+            ] {
+                assert_eq!(
+                    ink::env::pay_with_call!(bridge.submit_new_header(header), RELAY_FEE),
+                    Ok(())
+                );
+            }
+            let target_of = |header| {
+                bridge
+                    .headers
+                    .get(SpvBridge::hash_header(header))
+                    .unwrap()
+                    .target
+            };
+            assert_eq!(target_of(d_header), target(&[15, 192]));
+            assert_eq!(target_of(i_header), THRESHOLD);
+        }
+
+        #[ink::test]
+        fn test_shorter_heavier_fork_wins() {
+            // A slow chain that never gets harder, and a fast one that gets 4 times harder at 104.
+            // G---A1--A2--A3--A4--A5--A6--A7--A8
+            //  \
+            //   --B1--B2--B3--B4--B5
+            let default_accounts = default_accounts();
+            let (mut bridge, genesis_header) =
+                deploy_fast_retargeting_bridge(default_accounts.alice);
+
+            let mut slow_chain = Vec::new();
+            let mut parent = genesis_header;
+            for _ in 0..8 {
+                parent = make_child_after(parent, 40, THRESHOLD);
+                slow_chain.push(parent);
+            }
+            let mut fast_chain = Vec::new();
+            let mut parent = genesis_header;
+            for height in 101..=105 {
+                let target = if height < 104 {
+                    THRESHOLD
+                } else {
+                    target(&[15, 192])
+                };
+                parent = make_child_after(parent, 1, target);
+                fast_chain.push(parent);
+            }
+            for header in slow_chain.iter().chain(&fast_chain[..4]) {
+                assert_eq!(
+                    ink::env::pay_with_call!(bridge.submit_new_header(*header), RELAY_FEE),
+                    Ok(())
+                );
+            }
+
+            // B1 to B4 are worth 4 + 4 + 4 + 16 hashes, which is less than the 8 * 4 of A1 to A8.
+            assert_eq!(bridge.best_height, 108);
+            assert_eq!(
+                bridge.canon_chain.get(104),
+                Some(SpvBridge::hash_header(slow_chain[3]))
+            );
+
+            // With B5, the fast chain is worth 44 hashes, and becomes canon despite being shorter.
+            assert_eq!(
+                ink::env::pay_with_call!(bridge.submit_new_header(fast_chain[4]), RELAY_FEE),
+                Ok(())
+            );
+            assert_eq!(bridge.best_height, 105);
+            for header in &fast_chain {
+                assert_eq!(
+                    bridge.canon_chain.get(header.height),
+                    Some(SpvBridge::hash_header(*header))
+                );
+            }
+            for height in 106..=108 {
+                assert_eq!(bridge.canon_chain.get(height), None);
+            }
+            assert!(!bridge.header_is_canon(SpvBridge::hash_header(slow_chain[7])));
+        }
+
+        fn from_hex<const N: usize>(hex: &str) -> [u8; N] {
+            let mut bytes = [0u8; N];
+            assert_eq!(hex.len(), 2 * N);
+            for (i, byte) in bytes.iter_mut().enumerate() {
+                *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+            }
+            bytes
+        }
+
+        /// The raw header of the mainnet block at `height`, from the fixtures.
+        fn bitcoin_header(height: u64) -> [u8; 80] {
+            include_str!("fixtures/bitcoin_mainnet_headers.txt")
+                .lines()
+                .filter(|line| !line.starts_with('#'))
+                .find_map(|line| {
+                    let (h, header) = line.split_once(' ')?;
+                    (h.parse() == Ok(height)).then(|| from_hex(header))
+                })
+                .expect("no fixture for this height")
+        }
+
+        fn block_170_txids() -> Vec<HashValue> {
+            include_str!("fixtures/bitcoin_mainnet_block_170_txids.txt")
+                .lines()
+                .filter(|line| !line.starts_with('#'))
+                .map(from_hex)
+                .collect()
+        }
+
+        /// Deploy a Bitcoin bridge, with the mainnet block at `height` as its checkpoint.
+        fn deploy_bitcoin_bridge(deployer: AccountId, height: u64) -> SpvBridge {
+            set_next_caller(deployer);
+            set_contract_account();
+            let checkpoint = bitcoin_header(height);
+            let window_start = BitcoinHeader::parse(&checkpoint).time.into();
+            SpvBridge::new_bitcoin(
+                checkpoint,
+                height,
+                window_start,
+                FINALITY_DEPTH,
+                RELAY_FEE,
+                VERIFY_FEE,
+            )
+        }
+
+        #[ink::test]
+        fn test_parse_bitcoin_genesis_header() {
+            let raw = bitcoin_header(0);
+            let header = BitcoinHeader::parse(&raw);
+            assert_eq!(header.version, 1);
+            assert_eq!(header.prev_hash, [0; 32]);
+            assert_eq!(
+                header.merkle_root,
+                from_hex("4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b")
+            );
+            assert_eq!(header.time, 1_231_006_505);
+            assert_eq!(header.bits, 0x1d00ffff);
+            assert_eq!(header.nonce, 2_083_236_893);
+            assert_eq!(
+                BitcoinHeader::hash(&raw),
+                from_hex::<32>("000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f")
+            );
+            assert_eq!(bitcoin::expand_bits(header.bits), Some(bitcoin::POW_LIMIT));
+        }
+
+        #[ink::test]
+        fn test_submit_bitcoin_headers() {
+            let default_accounts = default_accounts();
+            let mut bridge = deploy_bitcoin_bridge(default_accounts.alice, 0);
+
+            for height in 1..=2 {
+                assert_eq!(
+                    ink::env::pay_with_call!(
+                        bridge.submit_bitcoin_header(bitcoin_header(height)),
+                        RELAY_FEE
+                    ),
+                    Ok(())
+                );
+            }
+            assert_eq!(bridge.best_height, 2);
+            assert_eq!(
+                bridge.canon_chain.get(1),
+                Some(from_hex(
+                    "00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048"
+                ))
+            );
+            assert_eq!(
+                bridge.canon_chain.get(2),
+                Some(from_hex(
+                    "000000006a625f06636b8bb6ac7b960a8d03705d1ace08b1a19da3fdcc99ddbd"
+                ))
+            );
+
+            assert_eq!(
+                ink::env::pay_with_call!(
+                    bridge.submit_bitcoin_header(bitcoin_header(2)),
+                    RELAY_FEE
+                ),
+                Err(Error::HeaderAlreadySubmitted)
+            );
+            assert_eq!(
+                ink::env::pay_with_call!(
+                    bridge.submit_bitcoin_header(bitcoin_header(170)),
+                    RELAY_FEE
+                ),
+                Err(Error::UnknownParent)
+            );
+            assert_eq!(
+                ink::env::pay_with_call!(
+                    bridge.submit_new_header(make_child(Header {
+                        height: 2,
+                        parent: [0; 32],
+                        timestamp: 0,
+                        storage_root: [0; 32],
+                        transactions_root: [0; 32],
+                        pow_nonce: 0,
+                    })),
+                    RELAY_FEE
+                ),
+                Err(Error::WrongHeaderFormat)
+            );
+        }
+
+        #[ink::test]
+        fn test_bitcoin_header_rejects_bad_pow_and_bits() {
+            let default_accounts = default_accounts();
+            let mut bridge = deploy_bitcoin_bridge(default_accounts.alice, 0);
+
+            // Another nonce does not meet the target.
+            let mut raw = bitcoin_header(1);
+            raw[76] ^= 1;
+            assert_eq!(
+                ink::env::pay_with_call!(bridge.submit_bitcoin_header(raw), RELAY_FEE),
+                Err(Error::PoWThresholdNotMet)
+            );
+
+            // Block 1 is not at a retarget, so its bits must be the same as its parent's, be they
+            // easier or harder.
+            for bits in [0x1d01ffffu32, 0x1c00ffff] {
+                let mut raw = bitcoin_header(1);
+                raw[72..76].copy_from_slice(&bits.to_le_bytes());
+                assert_eq!(
+                    ink::env::pay_with_call!(bridge.submit_bitcoin_header(raw), RELAY_FEE),
+                    Err(Error::UnexpectedDifficulty)
+                );
+            }
+
+            assert_eq!(
+                ink::env::pay_with_call!(
+                    bridge.submit_bitcoin_header(bitcoin_header(1)),
+                    RELAY_FEE
+                ),
+                Ok(())
+            );
+
+            let (mut scale_bridge, _) = deploy_bridge(default_accounts.alice);
+            assert_eq!(
+                ink::env::pay_with_call!(
+                    scale_bridge.submit_bitcoin_header(bitcoin_header(1)),
+                    RELAY_FEE
+                ),
+                Err(Error::WrongHeaderFormat)
+            );
+        }
+
+        #[ink::test]
+        fn test_bitcoin_transaction_verification() {
+            let default_accounts = default_accounts();
+            let mut bridge = deploy_bitcoin_bridge(default_accounts.alice, 170);
+            let block_hash = BitcoinHeader::hash(&bitcoin_header(170));
+            let txids = block_170_txids();
+
+            for index in 0..txids.len() {
+                let (root, proof) = bitcoin_merkle_root_and_proof(&txids, index);
+                assert_eq!(root, BitcoinHeader::parse(&bitcoin_header(170)).merkle_root);
+                assert_eq!(
+                    ink::env::pay_with_call!(
+                        bridge.verify_transaction(txids[index], block_hash, 0, proof),
+                        VERIFY_FEE
+                    ),
+                    Ok(true)
+                );
+            }
+
+            // The sibling of the transaction is on its left, not its right.
+            let proof = MerkleProof::new(Vec::from([SiblingNode::Right(txids[0])]));
+            assert_eq!(
+                ink::env::pay_with_call!(
+                    bridge.verify_transaction(txids[1], block_hash, 0, proof),
+                    VERIFY_FEE
+                ),
+                Ok(false)
+            );
+
+            // The coinbase of the genesis block is not in block 170.
+            let genesis_coinbase =
+                from_hex("4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b");
+            let (_, proof) = bitcoin_merkle_root_and_proof(&txids, 1);
+            assert_eq!(
+                ink::env::pay_with_call!(
+                    bridge.verify_transaction(genesis_coinbase, block_hash, 0, proof),
+                    VERIFY_FEE
+                ),
+                Ok(false)
+            );
+        }
+
+        #[ink::test]
+        fn test_bitcoin_merkle_proof_of_duplicated_last_node() {
+            // With three transactions, the third one is paired with itself, and its parent with
+            // itself in turn: the proof of C is [Right(C), Left(hash(A, B))].
+            //        root
+            //      /      \
+            //    AB        CC
+            //   /  \     /  \
+            //  A    B   C    (C)
+            let txids = [[0xaa; 32], [0xbb; 32], [0xcc; 32]];
+            let (root, proof) = bitcoin_merkle_root_and_proof(&txids, 2);
+            let ab = bitcoin::hash_pair(&txids[0], &txids[1]);
+            assert_eq!(
+                proof,
+                MerkleProof::new(Vec::from([
+                    SiblingNode::Right(txids[2]),
+                    SiblingNode::Left(ab)
+                ]))
+            );
+            assert_eq!(
+                root,
+                bitcoin::hash_pair(&ab, &bitcoin::hash_pair(&txids[2], &txids[2]))
+            );
+            assert!(MerkleProof::check_bitcoin_merkle_proof(
+                txids[2], &proof, root
+            ));
+            for index in 0..2 {
+                let (_, proof) = bitcoin_merkle_root_and_proof(&txids, index);
+                assert!(MerkleProof::check_bitcoin_merkle_proof(
+                    txids[index],
+                    &proof,
+                    root
+                ));
+            }
+        }
+
+        fn balance_of(account: AccountId) -> Balance {
+            ink::env::test::get_account_balance::<Environment>(account).unwrap()
+        }
+
+        #[ink::test]
+        fn test_submit_headers() {
+            // G---A---B---C, all in one call
+            let default_accounts = default_accounts();
+            let (mut bridge, genesis_header) = deploy_bridge(default_accounts.alice);
+            let a_header = make_child(genesis_header);
+            let b_header = make_child(a_header);
+            let c_header = make_child(b_header);
+            let segment = Vec::from([a_header, b_header, c_header]);
+
+            set_next_caller(default_accounts.bob);
+            ink::env::test::set_account_balance::<Environment>(
+                default_accounts.bob,
+                10 * RELAY_FEE,
+            );
+            assert_eq!(
+                ink::env::pay_with_call!(bridge.submit_headers(segment.clone()), 2 * RELAY_FEE),
+                Err(Error::InsufficientRelayFee)
+            );
+            assert_eq!(
+                ink::env::pay_with_call!(bridge.submit_headers(segment.clone()), 3 * RELAY_FEE),
+                Ok(())
+            );
+
+            assert_eq!(bridge.best_height, 103);
+            for header in segment {
+                let hash = SpvBridge::hash_header(header);
+                assert_eq!(bridge.canon_chain.get(header.height), Some(hash));
+                assert_eq!(bridge.fee_recipient.get(hash), Some(default_accounts.bob));
+            }
+        }
+
+        #[ink::test]
+        fn test_submit_headers_stores_nothing_if_any_is_invalid() {
+            // G---A---B---C
+            let default_accounts = default_accounts();
+            let (mut bridge, genesis_header) = deploy_bridge(default_accounts.alice);
+            let a_header = make_child(genesis_header);
+            let b_header = make_child(a_header);
+            let c_header = make_child(b_header);
+            let mut wrong_height = b_header;
+            wrong_height.height += 1;
+            let wrong_height = mine(wrong_height, THRESHOLD);
+
+            for (segment, error) in [
+                (
+                    Vec::from([a_header, wrong_height, c_header]),
+                    Error::IncorrectHeight,
+                ),
+                (Vec::from([a_header, c_header]), Error::UnknownParent),
+                (Vec::from([a_header, a_header]), Error::UnknownParent),
+            ] {
+                assert_eq!(
+                    ink::env::pay_with_call!(bridge.submit_headers(segment), 3 * RELAY_FEE),
+                    Err(error)
+                );
+                assert!(!bridge.header_is_known(SpvBridge::hash_header(a_header)));
+                assert_eq!(bridge.best_height, 100);
+            }
+
+            assert_eq!(
+                ink::env::pay_with_call!(bridge.submit_new_header(a_header), RELAY_FEE),
+                Ok(())
+            );
+            assert_eq!(
+                ink::env::pay_with_call!(
+                    bridge.submit_headers(Vec::from([a_header, b_header])),
+                    2 * RELAY_FEE
+                ),
+                Err(Error::HeaderAlreadySubmitted)
+            );
+        }
+
+        #[ink::test]
+        fn test_pruning_refunds_deposits() {
+            // With a finality depth of 2, C makes A final, so G and Bob's X are pruned,
+            // then D makes B final, so A is pruned.
+            // G---A---B---C---D
+            //  \
+            //   --X
+            let default_accounts = default_accounts();
+            let (mut bridge, genesis_header) = deploy_pruning_bridge(default_accounts.alice, 2);
+            let genesis_hash = SpvBridge::hash_header(genesis_header);
+            let a_header = make_child(genesis_header);
+            let b_header = make_child(a_header);
+            let c_header = make_child(b_header);
+            let d_header = make_child(c_header);
+            let x_header = make_child_with_transactions_root(genesis_header, [1; 32]);
+            let x_hash = SpvBridge::hash_header(x_header);
+
+            assert_eq!(
+                ink::env::pay_with_call!(
+                    bridge.submit_headers(Vec::from([a_header, b_header])),
+                    2 * RELAY_FEE
+                ),
+                Ok(())
+            );
+            let bob_balance = balance_of(default_accounts.bob);
+            set_next_caller(default_accounts.bob);
+            assert_eq!(
+                ink::env::pay_with_call!(bridge.submit_new_header(x_header), RELAY_FEE),
+                Ok(())
+            );
+            assert_eq!(balance_of(default_accounts.bob), bob_balance - RELAY_FEE);
+
+            set_next_caller(default_accounts.alice);
+            assert_eq!(
+                ink::env::pay_with_call!(bridge.submit_new_header(c_header), RELAY_FEE),
+                Ok(())
+            );
+            assert_eq!(bridge.finalized_height, 101);
+            assert!(!bridge.header_is_known(genesis_hash));
+            assert!(!bridge.header_is_known(x_hash));
+            assert_eq!(bridge.fee_recipient.get(x_hash), None);
+            assert_eq!(bridge.canon_chain.get(100), None);
+            assert_eq!(
+                bridge.canon_chain.get(101),
+                Some(SpvBridge::hash_header(a_header))
+            );
+            assert_eq!(balance_of(default_accounts.bob), bob_balance);
+
+            // Alice pays for D, and gets the deposit of A back.
+            let alice_balance = balance_of(default_accounts.alice);
+            assert_eq!(
+                ink::env::pay_with_call!(bridge.submit_new_header(d_header), RELAY_FEE),
+                Ok(())
+            );
+            assert_eq!(balance_of(default_accounts.alice), alice_balance);
+            assert_eq!(bridge.finalized_height, 102);
+            assert!(!bridge.header_is_known(SpvBridge::hash_header(a_header)));
+            assert_eq!(
+                bridge.headers_at_height.get(102),
+                Some(Vec::from([SpvBridge::hash_header(b_header)]))
+            );
+
+            // Pruned headers can no longer be verified against, nor built upon.
+            assert_eq!(
+                ink::env::pay_with_call!(
+                    bridge.verify_transaction(
+                        transactions()[0],
+                        genesis_hash,
+                        0,
+                        merkle_root_and_proof(&transactions(), 0).1
+                    ),
+                    VERIFY_FEE
+                ),
+                Ok(false)
+            );
+            assert_eq!(
+                ink::env::pay_with_call!(bridge.submit_new_header(make_child(x_header)), RELAY_FEE),
+                Err(Error::UnknownParent)
+            );
+        }
+
+        #[ink::test]
+        fn test_fork_below_finality_cannot_reorg() {
+            // With a finality depth of 2, C makes A final, and X1 is pruned. The rest of the X
+            // fork can no longer become canon, however heavy it gets.
+            // G---A---B---C
+            //  \
+            //   --X1--X2--X3--X4
+            let default_accounts = default_accounts();
+            let (mut bridge, genesis_header) = deploy_pruning_bridge(default_accounts.alice, 2);
+            let a_header = make_child(genesis_header);
+            let b_header = make_child(a_header);
+            let c_header = make_child(b_header);
+            let x1_header = make_child_with_transactions_root(genesis_header, [1; 32]);
+            let x2_header = make_child_with_transactions_root(x1_header, [1; 32]);
+            let x3_header = make_child_with_transactions_root(x2_header, [1; 32]);
+            let x4_header = make_child_with_transactions_root(x3_header, [1; 32]);
+
+            for segment in [
+                Vec::from([a_header, b_header]),
+                Vec::from([x1_header, x2_header]),
+                Vec::from([c_header]),
+            ] {
+                let fees = RELAY_FEE * segment.len() as Balance;
+                assert_eq!(
+                    ink::env::pay_with_call!(bridge.submit_headers(segment), fees),
+                    Ok(())
+                );
+            }
+            assert!(!bridge.header_is_known(SpvBridge::hash_header(x1_header)));
+
+            // X3 is no heavier than C, so it is stored without a re-org, until it is pruned.
+            assert_eq!(
+                ink::env::pay_with_call!(bridge.submit_new_header(x3_header), RELAY_FEE),
+                Ok(())
+            );
+            assert_eq!(
+                ink::env::pay_with_call!(bridge.submit_new_header(x4_header), RELAY_FEE),
+                Err(Error::FinalizedFork)
+            );
+            assert_eq!(bridge.best_height, 103);
+            assert_eq!(
+                bridge.canon_chain.get(103),
+                Some(SpvBridge::hash_header(c_header))
+            );
+        }
+
+        type Event = <SpvBridge as ink::reflect::ContractEventBase>::Type;
+
+        fn recorded_events() -> Vec<Event> {
+            ink::env::test::recorded_events()
+                .map(|event| <Event as scale::Decode>::decode(&mut &event.data[..]).unwrap())
+                .collect()
+        }
+
+        #[ink::test]
+        fn test_events() {
+            // G---A---B
+            //  \
+            //   --C---D---E
+            let default_accounts = default_accounts();
+            set_next_caller(default_accounts.alice);
+            let (mut bridge, genesis_header) = deploy_bridge(default_accounts.alice);
+            let genesis_hash = SpvBridge::hash_header(genesis_header);
+            let a_header = make_child(genesis_header);
+            let b_header = make_child(a_header);
+            let c_header = make_child_with_transactions_root(genesis_header, [1; 32]);
+            let d_header = make_child_with_transactions_root(c_header, [1; 32]);
+            let e_header = make_child_with_transactions_root(d_header, [1; 32]);
+
+            assert!(matches!(
+                recorded_events()[..],
+                [Event::FeeUpdated(FeeUpdated {
+                    relay_fee: RELAY_FEE,
+                    verify_fee: VERIFY_FEE,
+                })]
+            ));
+
+            set_next_caller(default_accounts.bob);
+            ink::env::test::set_account_balance::<Environment>(
+                default_accounts.bob,
+                10 * RELAY_FEE,
+            );
+            assert_eq!(
+                ink::env::pay_with_call!(
+                    bridge.submit_headers(Vec::from([a_header, b_header])),
+                    2 * RELAY_FEE
+                ),
+                Ok(())
+            );
+            let events = recorded_events();
+            assert_eq!(events.len(), 3);
+            for (event, header) in events[1..].iter().zip([a_header, b_header]) {
+                let Event::HeaderSubmitted(submitted) = event else {
+                    panic!("expected HeaderSubmitted");
+                };
+                assert_eq!(submitted.block_hash, SpvBridge::hash_header(header));
+                assert_eq!(submitted.block_height, header.height);
+                assert_eq!(submitted.submitter, default_accounts.bob);
+            }
+
+            // The C fork is no heavier than A---B until E, which re-orgs the two of them.
+            assert_eq!(
+                ink::env::pay_with_call!(
+                    bridge.submit_headers(Vec::from([c_header, d_header, e_header])),
+                    3 * RELAY_FEE
+                ),
+                Ok(())
+            );
+            let events = recorded_events();
+            assert_eq!(events.len(), 7);
+            let Event::Reorged(reorged) = &events[6] else {
+                panic!("expected Reorged");
+            };
+            assert_eq!(reorged.old_tip, SpvBridge::hash_header(b_header));
+            assert_eq!(reorged.new_tip, SpvBridge::hash_header(e_header));
+            assert_eq!(reorged.common_ancestor, genesis_hash);
+            assert_eq!(reorged.depth, 2);
+
+            // Extending the canonical chain is not a re-org.
+            assert_eq!(
+                ink::env::pay_with_call!(bridge.submit_new_header(make_child(e_header)), RELAY_FEE),
+                Ok(())
+            );
+            let events = recorded_events();
+            assert_eq!(events.len(), 8);
+            assert!(matches!(events[7], Event::HeaderSubmitted(_)));
+        }
+
+        #[ink::test]
+        fn test_queries() {
+            let default_accounts = default_accounts();
+            set_next_caller(default_accounts.alice);
+            let (mut bridge, genesis_header) = deploy_bridge(default_accounts.alice);
+            let genesis_hash = SpvBridge::hash_header(genesis_header);
+            let a_header = make_child(genesis_header);
+            let a_hash = SpvBridge::hash_header(a_header);
+            let b_header = make_child(a_header);
+            let b_hash = SpvBridge::hash_header(b_header);
+            let x_header = make_child_with_transactions_root(genesis_header, [1; 32]);
+            let x_hash = SpvBridge::hash_header(x_header);
+
+            assert_eq!(bridge.get_fees(), (RELAY_FEE, VERIFY_FEE));
+            assert_eq!(bridge.best_header(), (genesis_hash, genesis_header));
+            assert_eq!(bridge.confirmations(genesis_hash), Some(0));
+
+            for header in [a_header, b_header, x_header] {
+                assert_eq!(
+                    ink::env::pay_with_call!(bridge.submit_new_header(header), RELAY_FEE),
+                    Ok(())
+                );
+            }
+
+            assert_eq!(bridge.best_header(), (b_hash, b_header));
+            assert_eq!(bridge.canonical_hash_at(100), Some(genesis_hash));
+            assert_eq!(bridge.canonical_hash_at(101), Some(a_hash));
+            assert_eq!(bridge.canonical_hash_at(102), Some(b_hash));
+            assert_eq!(bridge.canonical_hash_at(103), None);
+            assert_eq!(bridge.confirmations(genesis_hash), Some(2));
+            assert_eq!(bridge.confirmations(b_hash), Some(0));
+
+            // The fork is stored, but it has no confirmations.
+            let x = bridge.header(x_hash).unwrap();
+            assert_eq!(x.header, x_header);
+            assert_eq!(x.deposit, RELAY_FEE);
+            assert_eq!(x.chain_work, bridge.header(a_hash).unwrap().chain_work);
+            assert!(!bridge.header_is_canon(x_hash));
+            assert_eq!(bridge.confirmations(x_hash), None);
+        }
+
+        #[ink::test]
+        fn test_unknown_header() {
+            let default_accounts = default_accounts();
+            set_next_caller(default_accounts.alice);
+            let (bridge, _) = deploy_bridge(default_accounts.alice);
+
+            assert!(!bridge.header_is_known([7; 32]));
+            assert!(!bridge.header_is_canon([7; 32]));
+            assert_eq!(bridge.header([7; 32]), None);
+            assert_eq!(bridge.confirmations([7; 32]), None);
+        }
+
+        #[ink::test]
+        fn test_parameter_updates_wait_for_the_timelock() {
+            let default_accounts = default_accounts();
+            let (mut bridge, genesis_header) = deploy_pruning_bridge(default_accounts.alice, 100);
+            let a_header = make_child(genesis_header);
+            let b_header = make_child(a_header);
+            assert_eq!(
+                ink::env::pay_with_call!(
+                    bridge.submit_headers(Vec::from([a_header, b_header])),
+                    2 * RELAY_FEE
+                ),
+                Ok(())
+            );
+            let fees = ParameterUpdate::Fees {
+                relay_fee: 2 * RELAY_FEE,
+                verify_fee: 2 * VERIFY_FEE,
+            };
+            ink::env::test::set_block_timestamp::<Environment>(GENESIS_TIME);
+
+            set_next_caller(default_accounts.bob);
+            assert_eq!(bridge.propose_update(fees), Err(Error::NotOwner));
+
+            set_next_caller(default_accounts.alice);
+            assert_eq!(bridge.execute_update(), Err(Error::NoPendingUpdate));
+            let eta = GENESIS_TIME + DEFAULT_TIMELOCK;
+            assert_eq!(bridge.propose_update(fees), Ok(eta));
+            assert_eq!(
+                bridge.propose_update(ParameterUpdate::FinalityDepth(1)),
+                Err(Error::UpdatePending)
+            );
+            assert_eq!(
+                bridge.pending_update(),
+                Some(PendingUpdate { update: fees, eta })
+            );
+
+            ink::env::test::set_block_timestamp::<Environment>(eta - 1);
+            assert_eq!(bridge.execute_update(), Err(Error::TimelockNotExpired));
+            assert_eq!(bridge.get_fees(), (RELAY_FEE, VERIFY_FEE));
+
+            ink::env::test::set_block_timestamp::<Environment>(eta);
+            assert_eq!(bridge.execute_update(), Ok(()));
+            assert_eq!(bridge.get_fees(), (2 * RELAY_FEE, 2 * VERIFY_FEE));
+            assert_eq!(bridge.pending_update(), None);
+            assert_eq!(bridge.execute_update(), Err(Error::NoPendingUpdate));
+            assert!(matches!(
+                recorded_events()[..],
+                [
+                    ..,
+                    Event::UpdateProposed(UpdateProposed { eta: proposed, .. }),
+                    Event::FeeUpdated(FeeUpdated {
+                        relay_fee: 2000,
+                        verify_fee: 200,
+                    }),
+                    Event::UpdateExecuted(_),
+                ] if proposed == eta
+            ));
+
+            // Lowering the finality depth prunes the headers that are now final.
+            let eta = bridge
+                .propose_update(ParameterUpdate::FinalityDepth(1))
+                .unwrap();
+            ink::env::test::set_block_timestamp::<Environment>(eta);
+            assert_eq!(bridge.execute_update(), Ok(()));
+            assert_eq!(bridge.finalized_height, 101);
+            assert!(!bridge.header_is_known(SpvBridge::hash_header(genesis_header)));
+
+            // The owner can hand the role over, and the new owner can cancel updates.
+            let eta = bridge
+                .propose_update(ParameterUpdate::Owner(default_accounts.bob))
+                .unwrap();
+            ink::env::test::set_block_timestamp::<Environment>(eta);
+            assert_eq!(bridge.execute_update(), Ok(()));
+            assert_eq!(bridge.owner(), default_accounts.bob);
+            assert_eq!(bridge.cancel_update(), Err(Error::NotOwner));
+
+            set_next_caller(default_accounts.bob);
+            let code = ParameterUpdate::Code(Hash::from([1; 32]));
+            assert_eq!(bridge.cancel_update(), Err(Error::NoPendingUpdate));
+            assert!(bridge.propose_update(code).is_ok());
+            assert_eq!(bridge.cancel_update(), Ok(()));
+            assert_eq!(bridge.pending_update(), None);
+            assert!(matches!(
+                recorded_events().last(),
+                Some(Event::UpdateCancelled(UpdateCancelled { update })) if *update == code
+            ));
+        }
+
+        #[ink::test]
+        fn test_withdraw_surplus() {
+            let default_accounts = default_accounts();
+            let (mut bridge, genesis_header) = deploy_bridge(default_accounts.alice);
+            let contract = AccountId::from([0xc0; 32]);
+            ink::env::test::set_account_balance::<Environment>(
+                contract,
+                ink::env::minimum_balance::<Environment>(),
+            );
+            assert_eq!(bridge.surplus(), 0);
+
+            // Bob pays more than the relay fee, and only the deposit is held for him.
+            set_next_caller(default_accounts.bob);
+            ink::env::test::set_account_balance::<Environment>(
+                default_accounts.bob,
+                10 * RELAY_FEE,
+            );
+            assert_eq!(
+                ink::env::pay_with_call!(
+                    bridge.submit_new_header(make_child(genesis_header)),
+                    3 * RELAY_FEE
+                ),
+                Ok(())
+            );
+            assert_eq!(bridge.deposits, RELAY_FEE);
+            assert_eq!(bridge.surplus(), 2 * RELAY_FEE);
+            assert_eq!(
+                bridge.withdraw_surplus(default_accounts.bob),
+                Err(Error::NotOwner)
+            );
+
+            set_next_caller(default_accounts.alice);
+            let treasury = default_accounts.charlie;
+            let treasury_balance = balance_of(treasury);
+            assert_eq!(bridge.withdraw_surplus(treasury), Ok(2 * RELAY_FEE));
+            assert_eq!(balance_of(treasury), treasury_balance + 2 * RELAY_FEE);
+            assert_eq!(
+                balance_of(contract),
+                ink::env::minimum_balance::<Environment>() + RELAY_FEE
+            );
+            assert_eq!(
+                bridge.withdraw_surplus(treasury),
+                Err(Error::NothingToWithdraw)
+            );
+            assert!(matches!(
+                recorded_events().last(),
+                Some(Event::SurplusWithdrawn(SurplusWithdrawn { to, amount }))
+                    if *to == treasury && *amount == 2 * RELAY_FEE
+            ));
+        }
+
+        const BOND: Balance = 10 * RELAY_FEE;
+        const CHALLENGE_PERIOD: Timestamp = 60 * 60 * 1000;
+
+        /// Have the owner, Alice, enable optimistic headers through the timelock, and return the
+        /// time at which they were enabled.
+        fn enable_optimistic_headers(bridge: &mut SpvBridge) -> Timestamp {
+            set_next_caller(default_accounts().alice);
+            let eta = bridge
+                .propose_update(ParameterUpdate::Optimistic(Some(OptimisticRule {
+                    bond: BOND,
+                    challenge_period: CHALLENGE_PERIOD,
+                })))
+                .unwrap();
+            ink::env::test::set_block_timestamp::<Environment>(eta);
+            bridge.execute_update().unwrap();
+            eta
+        }
+
+        /// Have Bob post a bond and submit `headers` optimistically.
+        fn submit_optimistically(bridge: &mut SpvBridge, headers: Vec<Header>) -> Result<()> {
+            let bob = default_accounts().bob;
+            set_next_caller(bob);
+            ink::env::test::set_account_balance::<Environment>(bob, 100 * RELAY_FEE);
+            if bridge.bond_of(bob).amount < BOND {
+                ink::env::pay_with_call!(bridge.post_bond(), BOND).unwrap();
+            }
+            let fees = RELAY_FEE * headers.len() as Balance;
+            ink::env::pay_with_call!(bridge.submit_optimistic_headers(headers), fees)
+        }
+
+        #[ink::test]
+        fn test_optimistic_headers_require_a_bond() {
+            let default_accounts = default_accounts();
+            let (mut bridge, genesis_header) = deploy_bridge(default_accounts.alice);
+            let a_header = make_child(genesis_header);
+
+            set_next_caller(default_accounts.bob);
+            ink::env::test::set_account_balance::<Environment>(
+                default_accounts.bob,
+                100 * RELAY_FEE,
+            );
+            assert_eq!(
+                ink::env::pay_with_call!(
+                    bridge.submit_optimistic_headers(Vec::from([a_header])),
+                    RELAY_FEE
+                ),
+                Err(Error::OptimisticModeDisabled)
+            );
+
+            enable_optimistic_headers(&mut bridge);
+            set_next_caller(default_accounts.bob);
+            assert_eq!(
+                ink::env::pay_with_call!(
+                    bridge.submit_optimistic_headers(Vec::from([a_header])),
+                    RELAY_FEE
+                ),
+                Err(Error::InsufficientBond)
+            );
+            assert_eq!(
+                ink::env::pay_with_call!(bridge.post_bond(), BOND - RELAY_FEE),
+                Ok(())
+            );
+            assert_eq!(
+                ink::env::pay_with_call!(
+                    bridge.submit_optimistic_headers(Vec::from([a_header])),
+                    RELAY_FEE
+                ),
+                Err(Error::InsufficientBond)
+            );
+            assert_eq!(
+                bridge.bond_of(default_accounts.bob).amount,
+                BOND - RELAY_FEE
+            );
+        }
+
+        #[ink::test]
+        fn test_optimistic_headers_count_once_their_window_has_passed() {
+            // G---A---B, A and B submitted optimistically
+            let default_accounts = default_accounts();
+            let (mut bridge, genesis_header) = deploy_bridge(default_accounts.alice);
+            let a_header = make_child(genesis_header);
+            let b_header = make_child(a_header);
+            let a_hash = SpvBridge::hash_header(a_header);
+            let b_hash = SpvBridge::hash_header(b_header);
+            let submitted_at = enable_optimistic_headers(&mut bridge);
+
+            assert_eq!(
+                submit_optimistically(&mut bridge, Vec::from([a_header, b_header])),
+                Ok(())
+            );
+            assert_eq!(
+                submit_optimistically(&mut bridge, Vec::from([a_header])),
+                Err(Error::HeaderAlreadySubmitted)
+            );
+            let deadline = submitted_at + CHALLENGE_PERIOD;
+            assert_eq!(
+                bridge.optimistic_header(a_hash),
+                Some(OptimisticHeader {
+                    header: a_header,
+                    relayer: default_accounts.bob,
+                    deposit: RELAY_FEE,
+                    challenge_deadline: deadline,
+                })
+            );
+
+            // Until the window has passed, the headers do not count, and the bond is locked.
+            let (transactions_root, proof) = merkle_root_and_proof(&transactions(), 0);
+            assert_eq!(transactions_root, genesis_header.transactions_root);
+            let verify = |bridge: &mut SpvBridge, header_hash: HashValue| {
+                ink::env::pay_with_call!(
+                    bridge.verify_transaction(transactions()[0], header_hash, 1, proof.clone()),
+                    VERIFY_FEE
+                )
+            };
+            let genesis_hash = SpvBridge::hash_header(genesis_header);
+            assert_eq!(
+                verify(&mut bridge, genesis_hash),
+                Err(Error::IncorrectHeight)
+            );
+            ink::env::test::set_block_timestamp::<Environment>(deadline);
+            assert_eq!(
+                bridge.accept_header(a_hash),
+                Err(Error::ChallengeWindowOpen)
+            );
+            assert_eq!(bridge.withdraw_bond(), Err(Error::BondLocked));
+            assert!(!bridge.header_is_known(a_hash));
+
+            // Then they are accepted from the oldest up, with Bob as their relayer.
+            ink::env::test::set_block_timestamp::<Environment>(deadline + 1);
+            set_next_caller(default_accounts.charlie);
+            assert_eq!(bridge.accept_header(b_hash), Err(Error::UnknownParent));
+            assert_eq!(bridge.accept_header(a_hash), Ok(()));
+            assert_eq!(bridge.accept_header(b_hash), Ok(()));
+            assert_eq!(bridge.accept_header(b_hash), Err(Error::NotOptimistic));
+            assert_eq!(bridge.best_header(), (b_hash, b_header));
+            assert_eq!(bridge.fee_recipient.get(a_hash), Some(default_accounts.bob));
+            assert_eq!(
+                bridge.header(a_hash).map(|stored| stored.deposit),
+                Some(RELAY_FEE)
+            );
+            assert_eq!(bridge.optimistic_header(a_hash), None);
+            assert_eq!(verify(&mut bridge, genesis_hash), Ok(true));
+
+            set_next_caller(default_accounts.bob);
+            assert_eq!(bridge.withdraw_bond(), Ok(BOND));
+            assert_eq!(bridge.bond_of(default_accounts.bob), Bond::default());
+        }
+
+        #[ink::test]
+        fn test_optimistic_header_submitted_with_all_checks_is_dropped() {
+            let default_accounts = default_accounts();
+            let (mut bridge, genesis_header) = deploy_bridge(default_accounts.alice);
+            let a_header = make_child(genesis_header);
+            let a_hash = SpvBridge::hash_header(a_header);
+            let submitted_at = enable_optimistic_headers(&mut bridge);
+            assert_eq!(
+                submit_optimistically(&mut bridge, Vec::from([a_header])),
+                Ok(())
+            );
+
+            set_next_caller(default_accounts.alice);
+            assert_eq!(
+                ink::env::pay_with_call!(bridge.submit_new_header(a_header), RELAY_FEE),
+                Ok(())
+            );
+            ink::env::test::set_block_timestamp::<Environment>(submitted_at + CHALLENGE_PERIOD + 1);
+            let bob_balance = balance_of(default_accounts.bob);
+            assert_eq!(bridge.accept_header(a_hash), Ok(()));
+            assert_eq!(balance_of(default_accounts.bob), bob_balance + RELAY_FEE);
+            assert_eq!(bridge.optimistic_header(a_hash), None);
+            assert_eq!(
+                bridge.fee_recipient.get(a_hash),
+                Some(default_accounts.alice)
+            );
+        }
+
+        #[ink::test]
+        fn test_optimistic_header_whose_parent_is_pruned_is_dropped() {
+            // G---A---B---C
+            //  \
+            //   P---X, X submitted optimistically, then P pruned once A is final
+            let default_accounts = default_accounts();
+            let (mut bridge, genesis_header) = deploy_pruning_bridge(default_accounts.alice, 2);
+            let a_header = make_child(genesis_header);
+            let p_header = make_child_with_transactions_root(genesis_header, [1; 32]);
+            let x_header = make_child(p_header);
+            let x_hash = SpvBridge::hash_header(x_header);
+            let submitted_at = enable_optimistic_headers(&mut bridge);
+            set_next_caller(default_accounts.alice);
+            for header in [a_header, p_header] {
+                assert_eq!(
+                    ink::env::pay_with_call!(bridge.submit_new_header(header), RELAY_FEE),
+                    Ok(())
+                );
+            }
+            assert_eq!(
+                submit_optimistically(&mut bridge, Vec::from([x_header])),
+                Ok(())
+            );
+
+            let b_header = make_child(a_header);
+            let c_header = make_child(b_header);
+            set_next_caller(default_accounts.alice);
+            assert_eq!(
+                ink::env::pay_with_call!(
+                    bridge.submit_headers(Vec::from([b_header, c_header])),
+                    2 * RELAY_FEE
+                ),
+                Ok(())
+            );
+            assert!(!bridge.header_is_known(SpvBridge::hash_header(p_header)));
+
+            // The parent of X is unknown now, but it was valid, so Bob keeps his bond.
+            set_next_caller(default_accounts.charlie);
+            for fraud in [Fraud::BadPoW, Fraud::WrongHeight, Fraud::UnknownParent] {
+                assert_eq!(bridge.challenge_header(x_hash, fraud), Err(Error::NoFraud));
+            }
+            assert_eq!(bridge.bond_of(default_accounts.bob).amount, BOND);
+
+            // X can never be accepted, so it is dropped, and its deposit refunded.
+            ink::env::test::set_block_timestamp::<Environment>(submitted_at + CHALLENGE_PERIOD + 1);
+            let bob_balance = balance_of(default_accounts.bob);
+            let deposits = bridge.deposits;
+            assert_eq!(bridge.accept_header(x_hash), Ok(()));
+            assert_eq!(balance_of(default_accounts.bob), bob_balance + RELAY_FEE);
+            assert_eq!(bridge.deposits, deposits - RELAY_FEE);
+            assert_eq!(bridge.optimistic_header(x_hash), None);
+            assert!(!bridge.header_is_known(x_hash));
+
+            set_next_caller(default_accounts.bob);
+            assert_eq!(bridge.withdraw_bond(), Ok(BOND));
+        }
+
+        #[ink::test]
+        fn test_fraud_proofs_slash_the_bond() {
+            // G---A---B---C, all submitted optimistically, where B does not meet the threshold.
+            // Once B is dropped, C has an unknown parent. A is valid, and cannot be challenged.
+            let default_accounts = default_accounts();
+            let (mut bridge, genesis_header) = deploy_bridge(default_accounts.alice);
+            let a_header = make_child(genesis_header);
+            let mut b_header = make_child(a_header);
+            while SpvBridge::hash_header(b_header) < THRESHOLD {
+                b_header.pow_nonce += 1;
+            }
+            let c_header = make_child(b_header);
+            let [a_hash, b_hash, c_hash] =
+                [a_header, b_header, c_header].map(SpvBridge::hash_header);
+            let submitted_at = enable_optimistic_headers(&mut bridge);
+            assert_eq!(
+                submit_optimistically(&mut bridge, Vec::from([a_header, b_header, c_header])),
+                Ok(())
+            );
+
+            let challenger = default_accounts.charlie;
+            set_next_caller(challenger);
+            for fraud in [Fraud::BadPoW, Fraud::WrongHeight, Fraud::UnknownParent] {
+                assert_eq!(bridge.challenge_header(a_hash, fraud), Err(Error::NoFraud));
+            }
+            assert_eq!(
+                bridge.challenge_header(b_hash, Fraud::WrongHeight),
+                Err(Error::NoFraud)
+            );
+            assert_eq!(
+                bridge.challenge_header(c_hash, Fraud::UnknownParent),
+                Err(Error::NoFraud)
+            );
+            assert_eq!(
+                bridge.challenge_header([7; 32], Fraud::UnknownParent),
+                Err(Error::NotOptimistic)
+            );
+
+            let challenger_balance = balance_of(challenger);
+            assert_eq!(
+                bridge.challenge_header(b_hash, Fraud::BadPoW),
+                Ok(BOND + RELAY_FEE)
+            );
+            assert_eq!(
+                balance_of(challenger),
+                challenger_balance + BOND + RELAY_FEE
+            );
+            assert_eq!(bridge.bond_of(default_accounts.bob), Bond::default());
+            assert_eq!(bridge.optimistic_header(b_hash), None);
+            assert!(matches!(
+                recorded_events().last(),
+                Some(Event::FraudProven(FraudProven {
+                    fraud: Fraud::BadPoW,
+                    relayer,
+                    challenger: proven_by,
+                    slashed,
+                    ..
+                })) if *relayer == default_accounts.bob
+                    && *proven_by == challenger
+                    && *slashed == BOND + RELAY_FEE
+            ));
+
+            // The bond is gone, but the challenger still gets the deposit of C.
+            assert_eq!(
+                bridge.challenge_header(c_hash, Fraud::UnknownParent),
+                Ok(RELAY_FEE)
+            );
+
+            // Wrong heights are frauds too, but only during the challenge window.
+            let mut d_header = make_child(a_header);
+            d_header.height += 1;
+            let d_header = mine(d_header, THRESHOLD);
+            let d_hash = SpvBridge::hash_header(d_header);
+            ink::env::test::set_block_timestamp::<Environment>(submitted_at + CHALLENGE_PERIOD);
+            assert_eq!(
+                submit_optimistically(&mut bridge, Vec::from([d_header])),
+                Ok(())
+            );
+            set_next_caller(challenger);
+            assert_eq!(
+                bridge.challenge_header(d_hash, Fraud::BadPoW),
+                Err(Error::NoFraud)
+            );
+            ink::env::test::set_block_timestamp::<Environment>(
+                submitted_at + 2 * CHALLENGE_PERIOD + 1,
+            );
+            assert_eq!(
+                bridge.challenge_header(d_hash, Fraud::WrongHeight),
+                Err(Error::ChallengeWindowClosed)
+            );
+            ink::env::test::set_block_timestamp::<Environment>(submitted_at + 2 * CHALLENGE_PERIOD);
+            assert_eq!(
+                bridge.challenge_header(d_hash, Fraud::WrongHeight),
+                Ok(BOND + RELAY_FEE)
+            );
+
+            // A was never challenged, so it is accepted.
+            assert_eq!(bridge.accept_header(a_hash), Ok(()));
+            assert_eq!(bridge.best_header(), (a_hash, a_header));
+        }
+
+        /// Upgrades with `set_code_hash` keep the storage as it is, so the new code must read the
+        /// storage of the deployed contracts, laid out as in the fixture, the same way. It may
+        /// only add enum variants, and fields under keys of their own. If this test fails, the new
+        /// layout needs a migration of the deployed contracts, after which the fixture can be
+        /// updated by running the test again with `UPDATE_STORAGE_LAYOUT=1`.
+        #[test]
+        fn test_storage_layout_is_compatible() {
+            use ink::metadata::layout::Layout;
+            use ink::storage::traits::StorageLayout;
+            use scale_info::{form::PortableForm, IntoPortable, PortableRegistry, Registry};
+
+            let mut registry = Registry::new();
+            let layout: Layout<PortableForm> =
+                <SpvBridge as StorageLayout>::layout(&ink::primitives::Key::default())
+                    .into_portable(&mut registry);
+            let types: PortableRegistry = registry.into();
+            let actual = serde_json::json!({
+                "layout": layout,
+                "types": types,
+            });
+
+            let path = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/storage_layout.json");
+            if std::env::var_os("UPDATE_STORAGE_LAYOUT").is_some() {
+                std::fs::write(path, serde_json::to_string_pretty(&actual).unwrap() + "\n")
+                    .unwrap();
+            }
+            let expected = std::fs::read_to_string(path)
+                .ok()
+                .and_then(|fixture| serde_json::from_str(&fixture).ok())
+                .unwrap_or_default();
+            assert!(
+                extends_layout(&expected, &actual, ""),
+                "The storage layout of the bridge changed, so upgrading deployed contracts to this \
+                 code would corrupt their storage. Write a migration, then update {} with \
+                 UPDATE_STORAGE_LAYOUT=1.",
+                path
+            );
+        }
+
+        /// Whether the storage layout `new` reads what was stored with the layout `old` the same
+        /// way, both as serialized along with their type registries. `key` is the key of the
+        /// values in their parent objects.
+        fn extends_layout(old: &serde_json::Value, new: &serde_json::Value, key: &str) -> bool {
+            use serde_json::Value;
+            match (old, new) {
+                (Value::Object(old), Value::Object(new)) => {
+                    // Enums may get new variants.
+                    (key == "variants" || old.len() == new.len())
+                        && old.iter().all(|(key, old)| {
+                            new.get(key)
+                                .is_some_and(|new| extends_layout(old, new, key))
+                        })
+                }
+                (Value::Array(old), Value::Array(new)) => {
+                    let (new, added) = new.split_at(old.len().min(new.len()));
+                    old.len() == new.len()
+                        && old
+                            .iter()
+                            .zip(new)
+                            .all(|(old, new)| extends_layout(old, new, key))
+                        && match key {
+                            // New types are registered after the known ones.
+                            "types" => true,
+                            // New fields must not be packed along with the existing ones.
+                            "fields" => added
+                                .iter()
+                                .all(|field| field["layout"]["root"].is_object()),
+                            _ => added.is_empty(),
+                        }
+                }
+                _ => old == new,
+            }
+        }
     }
 }
@@ -0,0 +1,583 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+/// A lock-and-mint token bridge, on top of the light client of `spv_bridge`.
+///
+/// Users lock funds on the source chain by sending them to the bridge's deposit address there,
+/// and claim the same amount of a wrapped PSP22 token here by proving that deposit with the light
+/// client. To go back, they burn wrapped tokens with a withdrawal request, which relayers pick up
+/// from its event to release the locked funds on the source chain.
+///
+/// The wrapped token is a PSP22 contract that implements `Mintable` and `Burnable` from
+/// `psp22-traits`, and lets only this contract mint and burn, such as `wrapped_token` once this
+/// contract is set as its minter.
+#[ink::contract]
+mod token_bridge {
+    use ink::codegen::{EmitEvent, TraitCallBuilder};
+    use ink::env::call::FromAccountId;
+    use ink::env::hash::{HashOutput, Sha2x256};
+    use ink::reflect::ContractEventBase;
+    use ink::storage::Mapping;
+    use psp22_traits::{Burnable, Mintable, PSP22Error};
+    use spv_bridge::{HashValue, MerkleProof, SpvBridgeRef};
+
+    /// A deposit to the bridge on the source chain, as the transaction that makes it.
+    ///
+    /// Its transaction hash, which the light client proves to be in a block, is the `Sha2x256`
+    /// hash of its encoding, see [`TokenBridge::hash_deposit`].
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct Deposit {
+        /// The address that the funds were sent to on the source chain
+        pub to: HashValue,
+        /// How much was deposited, and so how many wrapped tokens to mint
+        pub amount: Balance,
+        /// The account that receives the wrapped tokens
+        pub recipient: AccountId,
+        /// Tells apart deposits of the same amount to the same recipient
+        pub nonce: u64,
+    }
+
+    #[ink(storage)]
+    pub struct TokenBridge {
+        /// The `spv_bridge` contract that tracks the source chain
+        spv_bridge: AccountId,
+
+        /// The wrapped PSP22 token
+        token: AccountId,
+
+        /// The address on the source chain that deposits must be sent to
+        deposit_address: HashValue,
+
+        /// How many blocks must be on top of the block of a deposit before it can be claimed
+        min_depth: u64,
+
+        /// The transaction hashes of the deposits that were already claimed, so that each deposit
+        /// is only minted once, whichever block it is proven in.
+        claimed: Mapping<HashValue, ()>,
+
+        /// The number of withdrawal requests so far, which is the id of the next one
+        withdrawals: u64,
+    }
+
+    /// Errors that can occur upon calling this contract.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub enum Error {
+        /// The deposit was not sent to the deposit address of the bridge
+        WrongDepositAddress,
+        /// Nothing to mint or burn
+        ZeroAmount,
+        /// The deposit was already claimed
+        AlreadyClaimed,
+        /// The light client could not verify that the deposit is in a canonical block
+        NotVerified,
+        /// The light client rejected the verification
+        Verification(spv_bridge::Error),
+        /// The wrapped token rejected the mint or the burn
+        PSP22(PSP22Error),
+    }
+
+    impl From<spv_bridge::Error> for Error {
+        fn from(e: spv_bridge::Error) -> Self {
+            Error::Verification(e)
+        }
+    }
+
+    impl From<PSP22Error> for Error {
+        fn from(e: PSP22Error) -> Self {
+            Error::PSP22(e)
+        }
+    }
+
+    /// Type alias for the contract's `Result` type.
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    type Event = <TokenBridge as ContractEventBase>::Type;
+
+    /// Wrapped tokens were minted for a deposit on the source chain.
+    #[ink(event)]
+    pub struct DepositClaimed {
+        #[ink(topic)]
+        tx_hash: HashValue,
+        #[ink(topic)]
+        recipient: AccountId,
+        amount: Balance,
+    }
+
+    /// Wrapped tokens were burnt, and relayers should release `amount` to `destination` on the
+    /// source chain.
+    #[ink(event)]
+    pub struct WithdrawalRequested {
+        id: u64,
+        #[ink(topic)]
+        account: AccountId,
+        destination: HashValue,
+        amount: Balance,
+    }
+
+    impl TokenBridge {
+        /// Bridge deposits to `deposit_address` on the source chain tracked by `spv_bridge`, once
+        /// they are `min_depth` blocks deep, into `token`.
+        #[ink(constructor)]
+        pub fn new(
+            spv_bridge: AccountId,
+            token: AccountId,
+            deposit_address: HashValue,
+            min_depth: u64,
+        ) -> Self {
+            Self {
+                spv_bridge,
+                token,
+                deposit_address,
+                min_depth,
+                claimed: Mapping::default(),
+                withdrawals: 0,
+            }
+        }
+
+        /// Mint wrapped tokens for a deposit on the source chain.
+        ///
+        /// Anyone can claim a deposit, the tokens always go to its recipient. These conditions
+        /// must be met:
+        /// 0. The deposit was sent to the deposit address of the bridge
+        /// 1. The deposit was not claimed before
+        /// 2. The light client verifies that the deposit is in the canonical block `header_hash`,
+        ///    at least `min_depth` blocks deep. The caller pays its verification fee, by
+        ///    transferring it along with this call.
+        #[ink(message, payable)]
+        pub fn claim_deposit(
+            &mut self,
+            deposit: Deposit,
+            header_hash: HashValue,
+            proof: MerkleProof,
+        ) -> Result<()> {
+            // 0. The deposit was sent to the deposit address of the bridge
+            if deposit.to != self.deposit_address {
+                return Err(Error::WrongDepositAddress);
+            }
+            if deposit.amount == 0 {
+                return Err(Error::ZeroAmount);
+            }
+
+            // 1. The deposit was not claimed before
+            let tx_hash = Self::hash_deposit(deposit);
+            if self.claimed.contains(tx_hash) {
+                return Err(Error::AlreadyClaimed);
+            }
+
+            // 2. The light client verifies the deposit
+            let mut spv_bridge: SpvBridgeRef = FromAccountId::from_account_id(self.spv_bridge);
+            let verified = spv_bridge
+                .call_mut()
+                .verify_transaction(tx_hash, header_hash, self.min_depth, proof)
+                .transferred_value(self.env().transferred_value())
+                .invoke()?;
+            if !verified {
+                return Err(Error::NotVerified);
+            }
+
+            self.claimed.insert(tx_hash, &());
+            let mut token: ink::contract_ref!(Mintable) = self.token.into();
+            token.mint(deposit.recipient, deposit.amount)?;
+
+            Self::emit_event(
+                self.env(),
+                Event::DepositClaimed(DepositClaimed {
+                    tx_hash,
+                    recipient: deposit.recipient,
+                    amount: deposit.amount,
+                }),
+            );
+            Ok(())
+        }
+
+        /// Burn `amount` of the caller's wrapped tokens, to be released to `destination` on the
+        /// source chain by relayers, and return the id of the request.
+        #[ink(message)]
+        pub fn request_withdrawal(
+            &mut self,
+            amount: Balance,
+            destination: HashValue,
+        ) -> Result<u64> {
+            if amount == 0 {
+                return Err(Error::ZeroAmount);
+            }
+
+            let account = self.env().caller();
+            let mut token: ink::contract_ref!(Burnable) = self.token.into();
+            token.burn(account, amount)?;
+
+            let id = self.withdrawals;
+            self.withdrawals += 1;
+            Self::emit_event(
+                self.env(),
+                Event::WithdrawalRequested(WithdrawalRequested {
+                    id,
+                    account,
+                    destination,
+                    amount,
+                }),
+            );
+            Ok(id)
+        }
+
+        /// Whether the deposit with this transaction hash was already claimed.
+        #[ink(message)]
+        pub fn is_claimed(&self, tx_hash: HashValue) -> bool {
+            self.claimed.contains(tx_hash)
+        }
+
+        /// The address on the source chain that deposits must be sent to.
+        #[ink(message)]
+        pub fn deposit_address(&self) -> HashValue {
+            self.deposit_address
+        }
+
+        /// Helper function to hash a deposit, as its transaction appears in the leaves of the
+        /// transactions tree of its block.
+        pub fn hash_deposit(deposit: Deposit) -> HashValue {
+            let mut hash_value = <Sha2x256 as HashOutput>::Type::default();
+            ink::env::hash_encoded::<Sha2x256, _>(&deposit, &mut hash_value);
+            hash_value
+        }
+
+        fn emit_event<EE>(emitter: EE, event: Event)
+        where
+            EE: EmitEvent<TokenBridge>,
+        {
+            emitter.emit_event(event);
+        }
+    }
+
+    /// The off-chain environment cannot call other contracts, so these tests only cover what is
+    /// checked before the light client and the token are called.
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const DEPOSIT_ADDRESS: HashValue = [0xd0; 32];
+        const MIN_DEPTH: u64 = 6;
+
+        fn default_accounts() -> ink::env::test::DefaultAccounts<ink::env::DefaultEnvironment> {
+            ink::env::test::default_accounts::<Environment>()
+        }
+
+        fn deploy_token_bridge() -> TokenBridge {
+            TokenBridge::new(
+                AccountId::from([0x01; 32]),
+                AccountId::from([0x02; 32]),
+                DEPOSIT_ADDRESS,
+                MIN_DEPTH,
+            )
+        }
+
+        fn deposit(amount: Balance) -> Deposit {
+            Deposit {
+                to: DEPOSIT_ADDRESS,
+                amount,
+                recipient: default_accounts().bob,
+                nonce: 0,
+            }
+        }
+
+        #[ink::test]
+        fn deposits_have_distinct_hashes() {
+            let deposit = deposit(100);
+            let hash = TokenBridge::hash_deposit(deposit);
+            assert_ne!(
+                hash,
+                TokenBridge::hash_deposit(Deposit {
+                    nonce: 1,
+                    ..deposit
+                })
+            );
+            assert_ne!(
+                hash,
+                TokenBridge::hash_deposit(Deposit {
+                    amount: 101,
+                    ..deposit
+                })
+            );
+            assert_ne!(
+                hash,
+                TokenBridge::hash_deposit(Deposit {
+                    recipient: default_accounts().charlie,
+                    ..deposit
+                })
+            );
+        }
+
+        #[ink::test]
+        fn claim_deposit_to_other_address_fails() {
+            let mut token_bridge = deploy_token_bridge();
+            let deposit = Deposit {
+                to: [0xd1; 32],
+                ..deposit(100)
+            };
+            assert_eq!(
+                token_bridge.claim_deposit(deposit, [0; 32], MerkleProof::new(Vec::new())),
+                Err(Error::WrongDepositAddress)
+            );
+        }
+
+        #[ink::test]
+        fn claim_empty_deposit_fails() {
+            let mut token_bridge = deploy_token_bridge();
+            assert_eq!(
+                token_bridge.claim_deposit(deposit(0), [0; 32], MerkleProof::new(Vec::new())),
+                Err(Error::ZeroAmount)
+            );
+        }
+
+        #[ink::test]
+        fn claim_deposit_twice_fails() {
+            let mut token_bridge = deploy_token_bridge();
+            let deposit = deposit(100);
+            let tx_hash = TokenBridge::hash_deposit(deposit);
+            assert!(!token_bridge.is_claimed(tx_hash));
+
+            // As claim_deposit records it once the light client verified it
+            token_bridge.claimed.insert(tx_hash, &());
+            assert!(token_bridge.is_claimed(tx_hash));
+            assert_eq!(
+                token_bridge.claim_deposit(deposit, [0; 32], MerkleProof::new(Vec::new())),
+                Err(Error::AlreadyClaimed)
+            );
+        }
+
+        #[ink::test]
+        fn request_empty_withdrawal_fails() {
+            let mut token_bridge = deploy_token_bridge();
+            assert_eq!(
+                token_bridge.request_withdrawal(0, [0xe0; 32]),
+                Err(Error::ZeroAmount)
+            );
+            assert_eq!(token_bridge.withdrawals, 0);
+        }
+    }
+
+    /// These tests deploy `spv_bridge`, `wrapped_token` and this contract together on a
+    /// `substrate-contracts-node`, with `cargo test --features e2e-tests`.
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use ink_e2e::build_message;
+        use psp22_traits::PSP22;
+        use spv_bridge::{Header, RetargetRule, SiblingNode};
+        use wrapped_token::WrappedTokenRef;
+
+        use super::*;
+
+        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+        // The threshold is set so that we have roughly 1 in 4 chance of finding a valid block.
+        const THRESHOLD: HashValue = [
+            63, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0,
+        ];
+        const CHECKPOINT_HEIGHT: u64 = 100;
+        const GENESIS_TIME: u64 = 1_000_000;
+        const BLOCK_TIME: u64 = 600;
+        const FINALITY_DEPTH: u64 = 100;
+        const RELAY_FEE: Balance = 1_000;
+        const VERIFY_FEE: Balance = 100;
+        const DEPOSIT_ADDRESS: HashValue = [0xd0; 32];
+        const MIN_DEPTH: u64 = 1;
+
+        fn hash_header(header: Header) -> HashValue {
+            let mut hash_value = <Sha2x256 as HashOutput>::Type::default();
+            ink::env::hash_encoded::<Sha2x256, _>(&header, &mut hash_value);
+            hash_value
+        }
+
+        /// The child of the header with hash `parent` at `height`, that meets [`THRESHOLD`].
+        fn mine(parent: HashValue, height: u64, transactions_root: HashValue) -> Header {
+            let timestamp = GENESIS_TIME + (height - CHECKPOINT_HEIGHT) * BLOCK_TIME;
+            (1..)
+                .map(|nonce| {
+                    Header::new(height, parent, timestamp, [0; 32], transactions_root, nonce)
+                })
+                .find(|header| hash_header(*header) < THRESHOLD)
+                .expect("some nonce meets the threshold")
+        }
+
+        /// Bob deposits on the source chain, in a block that a relayer submits to the light
+        /// client with one more on top. Charlie claims it for him, and he requests to withdraw
+        /// part of it.
+        #[ink_e2e::test(
+            additional_contracts = "../spv_bridge/Cargo.toml ../wrapped_token/Cargo.toml"
+        )]
+        async fn deposit_claim_and_withdrawal(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let deposit = Deposit {
+                to: DEPOSIT_ADDRESS,
+                amount: 100,
+                recipient: bob,
+                nonce: 0,
+            };
+            let tx_hash = TokenBridge::hash_deposit(deposit);
+
+            // The deposit is the first of the two transactions of block 101
+            let other_tx = [0x01; 32];
+            let transactions_root = MerkleProof::hash_pair(&tx_hash, &other_tx);
+            let proof = MerkleProof::new(Vec::from([SiblingNode::Right(other_tx)]));
+
+            let checkpoint = Header::new(
+                CHECKPOINT_HEIGHT,
+                [0; 32],
+                GENESIS_TIME,
+                [0; 32],
+                [0; 32],
+                1,
+            );
+            let deposit_block = mine(
+                hash_header(checkpoint),
+                CHECKPOINT_HEIGHT + 1,
+                transactions_root,
+            );
+            let deposit_hash = hash_header(deposit_block);
+            let next_block = mine(deposit_hash, CHECKPOINT_HEIGHT + 2, [0; 32]);
+
+            // Alice deploys the light client, relays the two blocks, and then deploys the
+            // token and the bridge, which she makes the minter of the token.
+            let constructor = SpvBridgeRef::new(
+                checkpoint,
+                THRESHOLD,
+                GENESIS_TIME,
+                RetargetRule::bitcoin(THRESHOLD),
+                FINALITY_DEPTH,
+                RELAY_FEE,
+                VERIFY_FEE,
+            );
+            let spv_bridge = client
+                .instantiate("spv_bridge", &ink_e2e::alice(), constructor, 0, None)
+                .await
+                .expect("instantiate spv_bridge failed")
+                .account_id;
+
+            let submit = build_message::<SpvBridgeRef>(spv_bridge).call(|spv_bridge| {
+                spv_bridge.submit_headers(Vec::from([deposit_block, next_block]))
+            });
+            client
+                .call(&ink_e2e::alice(), submit, 2 * RELAY_FEE, None)
+                .await
+                .expect("submit_headers failed");
+
+            let token = client
+                .instantiate(
+                    "wrapped_token",
+                    &ink_e2e::alice(),
+                    WrappedTokenRef::new(),
+                    0,
+                    None,
+                )
+                .await
+                .expect("instantiate wrapped_token failed")
+                .account_id;
+
+            let constructor = TokenBridgeRef::new(spv_bridge, token, DEPOSIT_ADDRESS, MIN_DEPTH);
+            let token_bridge = client
+                .instantiate("token_bridge", &ink_e2e::alice(), constructor, 0, None)
+                .await
+                .expect("instantiate token_bridge failed")
+                .account_id;
+
+            let set_minter = build_message::<WrappedTokenRef>(token)
+                .call(|token| token.set_minter(token_bridge));
+            client
+                .call(&ink_e2e::alice(), set_minter, 0, None)
+                .await
+                .expect("set_minter failed");
+
+            // Charlie claims the deposit, which mints Bob's tokens
+            let claim = build_message::<TokenBridgeRef>(token_bridge).call(|token_bridge| {
+                token_bridge.claim_deposit(deposit, deposit_hash, proof.clone())
+            });
+            let claimed = client
+                .call(&ink_e2e::charlie(), claim, VERIFY_FEE, None)
+                .await
+                .expect("claim_deposit failed");
+            assert_eq!(claimed.return_value(), Ok(()));
+
+            let is_claimed = build_message::<TokenBridgeRef>(token_bridge)
+                .call(|token_bridge| token_bridge.is_claimed(tx_hash));
+            assert!(client
+                .call_dry_run(&ink_e2e::alice(), &is_claimed, 0, None)
+                .await
+                .return_value());
+
+            let balance_of =
+                build_message::<WrappedTokenRef>(token).call(|token| token.balance_of(bob));
+            let balance = client
+                .call_dry_run(&ink_e2e::alice(), &balance_of, 0, None)
+                .await
+                .return_value();
+            assert_eq!(balance, 100);
+
+            // The deposit cannot be claimed again, even though the light client would still
+            // verify it
+            let claim_again = build_message::<TokenBridgeRef>(token_bridge).call(|token_bridge| {
+                token_bridge.claim_deposit(deposit, deposit_hash, proof.clone())
+            });
+            let claimed_again = client
+                .call_dry_run(&ink_e2e::charlie(), &claim_again, VERIFY_FEE, None)
+                .await;
+            assert_eq!(claimed_again.return_value(), Err(Error::AlreadyClaimed));
+
+            // Nobody but the bridge can mint, not even the owner of the token
+            let mint = build_message::<WrappedTokenRef>(token).call(|token| token.mint(bob, 100));
+            let minted = client.call_dry_run(&ink_e2e::alice(), &mint, 0, None).await;
+            assert_eq!(minted.return_value(), Err(PSP22Error::NotMinter));
+
+            // Bob burns part of his tokens, to be released on the source chain
+            let destination = [0xe0; 32];
+            let withdraw = build_message::<TokenBridgeRef>(token_bridge)
+                .call(|token_bridge| token_bridge.request_withdrawal(40, destination));
+            let withdrawn = client
+                .call(&ink_e2e::bob(), withdraw, 0, None)
+                .await
+                .expect("request_withdrawal failed");
+
+            let requested: Vec<WithdrawalRequested> = withdrawn
+                .events
+                .iter()
+                .map(|event| event.expect("events decode"))
+                .filter(|event| {
+                    event.pallet_name() == "Contracts" && event.variant_name() == "ContractEmitted"
+                })
+                .filter_map(|event| {
+                    // The fields of `ContractEmitted` are the contract, and the event it emitted
+                    let (contract, data) =
+                        <(AccountId, Vec<u8>) as scale::Decode>::decode(&mut event.field_bytes())
+                            .expect("ContractEmitted decodes");
+                    match <Event as scale::Decode>::decode(&mut &data[..]) {
+                        Ok(Event::WithdrawalRequested(withdrawal)) if contract == token_bridge => {
+                            Some(withdrawal)
+                        }
+                        _ => None,
+                    }
+                })
+                .collect();
+            assert_eq!(withdrawn.return_value(), Ok(0));
+            assert_eq!(requested.len(), 1);
+            assert_eq!(requested[0].id, 0);
+            assert_eq!(requested[0].account, bob);
+            assert_eq!(requested[0].destination, destination);
+            assert_eq!(requested[0].amount, 40);
+
+            let balance = client
+                .call_dry_run(&ink_e2e::alice(), &balance_of, 0, None)
+                .await
+                .return_value();
+            assert_eq!(balance, 60);
+            let total_supply =
+                build_message::<WrappedTokenRef>(token).call(|token| token.total_supply());
+            let total_supply = client
+                .call_dry_run(&ink_e2e::alice(), &total_supply, 0, None)
+                .await
+                .return_value();
+            assert_eq!(total_supply, 60);
+
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,431 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::wrapped_token::{Error, WrappedToken, WrappedTokenRef};
+
+/// A PSP22 token that represents funds locked on another chain, and that only its minter, the
+/// `token_bridge` contract, can mint and burn.
+///
+/// The token is deployed before the bridge, which needs its address, so the deployer is its owner
+/// until it hands minting over to the bridge with [`WrappedToken::set_minter`]. That can only
+/// happen once: afterwards nobody, the owner included, can mint wrapped tokens without a proven
+/// deposit.
+#[ink::contract]
+mod wrapped_token {
+    use ink::codegen::EmitEvent;
+    use ink::prelude::vec::Vec;
+    use ink::reflect::ContractEventBase;
+    use ink::storage::Mapping;
+    use psp22_traits::{Burnable, Mintable, PSP22Error, PSP22};
+
+    #[ink(storage)]
+    pub struct WrappedToken {
+        /// The account that may set the minter, once
+        owner: AccountId,
+
+        /// The only account that may mint and burn, once it is set
+        minter: Option<AccountId>,
+
+        total_supply: Balance,
+        balances: Mapping<AccountId, Balance>,
+        allowances: Mapping<(AccountId, AccountId), Balance>,
+    }
+
+    /// Errors that can occur upon calling this contract, other than those of PSP22.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub enum Error {
+        /// Only the owner can set the minter
+        NotOwner,
+        /// The minter was already set
+        MinterAlreadySet,
+    }
+
+    /// Type alias for the contract's `Result` type.
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    type Event = <WrappedToken as ContractEventBase>::Type;
+
+    /// Tokens were moved, where `from` is `None` when they are minted and `to` is `None` when
+    /// they are burnt.
+    #[ink(event)]
+    pub struct Transfer {
+        #[ink(topic)]
+        from: Option<AccountId>,
+        #[ink(topic)]
+        to: Option<AccountId>,
+        value: Balance,
+    }
+
+    #[ink(event)]
+    pub struct Approval {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        spender: AccountId,
+        amount: Balance,
+    }
+
+    /// The minter was set.
+    #[ink(event)]
+    pub struct MinterSet {
+        #[ink(topic)]
+        minter: AccountId,
+    }
+
+    impl WrappedToken {
+        /// A token with no supply, owned by the caller, that nobody can mint yet.
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            Self {
+                owner: Self::env().caller(),
+                minter: None,
+                total_supply: 0,
+                balances: Mapping::default(),
+                allowances: Mapping::default(),
+            }
+        }
+
+        /// Let `minter`, and only it, mint and burn from now on.
+        #[ink(message)]
+        pub fn set_minter(&mut self, minter: AccountId) -> Result<()> {
+            if self.env().caller() != self.owner {
+                return Err(Error::NotOwner);
+            }
+            if self.minter.is_some() {
+                return Err(Error::MinterAlreadySet);
+            }
+
+            self.minter = Some(minter);
+            Self::emit_event(self.env(), Event::MinterSet(MinterSet { minter }));
+            Ok(())
+        }
+
+        /// The account that may mint and burn, if it is set.
+        #[ink(message)]
+        pub fn minter(&self) -> Option<AccountId> {
+            self.minter
+        }
+
+        fn ensure_minter(&self) -> core::result::Result<(), PSP22Error> {
+            if self.minter != Some(self.env().caller()) {
+                return Err(PSP22Error::NotMinter);
+            }
+            Ok(())
+        }
+
+        fn approve_from_to(&mut self, owner: AccountId, spender: AccountId, amount: Balance) {
+            self.allowances.insert((&owner, &spender), &amount);
+            Self::emit_event(
+                self.env(),
+                Event::Approval(Approval {
+                    owner,
+                    spender,
+                    amount,
+                }),
+            );
+        }
+
+        fn transfer_from_to(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+        ) -> core::result::Result<(), PSP22Error> {
+            let from_balance = self.balance_of(from);
+            if from_balance < value {
+                return Err(PSP22Error::InsufficientBalance);
+            }
+
+            // Cannot overflow, as the balances add up to the total supply
+            self.balances.insert(from, &(from_balance - value));
+            let to_balance = self.balance_of(to);
+            self.balances.insert(to, &(to_balance + value));
+
+            Self::emit_event(
+                self.env(),
+                Event::Transfer(Transfer {
+                    from: Some(from),
+                    to: Some(to),
+                    value,
+                }),
+            );
+            Ok(())
+        }
+
+        fn emit_event<EE>(emitter: EE, event: Event)
+        where
+            EE: EmitEvent<WrappedToken>,
+        {
+            emitter.emit_event(event);
+        }
+    }
+
+    impl Default for WrappedToken {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl PSP22 for WrappedToken {
+        #[ink(message)]
+        fn total_supply(&self) -> Balance {
+            self.total_supply
+        }
+
+        #[ink(message)]
+        fn balance_of(&self, owner: AccountId) -> Balance {
+            self.balances.get(owner).unwrap_or_default()
+        }
+
+        #[ink(message)]
+        fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+            self.allowances.get((owner, spender)).unwrap_or_default()
+        }
+
+        #[ink(message)]
+        fn approve(
+            &mut self,
+            spender: AccountId,
+            amount: Balance,
+        ) -> core::result::Result<(), PSP22Error> {
+            self.approve_from_to(self.env().caller(), spender, amount);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn increase_allowance(
+            &mut self,
+            spender: AccountId,
+            by: Balance,
+        ) -> core::result::Result<(), PSP22Error> {
+            let owner = self.env().caller();
+            let allowance = self.allowance(owner, spender).saturating_add(by);
+            self.approve_from_to(owner, spender, allowance);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn decrease_allowance(
+            &mut self,
+            spender: AccountId,
+            by: Balance,
+        ) -> core::result::Result<(), PSP22Error> {
+            let owner = self.env().caller();
+            let allowance = self
+                .allowance(owner, spender)
+                .checked_sub(by)
+                .ok_or(PSP22Error::InsufficientAllowance)?;
+            self.approve_from_to(owner, spender, allowance);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn transfer(
+            &mut self,
+            to: AccountId,
+            value: Balance,
+            _data: Vec<u8>,
+        ) -> core::result::Result<(), PSP22Error> {
+            self.transfer_from_to(self.env().caller(), to, value)
+        }
+
+        #[ink(message)]
+        fn transfer_from(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            _data: Vec<u8>,
+        ) -> core::result::Result<(), PSP22Error> {
+            let spender = self.env().caller();
+            let allowance = self.allowance(from, spender);
+            if allowance < value {
+                return Err(PSP22Error::InsufficientAllowance);
+            }
+
+            self.transfer_from_to(from, to, value)?;
+            self.approve_from_to(from, spender, allowance - value);
+            Ok(())
+        }
+    }
+
+    impl Mintable for WrappedToken {
+        /// Mint `amount` tokens to `account`, for funds locked on the other chain.
+        #[ink(message)]
+        fn mint(
+            &mut self,
+            account: AccountId,
+            amount: Balance,
+        ) -> core::result::Result<(), PSP22Error> {
+            self.ensure_minter()?;
+
+            // Cannot overflow either, as the funds are locked on the other chain
+            self.total_supply += amount;
+            let balance = self.balance_of(account);
+            self.balances.insert(account, &(balance + amount));
+
+            Self::emit_event(
+                self.env(),
+                Event::Transfer(Transfer {
+                    from: None,
+                    to: Some(account),
+                    value: amount,
+                }),
+            );
+            Ok(())
+        }
+    }
+
+    impl Burnable for WrappedToken {
+        /// Burn `amount` of the tokens of `account`, to release funds on the other chain.
+        ///
+        /// The minter burns on behalf of the account, which must have asked it to, without an
+        /// allowance.
+        #[ink(message)]
+        fn burn(
+            &mut self,
+            account: AccountId,
+            amount: Balance,
+        ) -> core::result::Result<(), PSP22Error> {
+            self.ensure_minter()?;
+
+            let balance = self.balance_of(account);
+            if balance < amount {
+                return Err(PSP22Error::InsufficientBalance);
+            }
+            self.total_supply -= amount;
+            self.balances.insert(account, &(balance - amount));
+
+            Self::emit_event(
+                self.env(),
+                Event::Transfer(Transfer {
+                    from: Some(account),
+                    to: None,
+                    value: amount,
+                }),
+            );
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn default_accounts() -> ink::env::test::DefaultAccounts<ink::env::DefaultEnvironment> {
+            ink::env::test::default_accounts::<Environment>()
+        }
+
+        fn set_caller(caller: AccountId) {
+            ink::env::test::set_caller::<Environment>(caller);
+        }
+
+        /// A token deployed by Alice, with Bob as its minter.
+        fn deploy_wrapped_token() -> WrappedToken {
+            let accounts = default_accounts();
+            set_caller(accounts.alice);
+            let mut token = WrappedToken::new();
+            assert_eq!(token.set_minter(accounts.bob), Ok(()));
+            token
+        }
+
+        #[ink::test]
+        fn only_owner_sets_minter_once() {
+            let accounts = default_accounts();
+            set_caller(accounts.alice);
+            let mut token = WrappedToken::new();
+            assert_eq!(token.minter(), None);
+
+            set_caller(accounts.bob);
+            assert_eq!(token.set_minter(accounts.bob), Err(Error::NotOwner));
+
+            set_caller(accounts.alice);
+            assert_eq!(token.set_minter(accounts.bob), Ok(()));
+            assert_eq!(token.minter(), Some(accounts.bob));
+            assert_eq!(
+                token.set_minter(accounts.alice),
+                Err(Error::MinterAlreadySet)
+            );
+            assert_eq!(token.minter(), Some(accounts.bob));
+        }
+
+        #[ink::test]
+        fn only_minter_mints_and_burns() {
+            let accounts = default_accounts();
+            let mut token = deploy_wrapped_token();
+
+            // Not even the owner
+            set_caller(accounts.alice);
+            assert_eq!(
+                token.mint(accounts.charlie, 100),
+                Err(PSP22Error::NotMinter)
+            );
+
+            set_caller(accounts.bob);
+            assert_eq!(token.mint(accounts.charlie, 100), Ok(()));
+            assert_eq!(token.balance_of(accounts.charlie), 100);
+            assert_eq!(token.total_supply(), 100);
+
+            set_caller(accounts.charlie);
+            assert_eq!(token.burn(accounts.charlie, 40), Err(PSP22Error::NotMinter));
+
+            set_caller(accounts.bob);
+            assert_eq!(token.burn(accounts.charlie, 40), Ok(()));
+            assert_eq!(token.balance_of(accounts.charlie), 60);
+            assert_eq!(token.total_supply(), 60);
+        }
+
+        #[ink::test]
+        fn burn_more_than_balance_fails() {
+            let accounts = default_accounts();
+            let mut token = deploy_wrapped_token();
+
+            set_caller(accounts.bob);
+            assert_eq!(token.mint(accounts.charlie, 100), Ok(()));
+            assert_eq!(
+                token.burn(accounts.charlie, 101),
+                Err(PSP22Error::InsufficientBalance)
+            );
+            assert_eq!(token.balance_of(accounts.charlie), 100);
+            assert_eq!(token.total_supply(), 100);
+        }
+
+        #[ink::test]
+        fn transfer_from_uses_up_allowance() {
+            let accounts = default_accounts();
+            let mut token = deploy_wrapped_token();
+            set_caller(accounts.bob);
+            assert_eq!(token.mint(accounts.charlie, 100), Ok(()));
+
+            set_caller(accounts.charlie);
+            assert_eq!(token.approve(accounts.django, 30), Ok(()));
+            assert_eq!(token.increase_allowance(accounts.django, 20), Ok(()));
+            assert_eq!(
+                token.decrease_allowance(accounts.django, 60),
+                Err(PSP22Error::InsufficientAllowance)
+            );
+
+            set_caller(accounts.django);
+            assert_eq!(
+                token.transfer_from(accounts.charlie, accounts.eve, 51, Vec::new()),
+                Err(PSP22Error::InsufficientAllowance)
+            );
+            assert_eq!(
+                token.transfer_from(accounts.charlie, accounts.eve, 50, Vec::new()),
+                Ok(())
+            );
+            assert_eq!(token.allowance(accounts.charlie, accounts.django), 0);
+            assert_eq!(token.balance_of(accounts.charlie), 50);
+            assert_eq!(token.balance_of(accounts.eve), 50);
+
+            set_caller(accounts.eve);
+            assert_eq!(
+                token.transfer(accounts.frank, 51, Vec::new()),
+                Err(PSP22Error::InsufficientBalance)
+            );
+            assert_eq!(token.transfer(accounts.frank, 50, Vec::new()), Ok(()));
+            assert_eq!(token.balance_of(accounts.frank), 50);
+            assert_eq!(token.total_supply(), 100);
+        }
+    }
+}
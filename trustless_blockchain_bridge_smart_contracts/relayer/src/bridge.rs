@@ -0,0 +1,219 @@
+//! The bridges that the relayer feeds: a contract deployed on a node, or one running in ink!'s
+//! off-chain environment.
+
+use crate::{chain::Chain, hex, Error, Result};
+use ink::{
+    env::{test, DefaultEnvironment},
+    primitives::AccountId,
+};
+use pallet_contracts_primitives::ContractExecResult;
+use scale::{Decode, Encode};
+use sp_weights::Weight;
+use spv_bridge::{HashValue, Header, SpvBridge, StoredHeader};
+use subxt::{dynamic::Value, utils::AccountId32, OnlineClient, SubstrateConfig};
+use subxt_signer::sr25519::Keypair;
+use tokio::runtime::Runtime;
+
+type Balance = <DefaultEnvironment as ink::env::Environment>::Balance;
+
+/// What the relayer needs from a bridge.
+pub trait Bridge {
+    /// Whether the bridge has the header with this hash, canonical or not.
+    fn is_known(&mut self, hash: HashValue) -> Result<bool>;
+
+    /// The hash of the tip of the canonical chain of the bridge.
+    fn best_hash(&mut self) -> Result<HashValue>;
+
+    /// The hash of the canonical header at `height`, if the bridge has one.
+    fn canonical_hash_at(&mut self, height: u64) -> Result<Option<HashValue>>;
+
+    /// Submit a segment of headers, each the parent of the next one, paying the relay fees.
+    fn submit_headers(&mut self, headers: Vec<Header>) -> Result<()>;
+}
+
+/// The bridge contract, in the off-chain environment of the current thread, as in its unit tests.
+///
+/// The relayer is Alice, who has plenty of funds for relay fees.
+pub struct OffchainBridge {
+    contract: SpvBridge,
+}
+
+impl OffchainBridge {
+    pub const RELAY_FEE: Balance = 1_000;
+    pub const VERIFY_FEE: Balance = 100;
+
+    /// Deploy the bridge with the genesis of `chain` as its checkpoint.
+    ///
+    /// The off-chain environment keeps the storage of each thread until it ends, so at most one
+    /// bridge can be deployed on each thread.
+    pub fn deploy(chain: &Chain, finality_depth: u64) -> Self {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        test::set_callee::<DefaultEnvironment>(AccountId::from([0xc0; 32]));
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        test::set_account_balance::<DefaultEnvironment>(accounts.alice, Balance::MAX / 2);
+        let genesis = chain.genesis_header();
+        let contract = SpvBridge::new(
+            genesis,
+            chain.target(),
+            genesis.timestamp(),
+            chain.retarget_rule(),
+            finality_depth,
+            Self::RELAY_FEE,
+            Self::VERIFY_FEE,
+        );
+        Self { contract }
+    }
+}
+
+impl Bridge for OffchainBridge {
+    fn is_known(&mut self, hash: HashValue) -> Result<bool> {
+        Ok(self.contract.header_is_known(hash))
+    }
+
+    fn best_hash(&mut self) -> Result<HashValue> {
+        Ok(self.contract.best_header().0)
+    }
+
+    fn canonical_hash_at(&mut self, height: u64) -> Result<Option<HashValue>> {
+        Ok(self.contract.canonical_hash_at(height))
+    }
+
+    fn submit_headers(&mut self, headers: Vec<Header>) -> Result<()> {
+        let (relay_fee, _) = self.contract.get_fees();
+        test::transfer_in::<DefaultEnvironment>(relay_fee * headers.len() as Balance);
+        self.contract
+            .submit_headers(headers)
+            .map_err(Error::Contract)
+    }
+}
+
+/// The bridge contract deployed on a `substrate-contracts-node`, called through
+/// `pallet-contracts`. Queries are dry runs, and submissions are dry run first, so that they are
+/// only sent if the contract accepts them.
+pub struct NodeBridge {
+    runtime: Runtime,
+    client: OnlineClient<SubstrateConfig>,
+    contract: AccountId32,
+    signer: Keypair,
+}
+
+impl NodeBridge {
+    /// Connect to the node at `url`, to call the bridge at `contract` as `signer`.
+    pub fn connect(url: &str, contract: AccountId32, signer: Keypair) -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| Error::Node(e.to_string()))?;
+        let client = runtime
+            .block_on(OnlineClient::from_url(url))
+            .map_err(|e| Error::Node(format!("Cannot connect to {}: {}", url, e)))?;
+        Ok(Self {
+            runtime,
+            client,
+            contract,
+            signer,
+        })
+    }
+
+    /// Dry run a call of the contract, and return what it returns, along with the gas it needs.
+    fn dry_run<T: Decode>(&self, input: Vec<u8>, value: Balance) -> Result<(T, Weight)> {
+        let origin = self.signer.public_key().to_account_id();
+        let request = (
+            origin,
+            self.contract.clone(),
+            value,
+            None::<Weight>,
+            None::<Balance>,
+            input,
+        );
+        let exec: ContractExecResult<Balance, ()> = self
+            .runtime
+            .block_on(self.client.rpc().state_call(
+                "ContractsApi_call",
+                Some(&request.encode()),
+                None,
+            ))
+            .map_err(|e| Error::Node(format!("Dry run failed: {}", e)))?;
+        let returned = exec
+            .result
+            .map_err(|e| Error::Node(format!("The contract call failed: {:?}", e)))?;
+        let output = <ink::MessageResult<T>>::decode(&mut &returned.data[..])
+            .map_err(|e| Error::Node(format!("Cannot decode what the contract returned: {}", e)))?
+            .map_err(|e| Error::Node(format!("The contract cannot dispatch the call: {:?}", e)))?;
+        Ok((output, exec.gas_required))
+    }
+
+    fn query<T: Decode>(&self, input: Vec<u8>) -> Result<T> {
+        self.dry_run(input, 0).map(|(output, _)| output)
+    }
+}
+
+/// The input of a call of the message with this selector and these arguments.
+fn input(selector: [u8; 4], args: impl Encode) -> Vec<u8> {
+    let mut input = selector.to_vec();
+    args.encode_to(&mut input);
+    input
+}
+
+impl Bridge for NodeBridge {
+    fn is_known(&mut self, hash: HashValue) -> Result<bool> {
+        let stored: Option<StoredHeader> =
+            self.query(input(ink::selector_bytes!("header"), hash))?;
+        Ok(stored.is_some())
+    }
+
+    fn best_hash(&mut self) -> Result<HashValue> {
+        let (hash, _): (HashValue, Header) =
+            self.query(input(ink::selector_bytes!("best_header"), ()))?;
+        Ok(hash)
+    }
+
+    fn canonical_hash_at(&mut self, height: u64) -> Result<Option<HashValue>> {
+        self.query(input(ink::selector_bytes!("canonical_hash_at"), height))
+    }
+
+    fn submit_headers(&mut self, headers: Vec<Header>) -> Result<()> {
+        let (relay_fee, _): (Balance, Balance) =
+            self.query(input(ink::selector_bytes!("get_fees"), ()))?;
+        let value = relay_fee * headers.len() as Balance;
+        let data = input(ink::selector_bytes!("submit_headers"), &headers);
+        let (result, gas_required): (core::result::Result<(), spv_bridge::Error>, Weight) =
+            self.dry_run(data.clone(), value)?;
+        result.map_err(Error::Contract)?;
+
+        let call = subxt::dynamic::tx(
+            "Contracts",
+            "call",
+            vec![
+                Value::unnamed_variant("Id", [Value::from_bytes(self.contract.0)]),
+                Value::u128(value),
+                Value::named_composite([
+                    ("ref_time", Value::u128(gas_required.ref_time().into())),
+                    ("proof_size", Value::u128(gas_required.proof_size().into())),
+                ]),
+                Value::unnamed_variant("None", []),
+                Value::from_bytes(data),
+            ],
+        );
+        self.runtime
+            .block_on(async {
+                self.client
+                    .tx()
+                    .sign_and_submit_then_watch_default(&call, &self.signer)
+                    .await?
+                    .wait_for_in_block()
+                    .await?
+                    .wait_for_success()
+                    .await
+            })
+            .map_err(|e| {
+                let tip = headers.last().map(|tip| hex(&SpvBridge::hash_header(*tip)));
+                Error::Node(format!(
+                    "Submitting the headers up to {} failed: {}",
+                    tip.unwrap_or_default(),
+                    e
+                ))
+            })?;
+        Ok(())
+    }
+}
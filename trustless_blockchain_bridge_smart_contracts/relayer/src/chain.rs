@@ -0,0 +1,140 @@
+//! A toy proof-of-work chain, mined locally.
+//!
+//! Its headers are [`Header`]s hashed with [`SpvBridge::hash_header`], and every hash must be below
+//! the same target, so they are valid for a bridge deployed with [`Chain::retarget_rule`] and the
+//! genesis of the chain as its checkpoint. Blocks are [`BLOCK_TIME`] apart, so that the same script
+//! always mines the same headers.
+
+use spv_bridge::{HashValue, Header, RetargetRule, SpvBridge};
+use std::collections::HashMap;
+
+/// The timestamp of the genesis block.
+pub const GENESIS_TIME: u64 = 1_000_000;
+
+/// How many seconds after its parent each block is mined.
+pub const BLOCK_TIME: u64 = 600;
+
+/// The default target, which takes about four attempts per block.
+pub const DEFAULT_TARGET: HashValue = target_from_prefix(&[0x3f]);
+
+/// A target made of the given leading bytes, followed by zeros.
+pub const fn target_from_prefix(prefix: &[u8]) -> HashValue {
+    let mut target = [0; 32];
+    let mut i = 0;
+    while i < prefix.len() && i < 32 {
+        target[i] = prefix[i];
+        i += 1;
+    }
+    target
+}
+
+/// Parse a target given as its leading bytes in hex, e.g. `3f` for [`DEFAULT_TARGET`].
+pub fn parse_target(hex: &str) -> Option<HashValue> {
+    if !hex.len().is_multiple_of(2) || hex.len() > 64 {
+        return None;
+    }
+    let prefix = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Some(target_from_prefix(&prefix))
+}
+
+/// Find the first nonce that makes the hash of the header with the given fields meet `target`.
+pub fn mine(
+    height: u64,
+    parent: HashValue,
+    timestamp: u64,
+    transactions_root: HashValue,
+    target: HashValue,
+) -> Header {
+    (0..)
+        .map(|nonce| Header::new(height, parent, timestamp, [0; 32], transactions_root, nonce))
+        .find(|header| SpvBridge::hash_header(*header) < target)
+        .expect("some nonce meets any target but zero")
+}
+
+/// A tree of blocks growing from a genesis block, all mined with the same target.
+pub struct Chain {
+    target: HashValue,
+    genesis: HashValue,
+    headers: HashMap<HashValue, Header>,
+    /// The tip of the longest chain, the first one mined among equally long ones. With a fixed
+    /// target, it is also the one with the most work.
+    best: HashValue,
+}
+
+impl Chain {
+    /// Mine the genesis block of a new chain.
+    pub fn new(target: HashValue) -> Self {
+        let genesis_header = mine(0, [0; 32], GENESIS_TIME, [0; 32], target);
+        let genesis = SpvBridge::hash_header(genesis_header);
+        Self {
+            target,
+            genesis,
+            headers: HashMap::from([(genesis, genesis_header)]),
+            best: genesis,
+        }
+    }
+
+    pub fn target(&self) -> HashValue {
+        self.target
+    }
+
+    pub fn genesis(&self) -> HashValue {
+        self.genesis
+    }
+
+    pub fn genesis_header(&self) -> Header {
+        self.headers[&self.genesis]
+    }
+
+    /// The rule that keeps the threshold of the bridge at the target of the chain.
+    pub fn retarget_rule(&self) -> RetargetRule {
+        RetargetRule {
+            window: 0,
+            block_time: BLOCK_TIME,
+            max_adjustment: 1,
+            max_target: self.target,
+        }
+    }
+
+    /// The tip of the longest chain.
+    pub fn best(&self) -> HashValue {
+        self.best
+    }
+
+    pub fn header(&self, hash: HashValue) -> Option<Header> {
+        self.headers.get(&hash).copied()
+    }
+
+    /// Mine a block on top of `parent`, with the given transactions root, and return its hash.
+    /// Returns `None` if `parent` is not in the chain.
+    pub fn mine(&mut self, parent: HashValue, transactions_root: HashValue) -> Option<HashValue> {
+        let parent_header = self.header(parent)?;
+        let header = mine(
+            parent_header.height() + 1,
+            parent,
+            parent_header.timestamp() + BLOCK_TIME,
+            transactions_root,
+            self.target,
+        );
+        let hash = SpvBridge::hash_header(header);
+        self.headers.insert(hash, header);
+        if header.height() > self.headers[&self.best].height() {
+            self.best = hash;
+        }
+        Some(hash)
+    }
+
+    /// The ancestor of `hash` that is `depth` blocks below it, or the genesis if it is not as deep.
+    pub fn ancestor(&self, mut hash: HashValue, depth: u64) -> HashValue {
+        for _ in 0..depth {
+            if hash == self.genesis {
+                break;
+            }
+            hash = self.headers[&hash].parent();
+        }
+        hash
+    }
+}
@@ -0,0 +1,51 @@
+//! An off-chain relayer for `spv_bridge`, along with the toy proof-of-work source chain that it
+//! relays.
+//!
+//! The chain is mined locally with the exact [`spv_bridge::Header`] format and hashing rule of the
+//! bridge, see [`chain`]. The relayer submits the headers of the chain that the bridge does not
+//! know yet, see [`relay`], either to a bridge deployed on a `substrate-contracts-node`, or to one
+//! running in ink!'s off-chain environment, see [`bridge`]. Forks and re-orgs are scripted with
+//! scenario files, see [`scenario`].
+
+use std::fmt;
+
+pub mod bridge;
+pub mod chain;
+pub mod relay;
+pub mod scenario;
+
+/// Errors of the relayer.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The bridge contract rejected the call with this error
+    Contract(spv_bridge::Error),
+    /// The node could not be reached, or its answer could not be understood
+    Node(String),
+    /// The bridge has none of the ancestors of a block: it tracks another chain, or it pruned the
+    /// fork of the block
+    UnrelatedChain,
+    /// A scenario file is invalid, or one of its expectations failed, at this line
+    Scenario { line: usize, message: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Contract(e) => write!(f, "the bridge rejected the call: {:?}", e),
+            Error::Node(message) => write!(f, "{}", message),
+            Error::UnrelatedChain => {
+                write!(f, "the bridge has none of the ancestors of the block")
+            }
+            Error::Scenario { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A hash in hex, as block explorers show them.
+pub fn hex(hash: &[u8]) -> String {
+    hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,195 @@
+use clap::{Args, Parser, Subcommand};
+use relayer::{
+    bridge::{Bridge, NodeBridge, OffchainBridge},
+    chain::{self, Chain, DEFAULT_TARGET},
+    hex,
+    relay::Relayer,
+    scenario::{Scenario, DEFAULT_FINALITY_DEPTH},
+};
+use spv_bridge::{HashValue, SpvBridge};
+use std::{path::PathBuf, str::FromStr, thread, time::Duration};
+use subxt::utils::AccountId32;
+use subxt_signer::{sr25519::Keypair, SecretUri};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Mine a toy proof-of-work chain, and relay its headers to `spv_bridge`.
+#[derive(Debug, Parser)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the arguments of the `new` constructor of a bridge for the chain with this target.
+    Genesis {
+        /// The leading bytes of the target in hex, the rest being zeros
+        #[arg(long, value_parser = parse_target)]
+        difficulty: Option<HashValue>,
+    },
+    /// Replay a scenario file.
+    Scenario {
+        /// The scenario file, see the `scenario` module for its format
+        file: PathBuf,
+        #[command(flatten)]
+        node: NodeArgs,
+    },
+    /// Keep mining blocks and relaying the longest chain, with a re-org every so often.
+    Daemon {
+        /// The leading bytes of the target in hex, the rest being zeros
+        #[arg(long, value_parser = parse_target)]
+        difficulty: Option<HashValue>,
+        /// How long to wait between blocks, in milliseconds
+        #[arg(long, default_value_t = 6000)]
+        interval_ms: u64,
+        /// Stop after mining this many blocks, or never if 0
+        #[arg(long, default_value_t = 0)]
+        blocks: u64,
+        /// Re-org the chain every this many blocks, or never if 0
+        #[arg(long, default_value_t = 0)]
+        fork_every: u64,
+        /// How many blocks each re-org replaces
+        #[arg(long, default_value_t = 2)]
+        fork_depth: u64,
+        #[command(flatten)]
+        node: NodeArgs,
+    },
+}
+
+/// Where the bridge is. Without `--contract`, a bridge is deployed in the off-chain environment.
+#[derive(Debug, Args)]
+struct NodeArgs {
+    /// The websocket URL of the node
+    #[arg(long, default_value = "ws://127.0.0.1:9944")]
+    url: String,
+    /// The address of the bridge contract, deployed with the arguments that `genesis` prints
+    #[arg(long)]
+    contract: Option<String>,
+    /// The secret URI of the relayer account
+    #[arg(long, default_value = "//Alice")]
+    suri: String,
+    /// How many headers to submit in each call
+    #[arg(long, default_value_t = 16)]
+    batch_size: usize,
+}
+
+impl NodeArgs {
+    fn bridge(&self, chain: &Chain, finality_depth: u64) -> Result<Box<dyn Bridge>> {
+        let Some(contract) = &self.contract else {
+            return Ok(Box::new(OffchainBridge::deploy(chain, finality_depth)));
+        };
+        let contract = AccountId32::from_str(contract)
+            .map_err(|e| format!("Invalid contract address {}: {:?}", contract, e))?;
+        let signer = Keypair::from_uri(&SecretUri::from_str(&self.suri)?)
+            .map_err(|e| format!("Invalid secret URI: {:?}", e))?;
+        let mut bridge = NodeBridge::connect(&self.url, contract, signer)?;
+        if !bridge.is_known(chain.genesis())? {
+            return Err(format!(
+                "The bridge does not have the genesis {} of the chain, deploy it with the \
+                 arguments that `genesis` prints",
+                hex(&chain.genesis())
+            )
+            .into());
+        }
+        Ok(Box::new(bridge))
+    }
+}
+
+fn parse_target(hex: &str) -> std::result::Result<HashValue, String> {
+    chain::parse_target(hex).ok_or_else(|| "expected at most 32 bytes in hex".into())
+}
+
+fn genesis(target: HashValue) {
+    let chain = Chain::new(target);
+    let header = chain.genesis_header();
+    println!("genesis hash: 0x{}", hex(&chain.genesis()));
+    println!("constructor `new` arguments:");
+    println!("  source_genesis_header: {:?}", header);
+    println!("  difficulty: {:?}", target);
+    println!("  window_start: {}", header.timestamp());
+    println!("  retarget: {:?}", chain.retarget_rule());
+}
+
+fn daemon(
+    target: HashValue,
+    interval: Duration,
+    blocks: u64,
+    fork_every: u64,
+    fork_depth: u64,
+    node: &NodeArgs,
+) -> Result<()> {
+    let mut chain = Chain::new(target);
+    let mut bridge = node.bridge(&chain, DEFAULT_FINALITY_DEPTH)?;
+    let mut relayer = Relayer::new(node.batch_size);
+
+    for count in 1u64.. {
+        let mut transactions_root = [0; 32];
+        transactions_root[..8].copy_from_slice(&count.to_be_bytes());
+        let reorg = fork_every != 0 && count % fork_every == 0;
+        let tip = if reorg {
+            // A fork one block longer than the blocks it replaces.
+            let mut tip = chain.ancestor(chain.best(), fork_depth);
+            for _ in 0..=fork_depth {
+                tip = chain
+                    .mine(tip, transactions_root)
+                    .expect("the fork grows from the chain");
+            }
+            tip
+        } else {
+            chain
+                .mine(chain.best(), transactions_root)
+                .expect("the best block is in the chain")
+        };
+        let height = chain.header(tip).expect("the tip was just mined").height();
+
+        let relayed = relayer.relay(&chain, tip, bridge.as_mut())?;
+        println!(
+            "#{} 0x{}{}: relayed {} headers, the bridge is at 0x{}",
+            height,
+            hex(&SpvBridge::hash_header(chain.header(tip).unwrap())),
+            if reorg { " (re-org)" } else { "" },
+            relayed,
+            hex(&bridge.best_hash()?),
+        );
+
+        if count == blocks {
+            break;
+        }
+        thread::sleep(interval);
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    match Cli::parse().command {
+        Command::Genesis { difficulty } => genesis(difficulty.unwrap_or(DEFAULT_TARGET)),
+        Command::Scenario { file, node } => {
+            let scenario = Scenario::parse(&std::fs::read_to_string(&file)?)?;
+            let mut chain = scenario.chain();
+            let mut bridge = node.bridge(&chain, scenario.finality_depth)?;
+            scenario.run(
+                &mut chain,
+                bridge.as_mut(),
+                &mut Relayer::new(node.batch_size),
+            )?;
+            println!("{}: ok", file.display());
+        }
+        Command::Daemon {
+            difficulty,
+            interval_ms,
+            blocks,
+            fork_every,
+            fork_depth,
+            node,
+        } => daemon(
+            difficulty.unwrap_or(DEFAULT_TARGET),
+            Duration::from_millis(interval_ms),
+            blocks,
+            fork_every,
+            fork_depth,
+            &node,
+        )?,
+    }
+    Ok(())
+}
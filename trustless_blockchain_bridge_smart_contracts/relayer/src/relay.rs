@@ -0,0 +1,75 @@
+//! Relaying the blocks of the toy chain that the bridge does not know yet.
+
+use crate::{bridge::Bridge, chain::Chain, Error, Result};
+use spv_bridge::{HashValue, Header, SpvBridge};
+use std::collections::HashSet;
+
+/// Relays chains of blocks to a bridge, remembering which headers the bridge already has.
+pub struct Relayer {
+    /// Headers that the bridge is known to have, so that it is not asked about them again. The
+    /// bridge may have pruned some of them since.
+    known: HashSet<HashValue>,
+    /// How many headers to submit at once
+    batch_size: usize,
+}
+
+impl Relayer {
+    pub fn new(batch_size: usize) -> Self {
+        Self {
+            known: HashSet::new(),
+            batch_size: batch_size.max(1),
+        }
+    }
+
+    /// Submit the blocks of the chain ending at `tip` that the bridge does not have, oldest first,
+    /// and return how many there were.
+    pub fn relay(
+        &mut self,
+        chain: &Chain,
+        tip: HashValue,
+        bridge: &mut dyn Bridge,
+    ) -> Result<usize> {
+        match self.try_relay(chain, tip, bridge) {
+            // A header that was known may have been pruned since, so ask the bridge again.
+            Err(Error::Contract(spv_bridge::Error::UnknownParent)) => {
+                self.known.clear();
+                self.try_relay(chain, tip, bridge)
+            }
+            result => result,
+        }
+    }
+
+    fn try_relay(
+        &mut self,
+        chain: &Chain,
+        tip: HashValue,
+        bridge: &mut dyn Bridge,
+    ) -> Result<usize> {
+        let mut missing: Vec<Header> = Vec::new();
+        let mut hash = tip;
+        while !self.is_known(hash, bridge)? {
+            let header = chain.header(hash).ok_or(Error::UnrelatedChain)?;
+            missing.push(header);
+            hash = header.parent();
+        }
+        missing.reverse();
+
+        for batch in missing.chunks(self.batch_size) {
+            bridge.submit_headers(batch.to_vec())?;
+            self.known
+                .extend(batch.iter().map(|header| SpvBridge::hash_header(*header)));
+        }
+        Ok(missing.len())
+    }
+
+    fn is_known(&mut self, hash: HashValue, bridge: &mut dyn Bridge) -> Result<bool> {
+        if self.known.contains(&hash) {
+            return Ok(true);
+        }
+        let known = bridge.is_known(hash)?;
+        if known {
+            self.known.insert(hash);
+        }
+        Ok(known)
+    }
+}
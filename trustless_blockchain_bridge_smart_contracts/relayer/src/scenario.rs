@@ -0,0 +1,320 @@
+//! Scenario files: scripts that mine blocks of the toy chain, relay them, and check the state of
+//! the bridge, so that forks and re-orgs can be replayed, in tests against the off-chain bridge,
+//! or against a node.
+//!
+//! Each line holds one command, and `#` starts a comment:
+//!
+//! - `difficulty <hex>`: the target of the chain is these leading bytes followed by zeros, `3f`
+//!   by default. Only before `genesis`.
+//! - `finality-depth <n>`: the finality depth of the bridge, 100 by default, when the scenario
+//!   deploys it itself. Only before `genesis`.
+//! - `genesis <name>`: the name of the genesis block, which is the checkpoint of the bridge. It
+//!   must come before any other command.
+//! - `mine <name> <parent>`: mine a block on top of `parent`. Blocks with different names have
+//!   different transactions roots, so that siblings differ.
+//! - `relay [<name>]`: relay the chain ending at `name`, or at the tip of the longest chain.
+//! - `reject <name> <error>`: relaying the chain ending at `name` fails with this error of the
+//!   bridge, e.g. `FinalizedFork`.
+//! - `expect best <name>`: the block is the tip of the canonical chain of the bridge.
+//! - `expect canon <name>`: the block is in the canonical chain of the bridge.
+//! - `expect fork <name>`: the bridge has the block, but not in its canonical chain.
+//! - `expect unknown <name>`: the bridge does not have the block, e.g. because it pruned it.
+
+use crate::{
+    bridge::Bridge,
+    chain::{parse_target, Chain, DEFAULT_TARGET},
+    hex,
+    relay::Relayer,
+    Error, Result,
+};
+use ink::env::hash::{HashOutput, Sha2x256};
+use spv_bridge::HashValue;
+use std::collections::HashMap;
+
+/// The finality depth of the bridges that scenarios deploy, unless they set another.
+pub const DEFAULT_FINALITY_DEPTH: u64 = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expectation {
+    Best,
+    Canon,
+    Fork,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Mine {
+        name: String,
+        parent: String,
+    },
+    Relay {
+        tip: Option<String>,
+    },
+    Reject {
+        tip: String,
+        error: String,
+    },
+    Expect {
+        expectation: Expectation,
+        name: String,
+    },
+}
+
+/// A parsed scenario file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scenario {
+    /// The target of the chain
+    pub target: HashValue,
+    /// The finality depth of the bridge, if the scenario deploys it
+    pub finality_depth: u64,
+    genesis: String,
+    /// The steps after the genesis, along with their line numbers
+    steps: Vec<(usize, Step)>,
+}
+
+fn invalid(line: usize, message: impl Into<String>) -> Error {
+    Error::Scenario {
+        line,
+        message: message.into(),
+    }
+}
+
+impl Scenario {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut target = DEFAULT_TARGET;
+        let mut finality_depth = DEFAULT_FINALITY_DEPTH;
+        let mut genesis = None;
+        let mut steps = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let command = line.split('#').next().unwrap_or_default();
+            let words: Vec<&str> = command.split_whitespace().collect();
+            let name = |word: &str| word.to_string();
+            let step = match (words.as_slice(), &genesis) {
+                ([], _) => continue,
+                (["difficulty", prefix], None) => {
+                    target = parse_target(prefix).ok_or_else(|| {
+                        invalid(line_number, format!("`{}` is not a hex target", prefix))
+                    })?;
+                    continue;
+                }
+                (["finality-depth", depth], None) => {
+                    finality_depth = depth
+                        .parse()
+                        .map_err(|_| invalid(line_number, format!("`{}` is not a depth", depth)))?;
+                    continue;
+                }
+                (["genesis", block], None) => {
+                    genesis = Some(name(block));
+                    continue;
+                }
+                ([command, ..], None) => {
+                    return Err(invalid(
+                        line_number,
+                        format!("`{}` before `genesis`", command),
+                    ))
+                }
+                (["mine", block, parent], Some(_)) => Step::Mine {
+                    name: name(block),
+                    parent: name(parent),
+                },
+                (["relay"], Some(_)) => Step::Relay { tip: None },
+                (["relay", tip], Some(_)) => Step::Relay {
+                    tip: Some(name(tip)),
+                },
+                (["reject", tip, error], Some(_)) => Step::Reject {
+                    tip: name(tip),
+                    error: name(error),
+                },
+                (["expect", expectation, block], Some(_)) => Step::Expect {
+                    expectation: match *expectation {
+                        "best" => Expectation::Best,
+                        "canon" => Expectation::Canon,
+                        "fork" => Expectation::Fork,
+                        "unknown" => Expectation::Unknown,
+                        _ => {
+                            return Err(invalid(
+                                line_number,
+                                format!("cannot expect a block to be `{}`", expectation),
+                            ))
+                        }
+                    },
+                    name: name(block),
+                },
+                _ => {
+                    return Err(invalid(
+                        line_number,
+                        format!("invalid command `{}`", command.trim()),
+                    ))
+                }
+            };
+            steps.push((line_number, step));
+        }
+
+        Ok(Self {
+            target,
+            finality_depth,
+            genesis: genesis.ok_or_else(|| invalid(text.lines().count(), "no `genesis`"))?,
+            steps,
+        })
+    }
+
+    /// A chain with the genesis and target of the scenario.
+    pub fn chain(&self) -> Chain {
+        Chain::new(self.target)
+    }
+
+    /// Run the steps of the scenario on top of `chain`, a chain returned by [`Scenario::chain`]
+    /// that `bridge` was checkpointed on.
+    pub fn run(
+        &self,
+        chain: &mut Chain,
+        bridge: &mut dyn Bridge,
+        relayer: &mut Relayer,
+    ) -> Result<()> {
+        let mut blocks = HashMap::from([(self.genesis.as_str(), chain.genesis())]);
+        for (line, step) in &self.steps {
+            let line = *line;
+            let block = |name: &str| {
+                blocks
+                    .get(name)
+                    .copied()
+                    .ok_or_else(|| invalid(line, format!("no block `{}`", name)))
+            };
+            // Errors of the bridge are failures of the scenario at this line.
+            let at_line = |e: Error| invalid(line, e.to_string());
+
+            match step {
+                Step::Mine { name, parent } => {
+                    if blocks.contains_key(name.as_str()) {
+                        return Err(invalid(line, format!("block `{}` is already mined", name)));
+                    }
+                    let hash = chain
+                        .mine(block(parent)?, transactions_root(name))
+                        .expect("named blocks are in the chain");
+                    blocks.insert(name, hash);
+                }
+                Step::Relay { tip } => {
+                    let tip = match tip {
+                        Some(tip) => block(tip)?,
+                        None => chain.best(),
+                    };
+                    relayer.relay(chain, tip, bridge).map_err(at_line)?;
+                }
+                Step::Reject { tip, error } => match relayer.relay(chain, block(tip)?, bridge) {
+                    Err(Error::Contract(e)) if format!("{:?}", e) == *error => {}
+                    Err(e) => return Err(at_line(e)),
+                    Ok(_) => return Err(invalid(line, format!("`{}` was relayed", tip))),
+                },
+                Step::Expect { expectation, name } => {
+                    let hash = block(name)?;
+                    let height = chain
+                        .header(hash)
+                        .expect("named blocks are in the chain")
+                        .height();
+                    let canon = bridge.canonical_hash_at(height).map_err(at_line)? == Some(hash);
+                    let holds = match expectation {
+                        Expectation::Best => bridge.best_hash().map_err(at_line)? == hash,
+                        Expectation::Canon => canon,
+                        Expectation::Fork => !canon && bridge.is_known(hash).map_err(at_line)?,
+                        Expectation::Unknown => !bridge.is_known(hash).map_err(at_line)?,
+                    };
+                    if !holds {
+                        return Err(invalid(
+                            line,
+                            format!(
+                                "expected `{}` ({}) to be {:?}",
+                                name,
+                                hex(&hash),
+                                expectation
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The transactions root of the block with this name, so that blocks with different names differ.
+fn transactions_root(name: &str) -> HashValue {
+    let mut hash = <Sha2x256 as HashOutput>::Type::default();
+    ink::env::hash_bytes::<Sha2x256>(name.as_bytes(), &mut hash);
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::target_from_prefix;
+
+    #[test]
+    fn parse_scenario() {
+        let scenario = Scenario::parse(
+            "# A comment\n\
+             difficulty 0f\n\
+             finality-depth 3\n\
+             genesis G\n\
+             \n\
+             mine A G  # another comment\n\
+             relay\n\
+             relay A\n\
+             reject A FinalizedFork\n\
+             expect best A\n",
+        )
+        .unwrap();
+        assert_eq!(scenario.target, target_from_prefix(&[0x0f]));
+        assert_eq!(scenario.finality_depth, 3);
+        assert_eq!(scenario.genesis, "G");
+        assert_eq!(
+            scenario.steps,
+            Vec::from([
+                (
+                    6,
+                    Step::Mine {
+                        name: "A".into(),
+                        parent: "G".into()
+                    }
+                ),
+                (7, Step::Relay { tip: None }),
+                (
+                    8,
+                    Step::Relay {
+                        tip: Some("A".into())
+                    }
+                ),
+                (
+                    9,
+                    Step::Reject {
+                        tip: "A".into(),
+                        error: "FinalizedFork".into()
+                    }
+                ),
+                (
+                    10,
+                    Step::Expect {
+                        expectation: Expectation::Best,
+                        name: "A".into()
+                    }
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let line_of = |text: &str| match Scenario::parse(text) {
+            Err(Error::Scenario { line, .. }) => line,
+            other => panic!("expected an invalid scenario, got {:?}", other),
+        };
+        assert_eq!(line_of("mine A G\n"), 1);
+        assert_eq!(line_of("genesis G\ndifficulty 0f\n"), 2);
+        assert_eq!(line_of("difficulty 0g\n"), 1);
+        assert_eq!(line_of("genesis G\nmine A\n"), 2);
+        assert_eq!(line_of("genesis G\nexpect final G\n"), 2);
+        assert_eq!(line_of("# no genesis\n"), 1);
+    }
+}
@@ -0,0 +1,81 @@
+use relayer::{
+    bridge::{Bridge, OffchainBridge},
+    chain::{target_from_prefix, Chain, DEFAULT_TARGET},
+    relay::Relayer,
+    scenario::Scenario,
+};
+use std::{fs, path::Path, thread};
+
+/// Run a scenario against a bridge in the off-chain environment. Each scenario runs on its own
+/// thread, so that it gets a fresh environment.
+fn run_scenario(path: &Path) -> relayer::Result<()> {
+    let text = fs::read_to_string(path).unwrap();
+    thread::spawn(move || {
+        let scenario = Scenario::parse(&text)?;
+        let mut chain = scenario.chain();
+        let mut bridge = OffchainBridge::deploy(&chain, scenario.finality_depth);
+        scenario.run(&mut chain, &mut bridge, &mut Relayer::new(2))
+    })
+    .join()
+    .unwrap()
+}
+
+#[test]
+fn scenarios() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios");
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+    for path in paths {
+        if let Err(e) = run_scenario(&path) {
+            panic!("{}: {}", path.display(), e);
+        }
+    }
+}
+
+#[test]
+fn failed_expectation_reports_its_line() {
+    let scenario = Scenario::parse("genesis G\nmine A G\nexpect best A\n").unwrap();
+    let result = thread::spawn(move || {
+        let mut chain = scenario.chain();
+        let mut bridge = OffchainBridge::deploy(&chain, 100);
+        scenario.run(&mut chain, &mut bridge, &mut Relayer::new(2))
+    })
+    .join()
+    .unwrap();
+    assert!(matches!(
+        result,
+        Err(relayer::Error::Scenario { line: 3, .. })
+    ));
+}
+
+#[test]
+fn relayer_only_submits_unknown_headers() {
+    let mut chain = Chain::new(DEFAULT_TARGET);
+    let mut bridge = OffchainBridge::deploy(&chain, 100);
+    let mut relayer = Relayer::new(2);
+
+    let mut tip = chain.genesis();
+    for i in 0..5 {
+        tip = chain.mine(tip, [i; 32]).unwrap();
+    }
+    assert_eq!(relayer.relay(&chain, tip, &mut bridge), Ok(5));
+    assert_eq!(relayer.relay(&chain, tip, &mut bridge), Ok(0));
+    assert_eq!(bridge.best_hash(), Ok(tip));
+
+    // A new relayer asks the bridge which headers it has.
+    let tip = chain.mine(tip, [5; 32]).unwrap();
+    assert_eq!(Relayer::new(2).relay(&chain, tip, &mut bridge), Ok(1));
+    assert_eq!(bridge.best_hash(), Ok(tip));
+
+    // A chain that the bridge was not checkpointed on, since it has another target.
+    let mut other = Chain::new(target_from_prefix(&[0x1f]));
+    let other_tip = other.mine(other.genesis(), [0xff; 32]).unwrap();
+    assert_eq!(
+        relayer.relay(&other, other_tip, &mut bridge),
+        Err(relayer::Error::UnrelatedChain)
+    );
+}
@@ -13,6 +13,7 @@ pub type Balance = <DefaultEnvironment as Environment>::Balance;
 pub enum PSP22Error {
     InsufficientBalance,
     InsufficientAllowance,
+    NotMinter,
 }
 
 #[ink::trait_definition]